@@ -0,0 +1,431 @@
+use {
+    crate::{
+        data_center_info::DataCenterInfo,
+        db::{EpochClassificationDb, ValidatorClassification, ValidatorMetrics},
+        generic_stake_pool::ValidatorStakeState,
+        BoxResult,
+    },
+    log::*,
+    serde::Serialize,
+    solana_sdk::{clock::Epoch, pubkey::Pubkey},
+    std::{
+        collections::BTreeMap,
+        io::{self, BufRead, BufReader, Read, Write},
+        net::{SocketAddr, TcpListener, TcpStream},
+        str::FromStr,
+        time::{Duration, Instant},
+    },
+};
+
+// Requests are served one at a time, so a client may hold the server for no longer than this
+const CONNECTION_DEADLINE: Duration = Duration::from_secs(5);
+
+// Limit of the request line and headers
+const MAX_REQUEST_BYTES: u64 = 8 * 1024;
+
+// The stored classifications serialize `Pubkey`s as bytes and key maps by `Pubkey` and
+// `DataCenterId`, neither of which suit JSON.  The views below present them as strings
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct DataCenterView {
+    id: String,
+    stake: u64,
+    stake_percent: f64,
+    validators: Vec<String>,
+}
+
+impl From<&DataCenterInfo> for DataCenterView {
+    fn from(data_center_info: &DataCenterInfo) -> Self {
+        Self {
+            id: data_center_info.id.to_string(),
+            stake: data_center_info.stake,
+            stake_percent: data_center_info.stake_percent,
+            validators: data_center_info
+                .validators
+                .iter()
+                .map(|identity| identity.to_string())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct ValidatorView<'a> {
+    identity: String,
+    vote_address: String,
+    participant: Option<String>,
+    stake_state: ValidatorStakeState,
+    stake_state_reason: &'a str,
+    stake_tier: &'a Option<String>,
+    stake_action: &'a Option<String>,
+    stake_states: &'a Option<Vec<(ValidatorStakeState, String)>>,
+    notes: &'a [String],
+    data_center_residency: Option<BTreeMap<String, usize>>,
+    current_data_center: Option<String>,
+    prioritize_funding_in_next_epoch: Option<bool>,
+    metrics: &'a Option<ValidatorMetrics>,
+}
+
+impl<'a> From<&'a ValidatorClassification> for ValidatorView<'a> {
+    fn from(vc: &'a ValidatorClassification) -> Self {
+        Self {
+            identity: vc.identity.to_string(),
+            vote_address: vc.vote_address.to_string(),
+            participant: vc.participant.map(|participant| participant.to_string()),
+            stake_state: vc.stake_state,
+            stake_state_reason: &vc.stake_state_reason,
+            stake_tier: &vc.stake_tier,
+            stake_action: &vc.stake_action,
+            stake_states: &vc.stake_states,
+            notes: &vc.notes,
+            data_center_residency: vc.data_center_residency.as_ref().map(|residency| {
+                residency
+                    .iter()
+                    .map(|(data_center, count)| (data_center.to_string(), *count))
+                    .collect()
+            }),
+            current_data_center: vc
+                .current_data_center
+                .as_ref()
+                .map(|data_center| data_center.to_string()),
+            prioritize_funding_in_next_epoch: vc.prioritize_funding_in_next_epoch,
+            metrics: &vc.metrics,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct EpochView<'a> {
+    epoch: Epoch,
+    notes: &'a [String],
+    data_centers: Vec<DataCenterView>,
+
+    // `None` if stake adjustments were paused for the epoch
+    validators: Option<BTreeMap<String, ValidatorView<'a>>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct ValidatorEpochView<'a> {
+    epoch: Epoch,
+    #[serde(flatten)]
+    classification: ValidatorView<'a>,
+}
+
+#[derive(Serialize)]
+struct ErrorView {
+    error: String,
+}
+
+struct Response {
+    status: u16,
+    body: String,
+}
+
+impl Response {
+    fn ok<T: Serialize>(value: &T) -> BoxResult<Self> {
+        Ok(Self {
+            status: 200,
+            body: serde_json::to_string_pretty(value)?,
+        })
+    }
+
+    fn error(status: u16, error: String) -> Self {
+        Self {
+            status,
+            body: serde_json::to_string_pretty(&ErrorView { error }).unwrap(),
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+fn parse_epoch(epoch: &str) -> Result<Epoch, Response> {
+    epoch
+        .parse()
+        .map_err(|_| Response::error(400, format!("Invalid epoch: {}", epoch)))
+}
+
+fn parse_identity(identity: &str) -> Result<Pubkey, Response> {
+    Pubkey::from_str(identity)
+        .map_err(|_| Response::error(400, format!("Invalid validator identity: {}", identity)))
+}
+
+fn get(db: &dyn EpochClassificationDb, path: &str) -> BoxResult<Response> {
+    let segments = path
+        .trim_matches('/')
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+
+    Ok(match segments.as_slice() {
+        ["epochs"] => Response::ok(&db.epochs()?)?,
+        ["epochs", epoch] => {
+            let epoch = match parse_epoch(epoch) {
+                Ok(epoch) => epoch,
+                Err(response) => return Ok(response),
+            };
+            if !db.exists(epoch)? {
+                return Ok(Response::error(404, format!("Epoch {} not found", epoch)));
+            }
+            let epoch_classification = db.load(epoch)?.into_current();
+            Response::ok(&EpochView {
+                epoch,
+                notes: &epoch_classification.notes,
+                data_centers: epoch_classification
+                    .data_center_info
+                    .iter()
+                    .map(DataCenterView::from)
+                    .collect(),
+                validators: epoch_classification.validator_classifications.as_ref().map(
+                    |validator_classifications| {
+                        validator_classifications
+                            .iter()
+                            .map(|(identity, vc)| (identity.to_string(), ValidatorView::from(vc)))
+                            .collect()
+                    },
+                ),
+            })?
+        }
+        ["validators", identity] | ["validators", identity, "latest"] => {
+            let identity = match parse_identity(identity) {
+                Ok(identity) => identity,
+                Err(response) => return Ok(response),
+            };
+            let history = db.load_validator_history(&identity)?;
+            if history.is_empty() {
+                return Ok(Response::error(
+                    404,
+                    format!("Validator {} not found", identity),
+                ));
+            }
+            let mut history = history.iter().map(|(epoch, vc)| ValidatorEpochView {
+                epoch: *epoch,
+                classification: ValidatorView::from(vc),
+            });
+            if segments.len() == 3 {
+                // The history is ordered newest first
+                Response::ok(&history.next())?
+            } else {
+                Response::ok(&history.collect::<Vec<_>>())?
+            }
+        }
+        _ => Response::error(404, format!("Unknown path: {}", path)),
+    })
+}
+
+// Reads from a stream, failing once `deadline` has passed
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self
+            .deadline
+            .checked_duration_since(Instant::now())
+            .filter(|remaining| *remaining > Duration::default())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::TimedOut, "Connection deadline"))?;
+        self.stream.set_read_timeout(Some(remaining))?;
+        self.stream.read(buf)
+    }
+}
+
+// Read the request line of a request, consuming its headers
+fn read_request<R: Read>(reader: R) -> io::Result<String> {
+    let mut reader = BufReader::new(reader.take(MAX_REQUEST_BYTES));
+    let mut read_line = || {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        if !line.ends_with('\n') && reader.get_ref().limit() == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Request too large",
+            ));
+        }
+        Ok(line)
+    };
+
+    let request_line = read_line()?;
+    // The API has no use for the request headers, but they must be consumed before responding
+    loop {
+        let header = read_line()?;
+        if header.trim().is_empty() {
+            break;
+        }
+    }
+    Ok(request_line)
+}
+
+fn handle_connection(db: &dyn EpochClassificationDb, stream: TcpStream) -> io::Result<()> {
+    stream.set_write_timeout(Some(CONNECTION_DEADLINE))?;
+    let request_line = read_request(DeadlineReader {
+        stream: &stream,
+        deadline: Instant::now() + CONNECTION_DEADLINE,
+    })?;
+
+    let mut request = request_line.split_whitespace();
+    let method = request.next().unwrap_or_default();
+    // Ignore any query string
+    let path = request
+        .next()
+        .unwrap_or_default()
+        .split('?')
+        .next()
+        .unwrap_or_default();
+
+    let response = if method != "GET" && method != "HEAD" {
+        Response::error(405, format!("Unsupported method: {}", method))
+    } else {
+        get(db, path).unwrap_or_else(|err| Response::error(500, err.to_string()))
+    };
+    info!("{} {} {}", method, path, response.status);
+
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n",
+        response.status,
+        response.reason(),
+        response.body.len()
+    )?;
+    if method != "HEAD" {
+        stream.write_all(response.body.as_bytes())?;
+    }
+    stream.flush()
+}
+
+/// Serve the classifications of `db` as JSON over HTTP on `address`, one request at a time, each
+/// connection limited to `CONNECTION_DEADLINE`:
+///
+/// * `/epochs`: the classified epochs, in ascending order
+/// * `/epochs/{n}`: the classification of epoch `n`
+/// * `/validators/{identity}`: the classifications of a validator, newest first
+/// * `/validators/{identity}/latest`: the newest classification of a validator
+pub fn serve(db: &dyn EpochClassificationDb, address: SocketAddr) -> BoxResult<()> {
+    let listener = TcpListener::bind(address)
+        .map_err(|err| format!("Unable to listen on {}: {}", address, err))?;
+    info!("Serving the staking history on http://{}", address);
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(err) = handle_connection(db, stream) {
+                    warn!("Request failed: {}", err);
+                }
+            }
+            Err(err) => warn!("Connection failed: {}", err),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::db::{EpochClassification, EpochClassificationV2, YamlDb},
+        std::fs,
+    };
+
+    #[test]
+    fn test_get() {
+        let path = std::env::temp_dir().join(format!("api-{}", Pubkey::new_unique()));
+        fs::create_dir_all(&path).unwrap();
+        let db = YamlDb::new(&path);
+
+        let identity = Pubkey::new_unique();
+        for (epoch, stake_state) in &[
+            (1, ValidatorStakeState::Baseline),
+            (2, ValidatorStakeState::Bonus),
+        ] {
+            db.save(
+                *epoch,
+                &EpochClassification::new(EpochClassificationV2 {
+                    validator_classifications: Some(
+                        vec![(
+                            identity,
+                            ValidatorClassification {
+                                identity,
+                                stake_state: *stake_state,
+                                ..ValidatorClassification::default()
+                            },
+                        )]
+                        .into_iter()
+                        .collect(),
+                    ),
+                    ..EpochClassificationV2::default()
+                }),
+            )
+            .unwrap();
+        }
+
+        let get_json = |path: &str| {
+            let response = get(&db, path).unwrap();
+            (
+                response.status,
+                serde_json::from_str::<serde_json::Value>(&response.body).unwrap(),
+            )
+        };
+
+        assert_eq!(get_json("/epochs"), (200, serde_json::json!([1, 2])));
+
+        let (status, epoch) = get_json("/epochs/2");
+        assert_eq!(status, 200);
+        assert_eq!(
+            epoch["validators"][identity.to_string()]["stake-state"],
+            "Bonus"
+        );
+        assert_eq!(get_json("/epochs/3").0, 404);
+        assert_eq!(get_json("/epochs/three").0, 400);
+
+        let (status, history) = get_json(&format!("/validators/{}", identity));
+        assert_eq!(status, 200);
+        assert_eq!(history[0]["epoch"], 2);
+        assert_eq!(history[1]["epoch"], 1);
+        assert_eq!(history[1]["identity"], identity.to_string());
+
+        let (status, latest) = get_json(&format!("/validators/{}/latest", identity));
+        assert_eq!(status, 200);
+        assert_eq!(latest["epoch"], 2);
+        assert_eq!(latest["stake-state"], "Bonus");
+
+        assert_eq!(
+            get_json(&format!("/validators/{}", Pubkey::new_unique())).0,
+            404
+        );
+        assert_eq!(get_json("/validators/invalid").0, 400);
+        assert_eq!(get_json("/unknown").0, 404);
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_request() {
+        assert_eq!(
+            read_request(&b"GET /epochs HTTP/1.1\r\nHost: localhost\r\n\r\n"[..]).unwrap(),
+            "GET /epochs HTTP/1.1\r\n"
+        );
+        assert_eq!(read_request(&b""[..]).unwrap(), "");
+
+        let long_request = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(10_000));
+        assert!(read_request(long_request.as_bytes()).is_err());
+        let long_header = format!("GET / HTTP/1.1\r\nX: {}\r\n\r\n", "a".repeat(10_000));
+        assert!(read_request(long_header.as_bytes()).is_err());
+    }
+}
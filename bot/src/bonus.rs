@@ -0,0 +1,372 @@
+use {
+    crate::db::ValidatorClassification,
+    solana_sdk::{
+        native_token::{Sol, LAMPORTS_PER_SOL},
+        pubkey::Pubkey,
+    },
+    std::collections::HashMap,
+    thiserror::Error,
+};
+
+/// Names of the built-in bonus weightings
+pub const BONUS_WEIGHTINGS: &[&str] = &[
+    "equal",
+    "performance",
+    "decentralization",
+    "inverse-active-stake",
+];
+
+/// Decides how the bonus stake is shared among the Bonus validators
+pub trait BonusWeighting {
+    /// Name used to select the weighting with `--bonus-weighting`
+    fn name(&self) -> &'static str;
+
+    /// The share of each of the Bonus `validators`, in the same order, relative to the others.
+    /// Weights are non-negative, and zero for a validator missing the metrics the weighting needs
+    fn weights(&self, validators: &[&ValidatorClassification]) -> Vec<f64>;
+}
+
+#[derive(Debug, Error)]
+#[error("unknown bonus weighting: {0}")]
+pub struct UnknownBonusWeightingError(String);
+
+pub fn bonus_weighting_from_name(
+    name: &str,
+) -> Result<Box<dyn BonusWeighting>, UnknownBonusWeightingError> {
+    Ok(match name {
+        "equal" => Box::new(Equal),
+        "performance" => Box::new(Performance),
+        "decentralization" => Box::new(Decentralization),
+        "inverse-active-stake" => Box::new(InverseActiveStake),
+        _ => return Err(UnknownBonusWeightingError(name.to_string())),
+    })
+}
+
+/// Every Bonus validator receives the same amount
+struct Equal;
+
+impl BonusWeighting for Equal {
+    fn name(&self) -> &'static str {
+        "equal"
+    }
+
+    fn weights(&self, validators: &[&ValidatorClassification]) -> Vec<f64> {
+        vec![1.; validators.len()]
+    }
+}
+
+/// Validators that skip fewer leader slots and earn more vote credits receive more.  The weight is
+/// the fraction of leader slots produced times the vote credits relative to the best voter
+struct Performance;
+
+impl BonusWeighting for Performance {
+    fn name(&self) -> &'static str {
+        "performance"
+    }
+
+    fn weights(&self, validators: &[&ValidatorClassification]) -> Vec<f64> {
+        let epoch_credits = |validator: &ValidatorClassification| {
+            validator
+                .metrics
+                .as_ref()
+                .and_then(|metrics| metrics.epoch_credits)
+                .unwrap_or_default()
+        };
+        let max_epoch_credits = validators
+            .iter()
+            .map(|validator| epoch_credits(validator))
+            .max()
+            .unwrap_or_default()
+            .max(1);
+
+        validators
+            .iter()
+            .map(|validator| {
+                // Validators that were not in the leader schedule are not penalized
+                let skip_rate = validator
+                    .metrics
+                    .as_ref()
+                    .and_then(|metrics| metrics.skip_rate)
+                    .unwrap_or_default()
+                    .min(100);
+                (100 - skip_rate) as f64 / 100.
+                    * (epoch_credits(validator) as f64 / max_epoch_credits as f64)
+            })
+            .collect()
+    }
+}
+
+/// Validators in data centers holding less of the cluster stake receive more.  Validators in an
+/// unknown data center receive nothing
+struct Decentralization;
+
+impl BonusWeighting for Decentralization {
+    fn name(&self) -> &'static str {
+        "decentralization"
+    }
+
+    fn weights(&self, validators: &[&ValidatorClassification]) -> Vec<f64> {
+        validators
+            .iter()
+            .map(|validator| {
+                validator
+                    .metrics
+                    .as_ref()
+                    .and_then(|metrics| metrics.data_center_stake_percent)
+                    .map(|data_center_stake_percent| (100. - data_center_stake_percent).max(0.))
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+}
+
+/// Validators with less active stake from outside of the stake pool receive more, so that the
+/// bonus stake does not count against the validators it was given to.  Validators with unknown
+/// active stake receive nothing
+struct InverseActiveStake;
+
+impl BonusWeighting for InverseActiveStake {
+    fn name(&self) -> &'static str {
+        "inverse-active-stake"
+    }
+
+    fn weights(&self, validators: &[&ValidatorClassification]) -> Vec<f64> {
+        validators
+            .iter()
+            .map(|validator| {
+                let metrics = match validator.metrics.as_ref() {
+                    Some(metrics) => metrics,
+                    None => return 0.,
+                };
+                match metrics.active_stake {
+                    Some(active_stake) => {
+                        let active_stake =
+                            active_stake.saturating_sub(metrics.pool_stake.unwrap_or_default());
+                        // Anything under 1 SOL is treated as 1 SOL to keep the weights bounded
+                        LAMPORTS_PER_SOL as f64 / active_stake.max(LAMPORTS_PER_SOL) as f64
+                    }
+                    None => 0.,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Bounds on the stake of each Bonus validator, in lamports
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BonusStakeLimits {
+    /// Floor of the bonus stake amount, applied only while the bonus stake suffices to give every
+    /// Bonus validator at least this amount
+    pub min: u64,
+
+    /// Cap of the bonus stake amount.  Bonus stake in excess of the caps is left in the reserve
+    pub max: Option<u64>,
+}
+
+/// Share `total_bonus_stake_amount` among the Bonus validators in proportion to their weights,
+/// within `limits`.  If every weight is zero the bonus stake is shared equally
+pub fn bonus_stake_amounts(
+    total_bonus_stake_amount: u64,
+    validator_weights: &[(Pubkey, f64)],
+    limits: &BonusStakeLimits,
+) -> HashMap<Pubkey, u64> {
+    let mut bonus_stake_amounts = HashMap::new();
+    if validator_weights.is_empty() {
+        return bonus_stake_amounts;
+    }
+
+    let min =
+        if limits.min.saturating_mul(validator_weights.len() as u64) <= total_bonus_stake_amount {
+            limits.min
+        } else {
+            0
+        };
+    let max = limits.max.unwrap_or(u64::MAX).max(min);
+
+    // Validators whose proportional share falls outside of the limits are fixed at the limit, and
+    // the remaining stake is shared again among the others until every share is within limits
+    let mut remaining_stake_amount = total_bonus_stake_amount;
+    let mut unfixed = validator_weights.to_vec();
+    while !unfixed.is_empty() {
+        let total_weight = unfixed.iter().map(|(_, weight)| weight).sum::<f64>();
+        let shares = unfixed
+            .iter()
+            .map(|(identity, weight)| {
+                let share = if total_weight > 0. {
+                    remaining_stake_amount as f64 * weight / total_weight
+                } else {
+                    remaining_stake_amount as f64 / unfixed.len() as f64
+                };
+                (*identity, share as u64)
+            })
+            .collect::<Vec<_>>();
+
+        // Raise the shares under the floor first, since doing so can only lower the others
+        let below_min = shares
+            .iter()
+            .filter(|(_, share)| *share < min)
+            .map(|(identity, _)| *identity)
+            .collect::<Vec<_>>();
+        let (fixed, limit) = if !below_min.is_empty() {
+            (below_min, min)
+        } else {
+            (
+                shares
+                    .iter()
+                    .filter(|(_, share)| *share > max)
+                    .map(|(identity, _)| *identity)
+                    .collect::<Vec<_>>(),
+                max,
+            )
+        };
+
+        if fixed.is_empty() {
+            bonus_stake_amounts.extend(shares);
+            break;
+        }
+        for identity in fixed {
+            bonus_stake_amounts.insert(identity, limit);
+            remaining_stake_amount = remaining_stake_amount.saturating_sub(limit);
+            unfixed.retain(|(unfixed_identity, _)| *unfixed_identity != identity);
+        }
+    }
+    bonus_stake_amounts
+}
+
+/// Human readable summary of the bonus stake amounts
+pub fn describe_bonus_stake_amounts(bonus_stake_amounts: &HashMap<Pubkey, u64>) -> String {
+    let min = bonus_stake_amounts
+        .values()
+        .min()
+        .cloned()
+        .unwrap_or_default();
+    let max = bonus_stake_amounts
+        .values()
+        .max()
+        .cloned()
+        .unwrap_or_default();
+    if min == max {
+        format!("{}", Sol(min))
+    } else {
+        format!("{} to {}", Sol(min), Sol(max))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::db::ValidatorMetrics};
+
+    #[test]
+    fn test_weightings() {
+        let validator =
+            |skip_rate, epoch_credits, active_stake, pool_stake| ValidatorClassification {
+                metrics: Some(ValidatorMetrics {
+                    skip_rate,
+                    epoch_credits: Some(epoch_credits),
+                    active_stake: Some(active_stake),
+                    pool_stake: Some(pool_stake),
+                    data_center_stake_percent: Some(25.),
+                    ..ValidatorMetrics::default()
+                }),
+                ..ValidatorClassification::default()
+            };
+        let v1 = validator(Some(0), 100, 3 * LAMPORTS_PER_SOL, LAMPORTS_PER_SOL);
+        let v2 = validator(Some(50), 50, 0, 0);
+        let v3 = validator(None, 100, 4 * LAMPORTS_PER_SOL, 0);
+        // Missing metrics earn nothing rather than the best case
+        let v4 = ValidatorClassification::default();
+        let validators = [&v1, &v2, &v3, &v4];
+
+        for name in BONUS_WEIGHTINGS {
+            assert_eq!(bonus_weighting_from_name(name).unwrap().name(), *name);
+        }
+        assert!(bonus_weighting_from_name("unknown").is_err());
+
+        let weights = |name| {
+            bonus_weighting_from_name(name)
+                .unwrap()
+                .weights(&validators)
+        };
+        assert_eq!(weights("equal"), vec![1., 1., 1., 1.]);
+        assert_eq!(weights("performance"), vec![1., 0.25, 1., 0.]);
+        assert_eq!(weights("decentralization"), vec![75., 75., 75., 0.]);
+        // The stake delegated by the pool is not counted
+        assert_eq!(weights("inverse-active-stake"), vec![0.5, 1., 0.25, 0.]);
+    }
+
+    #[test]
+    fn test_bonus_stake_amounts() {
+        let v1 = Pubkey::new_unique();
+        let v2 = Pubkey::new_unique();
+        let v3 = Pubkey::new_unique();
+        let amounts = |weights: &[f64], limits| {
+            let amounts = bonus_stake_amounts(
+                1_000,
+                &[(v1, weights[0]), (v2, weights[1]), (v3, weights[2])],
+                &limits,
+            );
+            vec![amounts[&v1], amounts[&v2], amounts[&v3]]
+        };
+        let no_limits = BonusStakeLimits::default();
+
+        assert_eq!(amounts(&[1., 1., 2.], no_limits), vec![250, 250, 500]);
+        // Zero weights share equally
+        assert_eq!(amounts(&[0., 0., 0.], no_limits), vec![333, 333, 333]);
+
+        // The floor is taken from the other validators
+        assert_eq!(
+            amounts(
+                &[0., 1., 1.],
+                BonusStakeLimits {
+                    min: 100,
+                    max: None
+                }
+            ),
+            vec![100, 450, 450]
+        );
+        // ...unless it cannot be met for every validator
+        assert_eq!(
+            amounts(
+                &[0., 1., 1.],
+                BonusStakeLimits {
+                    min: 400,
+                    max: None
+                }
+            ),
+            vec![0, 500, 500]
+        );
+
+        // The excess over the cap is shared by the other validators
+        assert_eq!(
+            amounts(
+                &[1., 1., 8.],
+                BonusStakeLimits {
+                    min: 0,
+                    max: Some(400)
+                }
+            ),
+            vec![300, 300, 400]
+        );
+        // ...or left over if every validator is capped
+        assert_eq!(
+            amounts(
+                &[1., 1., 1.],
+                BonusStakeLimits {
+                    min: 0,
+                    max: Some(100)
+                }
+            ),
+            vec![100, 100, 100]
+        );
+
+        assert!(bonus_stake_amounts(1_000, &[], &no_limits).is_empty());
+        assert_eq!(
+            describe_bonus_stake_amounts(
+                &vec![(v1, LAMPORTS_PER_SOL), (v2, LAMPORTS_PER_SOL)]
+                    .into_iter()
+                    .collect()
+            ),
+            "◎1.000000000"
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use {
+    crate::{data_center_info::DataCenters, rpc_client_utils::VoteAccountInfo},
+    serde::{Deserialize, Serialize},
+    solana_sdk::{
+        clock::{Epoch, Slot},
+        pubkey::Pubkey,
+    },
+    std::{
+        collections::{HashMap, HashSet},
+        fs::{self, File},
+        io::{self, Write},
+        path::{Path, PathBuf},
+    },
+};
+
+/// Everything `classify()` observes about the cluster for an epoch. Recording a snapshot allows
+/// the classification to be reproduced later without network access
+#[derive(Default, Deserialize, Serialize)]
+pub struct ClusterSnapshot {
+    // The epoch being classified
+    pub epoch: Epoch,
+
+    // Validators that are eligible for classification
+    pub validator_list: HashSet<Pubkey>,
+
+    // Map of validator identity to staking program participant
+    pub identity_to_participant: HashMap<Pubkey, Pubkey>,
+
+    // Data center observations from validators.app. `None` if they were unavailable
+    pub data_centers: Option<DataCenters>,
+
+    // Vote account observations for the previous epoch
+    pub vote_account_info: Vec<VoteAccountInfo>,
+    pub total_active_stake: u64,
+
+    pub self_stake_by_vote_account: HashMap<Pubkey, u64>,
+
+    // Lamports delegated to each vote account by the stake pool. Empty in snapshots recorded
+    // before it was observed
+    #[serde(default)]
+    pub pool_stake_by_vote_account: HashMap<Pubkey, u64>,
+
+    // Release version reported by each cluster node, by identity
+    pub cluster_node_versions: HashMap<Pubkey, String>,
+
+    // Block production inputs for the previous epoch
+    pub first_slot_in_epoch: Slot,
+    pub confirmed_blocks: HashSet<Slot>,
+    pub leader_schedule: HashMap<String, Vec<usize>>,
+
+    // Map of participant to whether they met the testnet participation requirement.
+    // `None` if testnet participation was not checked
+    pub testnet_participation: Option<HashMap<Pubkey, bool>>,
+}
+
+impl ClusterSnapshot {
+    pub fn file_name<P>(epoch: Epoch, path: P) -> PathBuf
+    where
+        P: AsRef<Path>,
+    {
+        path.as_ref()
+            .join(format!("cluster-snapshot-epoch-{}.yml", epoch))
+    }
+
+    pub fn load<P>(file_name: P) -> Result<Self, io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::open(file_name)?;
+        serde_yaml::from_reader(file)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, format!("{:?}", err)))
+    }
+
+    // Saves the snapshot into the directory `path`, returning the name of the file written
+    pub fn save<P>(&self, path: P) -> Result<PathBuf, io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        let serialized = serde_yaml::to_string(self)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, format!("{:?}", err)))?;
+
+        fs::create_dir_all(&path)?;
+        let file_name = Self::file_name(self.epoch, path);
+        let mut file = File::create(&file_name)?;
+        file.write_all(&serialized.into_bytes())?;
+
+        Ok(file_name)
+    }
+}
@@ -0,0 +1,525 @@
+use {
+    crate::{db::DbBackend, BoxResult, Cluster},
+    clap::{value_t, values_t, ArgMatches},
+    serde::{Deserialize, Serialize},
+    solana_sdk::{
+        pubkey::Pubkey,
+        signature::{read_keypair_file, Keypair, Signer},
+    },
+    std::{
+        collections::HashMap,
+        fs::File,
+        io,
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
+};
+
+/// Settings of the `stake-pool-v0` subcommand
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct StakePoolV0Settings {
+    // Path to the keypair of the authorized staker
+    pub authorized_staker: Option<String>,
+
+    // Reserve stake account address, or path to its keypair
+    pub reserve_stake_address: Option<String>,
+
+    // Amounts in SOL
+    pub min_reserve_stake_balance: Option<f64>,
+    pub baseline_stake_amount: Option<f64>,
+}
+
+impl StakePoolV0Settings {
+    fn from_matches(matches: &ArgMatches<'_>, with_defaults: bool) -> Self {
+        Self {
+            authorized_staker: string_of(matches, "authorized_staker", with_defaults),
+            reserve_stake_address: string_of(matches, "reserve_stake_address", with_defaults),
+            min_reserve_stake_balance: value_of(
+                matches,
+                "min_reserve_stake_balance",
+                with_defaults,
+            ),
+            baseline_stake_amount: value_of(matches, "baseline_stake_amount", with_defaults),
+        }
+    }
+
+    fn or(self, other: Self) -> Self {
+        Self {
+            authorized_staker: self.authorized_staker.or(other.authorized_staker),
+            reserve_stake_address: self.reserve_stake_address.or(other.reserve_stake_address),
+            min_reserve_stake_balance: self
+                .min_reserve_stake_balance
+                .or(other.min_reserve_stake_balance),
+            baseline_stake_amount: self.baseline_stake_amount.or(other.baseline_stake_amount),
+        }
+    }
+}
+
+/// Settings of the `stake-pool` subcommand
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct StakePoolSettings {
+    // Stake pool address, or path to its keypair
+    pub pool_address: Option<String>,
+
+    // Path to the keypair of the authorized staker
+    pub authorized_staker: Option<String>,
+
+    // Amount in SOL
+    pub baseline_stake_amount: Option<f64>,
+}
+
+impl StakePoolSettings {
+    fn from_matches(matches: &ArgMatches<'_>, with_defaults: bool) -> Self {
+        Self {
+            pool_address: string_of(matches, "pool_address", with_defaults),
+            authorized_staker: string_of(matches, "authorized_staker", with_defaults),
+            baseline_stake_amount: value_of(matches, "baseline_stake_amount", with_defaults),
+        }
+    }
+
+    fn or(self, other: Self) -> Self {
+        Self {
+            pool_address: self.pool_address.or(other.pool_address),
+            authorized_staker: self.authorized_staker.or(other.authorized_staker),
+            baseline_stake_amount: self.baseline_stake_amount.or(other.baseline_stake_amount),
+        }
+    }
+}
+
+/// A set of bot settings, each corresponding to the command-line argument of the same name.
+/// `None` fields are unset and defer to the next source of settings
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Profile {
+    pub url: Option<String>,
+    pub db_path: Option<PathBuf>,
+    pub db_backend: Option<DbBackend>,
+    pub confirm: Option<bool>,
+    pub markdown: Option<bool>,
+    pub html_report: Option<bool>,
+    pub report_templates: Option<PathBuf>,
+    pub rebuild_reports: Option<bool>,
+    pub require_classification: Option<bool>,
+    pub quality_block_producer_percentage: Option<usize>,
+    pub bad_cluster_average_skip_rate: Option<usize>,
+    pub max_poor_block_producer_percentage: Option<usize>,
+    pub min_epoch_credit_percentage_of_average: Option<usize>,
+    pub max_commission: Option<u8>,
+    pub min_release_version: Option<String>,
+    pub max_poor_voter_percentage: Option<usize>,
+    pub max_old_release_version_percentage: Option<usize>,
+    pub confirmed_block_cache_path: Option<PathBuf>,
+    pub max_infrastructure_concentration: Option<f64>,
+    pub infrastructure_concentration_affects: Option<String>,
+
+    // Amounts in SOL
+    pub min_self_stake: Option<f64>,
+    pub max_active_stake: Option<f64>,
+
+    pub enforce_min_self_stake: Option<bool>,
+    pub min_testnet_participation: Option<(/*n:*/ usize, /*m:*/ usize)>,
+    pub enforce_testnet_participation: Option<bool>,
+    pub criteria: Option<Vec<String>>,
+    pub bonus_weighting: Option<String>,
+
+    // Amounts in SOL
+    pub min_bonus_stake: Option<f64>,
+    pub max_bonus_stake: Option<f64>,
+
+    pub max_stake_change: Option<String>,
+    pub downgrade_epochs: Option<usize>,
+    pub stake_tiers: Option<PathBuf>,
+    pub save_snapshot: Option<PathBuf>,
+    pub export_plan: Option<PathBuf>,
+    pub metrics_file: Option<PathBuf>,
+    pub notify: Option<Vec<String>>,
+    pub validator_contacts: Option<PathBuf>,
+    pub smtp_server: Option<String>,
+    pub smtp_from: Option<String>,
+
+    pub stake_pool_v0: Option<StakePoolV0Settings>,
+    pub stake_pool: Option<StakePoolSettings>,
+}
+
+impl Profile {
+    /// Collect the settings provided on the command-line. Unless `with_defaults` is true,
+    /// arguments that were not explicitly provided are left unset so that they do not mask
+    /// settings from a config file
+    pub fn from_matches(matches: &ArgMatches<'_>, with_defaults: bool) -> Self {
+        // A flag set in a config file is cleared by its `--no-` counterpart
+        let flag_of = |name: &str| {
+            if matches.is_present(name) {
+                Some(true)
+            } else if matches.is_present(&format!("no_{}", name)) {
+                Some(false)
+            } else {
+                None
+            }
+        };
+
+        let (stake_pool_v0, stake_pool) = match matches.subcommand() {
+            ("stake-pool-v0", Some(matches)) => (
+                Some(StakePoolV0Settings::from_matches(matches, with_defaults)),
+                None,
+            ),
+            ("stake-pool", Some(matches)) => (
+                None,
+                Some(StakePoolSettings::from_matches(matches, with_defaults)),
+            ),
+            _ => (None, None),
+        };
+
+        Self {
+            url: string_of(matches, "json_rpc_url", with_defaults),
+            db_path: value_of(matches, "db_path", with_defaults),
+            db_backend: value_of(matches, "db_backend", with_defaults),
+            confirm: flag_of("confirm"),
+            markdown: flag_of("markdown"),
+            html_report: flag_of("html_report"),
+            report_templates: value_of(matches, "report_templates", with_defaults),
+            rebuild_reports: flag_of("rebuild_reports"),
+            require_classification: flag_of("require_classification"),
+            quality_block_producer_percentage: value_of(
+                matches,
+                "quality_block_producer_percentage",
+                with_defaults,
+            ),
+            bad_cluster_average_skip_rate: value_of(
+                matches,
+                "bad_cluster_average_skip_rate",
+                with_defaults,
+            ),
+            max_poor_block_producer_percentage: value_of(
+                matches,
+                "max_poor_block_producer_percentage",
+                with_defaults,
+            ),
+            min_epoch_credit_percentage_of_average: value_of(
+                matches,
+                "min_epoch_credit_percentage_of_average",
+                with_defaults,
+            ),
+            max_commission: value_of(matches, "max_commission", with_defaults),
+            min_release_version: string_of(matches, "min_release_version", with_defaults),
+            max_poor_voter_percentage: value_of(
+                matches,
+                "max_poor_voter_percentage",
+                with_defaults,
+            ),
+            max_old_release_version_percentage: value_of(
+                matches,
+                "max_old_release_version_percentage",
+                with_defaults,
+            ),
+            confirmed_block_cache_path: value_of(
+                matches,
+                "confirmed_block_cache_path",
+                with_defaults,
+            ),
+            max_infrastructure_concentration: value_of(
+                matches,
+                "max_infrastructure_concentration",
+                with_defaults,
+            ),
+            infrastructure_concentration_affects: string_of(
+                matches,
+                "infrastructure_concentration_affects",
+                with_defaults,
+            ),
+            min_self_stake: value_of(matches, "min_self_stake", with_defaults),
+            max_active_stake: value_of(matches, "max_active_stake", with_defaults),
+            enforce_min_self_stake: flag_of("enforce_min_self_stake"),
+            min_testnet_participation: if provided(matches, "min_testnet_participation", false) {
+                values_t!(matches, "min_testnet_participation", usize)
+                    .ok()
+                    .map(|v| (v[0], v[1]))
+            } else {
+                None
+            },
+            enforce_testnet_participation: flag_of("enforce_testnet_participation"),
+            criteria: if provided(matches, "criteria", false) {
+                values_t!(matches, "criteria", String).ok()
+            } else {
+                None
+            },
+            bonus_weighting: string_of(matches, "bonus_weighting", with_defaults),
+            min_bonus_stake: value_of(matches, "min_bonus_stake", with_defaults),
+            max_bonus_stake: value_of(matches, "max_bonus_stake", with_defaults),
+            max_stake_change: string_of(matches, "max_stake_change", with_defaults),
+            downgrade_epochs: value_of(matches, "downgrade_epochs", with_defaults),
+            stake_tiers: value_of(matches, "stake_tiers", with_defaults),
+            save_snapshot: value_of(matches, "save_snapshot", with_defaults),
+            export_plan: value_of(matches, "export_plan", with_defaults),
+            metrics_file: value_of(matches, "metrics_file", with_defaults),
+            notify: if provided(matches, "notify", false) {
+                values_t!(matches, "notify", String).ok()
+            } else {
+                None
+            },
+            validator_contacts: value_of(matches, "validator_contacts", with_defaults),
+            smtp_server: string_of(matches, "smtp_server", with_defaults),
+            smtp_from: string_of(matches, "smtp_from", with_defaults),
+            stake_pool_v0,
+            stake_pool,
+        }
+    }
+
+    /// Merge two profiles, preferring the settings of `self`
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            url: self.url.or(other.url),
+            db_path: self.db_path.or(other.db_path),
+            db_backend: self.db_backend.or(other.db_backend),
+            confirm: self.confirm.or(other.confirm),
+            markdown: self.markdown.or(other.markdown),
+            html_report: self.html_report.or(other.html_report),
+            report_templates: self.report_templates.or(other.report_templates),
+            rebuild_reports: self.rebuild_reports.or(other.rebuild_reports),
+            require_classification: self.require_classification.or(other.require_classification),
+            quality_block_producer_percentage: self
+                .quality_block_producer_percentage
+                .or(other.quality_block_producer_percentage),
+            bad_cluster_average_skip_rate: self
+                .bad_cluster_average_skip_rate
+                .or(other.bad_cluster_average_skip_rate),
+            max_poor_block_producer_percentage: self
+                .max_poor_block_producer_percentage
+                .or(other.max_poor_block_producer_percentage),
+            min_epoch_credit_percentage_of_average: self
+                .min_epoch_credit_percentage_of_average
+                .or(other.min_epoch_credit_percentage_of_average),
+            max_commission: self.max_commission.or(other.max_commission),
+            min_release_version: self.min_release_version.or(other.min_release_version),
+            max_poor_voter_percentage: self
+                .max_poor_voter_percentage
+                .or(other.max_poor_voter_percentage),
+            max_old_release_version_percentage: self
+                .max_old_release_version_percentage
+                .or(other.max_old_release_version_percentage),
+            confirmed_block_cache_path: self
+                .confirmed_block_cache_path
+                .or(other.confirmed_block_cache_path),
+            max_infrastructure_concentration: self
+                .max_infrastructure_concentration
+                .or(other.max_infrastructure_concentration),
+            infrastructure_concentration_affects: self
+                .infrastructure_concentration_affects
+                .or(other.infrastructure_concentration_affects),
+            min_self_stake: self.min_self_stake.or(other.min_self_stake),
+            max_active_stake: self.max_active_stake.or(other.max_active_stake),
+            enforce_min_self_stake: self.enforce_min_self_stake.or(other.enforce_min_self_stake),
+            min_testnet_participation: self
+                .min_testnet_participation
+                .or(other.min_testnet_participation),
+            enforce_testnet_participation: self
+                .enforce_testnet_participation
+                .or(other.enforce_testnet_participation),
+            criteria: self.criteria.or(other.criteria),
+            bonus_weighting: self.bonus_weighting.or(other.bonus_weighting),
+            min_bonus_stake: self.min_bonus_stake.or(other.min_bonus_stake),
+            max_bonus_stake: self.max_bonus_stake.or(other.max_bonus_stake),
+            max_stake_change: self.max_stake_change.or(other.max_stake_change),
+            downgrade_epochs: self.downgrade_epochs.or(other.downgrade_epochs),
+            stake_tiers: self.stake_tiers.or(other.stake_tiers),
+            save_snapshot: self.save_snapshot.or(other.save_snapshot),
+            export_plan: self.export_plan.or(other.export_plan),
+            metrics_file: self.metrics_file.or(other.metrics_file),
+            notify: self.notify.or(other.notify),
+            validator_contacts: self.validator_contacts.or(other.validator_contacts),
+            smtp_server: self.smtp_server.or(other.smtp_server),
+            smtp_from: self.smtp_from.or(other.smtp_from),
+            stake_pool_v0: match (self.stake_pool_v0, other.stake_pool_v0) {
+                (Some(a), Some(b)) => Some(a.or(b)),
+                (a, b) => a.or(b),
+            },
+            stake_pool: match (self.stake_pool, other.stake_pool) {
+                (Some(a), Some(b)) => Some(a.or(b)),
+                (a, b) => a.or(b),
+            },
+        }
+    }
+}
+
+/// Contents of the file provided with `--config`
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ConfigFile {
+    // Cluster to operate on when `--cluster` is not provided
+    pub cluster: Option<Cluster>,
+
+    // Settings shared by all clusters
+    pub defaults: Profile,
+
+    // Settings for a specific cluster, which take precedence over `defaults`
+    pub profiles: HashMap<Cluster, Profile>,
+}
+
+impl ConfigFile {
+    pub fn load<P>(file_name: P) -> Result<Self, io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::open(&file_name).map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("{}: {}", file_name.as_ref().display(), err),
+            )
+        })?;
+        serde_yaml::from_reader(file).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("{}: {}", file_name.as_ref().display(), err),
+            )
+        })
+    }
+
+    /// The settings for `cluster`
+    pub fn profile(&self, cluster: Cluster) -> Profile {
+        self.profiles
+            .get(&cluster)
+            .cloned()
+            .unwrap_or_default()
+            .or(self.defaults.clone())
+    }
+}
+
+/// The effective settings, as printed by `config show`
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct EffectiveConfig<'a> {
+    pub cluster: Cluster,
+    #[serde(flatten)]
+    pub profile: &'a Profile,
+}
+
+fn provided(matches: &ArgMatches<'_>, name: &str, with_defaults: bool) -> bool {
+    matches.occurrences_of(name) > 0 || (with_defaults && matches.is_present(name))
+}
+
+fn string_of(matches: &ArgMatches<'_>, name: &str, with_defaults: bool) -> Option<String> {
+    if provided(matches, name, with_defaults) {
+        matches.value_of(name).map(ToString::to_string)
+    } else {
+        None
+    }
+}
+
+fn value_of<T: FromStr>(matches: &ArgMatches<'_>, name: &str, with_defaults: bool) -> Option<T> {
+    if provided(matches, name, with_defaults) {
+        value_t!(matches, name, T).ok()
+    } else {
+        None
+    }
+}
+
+/// Fetch a setting that has no default value
+pub fn required<T>(name: &str, value: Option<T>) -> BoxResult<T> {
+    value.ok_or_else(|| {
+        format!(
+            "`{}` must be provided on the command-line or in the config file",
+            name
+        )
+        .into()
+    })
+}
+
+/// Check a percentage setting, which may have come from a config file and so was not
+/// validated by clap
+pub fn check_percentage(name: &str, value: f64) -> BoxResult<()> {
+    if (0. ..=100.).contains(&value) {
+        Ok(())
+    } else {
+        Err(format!("`{}` must be a percentage between 0 and 100", name).into())
+    }
+}
+
+pub fn keypair_of_setting(name: &str, value: Option<String>) -> BoxResult<Keypair> {
+    let path = required(name, value)?;
+    read_keypair_file(&path).map_err(|err| format!("`{}`: {}: {}", name, path, err).into())
+}
+
+pub fn pubkey_of_setting(name: &str, value: Option<String>) -> BoxResult<Pubkey> {
+    let value = required(name, value)?;
+    Pubkey::from_str(&value)
+        .or_else(|_| read_keypair_file(&value).map(|keypair| keypair.pubkey()))
+        .map_err(|_| format!("`{}`: invalid pubkey or keypair: {}", name, value).into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_profile_precedence() {
+        let config_file: ConfigFile = serde_yaml::from_str(
+            r#"
+cluster: mainnet-beta
+defaults:
+  db-path: /srv/db
+  max-commission: 10
+  stake-pool:
+    baseline-stake-amount: 5000
+profiles:
+  mainnet-beta:
+    max-commission: 5
+    min-testnet-participation: [5, 10]
+    stake-pool:
+      pool-address: 11111111111111111111111111111111
+"#,
+        )
+        .unwrap();
+        assert_eq!(config_file.cluster, Some(Cluster::MainnetBeta));
+
+        let profile = config_file.profile(Cluster::MainnetBeta);
+        assert_eq!(profile.db_path, Some(PathBuf::from("/srv/db")));
+        assert_eq!(profile.max_commission, Some(5));
+        assert_eq!(profile.min_testnet_participation, Some((5, 10)));
+        assert_eq!(
+            profile.stake_pool,
+            Some(StakePoolSettings {
+                pool_address: Some("11111111111111111111111111111111".to_string()),
+                authorized_staker: None,
+                baseline_stake_amount: Some(5000.),
+            })
+        );
+
+        let profile = config_file.profile(Cluster::Testnet);
+        assert_eq!(profile.max_commission, Some(10));
+        assert_eq!(profile.min_testnet_participation, None);
+
+        let command_line = Profile {
+            max_commission: Some(0),
+            ..Profile::default()
+        };
+        let profile = command_line.or(config_file.profile(Cluster::MainnetBeta));
+        assert_eq!(profile.max_commission, Some(0));
+        assert_eq!(profile.db_path, Some(PathBuf::from("/srv/db")));
+
+        let config_file = Profile {
+            confirm: Some(true),
+            ..Profile::default()
+        };
+        let command_line = Profile {
+            confirm: Some(false),
+            ..Profile::default()
+        };
+        assert_eq!(command_line.or(config_file.clone()).confirm, Some(false));
+        assert_eq!(Profile::default().or(config_file).confirm, Some(true));
+    }
+
+    #[test]
+    fn test_unknown_setting() {
+        assert!(serde_yaml::from_str::<ConfigFile>("defaults:\n  max-comission: 10\n").is_err());
+        assert!(serde_yaml::from_str::<ConfigFile>("profiles:\n  devnet: {}\n").is_err());
+    }
+
+    #[test]
+    fn test_check_percentage() {
+        assert!(check_percentage("p", 0.).is_ok());
+        assert!(check_percentage("p", 100.).is_ok());
+        assert!(check_percentage("p", 100.5).is_err());
+        assert!(check_percentage("p", -1.).is_err());
+    }
+}
@@ -0,0 +1,449 @@
+use {
+    crate::{
+        generic_stake_pool::ValidatorStakeState, Config, InfrastructureConcentrationAffectKind,
+        ValidatorList,
+    },
+    solana_sdk::{clock::Epoch, native_token::Sol, pubkey::Pubkey},
+    std::collections::HashMap,
+    thiserror::Error,
+};
+
+/// Names of the built-in criteria, in their default order of evaluation
+pub const DEFAULT_CRITERIA: &[&str] = &[
+    "infrastructure-concentration",
+    "self-stake",
+    "active-stake",
+    "commission",
+    "testnet-participation",
+    "vote-credits",
+    "release-version",
+    "block-production",
+];
+
+/// The result of evaluating a validator against a single criterion
+#[derive(Debug, Clone, PartialEq)]
+pub enum CriterionOutcome {
+    /// The validator satisfies the criterion. The optional reason describes the resulting stake
+    /// level if no other criterion overrides it
+    Pass(Option<String>),
+
+    /// The validator satisfies the criterion, but a note is attached to its classification
+    Warn(String),
+
+    /// The validator is limited to the Baseline stake level
+    Baseline(String),
+
+    /// The validator should receive no stake
+    Destake(String),
+}
+
+/// Cluster-wide observations shared by every criterion during an epoch classification
+pub struct ClusterContext<'a> {
+    pub config: &'a Config,
+
+    /// The epoch that the block production and voting observations were made in
+    pub last_epoch: Epoch,
+
+    /// Validators in a data center with too much stake, and that data center's stake percent
+    pub infrastructure_concentration_too_high: HashMap<Pubkey, f64>,
+
+    /// Map of participant to whether they meet the testnet participation requirement.
+    /// `None` if testnet participation is not being checked
+    pub testnet_participation: Option<HashMap<Pubkey, bool>>,
+
+    pub poor_voters: ValidatorList,
+
+    /// Validators running a release older than `Config::min_release_version`, keyed by identity
+    pub cluster_nodes_with_old_version: HashMap<String, semver::Version>,
+
+    pub quality_block_producers: ValidatorList,
+    pub poor_block_producers: ValidatorList,
+    pub block_producer_classification_reason: HashMap<Pubkey, String>,
+}
+
+/// Observations of the validator being classified
+#[derive(Debug, Default, Clone)]
+pub struct ValidatorContext {
+    pub identity: Pubkey,
+    pub vote_address: Pubkey,
+    pub commission: u8,
+    pub active_stake: u64,
+
+    /// Credits earned in `ClusterContext::last_epoch`
+    pub epoch_credits: u64,
+    pub self_stake: u64,
+    pub participant: Option<Pubkey>,
+
+    /// True if the validator has no residency history in its current data center
+    pub new_data_center: bool,
+
+    /// The validator's stake state in the previous classification
+    pub previous_stake_state: ValidatorStakeState,
+}
+
+impl ValidatorContext {
+    fn vote_credits_msg(&self, cluster: &ClusterContext) -> String {
+        format!(
+            "{} credits earned in epoch {}",
+            self.epoch_credits, cluster.last_epoch
+        )
+    }
+}
+
+/// A single check that contributes to a validator's stake state
+pub trait ValidatorCriterion {
+    /// Name used to select the criterion with `--criteria`
+    fn name(&self) -> &'static str;
+
+    fn evaluate(&self, cluster: &ClusterContext, validator: &ValidatorContext) -> CriterionOutcome;
+}
+
+#[derive(Debug, Error)]
+#[error("unknown classification criterion: {0}")]
+pub struct UnknownCriterionError(String);
+
+pub fn criterion_from_name(
+    name: &str,
+) -> Result<Box<dyn ValidatorCriterion>, UnknownCriterionError> {
+    Ok(match name {
+        "infrastructure-concentration" => Box::new(InfrastructureConcentration),
+        "self-stake" => Box::new(SelfStake),
+        "active-stake" => Box::new(ActiveStake),
+        "commission" => Box::new(Commission),
+        "testnet-participation" => Box::new(TestnetParticipation),
+        "vote-credits" => Box::new(VoteCredits),
+        "release-version" => Box::new(ReleaseVersion),
+        "block-production" => Box::new(BlockProduction),
+        _ => return Err(UnknownCriterionError(name.to_string())),
+    })
+}
+
+pub fn criteria_from_names<S: AsRef<str>>(
+    names: &[S],
+) -> Result<Vec<Box<dyn ValidatorCriterion>>, UnknownCriterionError> {
+    names
+        .iter()
+        .map(|name| criterion_from_name(name.as_ref()))
+        .collect()
+}
+
+/// Evaluate every criterion in order and combine the outcomes into a stake state, the reason for
+/// that stake state, and any notes.
+///
+/// The first `Destake` outcome wins, followed by the first `Baseline` outcome. Otherwise the
+/// validator earns the Bonus stake level. All criteria are always evaluated so that every
+/// warning is collected.
+pub fn evaluate_criteria(
+    criteria: &[Box<dyn ValidatorCriterion>],
+    cluster: &ClusterContext,
+    validator: &ValidatorContext,
+) -> (ValidatorStakeState, String, Vec<String>) {
+    let mut notes = vec![];
+    let mut destake_reason = None;
+    let mut baseline_reason = None;
+    let mut pass_reason = None;
+
+    for criterion in criteria {
+        match criterion.evaluate(cluster, validator) {
+            CriterionOutcome::Pass(reason) => pass_reason = pass_reason.or(reason),
+            CriterionOutcome::Warn(note) => notes.push(note),
+            CriterionOutcome::Baseline(reason) => {
+                baseline_reason = baseline_reason.or(Some(reason))
+            }
+            CriterionOutcome::Destake(reason) => destake_reason = destake_reason.or(Some(reason)),
+        }
+    }
+
+    let (stake_state, reason) = if let Some(reason) = destake_reason {
+        (ValidatorStakeState::None, reason)
+    } else if let Some(reason) = baseline_reason {
+        (ValidatorStakeState::Baseline, reason)
+    } else {
+        (
+            ValidatorStakeState::Bonus,
+            pass_reason.unwrap_or_else(|| "all classification criteria satisfied".to_string()),
+        )
+    };
+    (stake_state, reason, notes)
+}
+
+pub struct InfrastructureConcentration;
+
+impl ValidatorCriterion for InfrastructureConcentration {
+    fn name(&self) -> &'static str {
+        "infrastructure-concentration"
+    }
+
+    fn evaluate(&self, cluster: &ClusterContext, validator: &ValidatorContext) -> CriterionOutcome {
+        match cluster
+            .infrastructure_concentration_too_high
+            .get(&validator.identity)
+        {
+            Some(concentration) => match cluster.config.infrastructure_concentration_affects.memo(
+                &validator.identity,
+                validator.new_data_center,
+                *concentration,
+            ) {
+                InfrastructureConcentrationAffectKind::Destake(reason) => {
+                    CriterionOutcome::Destake(reason)
+                }
+                InfrastructureConcentrationAffectKind::Warn(reason) => {
+                    CriterionOutcome::Warn(reason)
+                }
+            },
+            None => CriterionOutcome::Pass(None),
+        }
+    }
+}
+
+pub struct SelfStake;
+
+impl ValidatorCriterion for SelfStake {
+    fn name(&self) -> &'static str {
+        "self-stake"
+    }
+
+    fn evaluate(&self, cluster: &ClusterContext, validator: &ValidatorContext) -> CriterionOutcome {
+        if validator.self_stake < cluster.config.min_self_stake_lamports {
+            let msg = format!("Insufficient self stake: {}", Sol(validator.self_stake));
+            if cluster.config.enforce_min_self_stake {
+                CriterionOutcome::Destake(msg)
+            } else {
+                CriterionOutcome::Warn(msg)
+            }
+        } else {
+            CriterionOutcome::Pass(None)
+        }
+    }
+}
+
+pub struct ActiveStake;
+
+impl ValidatorCriterion for ActiveStake {
+    fn name(&self) -> &'static str {
+        "active-stake"
+    }
+
+    fn evaluate(&self, cluster: &ClusterContext, validator: &ValidatorContext) -> CriterionOutcome {
+        if validator.active_stake > cluster.config.max_active_stake_lamports {
+            CriterionOutcome::Destake(format!(
+                "active stake is too high: {}",
+                Sol(validator.active_stake)
+            ))
+        } else {
+            CriterionOutcome::Pass(None)
+        }
+    }
+}
+
+pub struct Commission;
+
+impl ValidatorCriterion for Commission {
+    fn name(&self) -> &'static str {
+        "commission"
+    }
+
+    fn evaluate(&self, cluster: &ClusterContext, validator: &ValidatorContext) -> CriterionOutcome {
+        if validator.commission > cluster.config.max_commission {
+            CriterionOutcome::Destake(format!(
+                "commission is too high: {}% commission",
+                validator.commission
+            ))
+        } else {
+            CriterionOutcome::Pass(None)
+        }
+    }
+}
+
+pub struct TestnetParticipation;
+
+impl ValidatorCriterion for TestnetParticipation {
+    fn name(&self) -> &'static str {
+        "testnet-participation"
+    }
+
+    fn evaluate(&self, cluster: &ClusterContext, validator: &ValidatorContext) -> CriterionOutcome {
+        if let (Some(testnet_participation), Some(participant)) = (
+            cluster.testnet_participation.as_ref(),
+            validator.participant,
+        ) {
+            if !testnet_participation.get(&participant).unwrap_or(&true) {
+                let note = "Insufficient testnet participation".to_string();
+                return if cluster.config.enforce_testnet_participation {
+                    CriterionOutcome::Destake(note)
+                } else {
+                    CriterionOutcome::Warn(note)
+                };
+            }
+        }
+        CriterionOutcome::Pass(None)
+    }
+}
+
+pub struct VoteCredits;
+
+impl ValidatorCriterion for VoteCredits {
+    fn name(&self) -> &'static str {
+        "vote-credits"
+    }
+
+    fn evaluate(&self, cluster: &ClusterContext, validator: &ValidatorContext) -> CriterionOutcome {
+        if cluster.poor_voters.contains(&validator.identity) {
+            CriterionOutcome::Destake(format!(
+                "insufficient vote credits: {}",
+                validator.vote_credits_msg(cluster)
+            ))
+        } else {
+            CriterionOutcome::Pass(None)
+        }
+    }
+}
+
+pub struct ReleaseVersion;
+
+impl ValidatorCriterion for ReleaseVersion {
+    fn name(&self) -> &'static str {
+        "release-version"
+    }
+
+    fn evaluate(&self, cluster: &ClusterContext, validator: &ValidatorContext) -> CriterionOutcome {
+        match cluster
+            .cluster_nodes_with_old_version
+            .get(&validator.identity.to_string())
+        {
+            Some(version) => {
+                CriterionOutcome::Destake(format!("Outdated solana release: {}", version))
+            }
+            None => CriterionOutcome::Pass(None),
+        }
+    }
+}
+
+pub struct BlockProduction;
+
+impl ValidatorCriterion for BlockProduction {
+    fn name(&self) -> &'static str {
+        "block-production"
+    }
+
+    fn evaluate(&self, cluster: &ClusterContext, validator: &ValidatorContext) -> CriterionOutcome {
+        let block_producer_classification_reason_msg = cluster
+            .block_producer_classification_reason
+            .get(&validator.identity)
+            .cloned()
+            .unwrap_or_default();
+
+        if cluster
+            .quality_block_producers
+            .contains(&validator.identity)
+        {
+            CriterionOutcome::Pass(Some(format!(
+                "good block production during epoch {}: {}",
+                cluster.last_epoch, block_producer_classification_reason_msg
+            )))
+        } else if cluster.poor_block_producers.contains(&validator.identity) {
+            CriterionOutcome::Baseline(format!(
+                "poor block production during epoch {}: {}",
+                cluster.last_epoch, block_producer_classification_reason_msg
+            ))
+        } else {
+            let reason = format!("no leader slots; {}", validator.vote_credits_msg(cluster));
+
+            // If the validator is not in the leader schedule but was Bonus previously,
+            // maintain Bonus.
+            //
+            // Destaking due to delinquency will not be reflected in the leader schedule
+            // until 2 epochs later, which point the validator may have recovered and
+            // there's no need to punish the validator further by reducing it to the
+            // Baseline level.
+            if validator.previous_stake_state == ValidatorStakeState::Bonus {
+                CriterionOutcome::Pass(Some(reason))
+            } else {
+                CriterionOutcome::Baseline(reason)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct FixedOutcome(CriterionOutcome);
+
+    impl ValidatorCriterion for FixedOutcome {
+        fn name(&self) -> &'static str {
+            "fixed-outcome"
+        }
+
+        fn evaluate(&self, _: &ClusterContext, _: &ValidatorContext) -> CriterionOutcome {
+            self.0.clone()
+        }
+    }
+
+    fn cluster_context(config: &Config) -> ClusterContext {
+        ClusterContext {
+            config,
+            last_epoch: 0,
+            infrastructure_concentration_too_high: HashMap::default(),
+            testnet_participation: None,
+            poor_voters: ValidatorList::default(),
+            cluster_nodes_with_old_version: HashMap::default(),
+            quality_block_producers: ValidatorList::default(),
+            poor_block_producers: ValidatorList::default(),
+            block_producer_classification_reason: HashMap::default(),
+        }
+    }
+
+    #[test]
+    fn test_default_criteria_names() {
+        for name in DEFAULT_CRITERIA {
+            assert_eq!(criterion_from_name(name).unwrap().name(), *name);
+        }
+        assert!(criterion_from_name("bogus").is_err());
+    }
+
+    #[test]
+    fn test_evaluate_criteria() {
+        let config = Config::default_for_test();
+        let cluster = cluster_context(&config);
+        let validator = ValidatorContext::default();
+
+        let criteria: Vec<Box<dyn ValidatorCriterion>> = vec![
+            Box::new(FixedOutcome(CriterionOutcome::Warn("note".to_string()))),
+            Box::new(FixedOutcome(CriterionOutcome::Pass(Some(
+                "good".to_string(),
+            )))),
+        ];
+        assert_eq!(
+            evaluate_criteria(&criteria, &cluster, &validator),
+            (
+                ValidatorStakeState::Bonus,
+                "good".to_string(),
+                vec!["note".to_string()]
+            )
+        );
+
+        let criteria: Vec<Box<dyn ValidatorCriterion>> = vec![
+            Box::new(FixedOutcome(CriterionOutcome::Pass(Some(
+                "good".to_string(),
+            )))),
+            Box::new(FixedOutcome(CriterionOutcome::Baseline("poor".to_string()))),
+            Box::new(FixedOutcome(CriterionOutcome::Destake("bad".to_string()))),
+            Box::new(FixedOutcome(CriterionOutcome::Warn("note".to_string()))),
+        ];
+        assert_eq!(
+            evaluate_criteria(&criteria, &cluster, &validator),
+            (
+                ValidatorStakeState::None,
+                "bad".to_string(),
+                vec!["note".to_string()]
+            )
+        );
+
+        assert_eq!(
+            evaluate_criteria(&criteria[..2], &cluster, &validator).0,
+            ValidatorStakeState::Baseline
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use {
+    crate::BoxResult,
+    log::*,
+    serde::Serialize,
+    solana_client::rpc_client::RpcClient,
+    solana_sdk::clock::{Epoch, Slot, DEFAULT_MS_PER_SLOT},
+    std::{
+        fs,
+        path::{Path, PathBuf},
+        thread::sleep,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+};
+
+/// Longest sleep between checks of the epoch progress, which is also how often the health check
+/// file is rewritten
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+pub struct DaemonArgs {
+    /// Slots to wait after an epoch boundary before running, giving the cluster time to
+    /// activate the stake changes of the previous epoch
+    pub epoch_start_delay: Slot,
+
+    /// If Some(), kept updated with the state of the daemon for health checks
+    pub health_check_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RunKind {
+    /// Classify the epoch and apply the result
+    EpochStart,
+
+    /// Apply the classification again, funding validators whose transient stake was still
+    /// pending at the start of the epoch
+    MidEpoch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ScheduledRun {
+    kind: RunKind,
+    epoch: Epoch,
+
+    /// The run is due once the epoch reaches this slot index
+    slot_index: Slot,
+}
+
+impl ScheduledRun {
+    fn epoch_start(epoch: Epoch, epoch_start_delay: Slot) -> Self {
+        Self {
+            kind: RunKind::EpochStart,
+            epoch,
+            slot_index: epoch_start_delay,
+        }
+    }
+
+    /// The run that follows this one, which completed at `slot_index`
+    fn next(&self, slot_index: Slot, slots_in_epoch: u64, epoch_start_delay: Slot) -> Self {
+        let mid_epoch = Self {
+            kind: RunKind::MidEpoch,
+            epoch: self.epoch,
+            slot_index: (slots_in_epoch / 2).max(epoch_start_delay),
+        };
+        if self.kind == RunKind::EpochStart && slot_index < mid_epoch.slot_index {
+            mid_epoch
+        } else {
+            Self::epoch_start(self.epoch + 1, epoch_start_delay)
+        }
+    }
+}
+
+#[derive(Default, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Health {
+    /// Seconds since the Unix epoch that the file was written
+    updated: u64,
+
+    epoch: Epoch,
+    slot_index: Slot,
+    status: String,
+
+    last_run: Option<u64>,
+    last_run_epoch: Option<Epoch>,
+    last_error: Option<String>,
+}
+
+impl Health {
+    fn write(&mut self, path: &Path) -> BoxResult<()> {
+        self.updated = timestamp();
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, serde_json::to_string_pretty(self)?)
+            .map_err(|err| format!("Unable to write {}: {}", temp_path.display(), err))?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+}
+
+fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Call `run` immediately, then shortly after every epoch boundary and again midway through each
+/// epoch.  A failed run is logged and retried at the next scheduled run.  Never returns unless
+/// the health check file cannot be written
+pub fn run<F>(rpc_client: &RpcClient, args: &DaemonArgs, mut run: F) -> BoxResult<()>
+where
+    F: FnMut() -> BoxResult<()>,
+{
+    let mut health = Health::default();
+    let mut next_run = None;
+
+    loop {
+        let epoch_info = match rpc_client.get_epoch_info() {
+            Ok(epoch_info) => epoch_info,
+            Err(err) => {
+                warn!("Unable to get epoch info: {}", err);
+                health.status = format!("Unable to get epoch info: {}", err);
+                if let Some(ref health_check_path) = args.health_check_path {
+                    health.write(health_check_path)?;
+                }
+                sleep(POLL_INTERVAL);
+                continue;
+            }
+        };
+        health.epoch = epoch_info.epoch;
+        health.slot_index = epoch_info.slot_index;
+
+        let scheduled_run = match next_run {
+            // A run that was missed, such as while a previous run was in progress, is replaced by
+            // the start of the current epoch
+            Some(scheduled_run) if scheduled_run.epoch >= epoch_info.epoch => scheduled_run,
+            Some(_) => ScheduledRun::epoch_start(epoch_info.epoch, args.epoch_start_delay),
+            // Run immediately on startup
+            None => ScheduledRun::epoch_start(epoch_info.epoch, 0),
+        };
+
+        if scheduled_run.epoch == epoch_info.epoch
+            && epoch_info.slot_index >= scheduled_run.slot_index
+        {
+            info!(
+                "{:?} run of epoch {} at slot index {}",
+                scheduled_run.kind, epoch_info.epoch, epoch_info.slot_index
+            );
+            health.status = format!("{:?} run in progress", scheduled_run.kind);
+            if let Some(ref health_check_path) = args.health_check_path {
+                health.write(health_check_path)?;
+            }
+
+            health.last_error = match run() {
+                Ok(()) => None,
+                Err(err) => {
+                    error!("{:?} run failed: {}", scheduled_run.kind, err);
+                    Some(err.to_string())
+                }
+            };
+            health.last_run = Some(timestamp());
+            health.last_run_epoch = Some(epoch_info.epoch);
+
+            let slot_index = rpc_client
+                .get_epoch_info()
+                .map(|epoch_info| epoch_info.slot_index)
+                .unwrap_or(epoch_info.slot_index);
+            next_run = Some(scheduled_run.next(
+                slot_index,
+                epoch_info.slots_in_epoch,
+                args.epoch_start_delay,
+            ));
+            continue;
+        }
+        next_run = Some(scheduled_run);
+
+        let remaining_slots = if scheduled_run.epoch == epoch_info.epoch {
+            scheduled_run.slot_index - epoch_info.slot_index
+        } else {
+            epoch_info
+                .slots_in_epoch
+                .saturating_sub(epoch_info.slot_index)
+                + scheduled_run.slot_index
+        };
+        health.status = format!(
+            "Waiting {} slots for the {:?} run of epoch {}",
+            remaining_slots, scheduled_run.kind, scheduled_run.epoch
+        );
+        info!("{}", health.status);
+        if let Some(ref health_check_path) = args.health_check_path {
+            health.write(health_check_path)?;
+        }
+
+        sleep(POLL_INTERVAL.min(Duration::from_millis(
+            remaining_slots.saturating_mul(DEFAULT_MS_PER_SLOT),
+        )));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_scheduled_run_next() {
+        let epoch_start = ScheduledRun::epoch_start(10, 100);
+        assert_eq!(epoch_start.slot_index, 100);
+
+        // An epoch start run is followed by a mid-epoch run
+        let mid_epoch = epoch_start.next(150, 1_000, 100);
+        assert_eq!(
+            mid_epoch,
+            ScheduledRun {
+                kind: RunKind::MidEpoch,
+                epoch: 10,
+                slot_index: 500,
+            }
+        );
+
+        // ...unless the epoch start run completed after the middle of the epoch
+        assert_eq!(
+            epoch_start.next(600, 1_000, 100),
+            ScheduledRun::epoch_start(11, 100)
+        );
+
+        // A mid-epoch run is followed by the start of the next epoch
+        assert_eq!(
+            mid_epoch.next(550, 1_000, 100),
+            ScheduledRun::epoch_start(11, 100)
+        );
+
+        // The mid-epoch run never comes before the epoch start delay
+        assert_eq!(epoch_start.next(150, 100, 100).kind, RunKind::EpochStart);
+    }
+}
@@ -2,15 +2,19 @@ use {
     crate::{
         data_center_info::{DataCenterId, DataCenterInfo},
         generic_stake_pool::ValidatorStakeState,
+        sqlite_db::SqliteDb,
     },
+    lazy_static::lazy_static,
     log::*,
+    regex::Regex,
     serde::{Deserialize, Serialize},
-    solana_sdk::{clock::Epoch, pubkey::Pubkey},
+    solana_sdk::{clock::Epoch, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey},
     std::{
         collections::HashMap,
         fs::{self, File},
         io::{self, Write},
         path::{Path, PathBuf},
+        str::FromStr,
     },
 };
 
@@ -22,6 +26,9 @@ pub struct ValidatorClassification {
     pub stake_state: ValidatorStakeState,
     pub stake_state_reason: String,
 
+    // Name of the `tiers::StakeTier` refining `stake_state`, if any
+    pub stake_tier: Option<String>,
+
     // Summary of the action was taken this epoch to advance the validator's stake
     pub stake_action: Option<String>,
 
@@ -43,6 +50,109 @@ pub struct ValidatorClassification {
 
     // The validator was not funded this epoch and should be prioritized next epoch
     pub prioritize_funding_in_next_epoch: Option<bool>,
+
+    // Observations that the stake state was derived from. `None` for V1 classifications with
+    // reason text that could not be parsed
+    pub metrics: Option<ValidatorMetrics>,
+}
+
+/// Typed observations of a validator, as of the classification
+#[derive(Default, Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ValidatorMetrics {
+    // Lamports delegated to the vote account by the validator identity
+    pub self_stake: Option<u64>,
+
+    // Vote credits earned in the previous epoch
+    pub epoch_credits: Option<u64>,
+
+    // Blocks produced in, and leader slots assigned for, the previous epoch. `None` if the
+    // validator was not in the leader schedule
+    pub blocks: Option<usize>,
+    pub leader_slots: Option<usize>,
+
+    // Percentage of leader slots skipped in the previous epoch. `None` if the validator was not
+    // in the leader schedule
+    pub skip_rate: Option<usize>,
+
+    // Lamports actively staked to the vote account
+    pub active_stake: Option<u64>,
+
+    // Lamports of `active_stake` delegated by the stake pool
+    pub pool_stake: Option<u64>,
+
+    pub commission: Option<u8>,
+
+    pub release_version: Option<String>,
+
+    // Percentage of the cluster stake in the validator's current data center
+    pub data_center_stake_percent: Option<f64>,
+}
+
+// Converts the whole and fractional captures of a formatted `Sol` into lamports
+fn lamports_of_sol_captures(captures: &regex::Captures) -> Option<u64> {
+    captures[1]
+        .parse::<u64>()
+        .ok()
+        .zip(captures[2].parse::<u64>().ok())
+        .map(|(sol, lamports)| sol * LAMPORTS_PER_SOL + lamports)
+}
+
+// Patterns of the V1 reason text that `ValidatorMetrics::parse_v1` recovers metrics from
+lazy_static! {
+    static ref SELF_STAKE_REGEX: Regex =
+        Regex::new(r"Insufficient self stake: ◎(\d+)\.(\d{9})").unwrap();
+    static ref ACTIVE_STAKE_REGEX: Regex =
+        Regex::new(r"active stake is too high: ◎(\d+)\.(\d{9})").unwrap();
+    static ref BLOCKS_REGEX: Regex = Regex::new(r"(\d+) blocks in (\d+) slots").unwrap();
+    static ref EPOCH_CREDITS_REGEX: Regex = Regex::new(r"(\d+) credits earned in epoch").unwrap();
+    static ref SKIP_RATE_REGEX: Regex = Regex::new(r"(\d+)(?:\.\d+)?% skip rate").unwrap();
+    static ref COMMISSION_REGEX: Regex = Regex::new(r"(\d+)% commission").unwrap();
+    static ref RELEASE_VERSION_REGEX: Regex =
+        Regex::new(r"Outdated solana release: (\S+)").unwrap();
+}
+
+// Pattern of the epoch note that the stake pool records its size in
+lazy_static! {
+    static ref STAKE_POOL_SIZE_REGEX: Regex =
+        Regex::new(r"Stake pool size: ◎(\d+)\.(\d{9})").unwrap();
+}
+
+impl ValidatorMetrics {
+    // Recover what metrics are available from the free-text reasons and notes of a V1
+    // classification. Returns `None` if nothing could be recovered
+    fn parse_v1<'a>(texts: impl Iterator<Item = &'a str>) -> Option<Self> {
+        let mut metrics = Self::default();
+        for text in texts {
+            if let Some(captures) = SELF_STAKE_REGEX.captures(text) {
+                metrics.self_stake = lamports_of_sol_captures(&captures);
+            }
+            if let Some(captures) = ACTIVE_STAKE_REGEX.captures(text) {
+                metrics.active_stake = lamports_of_sol_captures(&captures);
+            }
+            if let Some(captures) = BLOCKS_REGEX.captures(text) {
+                metrics.blocks = captures[1].parse().ok();
+                metrics.leader_slots = captures[2].parse().ok();
+            }
+            if let Some(captures) = EPOCH_CREDITS_REGEX.captures(text) {
+                metrics.epoch_credits = captures[1].parse().ok();
+            }
+            if let Some(captures) = SKIP_RATE_REGEX.captures(text) {
+                metrics.skip_rate = captures[1].parse().ok();
+            }
+            if let Some(captures) = COMMISSION_REGEX.captures(text) {
+                metrics.commission = captures[1].parse().ok();
+            }
+            if let Some(captures) = RELEASE_VERSION_REGEX.captures(text) {
+                metrics.release_version = Some(captures[1].to_string());
+            }
+        }
+
+        if metrics == Self::default() {
+            None
+        } else {
+            Some(metrics)
+        }
+    }
 }
 
 impl ValidatorClassification {
@@ -57,6 +167,30 @@ impl ValidatorClassification {
         streak
     }
 
+    // The stake state to delegate for when a downgrade takes effect only after the validator has
+    // been classified at the lower stake state for `downgrade_epochs` consecutive epochs: the
+    // highest stake state of the last `downgrade_epochs` epochs
+    pub fn held_stake_state(&self, downgrade_epochs: usize) -> ValidatorStakeState {
+        self.stake_states
+            .as_ref()
+            .and_then(|stake_states| {
+                stake_states
+                    .iter()
+                    .take(downgrade_epochs.max(1))
+                    .map(|(stake_state, _)| *stake_state)
+                    .max_by_key(|stake_state| *stake_state as u8)
+            })
+            .unwrap_or(self.stake_state)
+    }
+
+    // The stake state, qualified by the stake tier if any. For example "Baseline (probation)"
+    pub fn stake_level(&self) -> String {
+        match self.stake_tier {
+            Some(ref stake_tier) => format!("{:?} ({})", self.stake_state, stake_tier),
+            None => format!("{:?}", self.stake_state),
+        }
+    }
+
     // Was the validator staked for at last `n` of the last `m` epochs?
     pub fn staked_for(&self, n: usize, m: usize) -> bool {
         self.stake_states
@@ -88,105 +222,236 @@ pub struct EpochClassificationV1 {
     pub notes: Vec<String>,
 }
 
+// V2 adds `ValidatorClassification::metrics`, which is never present in V1
+#[derive(Default, Deserialize, Serialize, Clone)]
+pub struct EpochClassificationV2 {
+    // Data Center observations for this epoch
+    pub data_center_info: Vec<DataCenterInfo>,
+
+    // `None` indicates a pause due to unusual observations during classification
+    pub validator_classifications: Option<ValidatorClassificationByIdentity>,
+
+    // Informational notes regarding this epoch
+    pub notes: Vec<String>,
+}
+
+impl EpochClassificationV2 {
+    /// Lamports in the stake pool when the epoch was classified, if its notes recorded them
+    pub fn stake_pool_size(&self) -> Option<u64> {
+        self.notes.iter().find_map(|note| {
+            STAKE_POOL_SIZE_REGEX
+                .captures(note)
+                .and_then(|captures| lamports_of_sol_captures(&captures))
+        })
+    }
+}
+
+impl From<EpochClassificationV1> for EpochClassificationV2 {
+    fn from(v1: EpochClassificationV1) -> Self {
+        let EpochClassificationV1 {
+            data_center_info,
+            validator_classifications,
+            notes,
+        } = v1;
+
+        let validator_classifications = validator_classifications.map(|mut vcs| {
+            for vc in vcs.values_mut() {
+                vc.metrics = ValidatorMetrics::parse_v1(
+                    std::iter::once(&vc.stake_state_reason)
+                        .chain(vc.notes.iter())
+                        .map(String::as_str),
+                );
+            }
+            vcs
+        });
+
+        Self {
+            data_center_info,
+            validator_classifications,
+            notes,
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Clone)]
 pub enum EpochClassification {
     V1(EpochClassificationV1),
+    V2(EpochClassificationV2),
 }
 
 impl Default for EpochClassification {
     fn default() -> Self {
-        Self::V1(EpochClassificationV1::default())
+        Self::V2(EpochClassificationV2::default())
     }
 }
 
 impl EpochClassification {
-    pub fn new(v1: EpochClassificationV1) -> Self {
-        EpochClassification::V1(v1)
+    pub fn new(v2: EpochClassificationV2) -> Self {
+        EpochClassification::V2(v2)
     }
 
-    pub fn into_current(self) -> EpochClassificationV1 {
+    pub fn into_current(self) -> EpochClassificationV2 {
         match self {
-            EpochClassification::V1(v1) => v1,
+            EpochClassification::V1(v1) => v1.into(),
+            EpochClassification::V2(v2) => v2,
         }
     }
+}
 
-    fn file_name<P>(epoch: Epoch, path: P) -> PathBuf
-    where
-        P: AsRef<Path>,
-    {
-        path.as_ref().join(format!("epoch-{}.yml", epoch))
+/// Storage backends for `EpochClassification`s
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DbBackend {
+    // One `epoch-N.yml` file per epoch
+    Yaml,
+
+    // A single SQLite database, indexed by epoch and validator identity
+    Sqlite,
+}
+
+pub const DB_BACKENDS: &[&str] = &["yaml", "sqlite"];
+
+impl std::fmt::Display for DbBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Yaml => "yaml",
+                Self::Sqlite => "sqlite",
+            }
+        )
     }
+}
 
-    pub fn exists<P>(epoch: Epoch, path: P) -> bool
-    where
-        P: AsRef<Path>,
-    {
-        Self::file_name(epoch, path).exists()
+impl FromStr for DbBackend {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "yaml" => Ok(Self::Yaml),
+            "sqlite" => Ok(Self::Sqlite),
+            _ => Err(format!("unknown db backend: {}", s)),
+        }
     }
+}
 
-    pub fn load<P>(epoch: Epoch, path: P) -> Result<Self, io::Error>
+impl DbBackend {
+    /// Open the `EpochClassificationDb` of this backend within the directory `path`
+    pub fn open<P>(&self, path: P) -> Result<Box<dyn EpochClassificationDb>, io::Error>
     where
         P: AsRef<Path>,
     {
-        let file = File::open(Self::file_name(epoch, path))?;
-        serde_yaml::from_reader(file)
-            .map_err(|err| io::Error::new(io::ErrorKind::Other, format!("{:?}", err)))
+        Ok(match self {
+            Self::Yaml => Box::new(YamlDb::new(path)),
+            Self::Sqlite => Box::new(SqliteDb::open(path)?),
+        })
     }
+}
+
+pub trait EpochClassificationDb {
+    /// Epochs with a stored classification, in ascending order
+    fn epochs(&self) -> Result<Vec<Epoch>, io::Error>;
+
+    fn exists(&self, epoch: Epoch) -> Result<bool, io::Error>;
+
+    fn load(&self, epoch: Epoch) -> Result<EpochClassification, io::Error>;
+
+    fn save(
+        &self,
+        epoch: Epoch,
+        epoch_classification: &EpochClassification,
+    ) -> Result<(), io::Error>;
+
+    /// Classifications of the validator `identity`, newest first
+    fn load_validator_history(
+        &self,
+        identity: &Pubkey,
+    ) -> Result<Vec<(Epoch, ValidatorClassification)>, io::Error>;
 
     // Loads the first epoch older than `epoch` that contains `Some(validator_classifications)`.
     // Returns `Ok(None)` if no previous epochs are available
-    pub fn load_previous<P>(epoch: Epoch, path: P) -> Result<Option<(Epoch, Self)>, io::Error>
-    where
-        P: AsRef<Path>,
-    {
-        let mut previous_epoch = epoch;
-        loop {
-            if previous_epoch == 0 {
+    fn load_previous(
+        &self,
+        epoch: Epoch,
+    ) -> Result<Option<(Epoch, EpochClassification)>, io::Error> {
+        for previous_epoch in self.epochs()?.into_iter().rev() {
+            if previous_epoch >= epoch {
+                continue;
+            }
+            let previous_epoch_classification = self.load(previous_epoch)?.into_current();
+
+            if previous_epoch_classification
+                .validator_classifications
+                .is_some()
+            {
                 info!(
-                    "No previous EpochClassification found at {}",
-                    path.as_ref().display()
+                    "Previous EpochClassification found for epoch {}",
+                    previous_epoch
+                );
+                return Ok(Some((
+                    previous_epoch,
+                    EpochClassification::new(previous_epoch_classification),
+                )));
+            } else {
+                info!(
+                    "Skipping previous EpochClassification for epoch {}",
+                    previous_epoch
                 );
-                return Ok(None);
-            }
-            previous_epoch -= 1;
-
-            if Self::exists(previous_epoch, &path) {
-                let previous_epoch_classification =
-                    Self::load(previous_epoch, &path)?.into_current();
-
-                if previous_epoch_classification
-                    .validator_classifications
-                    .is_some()
-                {
-                    info!(
-                        "Previous EpochClassification found for epoch {} at {}",
-                        previous_epoch,
-                        path.as_ref().display()
-                    );
-                    return Ok(Some((
-                        previous_epoch,
-                        Self::V1(previous_epoch_classification),
-                    )));
-                } else {
-                    info!(
-                        "Skipping previous EpochClassification for epoch {}",
-                        previous_epoch
-                    );
-                }
             }
         }
+        info!("No previous EpochClassification found");
+        Ok(None)
     }
 
     // Loads the latest epoch that contains `Some(validator_classifications)`
     // Returns `Ok(None)` if no epoch is available
-    pub fn load_latest<P>(path: P) -> Result<Option<(Epoch, Self)>, io::Error>
+    fn load_latest(&self) -> Result<Option<(Epoch, EpochClassification)>, io::Error> {
+        match self.epochs()?.last() {
+            Some(latest_epoch) => self.load_previous(*latest_epoch + 1),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Copy every classification in `from` into `to`, returning the number of epochs copied
+pub fn migrate(
+    from: &dyn EpochClassificationDb,
+    to: &dyn EpochClassificationDb,
+) -> Result<usize, io::Error> {
+    let epochs = from.epochs()?;
+    for epoch in &epochs {
+        info!("Migrating epoch {}", epoch);
+        to.save(*epoch, &from.load(*epoch)?)?;
+    }
+    Ok(epochs.len())
+}
+
+/// The original backend: a directory of `epoch-N.yml` files
+pub struct YamlDb {
+    path: PathBuf,
+}
+
+impl YamlDb {
+    pub fn new<P>(path: P) -> Self
     where
         P: AsRef<Path>,
     {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    fn file_name(&self, epoch: Epoch) -> PathBuf {
+        self.path.join(format!("epoch-{}.yml", epoch))
+    }
+}
+
+impl EpochClassificationDb for YamlDb {
+    fn epochs(&self) -> Result<Vec<Epoch>, io::Error> {
         let epoch_filename_regex = regex::Regex::new(r"^epoch-(\d+).yml$").unwrap();
 
         let mut epochs = vec![];
-        if let Ok(entries) = fs::read_dir(&path) {
+        if let Ok(entries) = fs::read_dir(&self.path) {
             for entry in entries.filter_map(|entry| entry.ok()) {
                 if entry.path().is_file() {
                     let filename = entry
@@ -201,27 +466,51 @@ impl EpochClassification {
             }
         }
         epochs.sort_unstable();
+        Ok(epochs)
+    }
 
-        if let Some(latest_epoch) = epochs.last() {
-            Self::load_previous(*latest_epoch + 1, path)
-        } else {
-            Ok(None)
-        }
+    fn exists(&self, epoch: Epoch) -> Result<bool, io::Error> {
+        Ok(self.file_name(epoch).exists())
     }
 
-    pub fn save<P>(&self, epoch: Epoch, path: P) -> Result<(), io::Error>
-    where
-        P: AsRef<Path>,
-    {
-        let serialized = serde_yaml::to_string(self)
+    fn load(&self, epoch: Epoch) -> Result<EpochClassification, io::Error> {
+        let file = File::open(self.file_name(epoch))?;
+        serde_yaml::from_reader(file)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, format!("{:?}", err)))
+    }
+
+    fn save(
+        &self,
+        epoch: Epoch,
+        epoch_classification: &EpochClassification,
+    ) -> Result<(), io::Error> {
+        let serialized = serde_yaml::to_string(epoch_classification)
             .map_err(|err| io::Error::new(io::ErrorKind::Other, format!("{:?}", err)))?;
 
-        fs::create_dir_all(&path)?;
-        let mut file = File::create(Self::file_name(epoch, path))?;
+        fs::create_dir_all(&self.path)?;
+        let mut file = File::create(self.file_name(epoch))?;
         file.write_all(&serialized.into_bytes())?;
 
         Ok(())
     }
+
+    // Every epoch file must be read, prefer the `Sqlite` backend for frequent queries
+    fn load_validator_history(
+        &self,
+        identity: &Pubkey,
+    ) -> Result<Vec<(Epoch, ValidatorClassification)>, io::Error> {
+        let mut history = vec![];
+        for epoch in self.epochs()?.into_iter().rev() {
+            if let Some(mut validator_classifications) =
+                self.load(epoch)?.into_current().validator_classifications
+            {
+                if let Some(validator_classification) = validator_classifications.remove(identity) {
+                    history.push((epoch, validator_classification));
+                }
+            }
+        }
+        Ok(history)
+    }
 }
 
 #[cfg(test)]
@@ -244,4 +533,104 @@ mod test {
         assert!(!vc.staked_for(3, 3));
         assert!(vc.staked_for(2, 3));
     }
+
+    #[test]
+    fn test_held_stake_state() {
+        let mut vc = ValidatorClassification {
+            stake_state: ValidatorStakeState::Baseline,
+            ..ValidatorClassification::default()
+        };
+        assert_eq!(vc.held_stake_state(3), ValidatorStakeState::Baseline);
+
+        vc.stake_state = ValidatorStakeState::None;
+        vc.stake_states = Some(vec![
+            (ValidatorStakeState::None, String::new()),
+            (ValidatorStakeState::None, String::new()),
+            (ValidatorStakeState::Bonus, String::new()),
+        ]);
+        assert_eq!(vc.held_stake_state(0), ValidatorStakeState::None);
+        assert_eq!(vc.held_stake_state(1), ValidatorStakeState::None);
+        assert_eq!(vc.held_stake_state(2), ValidatorStakeState::None);
+        assert_eq!(vc.held_stake_state(3), ValidatorStakeState::Bonus);
+    }
+
+    #[test]
+    fn test_v1_into_current() {
+        let identity = Pubkey::new_unique();
+        let poor_producer = Pubkey::new_unique();
+        let unparsable = Pubkey::new_unique();
+
+        let vc = |identity, stake_state_reason: &str, notes: &[&str]| ValidatorClassification {
+            identity,
+            stake_state_reason: stake_state_reason.to_string(),
+            notes: notes.iter().map(|note| note.to_string()).collect(),
+            ..ValidatorClassification::default()
+        };
+
+        let v1 = EpochClassificationV1 {
+            validator_classifications: Some(
+                vec![
+                    vc(
+                        identity,
+                        "commission is too high: 100% commission",
+                        &[
+                            "Insufficient self stake: \u{25ce}1.500000000",
+                            "Outdated solana release: 1.6.9",
+                        ],
+                    ),
+                    vc(
+                        poor_producer,
+                        "poor block production during epoch 9: 1 blocks in 4 slots, 75% skip rate",
+                        &[],
+                    ),
+                    vc(unparsable, "all classification criteria satisfied", &[]),
+                ]
+                .into_iter()
+                .map(|vc| (vc.identity, vc))
+                .collect(),
+            ),
+            ..EpochClassificationV1::default()
+        };
+
+        let validator_classifications = EpochClassification::V1(v1)
+            .into_current()
+            .validator_classifications
+            .unwrap();
+        assert_eq!(
+            validator_classifications[&identity].metrics,
+            Some(ValidatorMetrics {
+                self_stake: Some(LAMPORTS_PER_SOL * 3 / 2),
+                commission: Some(100),
+                release_version: Some("1.6.9".to_string()),
+                ..ValidatorMetrics::default()
+            })
+        );
+        assert_eq!(
+            validator_classifications[&poor_producer].metrics,
+            Some(ValidatorMetrics {
+                blocks: Some(1),
+                leader_slots: Some(4),
+                skip_rate: Some(75),
+                ..ValidatorMetrics::default()
+            })
+        );
+        assert_eq!(validator_classifications[&unparsable].metrics, None);
+    }
+
+    #[test]
+    fn test_stake_pool_size() {
+        let mut epoch_classification = EpochClassificationV2 {
+            notes: vec!["Baseline stake amount: \u{25ce}5000.000000000".to_string()],
+            ..EpochClassificationV2::default()
+        };
+        assert_eq!(epoch_classification.stake_pool_size(), None);
+
+        epoch_classification.notes.push(
+            "Stake pool size: \u{25ce}1234.500000000 (inactive: \u{25ce}1.000000000)".to_string(),
+        );
+        assert_eq!(
+            epoch_classification.stake_pool_size(),
+            Some(1234 * LAMPORTS_PER_SOL + LAMPORTS_PER_SOL / 2)
+        );
+    }
 }
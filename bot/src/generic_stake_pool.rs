@@ -1,11 +1,19 @@
 use {
-    serde::{Deserialize, Serialize},
+    crate::journal::Journal,
+    serde::{Deserialize, Deserializer, Serialize, Serializer},
     solana_client::rpc_client::RpcClient,
-    solana_sdk::pubkey::Pubkey,
+    solana_sdk::{
+        clock::Epoch,
+        native_token::{sol_to_lamports, Sol},
+        pubkey::Pubkey,
+    },
     std::{
         collections::{HashMap, HashSet},
-        error,
+        error, fs,
+        path::Path,
+        str::FromStr,
     },
+    thiserror::Error,
 };
 
 #[derive(Debug, PartialEq, Clone, Copy, Deserialize, Serialize)]
@@ -27,17 +35,368 @@ pub struct ValidatorStake {
     pub vote_address: Pubkey,
     pub stake_state: ValidatorStakeState,
     pub priority: bool,
+
+    /// Share of the bonus stake relative to the other Bonus validators. See
+    /// `bonus::BonusWeighting`
+    pub bonus_weight: f64,
+
+    /// Fixed stake amount of the validator's stake tier, in place of the baseline stake amount or
+    /// a share of the bonus stake. See `tiers::StakeTier`
+    pub stake_amount: Option<u64>,
+}
+
+impl ValidatorStake {
+    /// The stake delegated to the validator ahead of sharing the bonus stake, if any
+    pub fn fixed_stake_amount(&self, baseline_stake_amount: u64) -> Option<u64> {
+        match self.stake_state {
+            ValidatorStakeState::None => Some(0),
+            ValidatorStakeState::Baseline => {
+                Some(self.stake_amount.unwrap_or(baseline_stake_amount))
+            }
+            ValidatorStakeState::Bonus => self.stake_amount,
+        }
+    }
+}
+
+/// Bound on how much the stake of a validator changes in one epoch.  A validator further from its
+/// desired balance moves towards it over several epochs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MaxStakeChange {
+    Amount(u64), // Lamports
+    // Percentage of the current balance, or of the baseline stake amount if greater, so that a
+    // validator without stake can still be funded
+    Percentage(f64),
+}
+
+impl MaxStakeChange {
+    /// The balance a validator holding `balance` moves to this epoch on the way to
+    /// `desired_balance`.  Changes smaller than `min_change` are not made, so the change is
+    /// allowed to be at least `min_change`
+    pub fn limit(
+        &self,
+        balance: u64,
+        desired_balance: u64,
+        baseline_stake_amount: u64,
+        min_change: u64,
+    ) -> u64 {
+        let max_change = match self {
+            Self::Amount(amount) => *amount,
+            Self::Percentage(percentage) => {
+                (balance.max(baseline_stake_amount) as f64 * percentage / 100.) as u64
+            }
+        }
+        .max(min_change);
+        if desired_balance > balance {
+            desired_balance.min(balance.saturating_add(max_change))
+        } else {
+            desired_balance.max(balance.saturating_sub(max_change))
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("cannot convert to MaxStakeChange: {0}")]
+pub struct MaxStakeChangeFromStrError(String);
+
+impl FromStr for MaxStakeChange {
+    type Err = MaxStakeChangeFromStrError;
+
+    /// An amount of SOL, or a percentage such as `10%`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || MaxStakeChangeFromStrError(s.to_string());
+        if let Some(percentage) = s.strip_suffix('%') {
+            let percentage = percentage.parse::<f64>().map_err(|_| err())?;
+            if percentage > 0. && percentage <= 100. {
+                Ok(Self::Percentage(percentage))
+            } else {
+                Err(err())
+            }
+        } else {
+            let amount = s.parse::<f64>().map_err(|_| err())?;
+            if amount > 0. {
+                Ok(Self::Amount(sol_to_lamports(amount)))
+            } else {
+                Err(err())
+            }
+        }
+    }
 }
 
 pub type ValidatorStakeActions = HashMap<Pubkey, String>;
 pub type EpochStakeNotes = Vec<String>;
 pub type UnfundedValidators = HashSet<Pubkey>;
+pub type ApplyResult = (
+    EpochStakeNotes,
+    ValidatorStakeActions,
+    UnfundedValidators,
+    StakePlan,
+);
+
+// Pubkeys are written as base58 strings so that a `StakePlan` can be reviewed by a human
+fn serialize_pubkey<S>(pubkey: &Pubkey, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&pubkey.to_string())
+}
+
+fn deserialize_pubkey<'de, D>(deserializer: D) -> Result<Pubkey, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let pubkey = String::deserialize(deserializer)?;
+    Pubkey::from_str(&pubkey).map_err(serde::de::Error::custom)
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StakeOperation {
+    Increase, // Move `amount` lamports from the reserve to the validator
+    Decrease, // Move `amount` lamports from the validator back to the reserve
+    None,     // Leave the validator stake as is
+}
+
+#[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PlannedStakeChange {
+    #[serde(
+        serialize_with = "serialize_pubkey",
+        deserialize_with = "deserialize_pubkey"
+    )]
+    pub identity: Pubkey,
+    #[serde(
+        serialize_with = "serialize_pubkey",
+        deserialize_with = "deserialize_pubkey"
+    )]
+    pub vote_address: Pubkey,
+    pub stake_state: ValidatorStakeState,
+    pub priority: bool,
+    pub current_balance: u64,
+    pub desired_balance: u64,
+    /// The balance the validator is moving towards, if `desired_balance` was limited by
+    /// `MaxStakeChange`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub final_balance: Option<u64>,
+    pub operation: StakeOperation,
+    pub amount: u64,
+    pub reason: String,
+}
+
+impl PlannedStakeChange {
+    /// Human readable summary, as recorded in `ValidatorClassification::stake_action`
+    pub fn action(&self) -> String {
+        match self.final_balance {
+            Some(final_balance) => format!(
+                "target stake amount: {} (limited, moving towards {}), current stake amount: {} - {}",
+                Sol(self.desired_balance),
+                Sol(final_balance),
+                Sol(self.current_balance),
+                self.reason,
+            ),
+            None => format!(
+                "target stake amount: {}, current stake amount: {} - {}",
+                Sol(self.desired_balance),
+                Sol(self.current_balance),
+                self.reason,
+            ),
+        }
+    }
+}
+
+/// The stake adjustments computed by `GenericStakePool::apply()` for an epoch.  A plan produced
+/// by a dry run may be reviewed and later submitted unmodified with
+/// `GenericStakePool::execute_plan()`
+#[derive(Debug, Default, PartialEq, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct StakePlan {
+    /// Address identifying the stake pool: the pool address, or the reserve stake address of a
+    /// stake-pool-v0 pool
+    #[serde(
+        serialize_with = "serialize_pubkey",
+        deserialize_with = "deserialize_pubkey"
+    )]
+    pub stake_pool: Pubkey,
+    pub epoch: Epoch,
+    pub changes: Vec<PlannedStakeChange>,
+}
+
+impl StakePlan {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn error::Error>> {
+        let file = fs::File::open(&path)
+            .map_err(|err| format!("Unable to open {}: {}", path.as_ref().display(), err))?;
+        Ok(serde_json::from_reader(file)
+            .map_err(|err| format!("Invalid stake plan {}: {}", path.as_ref().display(), err))?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn error::Error>> {
+        let file = fs::File::create(&path)
+            .map_err(|err| format!("Unable to create {}: {}", path.as_ref().display(), err))?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    /// Ensure the plan was produced for `stake_pool` during `epoch`, and that the balance of every
+    /// validator to adjust still matches the balance the plan was computed from
+    pub fn verify<F>(
+        &self,
+        stake_pool: &Pubkey,
+        epoch: Epoch,
+        mut current_balance: F,
+    ) -> Result<(), Box<dyn error::Error>>
+    where
+        F: FnMut(&PlannedStakeChange) -> Result<u64, Box<dyn error::Error>>,
+    {
+        if self.stake_pool != *stake_pool {
+            return Err(format!(
+                "Stake plan is for stake pool {}, not {}",
+                self.stake_pool, stake_pool
+            )
+            .into());
+        }
+        if self.epoch != epoch {
+            return Err(format!(
+                "Stake plan is for epoch {}, the current epoch is {}",
+                self.epoch, epoch
+            )
+            .into());
+        }
+        for change in self.operations() {
+            let balance = current_balance(change)?;
+            if balance != change.current_balance {
+                return Err(format!(
+                    "Stake plan is stale: {} has a stake balance of {}, the plan expects {}",
+                    change.identity,
+                    Sol(balance),
+                    Sol(change.current_balance)
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Changes that require a transaction
+    pub fn operations(&self) -> impl Iterator<Item = &PlannedStakeChange> {
+        self.changes
+            .iter()
+            .filter(|change| change.operation != StakeOperation::None)
+    }
+}
 
 pub trait GenericStakePool {
+    /// The stake authority of the stake accounts the pool delegates to validators
+    fn stake_authority(&self) -> Pubkey;
+
+    /// Compute the stake adjustments required to reach `desired_validator_stake` and, unless
+    /// `dry_run`, submit them.  Phases already completed according to `journal` are skipped
     fn apply(
         &mut self,
         rpc_client: &RpcClient,
         dry_run: bool,
         desired_validator_stake: &[ValidatorStake],
-    ) -> Result<(EpochStakeNotes, ValidatorStakeActions, UnfundedValidators), Box<dyn error::Error>>;
+        journal: &mut Journal,
+    ) -> Result<ApplyResult, Box<dyn error::Error>>;
+
+    /// Submit exactly the operations of a `StakePlan` previously produced by `apply()`.  The plan
+    /// is rejected if it was produced for another stake pool or epoch, or if a validator balance
+    /// has changed since.  If `dry_run`, the plan is only verified
+    fn execute_plan(
+        &mut self,
+        rpc_client: &RpcClient,
+        dry_run: bool,
+        stake_plan: &StakePlan,
+    ) -> Result<(), Box<dyn error::Error>>;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_stake_plan_json() {
+        let stake_plan = StakePlan {
+            stake_pool: Pubkey::new_unique(),
+            epoch: 42,
+            changes: vec![
+                PlannedStakeChange {
+                    identity: Pubkey::new_unique(),
+                    vote_address: Pubkey::new_unique(),
+                    stake_state: ValidatorStakeState::Bonus,
+                    priority: false,
+                    current_balance: 1,
+                    desired_balance: 3,
+                    final_balance: Some(5),
+                    operation: StakeOperation::Increase,
+                    amount: 2,
+                    reason: "adding ◎0.000000002".to_string(),
+                },
+                PlannedStakeChange {
+                    identity: Pubkey::new_unique(),
+                    vote_address: Pubkey::new_unique(),
+                    stake_state: ValidatorStakeState::Baseline,
+                    priority: true,
+                    current_balance: 3,
+                    desired_balance: 3,
+                    final_balance: None,
+                    operation: StakeOperation::None,
+                    amount: 0,
+                    reason: "no change".to_string(),
+                },
+            ],
+        };
+
+        let json = serde_json::to_string(&stake_plan).unwrap();
+        assert!(json.contains(&format!("\"stake-pool\":\"{}\"", stake_plan.stake_pool)));
+        assert_eq!(
+            serde_json::from_str::<StakePlan>(&json).unwrap(),
+            stake_plan
+        );
+        assert_eq!(stake_plan.operations().count(), 1);
+    }
+
+    #[test]
+    fn test_max_stake_change() {
+        let sol = sol_to_lamports;
+        assert_eq!(
+            "10".parse::<MaxStakeChange>().unwrap(),
+            MaxStakeChange::Amount(sol(10.))
+        );
+        assert_eq!(
+            "25%".parse::<MaxStakeChange>().unwrap(),
+            MaxStakeChange::Percentage(25.)
+        );
+        for invalid in &["", "-1", "0", "0%", "101%", "ten"] {
+            assert!(invalid.parse::<MaxStakeChange>().is_err());
+        }
+
+        let min_change = sol(1.);
+        let amount = MaxStakeChange::Amount(sol(10.));
+        assert_eq!(
+            amount.limit(sol(100.), sol(5.), sol(1.), min_change),
+            sol(90.)
+        );
+        assert_eq!(
+            amount.limit(sol(100.), sol(95.), sol(1.), min_change),
+            sol(95.)
+        );
+        assert_eq!(amount.limit(0, sol(50.), sol(1.), min_change), sol(10.));
+        // A limit below the minimum change would prevent any change
+        let amount = MaxStakeChange::Amount(sol(0.5));
+        assert_eq!(amount.limit(sol(100.), 0, sol(1.), min_change), sol(99.));
+
+        let percentage = MaxStakeChange::Percentage(50.);
+        assert_eq!(
+            percentage.limit(sol(100.), 0, sol(10.), min_change),
+            sol(50.)
+        );
+        assert_eq!(
+            percentage.limit(sol(100.), sol(300.), sol(10.), min_change),
+            sol(150.)
+        );
+        // A validator without stake is funded by a share of the baseline stake amount
+        assert_eq!(
+            percentage.limit(0, sol(300.), sol(10.), min_change),
+            sol(5.)
+        );
+    }
 }
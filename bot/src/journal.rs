@@ -0,0 +1,239 @@
+use {
+    crate::{
+        db::EpochClassification,
+        generic_stake_pool::{ApplyResult, ValidatorStakeActions},
+        BoxResult,
+    },
+    log::*,
+    serde::{Deserialize, Serialize},
+    solana_sdk::{clock::Epoch, signature::Signature},
+    std::{
+        fs,
+        path::{Path, PathBuf},
+    },
+};
+
+/// Phases of a run, in the order they complete
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RunPhase {
+    Classified,
+    ValidatorsRemoved,
+    ValidatorsAdded,
+    StakeAccountsCreated,
+    DistributionSent,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct JournalState {
+    epoch: Epoch,
+    completed_phases: Vec<RunPhase>,
+
+    // Recorded by `RunPhase::Classified`
+    classification: Option<EpochClassification>,
+    first_time: bool,
+
+    // Validators made busy by the stake pool phases completed so far
+    validator_stake_actions: ValidatorStakeActions,
+
+    // Recorded by `RunPhase::DistributionSent`
+    distribution_signatures: Vec<String>,
+    apply_result: Option<ApplyResult>,
+}
+
+/// Progress of the run for an epoch, persisted in the db directory as each phase completes so
+/// that a run interrupted midway resumes from the last completed phase.  The journal is removed
+/// once the run completes
+#[derive(Default)]
+pub struct Journal {
+    // None for a journal that is not persisted, such as for a dry run
+    path: Option<PathBuf>,
+    state: JournalState,
+}
+
+impl Journal {
+    pub const FILE_NAME: &'static str = "journal.yml";
+
+    /// A journal for `epoch` that is not persisted
+    pub fn new(epoch: Epoch) -> Self {
+        Self {
+            path: None,
+            state: JournalState {
+                epoch,
+                ..JournalState::default()
+            },
+        }
+    }
+
+    /// Load the journal of `epoch` from the directory `path`.  A journal left by another epoch is
+    /// discarded
+    pub fn load(path: &Path, epoch: Epoch) -> BoxResult<Self> {
+        let path = path.join(Self::FILE_NAME);
+        let mut journal = Self {
+            path: Some(path.clone()),
+            ..Self::new(epoch)
+        };
+        if path.exists() {
+            let state: JournalState = serde_yaml::from_reader(fs::File::open(&path)?)
+                .map_err(|err| format!("Invalid journal {}: {}", path.display(), err))?;
+            if state.epoch == epoch {
+                info!(
+                    "Resuming epoch {} after phases {:?}",
+                    epoch, state.completed_phases
+                );
+                journal.state = state;
+            } else {
+                warn!("Discarding the incomplete journal of epoch {}", state.epoch);
+            }
+        }
+        Ok(journal)
+    }
+
+    fn save(&self) -> BoxResult<()> {
+        if let Some(ref path) = self.path {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            // Write then rename so that a crash never leaves a truncated journal behind
+            let temp_path = path.with_extension("yml.tmp");
+            serde_yaml::to_writer(fs::File::create(&temp_path)?, &self.state)?;
+            fs::rename(&temp_path, path)?;
+        }
+        Ok(())
+    }
+
+    pub fn is_completed(&self, phase: RunPhase) -> bool {
+        self.state.completed_phases.contains(&phase)
+    }
+
+    pub fn complete(&mut self, phase: RunPhase) -> BoxResult<()> {
+        if !self.is_completed(phase) {
+            self.state.completed_phases.push(phase);
+        }
+        self.save()
+    }
+
+    /// Complete a stake pool phase, recording the validators that it made busy
+    pub fn complete_stake_phase(
+        &mut self,
+        phase: RunPhase,
+        validator_stake_actions: &ValidatorStakeActions,
+    ) -> BoxResult<()> {
+        self.state.validator_stake_actions = validator_stake_actions.clone();
+        self.complete(phase)
+    }
+
+    /// Validators made busy by the stake pool phases of an interrupted run
+    pub fn validator_stake_actions(&self) -> &ValidatorStakeActions {
+        &self.state.validator_stake_actions
+    }
+
+    /// The classification of an interrupted run, and whether that run was the first for the
+    /// epoch
+    pub fn classification(&self) -> Option<(EpochClassification, bool)> {
+        self.state
+            .classification
+            .clone()
+            .map(|classification| (classification, self.state.first_time))
+    }
+
+    pub fn classified(
+        &mut self,
+        classification: EpochClassification,
+        first_time: bool,
+    ) -> BoxResult<()> {
+        self.state.classification = Some(classification);
+        self.state.first_time = first_time;
+        self.complete(RunPhase::Classified)
+    }
+
+    /// The result of `GenericStakePool::apply()` of an interrupted run that completed its stake
+    /// distribution
+    pub fn apply_result(&self) -> Option<&ApplyResult> {
+        self.state.apply_result.as_ref()
+    }
+
+    pub fn distribution_sent<I>(
+        &mut self,
+        signatures: I,
+        apply_result: &ApplyResult,
+    ) -> BoxResult<()>
+    where
+        I: IntoIterator<Item = Signature>,
+    {
+        self.state.distribution_signatures = signatures
+            .into_iter()
+            .map(|signature| signature.to_string())
+            .collect();
+        self.state.apply_result = Some(apply_result.clone());
+        self.complete(RunPhase::DistributionSent)
+    }
+
+    /// The run is complete, remove the journal
+    pub fn completed(self) -> BoxResult<()> {
+        if let Some(path) = self.path {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::generic_stake_pool::StakePlan,
+        solana_sdk::pubkey::Pubkey,
+        std::collections::{HashMap, HashSet},
+    };
+
+    #[test]
+    fn test_journal() {
+        let path = std::env::temp_dir().join(format!("journal-{}", Pubkey::new_unique()));
+        fs::create_dir_all(&path).unwrap();
+
+        let mut journal = Journal::load(&path, 42).unwrap();
+        assert!(journal.classification().is_none());
+        journal
+            .classified(EpochClassification::default(), true)
+            .unwrap();
+        let validator_stake_actions = vec![(Pubkey::new_unique(), "busy".to_string())]
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        journal
+            .complete_stake_phase(RunPhase::ValidatorsRemoved, &validator_stake_actions)
+            .unwrap();
+
+        // A restarted run resumes after the completed phases
+        let mut journal = Journal::load(&path, 42).unwrap();
+        assert!(journal.classification().unwrap().1);
+        assert!(journal.is_completed(RunPhase::ValidatorsRemoved));
+        assert!(!journal.is_completed(RunPhase::ValidatorsAdded));
+        assert_eq!(journal.validator_stake_actions(), &validator_stake_actions);
+
+        let apply_result = (
+            vec!["note".to_string()],
+            HashMap::new(),
+            HashSet::new(),
+            StakePlan::default(),
+        );
+        journal
+            .distribution_sent(vec![Signature::default()], &apply_result)
+            .unwrap();
+        let journal = Journal::load(&path, 42).unwrap();
+        assert!(journal.is_completed(RunPhase::DistributionSent));
+        assert_eq!(journal.apply_result().unwrap().0, apply_result.0);
+
+        // The journal of another epoch is discarded
+        assert!(!Journal::load(&path, 43)
+            .unwrap()
+            .is_completed(RunPhase::Classified));
+
+        journal.completed().unwrap();
+        assert!(!path.join(Journal::FILE_NAME).exists());
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -1,20 +1,40 @@
 use {
-    crate::{db::*, generic_stake_pool::*, rpc_client_utils::*},
+    crate::{
+        bonus::{bonus_weighting_from_name, BonusStakeLimits, BONUS_WEIGHTINGS},
+        cluster_snapshot::ClusterSnapshot,
+        config_file::{
+            check_percentage, keypair_of_setting, pubkey_of_setting, ConfigFile, EffectiveConfig,
+            Profile,
+        },
+        criteria::{
+            criteria_from_names, evaluate_criteria, ClusterContext, ValidatorContext,
+            ValidatorCriterion, DEFAULT_CRITERIA,
+        },
+        db::*,
+        generic_stake_pool::*,
+        journal::{Journal, RunPhase},
+        nonce_pool::NoncePool,
+        notifications::{digest, is_notification_sink, Notifications, SmtpSettings, StakeChange},
+        offline_signing::OfflineStaker,
+        rpc_client_utils::*,
+        tiers::StakeTier,
+    },
     clap::{
-        crate_description, crate_name, value_t, value_t_or_exit, values_t, App, AppSettings, Arg,
-        ArgMatches, SubCommand,
+        crate_description, crate_name, value_t, value_t_or_exit, App, AppSettings, Arg, SubCommand,
     },
     log::*,
+    serde::{Deserialize, Serialize},
     solana_clap_utils::{
-        input_parsers::{keypair_of, lamports_of_sol, pubkey_of},
+        input_parsers::{lamports_of_sol, pubkeys_of},
         input_validators::{
-            is_amount, is_keypair, is_parsable, is_pubkey_or_keypair, is_url, is_valid_percentage,
+            is_amount, is_parsable, is_pubkey, is_pubkey_or_keypair, is_url, is_valid_percentage,
+            is_valid_signer,
         },
+        keypair::signer_from_path,
     },
     solana_client::rpc_client::RpcClient,
     solana_foundation_delegation_program_cli::get_participants_with_state,
-    solana_foundation_delegation_program_registry::state::{Participant, ParticipantState},
-    solana_notifier::Notifier,
+    solana_foundation_delegation_program_registry::state::{ParticipantState, ParticipantV2},
     solana_sdk::{
         account::from_account,
         account_utils::StateMut,
@@ -22,6 +42,7 @@ use {
         commitment_config::CommitmentConfig,
         native_token::*,
         pubkey::Pubkey,
+        signature::Signer,
         slot_history::{self, SlotHistory},
         stake_history::StakeHistory,
         sysvar,
@@ -29,24 +50,40 @@ use {
     solana_stake_program::stake_state::StakeState,
     solana_vote_program::vote_state::VoteState,
     std::{
-        collections::{HashMap, HashSet},
+        collections::{BTreeMap, HashMap, HashSet},
         error,
         fs::{self, File},
-        io::Write,
-        path::PathBuf,
+        io::{self, Write},
+        net::SocketAddr,
+        path::{Path, PathBuf},
         process,
         str::FromStr,
-        time::Duration,
+        time::{Duration, Instant, SystemTime, UNIX_EPOCH},
     },
     thiserror::Error,
 };
 
+mod api;
+mod bonus;
+mod cluster_snapshot;
+mod config_file;
+mod criteria;
+mod daemon;
 mod data_center_info;
 mod db;
 mod generic_stake_pool;
+mod journal;
+mod metrics;
+mod nonce_pool;
+mod notifications;
+mod offline_signing;
+mod report;
 mod rpc_client_utils;
+mod simulate;
+mod sqlite_db;
 mod stake_pool;
 mod stake_pool_v0;
+mod tiers;
 mod validator_list;
 mod validators_app;
 
@@ -153,21 +190,23 @@ fn is_release_version(string: String) -> Result<(), String> {
         .map_err(|err| format!("{:?}", err))
 }
 
-fn release_version_of(matches: &ArgMatches<'_>, name: &str) -> Option<semver::Version> {
-    matches
-        .value_of(name)
-        .map(ToString::to_string)
-        .map(|string| {
-            if string.starts_with('v') {
-                semver::Version::parse(string.split_at(1).1)
-            } else {
-                semver::Version::parse(&string)
-            }
-            .expect("semver::Version")
-        })
+fn is_max_stake_change(string: String) -> Result<(), String> {
+    MaxStakeChange::from_str(&string)
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
+
+fn parse_release_version(string: &str) -> semver::Version {
+    if string.starts_with('v') {
+        semver::Version::parse(string.split_at(1).1)
+    } else {
+        semver::Version::parse(string)
+    }
+    .expect("semver::Version")
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum Cluster {
     Testnet,
     MainnetBeta,
@@ -187,15 +226,37 @@ impl std::fmt::Display for Cluster {
 }
 
 #[derive(Debug)]
-struct Config {
+pub struct Config {
     json_rpc_url: String,
     cluster: Cluster,
     db_path: PathBuf,
+
+    /// Storage backend for the epoch classifications within `db_path`
+    db_backend: DbBackend,
+
     require_classification: bool,
     markdown_path: Option<PathBuf>,
 
+    /// If Some(), write a static HTML report of the staking history into this directory
+    html_report_path: Option<PathBuf>,
+
+    /// If Some(), templates in this directory override the built-in report templates
+    report_templates_path: Option<PathBuf>,
+
+    /// Generate the reports of every epoch rather than only those affected by the current epoch
+    rebuild_reports: bool,
+
     dry_run: bool,
 
+    /// Record the progress of each run in a journal within `db_path`, so that an interrupted run
+    /// resumes where it stopped
+    journal: bool,
+
+    /// Transactions are written for offline signing rather than sent.  As they are yet to execute,
+    /// the classification of the epoch is neither saved nor notified until a run after their
+    /// broadcast
+    sign_offline: bool,
+
     /// Quality validators produce within this percentage of the cluster average skip rate over
     /// the previous epoch
     quality_block_producer_percentage: usize,
@@ -261,6 +322,77 @@ struct Config {
     ///
     /// This setting is ignored if `cluster` is not `"mainnet-beta"`
     min_testnet_participation: Option<(/*n:*/ usize, /*m:*/ usize)>,
+
+    /// Names of the classification criteria to evaluate, in order. See `criteria::DEFAULT_CRITERIA`
+    criteria: Vec<String>,
+
+    /// Name of the weighting that shares the bonus stake among Bonus validators. See
+    /// `bonus::BONUS_WEIGHTINGS`
+    bonus_weighting: String,
+
+    /// Graduated stake levels within the Baseline and Bonus stake states. See `tiers::StakeTier`
+    stake_tiers: Vec<StakeTier>,
+
+    /// Number of consecutive epochs a validator must be classified at a lower stake state before
+    /// its stake is downgraded
+    downgrade_epochs: usize,
+
+    /// If Some(), record the cluster observations used for classification into this directory
+    save_snapshot_path: Option<PathBuf>,
+
+    /// If Some(), write the `StakePlan` produced by the stake pool to this file
+    export_plan_path: Option<PathBuf>,
+
+    /// If Some(), write the metrics of the run to this file in the Prometheus text format
+    metrics_file: Option<PathBuf>,
+
+    /// Destinations of the epoch digest, in addition to the channels `solana_notifier`
+    /// configures from the environment
+    notification_sinks: Vec<String>,
+
+    /// If Some(), a YAML file of validator identities and the notification sinks through which
+    /// each is told of its stake state changes
+    validator_contacts_path: Option<PathBuf>,
+
+    /// SMTP relay and sender address of `mailto:` notification sinks
+    smtp_server: String,
+    smtp_from: String,
+}
+
+/// The operation selected on the command-line
+enum Command {
+    /// Classify the current epoch and apply the result to a stake pool
+    Run(Box<dyn GenericStakePool>),
+
+    /// `Run` at every epoch boundary, without exiting
+    Daemon(Box<dyn GenericStakePool>, daemon::DaemonArgs),
+
+    /// Classify a recorded `ClusterSnapshot` without network access. No stake adjustments are made
+    ClassifyFromSnapshot(PathBuf),
+
+    /// Replay the stored classification history under the current `Config`
+    Simulate(simulate::SimulateArgs),
+
+    /// Print the effective settings, after merging the command-line with the config file
+    ShowConfig(Profile),
+
+    /// Copy the staking history into the given storage backend
+    MigrateDb(DbBackend),
+
+    /// Print the classification history of a validator identity
+    ValidatorHistory(Pubkey),
+
+    /// Serve the staking history as JSON over HTTP on the given address
+    ServeDb(SocketAddr),
+
+    /// Submit a previously exported `StakePlan` to a stake pool, without classifying
+    ExecutePlan(Box<dyn GenericStakePool>, StakePlan),
+
+    /// Add the signatures of a signer to a file of transactions written with `--sign-offline`
+    SignOffline(PathBuf, Box<dyn Signer>),
+
+    /// Send the fully signed transactions of a file written with `--sign-offline`
+    BroadcastOffline(PathBuf),
 }
 
 impl Config {
@@ -270,9 +402,15 @@ impl Config {
             json_rpc_url: "https://api.mainnet-beta.solana.com".to_string(),
             cluster: Cluster::MainnetBeta,
             db_path: PathBuf::default(),
+            db_backend: DbBackend::Yaml,
             require_classification: false,
             markdown_path: None,
+            html_report_path: None,
+            report_templates_path: None,
+            rebuild_reports: false,
             dry_run: true,
+            journal: false,
+            sign_offline: false,
             quality_block_producer_percentage: 15,
             max_poor_block_producer_percentage: 20,
             max_commission: 100,
@@ -289,6 +427,17 @@ impl Config {
             enforce_min_self_stake: false,
             enforce_testnet_participation: false,
             min_testnet_participation: None,
+            criteria: DEFAULT_CRITERIA.iter().map(|s| s.to_string()).collect(),
+            bonus_weighting: "equal".to_string(),
+            stake_tiers: vec![],
+            downgrade_epochs: 1,
+            save_snapshot_path: None,
+            export_plan_path: None,
+            metrics_file: None,
+            notification_sinks: vec![],
+            validator_contacts_path: None,
+            smtp_server: "127.0.0.1:25".to_string(),
+            smtp_from: "stake-o-matic@localhost".to_string(),
         }
     }
 
@@ -299,6 +448,17 @@ impl Config {
     fn cluster_db_path(&self) -> PathBuf {
         self.cluster_db_path_for(self.cluster)
     }
+
+    fn epoch_classification_db_for(
+        &self,
+        cluster: Cluster,
+    ) -> Result<Box<dyn EpochClassificationDb>, io::Error> {
+        self.db_backend.open(self.cluster_db_path_for(cluster))
+    }
+
+    fn epoch_classification_db(&self) -> Result<Box<dyn EpochClassificationDb>, io::Error> {
+        self.epoch_classification_db_for(self.cluster)
+    }
 }
 
 fn default_confirmed_block_cache_path() -> PathBuf {
@@ -317,7 +477,7 @@ fn app_version() -> String {
     })
 }
 
-fn get_config() -> BoxResult<(Config, RpcClient, Box<dyn GenericStakePool>)> {
+fn get_config() -> BoxResult<(Config, RpcClient, Command)> {
     let default_confirmed_block_cache_path = default_confirmed_block_cache_path()
         .to_str()
         .unwrap()
@@ -326,7 +486,7 @@ fn get_config() -> BoxResult<(Config, RpcClient, Box<dyn GenericStakePool>)> {
     let matches = App::new(crate_name!())
         .about(crate_description!())
         .version(app_version)
-        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .setting(AppSettings::ArgRequiredElseHelp)
         .setting(AppSettings::VersionlessSubcommands)
         .setting(AppSettings::InferSubcommands)
         .arg(
@@ -347,18 +507,78 @@ fn get_config() -> BoxResult<(Config, RpcClient, Box<dyn GenericStakePool>)> {
                 .required(true)
                 .help("Name of the cluster to operate on")
         )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .value_name("FILE")
+                .takes_value(true)
+                .help("YAML file of settings, with optional per-cluster profiles. \
+                       Command-line arguments take precedence over the file")
+        )
         .arg(
             Arg::with_name("confirm")
                 .long("confirm")
                 .takes_value(false)
                 .help("Confirm that the stake adjustments should actually be made")
         )
+        .arg(
+            Arg::with_name("no_confirm")
+                .long("no-confirm")
+                .takes_value(false)
+                .conflicts_with("confirm")
+                .help("Do not make the stake adjustments, even if the config file confirms \
+                       them")
+        )
         .arg(
             Arg::with_name("markdown")
                 .long("markdown")
                 .takes_value(false)
                 .help("Output markdown")
         )
+        .arg(
+            Arg::with_name("no_markdown")
+                .long("no-markdown")
+                .takes_value(false)
+                .conflicts_with("markdown")
+                .help("Do not output markdown, even if the config file enables it")
+        )
+        .arg(
+            Arg::with_name("html_report")
+                .long("html-report")
+                .takes_value(false)
+                .help("Output a static HTML report of the staking history")
+        )
+        .arg(
+            Arg::with_name("no_html_report")
+                .long("no-html-report")
+                .takes_value(false)
+                .conflicts_with("html_report")
+                .help("Do not output the HTML report, even if the config file enables it")
+        )
+        .arg(
+            Arg::with_name("report_templates")
+                .long("report-templates")
+                .value_name("DIR")
+                .takes_value(true)
+                .help("Directory of Handlebars templates overriding the built-in report \
+                       templates: cluster.md.hbs, validator.md.hbs, index.html.hbs, \
+                       epoch.html.hbs, validator.html.hbs and style.css.hbs")
+        )
+        .arg(
+            Arg::with_name("rebuild_reports")
+                .long("rebuild-reports")
+                .takes_value(false)
+                .help("Generate the reports of every epoch. By default only the reports \
+                       affected by the epochs classified since the last update are updated")
+        )
+        .arg(
+            Arg::with_name("no_rebuild_reports")
+                .long("no-rebuild-reports")
+                .takes_value(false)
+                .conflicts_with("rebuild_reports")
+                .help("Only update the reports affected by the epochs classified since the \
+                       last update, even if the config file enables --rebuild-reports")
+        )
         .arg(
             Arg::with_name("db_path")
                 .long("db-path")
@@ -367,12 +587,29 @@ fn get_config() -> BoxResult<(Config, RpcClient, Box<dyn GenericStakePool>)> {
                 .default_value("db")
                 .help("Location for storing staking history")
         )
+        .arg(
+            Arg::with_name("db_backend")
+                .long("db-backend")
+                .value_name("BACKEND")
+                .takes_value(true)
+                .possible_values(DB_BACKENDS)
+                .default_value("yaml")
+                .help("Storage backend for the staking history")
+        )
         .arg(
             Arg::with_name("require_classification")
                 .long("require-classification")
                 .takes_value(false)
                 .help("Fail if the classification for the previous epoch does not exist")
         )
+        .arg(
+            Arg::with_name("no_require_classification")
+                .long("no-require-classification")
+                .takes_value(false)
+                .conflicts_with("require_classification")
+                .help("Do not require the classification for the previous epoch, even if the \
+                       config file does")
+        )
         .arg(
             Arg::with_name("quality_block_producer_percentage")
                 .long("quality-block-producer-percentage")
@@ -516,6 +753,14 @@ fn get_config() -> BoxResult<(Config, RpcClient, Box<dyn GenericStakePool>)> {
                 .takes_value(false)
                 .help("Enforce the minimum self-stake requirement")
         )
+        .arg(
+            Arg::with_name("no_enforce_min_self_stake")
+                .long("no-enforce-min-self-stake")
+                .takes_value(false)
+                .conflicts_with("enforce_min_self_stake")
+                .help("Do not enforce the minimum self-stake requirement, even if the config \
+                       file does")
+        )
         .arg(
             Arg::with_name("min_testnet_participation")
                 .long("min-testnet-participation")
@@ -535,6 +780,360 @@ fn get_config() -> BoxResult<(Config, RpcClient, Box<dyn GenericStakePool>)> {
                 .help("Enforce the minimum testnet participation requirement.\n
                        This setting is ignored if the --cluster is not `mainnet-beta`")
         )
+        .arg(
+            Arg::with_name("no_enforce_testnet_participation")
+                .long("no-enforce-testnet-participation")
+                .takes_value(false)
+                .conflicts_with("enforce_testnet_participation")
+                .help("Do not enforce the minimum testnet participation requirement, even if \
+                       the config file does")
+        )
+        .arg(
+            Arg::with_name("criteria")
+                .long("criteria")
+                .value_name("NAME")
+                .takes_value(true)
+                .multiple(true)
+                .use_delimiter(true)
+                .possible_values(DEFAULT_CRITERIA)
+                .help("Classification criteria to evaluate, in order. \
+                       Omitted criteria are disabled [default: all criteria, in the listed order]")
+        )
+        .arg(
+            Arg::with_name("bonus_weighting")
+                .long("bonus-weighting")
+                .value_name("NAME")
+                .takes_value(true)
+                .possible_values(BONUS_WEIGHTINGS)
+                .help("How the bonus stake is shared among Bonus validators: equally, by block \
+                       production and vote credits, in favor of less concentrated data centers, \
+                       or in favor of validators with less active stake from outside of the pool \
+                       [default: equal]")
+        )
+        .arg(
+            Arg::with_name("min_bonus_stake")
+                .long("min-bonus-stake")
+                .value_name("AMOUNT")
+                .takes_value(true)
+                .validator(is_amount)
+                .help("Minimum amount of SOL delegated to each Bonus validator, as long as the \
+                       bonus stake suffices for every Bonus validator [default: 0]")
+        )
+        .arg(
+            Arg::with_name("max_bonus_stake")
+                .long("max-bonus-stake")
+                .value_name("AMOUNT")
+                .takes_value(true)
+                .validator(is_amount)
+                .help("Maximum amount of SOL delegated to each Bonus validator. \
+                       Bonus stake over the maximum is left in the reserve")
+        )
+        .arg(
+            Arg::with_name("max_stake_change")
+                .long("max-stake-change")
+                .value_name("AMOUNT|PERCENTAGE%")
+                .takes_value(true)
+                .validator(is_max_stake_change)
+                .help("Maximum change of a validator's stake in one epoch, as an amount of SOL \
+                       or a percentage of its current stake. Validators further from their \
+                       target stake move towards it over several epochs [default: unlimited]")
+        )
+        .arg(
+            Arg::with_name("downgrade_epochs")
+                .long("downgrade-epochs")
+                .value_name("K")
+                .takes_value(true)
+                .validator(is_parsable::<usize>)
+                .help("Keep delegating a validator's stake level until it has been classified \
+                       at a lower level for K consecutive epochs [default: 1]")
+        )
+        .arg(
+            Arg::with_name("stake_tiers")
+                .long("stake-tiers")
+                .value_name("FILE")
+                .takes_value(true)
+                .help("YAML file listing graduated stake levels within the Baseline and Bonus \
+                       stake states, each with its own stake amount or bonus share and entry \
+                       criteria")
+        )
+        .arg(
+            Arg::with_name("save_snapshot")
+                .long("save-snapshot")
+                .value_name("DIR")
+                .takes_value(true)
+                .help("Record the cluster observations used for classification into DIR, \
+                       for replay with --from-snapshot")
+        )
+        .arg(
+            Arg::with_name("from_snapshot")
+                .long("from-snapshot")
+                .value_name("FILE")
+                .takes_value(true)
+                .help("Classify from a recorded cluster snapshot without network access. \
+                       The resulting classification is printed and no stake adjustments are made")
+        )
+        .arg(
+            Arg::with_name("export_plan")
+                .long("export-plan")
+                .value_name("FILE")
+                .takes_value(true)
+                .help("Write the planned stake adjustments to FILE as JSON. \
+                       Combined with a dry run, the plan may be reviewed before it is \
+                       submitted with --execute-plan")
+        )
+        .arg(
+            Arg::with_name("metrics_file")
+                .long("metrics-file")
+                .value_name("FILE")
+                .takes_value(true)
+                .help("Write the metrics of the run to FILE in the Prometheus text format, \
+                       for the node_exporter textfile collector. The file is rewritten at the \
+                       end of every run, including failed runs")
+        )
+        .arg(
+            Arg::with_name("notify")
+                .long("notify")
+                .value_name("SINK")
+                .takes_value(true)
+                .multiple(true)
+                .validator(is_notification_sink)
+                .help("Also send the epoch digest to SINK: an http(s) webhook URL, file:PATH, \
+                       mailto:ADDRESS or matrix://HOMESERVER/ROOM. \
+                       Matrix requires the MATRIX_ACCESS_TOKEN environment variable")
+        )
+        .arg(
+            Arg::with_name("validator_contacts")
+                .long("validator-contacts")
+                .value_name("FILE")
+                .takes_value(true)
+                .help("YAML file mapping validator identities to a list of additional \
+                       notification sinks. Validators that registered an email address as their \
+                       contact with the program, or are listed in FILE, are told directly when \
+                       their stake state changes")
+        )
+        .arg(
+            Arg::with_name("smtp_server")
+                .long("smtp-server")
+                .value_name("HOST:PORT")
+                .takes_value(true)
+                .default_value("127.0.0.1:25")
+                .help("SMTP relay for mailto: notification sinks and registered email contacts")
+        )
+        .arg(
+            Arg::with_name("smtp_from")
+                .long("smtp-from")
+                .value_name("ADDRESS")
+                .takes_value(true)
+                .default_value("stake-o-matic@localhost")
+                .help("Sender address of mailto: notification sinks and registered email contacts")
+        )
+        .arg(
+            Arg::with_name("daemon")
+                .long("daemon")
+                .takes_value(false)
+                .conflicts_with_all(&["execute_plan", "from_snapshot", "sign_offline"])
+                .help("Keep running, classifying and applying each epoch shortly after it \
+                       begins. The stake pool is applied again midway through the epoch for \
+                       validators whose transient stake was still pending")
+        )
+        .arg(
+            Arg::with_name("epoch_start_delay")
+                .long("epoch-start-delay")
+                .value_name("SLOTS")
+                .takes_value(true)
+                .default_value("100")
+                .validator(is_parsable::<Slot>)
+                .help("With --daemon, wait this many slots after an epoch boundary before running")
+        )
+        .arg(
+            Arg::with_name("health_check_file")
+                .long("health-check-file")
+                .value_name("FILE")
+                .takes_value(true)
+                .requires("daemon")
+                .help("With --daemon, keep FILE updated with the state of the daemon. \
+                       The file is rewritten at least once a minute")
+        )
+        .arg(
+            Arg::with_name("execute_plan")
+                .long("execute-plan")
+                .value_name("FILE")
+                .takes_value(true)
+                .conflicts_with_all(&["export_plan", "from_snapshot"])
+                .help("Submit exactly the stake adjustments of a plan written by --export-plan, \
+                       without classifying the epoch. The plan is rejected if the stake pool \
+                       balances have changed since it was written. \
+                       Without --confirm the plan is only verified")
+        )
+        .arg(
+            Arg::with_name("sign_offline")
+                .long("sign-offline")
+                .value_name("FILE")
+                .takes_value(true)
+                .requires("nonce_account")
+                .help("Do not sign or send transactions. Instead write them to FILE, unsigned and \
+                       using durable nonces, for signing with `offline sign` and sending with \
+                       `offline broadcast`. Only the pubkey of the authorized staker is required. \
+                       As nothing is sent, --confirm is implied. Only the first batch of \
+                       transactions is written, run again after the broadcast for the next. \
+                       The classification is not saved, reported or notified until a run after \
+                       the broadcast")
+        )
+        .arg(
+            Arg::with_name("nonce_account")
+                .long("nonce-account")
+                .value_name("ADDRESS")
+                .takes_value(true)
+                .multiple(true)
+                .validator(is_pubkey)
+                .help("Durable nonce account, with the authorized staker as nonce authority. \
+                       Transactions are sent with a nonce rather than a recent blockhash so they \
+                       can't expire, and are resumed by a later run if interrupted. \
+                       The nonce accounts are reused as transactions are confirmed, except with \
+                       --sign-offline where every transaction requires its own nonce account")
+        )
+        .subcommand(
+            SubCommand::with_name("config")
+            .about("Inspect the configuration")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                SubCommand::with_name("show")
+                .about("Print the effective settings, after merging the command-line \
+                        with the config file")
+            )
+        )
+        .subcommand(
+            SubCommand::with_name("db")
+            .about("Manage the staking history")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                SubCommand::with_name("migrate")
+                .about("Copy the staking history of the --db-backend into another backend")
+                .arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .value_name("BACKEND")
+                        .takes_value(true)
+                        .required(true)
+                        .possible_values(DB_BACKENDS)
+                        .help("Destination storage backend")
+                )
+            )
+            .subcommand(
+                SubCommand::with_name("validator-history")
+                .about("Print the classification history of a validator, newest first")
+                .arg(
+                    Arg::with_name("identity")
+                        .index(1)
+                        .value_name("IDENTITY")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(is_pubkey)
+                        .help("Validator identity")
+                )
+            )
+            .subcommand(
+                SubCommand::with_name("serve")
+                .about("Serve the staking history as JSON over a read-only HTTP API: \
+                        /epochs, /epochs/EPOCH, /validators/IDENTITY and \
+                        /validators/IDENTITY/latest")
+                .arg(
+                    Arg::with_name("bind_address")
+                        .long("bind-address")
+                        .value_name("ADDRESS")
+                        .takes_value(true)
+                        .default_value("127.0.0.1:8080")
+                        .validator(is_parsable::<SocketAddr>)
+                        .help("Address to listen on")
+                )
+            )
+        )
+        .subcommand(
+            SubCommand::with_name("offline")
+            .about("Sign and broadcast transactions written by --sign-offline")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                SubCommand::with_name("sign")
+                .about("Add the signatures of a signer to the transactions that require it. \
+                        No network access is performed")
+                .arg(
+                    Arg::with_name("file")
+                        .index(1)
+                        .value_name("FILE")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Transactions written by --sign-offline")
+                )
+                .arg(
+                    Arg::with_name("signer")
+                        .long("signer")
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(is_valid_signer)
+                        .help("Keypair file or hardware wallet URL, such as usb://ledger")
+                )
+            )
+            .subcommand(
+                SubCommand::with_name("broadcast")
+                .about("Send the fully signed transactions and wait for them to be confirmed")
+                .arg(
+                    Arg::with_name("file")
+                        .index(1)
+                        .value_name("FILE")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Transactions written by --sign-offline")
+                )
+            )
+        )
+        .subcommand(
+            SubCommand::with_name("simulate")
+            .about("Replay the stored classification history under the provided configuration \
+                    and report how the outcome would differ")
+            .arg(
+                Arg::with_name("snapshot_dir")
+                    .long("snapshot-dir")
+                    .value_name("DIR")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Directory of cluster snapshots recorded with --save-snapshot. \
+                           Epochs without a recorded snapshot are replayed from their stored metrics")
+            )
+            .arg(
+                Arg::with_name("first_epoch")
+                    .long("first-epoch")
+                    .value_name("EPOCH")
+                    .takes_value(true)
+                    .validator(is_parsable::<Epoch>)
+                    .help("First epoch to replay [default: the latest classified epoch]")
+            )
+            .arg(
+                Arg::with_name("last_epoch")
+                    .long("last-epoch")
+                    .value_name("EPOCH")
+                    .takes_value(true)
+                    .validator(is_parsable::<Epoch>)
+                    .help("Last epoch to replay [default: the latest classified epoch]")
+            )
+            .arg(
+                Arg::with_name("stake_pool_size")
+                    .long("stake-pool-size")
+                    .value_name("SOL")
+                    .takes_value(true)
+                    .validator(is_amount)
+                    .help("Stake pool size used to compute the bonus stake amount of epochs \
+                           that did not record theirs")
+            )
+            .arg(
+                Arg::with_name("baseline_stake_amount")
+                    .long("baseline-stake-amount")
+                    .value_name("SOL")
+                    .takes_value(true)
+                    .default_value("5000")
+                    .validator(is_amount)
+            )
+        )
         .subcommand(
             SubCommand::with_name("stake-pool-v0").about("Use the stake-pool v0 solution")
             .arg(
@@ -542,18 +1141,17 @@ fn get_config() -> BoxResult<(Config, RpcClient, Box<dyn GenericStakePool>)> {
                     .index(1)
                     .value_name("RESERVE_STAKE_ADDRESS")
                     .takes_value(true)
-                    .required(true)
                     .validator(is_pubkey_or_keypair)
-                    .help("The reserve stake account used to fund the stake pool")
+                    .help("The reserve stake account used to fund the stake pool [default: from the config file]")
             )
             .arg(
                 Arg::with_name("authorized_staker")
                     .index(2)
                     .value_name("KEYPAIR")
-                    .validator(is_keypair)
-                    .required(true)
+                    .validator(is_pubkey_or_keypair)
                     .takes_value(true)
-                    .help("Keypair of the authorized staker")
+                    .help("Keypair of the authorized staker, or its pubkey with --sign-offline \
+                           [default: from the config file]")
             )
             .arg(
                 Arg::with_name("min_reserve_stake_balance")
@@ -580,18 +1178,17 @@ fn get_config() -> BoxResult<(Config, RpcClient, Box<dyn GenericStakePool>)> {
                     .index(1)
                     .value_name("POOL_ADDRESS")
                     .takes_value(true)
-                    .required(true)
                     .validator(is_pubkey_or_keypair)
-                    .help("The stake pool address")
+                    .help("The stake pool address [default: from the config file]")
             )
             .arg(
                 Arg::with_name("authorized_staker")
                     .index(2)
                     .value_name("KEYPAIR")
-                    .validator(is_keypair)
-                    .required(true)
+                    .validator(is_pubkey_or_keypair)
                     .takes_value(true)
-                    .help("Keypair of the authorized staker")
+                    .help("Keypair of the authorized staker, or its pubkey with --sign-offline \
+                           [default: from the config file]")
             )
             .arg(
                 Arg::with_name("baseline_stake_amount")
@@ -604,74 +1201,173 @@ fn get_config() -> BoxResult<(Config, RpcClient, Box<dyn GenericStakePool>)> {
         )
         .get_matches();
 
-    let dry_run = !matches.is_present("confirm");
-    let cluster = match value_t_or_exit!(matches, "cluster", String).as_str() {
-        "mainnet-beta" => Cluster::MainnetBeta,
-        "testnet" => Cluster::Testnet,
-        _ => unreachable!(),
+    let config_file = match matches.value_of("config") {
+        Some(config_file) => ConfigFile::load(config_file)?,
+        None => ConfigFile::default(),
     };
-    let quality_block_producer_percentage =
-        value_t_or_exit!(matches, "quality_block_producer_percentage", usize);
+
+    let cluster = match config_file.cluster {
+        Some(cluster) if matches.occurrences_of("cluster") == 0 => cluster,
+        _ => match value_t_or_exit!(matches, "cluster", String).as_str() {
+            "mainnet-beta" => Cluster::MainnetBeta,
+            "testnet" => Cluster::Testnet,
+            _ => unreachable!(),
+        },
+    };
+
+    // Command-line arguments take precedence over the config file, which takes precedence over
+    // the command-line defaults
+    let profile = Profile::from_matches(&matches, false)
+        .or(config_file.profile(cluster))
+        .or(Profile::from_matches(&matches, true));
+
+    let sign_offline = matches.value_of("sign_offline").map(PathBuf::from);
+    let nonce_accounts = pubkeys_of(&matches, "nonce_account").unwrap_or_default();
+    let dry_run = !profile.confirm.unwrap_or(false) && sign_offline.is_none();
+    let quality_block_producer_percentage = profile.quality_block_producer_percentage.unwrap();
     let min_epoch_credit_percentage_of_average =
-        value_t_or_exit!(matches, "min_epoch_credit_percentage_of_average", usize);
-    let max_commission = value_t_or_exit!(matches, "max_commission", u8);
-    let max_poor_voter_percentage = value_t_or_exit!(matches, "max_poor_voter_percentage", usize);
-    let max_poor_block_producer_percentage =
-        value_t_or_exit!(matches, "max_poor_block_producer_percentage", usize);
-    let max_old_release_version_percentage =
-        value_t_or_exit!(matches, "max_old_release_version_percentage", usize);
-    let min_release_version = release_version_of(&matches, "min_release_version");
-
-    let enforce_min_self_stake = matches.is_present("enforce_min_self_stake");
-    let min_self_stake_lamports = lamports_of_sol(&matches, "min_self_stake").unwrap();
-    let max_active_stake_lamports = lamports_of_sol(&matches, "max_active_stake").unwrap();
-
-    let enforce_testnet_participation = matches.is_present("enforce_testnet_participation");
-    let min_testnet_participation = values_t!(matches, "min_testnet_participation", usize)
-        .ok()
-        .map(|v| (v[0], v[1]));
+        profile.min_epoch_credit_percentage_of_average.unwrap();
+    let max_commission = profile.max_commission.unwrap();
+    let max_poor_voter_percentage = profile.max_poor_voter_percentage.unwrap();
+    let max_poor_block_producer_percentage = profile.max_poor_block_producer_percentage.unwrap();
+    let max_old_release_version_percentage = profile.max_old_release_version_percentage.unwrap();
+    for (name, percentage) in &[
+        (
+            "quality-block-producer-percentage",
+            quality_block_producer_percentage,
+        ),
+        (
+            "min-epoch-credit-percentage-of-average",
+            min_epoch_credit_percentage_of_average,
+        ),
+        ("max-commission", max_commission as usize),
+        ("max-poor-voter-percentage", max_poor_voter_percentage),
+        (
+            "max-poor-block-producer-percentage",
+            max_poor_block_producer_percentage,
+        ),
+        (
+            "max-old-release-version-percentage",
+            max_old_release_version_percentage,
+        ),
+    ] {
+        check_percentage(name, *percentage as f64)?;
+    }
+    let min_release_version = match profile.min_release_version {
+        Some(ref min_release_version) => {
+            is_release_version(min_release_version.clone())
+                .map_err(|err| format!("`min-release-version`: {}", err))?;
+            Some(parse_release_version(min_release_version))
+        }
+        None => None,
+    };
+
+    let enforce_min_self_stake = profile.enforce_min_self_stake.unwrap_or(false);
+    let min_self_stake_lamports = sol_to_lamports(profile.min_self_stake.unwrap());
+    let max_active_stake_lamports = sol_to_lamports(profile.max_active_stake.unwrap());
+
+    let criteria = profile
+        .criteria
+        .clone()
+        .unwrap_or_else(|| DEFAULT_CRITERIA.iter().map(|s| s.to_string()).collect());
+    let bonus_weighting = profile
+        .bonus_weighting
+        .clone()
+        .unwrap_or_else(|| "equal".to_string());
+    bonus_weighting_from_name(&bonus_weighting)?;
+    let bonus_stake_limits = BonusStakeLimits {
+        min: profile
+            .min_bonus_stake
+            .map(sol_to_lamports)
+            .unwrap_or_default(),
+        max: profile.max_bonus_stake.map(sol_to_lamports),
+    };
+    let max_stake_change = profile
+        .max_stake_change
+        .as_ref()
+        .map(|max_stake_change| MaxStakeChange::from_str(max_stake_change))
+        .transpose()?;
+    let downgrade_epochs = profile.downgrade_epochs.unwrap_or(1).max(1);
+    let stake_tiers = match profile.stake_tiers {
+        Some(ref stake_tiers_path) => tiers::load(stake_tiers_path)?,
+        None => vec![],
+    };
+    let save_snapshot_path = profile.save_snapshot.clone();
+    let from_snapshot = matches.value_of("from_snapshot").map(PathBuf::from);
+    let export_plan_path = profile.export_plan.clone();
+    let metrics_file = profile.metrics_file.clone();
+    let notification_sinks = profile.notify.clone().unwrap_or_default();
+    let validator_contacts_path = profile.validator_contacts.clone();
+    let smtp_server = profile.smtp_server.clone().unwrap();
+    let smtp_from = profile.smtp_from.clone().unwrap();
+
+    let enforce_testnet_participation = profile.enforce_testnet_participation.unwrap_or(false);
+    let min_testnet_participation = profile.min_testnet_participation;
     if min_testnet_participation.is_some() && cluster != Cluster::MainnetBeta {
         error!("--min-testnet-participation only available for `--cluster mainnet-beta`");
         process::exit(1);
     }
 
     let json_rpc_url = match cluster {
-        Cluster::MainnetBeta => value_t!(matches, "json_rpc_url", String)
-            .unwrap_or_else(|_| "http://api.mainnet-beta.solana.com".into()),
-        Cluster::Testnet => value_t!(matches, "json_rpc_url", String)
-            .unwrap_or_else(|_| "http://testnet.solana.com".into()),
+        Cluster::MainnetBeta => profile
+            .url
+            .clone()
+            .unwrap_or_else(|| "http://api.mainnet-beta.solana.com".into()),
+        Cluster::Testnet => profile
+            .url
+            .clone()
+            .unwrap_or_else(|| "http://testnet.solana.com".into()),
     };
-    let db_path = value_t_or_exit!(matches, "db_path", PathBuf);
-    let markdown_path = if matches.is_present("markdown") {
+    let db_path = profile.db_path.clone().unwrap();
+    let db_backend = profile.db_backend.unwrap();
+    let markdown_path = if profile.markdown.unwrap_or(false) {
         Some(db_path.join("md"))
     } else {
         None
     };
-    let require_classification = matches.is_present("require_classification");
+    let html_report_path = if profile.html_report.unwrap_or(false) {
+        Some(db_path.join(format!("html-{}", cluster)))
+    } else {
+        None
+    };
+    let report_templates_path = profile.report_templates.clone();
+    let rebuild_reports = profile.rebuild_reports.unwrap_or(false);
+    let require_classification = profile.require_classification.unwrap_or(false);
 
-    let confirmed_block_cache_path = matches
-        .value_of("confirmed_block_cache_path")
-        .map(PathBuf::from)
-        .unwrap();
+    let confirmed_block_cache_path = profile.confirmed_block_cache_path.clone().unwrap();
 
-    let bad_cluster_average_skip_rate =
-        value_t!(matches, "bad_cluster_average_skip_rate", usize).unwrap_or(50);
-    let max_infrastructure_concentration =
-        value_t!(matches, "max_infrastructure_concentration", f64).ok();
-    let infrastructure_concentration_affects = value_t!(
-        matches,
-        "infrastructure_concentration_affects",
-        InfrastructureConcentrationAffects
-    )
-    .unwrap();
+    let bad_cluster_average_skip_rate = profile.bad_cluster_average_skip_rate.unwrap_or(50);
+    check_percentage(
+        "bad-cluster-average-skip-rate",
+        bad_cluster_average_skip_rate as f64,
+    )?;
+    let max_infrastructure_concentration = profile.max_infrastructure_concentration;
+    if let Some(max_infrastructure_concentration) = max_infrastructure_concentration {
+        check_percentage(
+            "max-infrastructure-concentration",
+            max_infrastructure_concentration,
+        )?;
+    }
+    let infrastructure_concentration_affects = InfrastructureConcentrationAffects::from_str(
+        profile
+            .infrastructure_concentration_affects
+            .as_ref()
+            .unwrap(),
+    )?;
 
     let config = Config {
         json_rpc_url,
         cluster,
         db_path,
+        db_backend,
         require_classification,
         markdown_path,
+        html_report_path,
+        report_templates_path,
+        rebuild_reports,
         dry_run,
+        journal: !dry_run && sign_offline.is_none(),
+        sign_offline: sign_offline.is_some(),
         quality_block_producer_percentage,
         max_poor_block_producer_percentage,
         max_commission,
@@ -688,49 +1384,163 @@ fn get_config() -> BoxResult<(Config, RpcClient, Box<dyn GenericStakePool>)> {
         enforce_min_self_stake,
         enforce_testnet_participation,
         min_testnet_participation,
+        criteria,
+        bonus_weighting,
+        stake_tiers,
+        downgrade_epochs,
+        save_snapshot_path,
+        export_plan_path,
+        metrics_file,
+        notification_sinks,
+        validator_contacts_path,
+        smtp_server,
+        smtp_from,
     };
 
     info!("RPC URL: {}", config.json_rpc_url);
     let rpc_client =
         RpcClient::new_with_timeout(config.json_rpc_url.clone(), Duration::from_secs(180));
 
+    if let ("db", Some(matches)) = matches.subcommand() {
+        let command = match matches.subcommand() {
+            ("migrate", Some(matches)) => {
+                Command::MigrateDb(value_t_or_exit!(matches, "to", DbBackend))
+            }
+            ("validator-history", Some(matches)) => {
+                Command::ValidatorHistory(value_t_or_exit!(matches, "identity", Pubkey))
+            }
+            ("serve", Some(matches)) => {
+                Command::ServeDb(value_t_or_exit!(matches, "bind_address", SocketAddr))
+            }
+            _ => unreachable!(),
+        };
+        return Ok((config, rpc_client, command));
+    }
+    if let ("offline", Some(matches)) = matches.subcommand() {
+        let command = match matches.subcommand() {
+            ("sign", Some(matches)) => {
+                let mut wallet_manager = None;
+                let signer = signer_from_path(
+                    matches,
+                    matches.value_of("signer").unwrap(),
+                    "signer",
+                    &mut wallet_manager,
+                )?;
+                Command::SignOffline(value_t_or_exit!(matches, "file", PathBuf), signer)
+            }
+            ("broadcast", Some(matches)) => {
+                Command::BroadcastOffline(value_t_or_exit!(matches, "file", PathBuf))
+            }
+            _ => unreachable!(),
+        };
+        return Ok((config, rpc_client, command));
+    }
+    if let ("config", Some(_)) = matches.subcommand() {
+        // `show` is the only `config` subcommand
+        return Ok((config, rpc_client, Command::ShowConfig(profile)));
+    }
+    if let Some(from_snapshot) = from_snapshot {
+        return Ok((
+            config,
+            rpc_client,
+            Command::ClassifyFromSnapshot(from_snapshot),
+        ));
+    }
+    if let ("simulate", Some(matches)) = matches.subcommand() {
+        let simulate_args = simulate::SimulateArgs {
+            snapshot_path: value_t_or_exit!(matches, "snapshot_dir", PathBuf),
+            first_epoch: value_t!(matches, "first_epoch", Epoch).ok(),
+            last_epoch: value_t!(matches, "last_epoch", Epoch).ok(),
+            stake_pool_size: lamports_of_sol(matches, "stake_pool_size"),
+            baseline_stake_amount: lamports_of_sol(matches, "baseline_stake_amount").unwrap(),
+        };
+        return Ok((config, rpc_client, Command::Simulate(simulate_args)));
+    }
+
     // Sanity check that the RPC endpoint is healthy before performing too much work
     rpc_client
         .get_health()
         .map_err(|err| format!("RPC endpoint is unhealthy: {:?}", err))?;
 
+    // With --sign-offline only the pubkey of the authorized staker is required
+    let authorized_staker = |authorized_staker: Option<String>| -> BoxResult<AuthorizedStaker> {
+        Ok(match sign_offline {
+            Some(ref sign_offline) => AuthorizedStaker::Offline(OfflineStaker::new(
+                pubkey_of_setting("authorized-staker", authorized_staker)?,
+                sign_offline.clone(),
+                nonce_accounts.clone(),
+            )),
+            None => {
+                let keypair = keypair_of_setting("authorized-staker", authorized_staker)?;
+                if nonce_accounts.is_empty() {
+                    AuthorizedStaker::Keypair(keypair)
+                } else {
+                    AuthorizedStaker::NoncePool(
+                        keypair,
+                        NoncePool::new(
+                            nonce_accounts.clone(),
+                            config.cluster_db_path().join("in-flight-transactions.json"),
+                        ),
+                    )
+                }
+            }
+        })
+    };
+
     let stake_pool: Box<dyn GenericStakePool> = match matches.subcommand() {
-        ("stake-pool-v0", Some(matches)) => {
-            let authorized_staker = keypair_of(&matches, "authorized_staker").unwrap();
-            let reserve_stake_address = pubkey_of(&matches, "reserve_stake_address").unwrap();
+        ("stake-pool-v0", Some(_)) => {
+            let settings = profile.stake_pool_v0.unwrap_or_default();
+            let authorized_staker = authorized_staker(settings.authorized_staker)?;
+            let reserve_stake_address =
+                pubkey_of_setting("reserve-stake-address", settings.reserve_stake_address)?;
             let min_reserve_stake_balance =
-                sol_to_lamports(value_t_or_exit!(matches, "min_reserve_stake_balance", f64));
-            let baseline_stake_amount =
-                sol_to_lamports(value_t_or_exit!(matches, "baseline_stake_amount", f64));
+                sol_to_lamports(settings.min_reserve_stake_balance.unwrap());
+            let baseline_stake_amount = sol_to_lamports(settings.baseline_stake_amount.unwrap());
             Box::new(stake_pool_v0::new(
                 &rpc_client,
                 authorized_staker,
                 baseline_stake_amount,
+                bonus_stake_limits,
+                max_stake_change,
                 reserve_stake_address,
                 min_reserve_stake_balance,
             )?)
         }
-        ("stake-pool", Some(matches)) => {
-            let authorized_staker = keypair_of(&matches, "authorized_staker").unwrap();
-            let pool_address = pubkey_of(&matches, "pool_address").unwrap();
-            let baseline_stake_amount =
-                sol_to_lamports(value_t_or_exit!(matches, "baseline_stake_amount", f64));
+        ("stake-pool", Some(_)) => {
+            let settings = profile.stake_pool.unwrap_or_default();
+            let authorized_staker = authorized_staker(settings.authorized_staker)?;
+            let pool_address = pubkey_of_setting("pool-address", settings.pool_address)?;
+            let baseline_stake_amount = sol_to_lamports(settings.baseline_stake_amount.unwrap());
             Box::new(stake_pool::new(
                 &rpc_client,
                 authorized_staker,
                 pool_address,
                 baseline_stake_amount,
+                bonus_stake_limits,
+                max_stake_change,
             )?)
         }
-        _ => unreachable!(),
+        _ => return Err("A stake pool subcommand is required".into()),
     };
 
-    Ok((config, rpc_client, stake_pool))
+    if let Some(execute_plan) = matches.value_of("execute_plan") {
+        let stake_plan = StakePlan::load(execute_plan)?;
+        return Ok((
+            config,
+            rpc_client,
+            Command::ExecutePlan(stake_pool, stake_plan),
+        ));
+    }
+
+    if matches.is_present("daemon") {
+        let daemon_args = daemon::DaemonArgs {
+            epoch_start_delay: value_t_or_exit!(matches, "epoch_start_delay", Slot),
+            health_check_path: matches.value_of("health_check_file").map(PathBuf::from),
+        };
+        return Ok((config, rpc_client, Command::Daemon(stake_pool, daemon_args)));
+    }
+
+    Ok((config, rpc_client, Command::Run(stake_pool)))
 }
 
 type ClassifyResult = (
@@ -740,6 +1550,15 @@ type ClassifyResult = (
     ValidatorList,
     // classification reason
     HashMap<Pubkey, String>,
+    // block production
+    HashMap<
+        Pubkey,
+        (
+            /*blocks:*/ usize,
+            /*slots:*/ usize,
+            /*skip_rate:*/ usize,
+        ),
+    >,
     // cluster_skip_rate
     usize,
     // too_many_poor_block_producers
@@ -756,6 +1575,7 @@ fn classify_producers(
     let mut quality_block_producers = HashSet::new();
     let mut blocks_and_slots = HashMap::new();
     let mut reason_msg = HashMap::new();
+    let mut block_production = HashMap::new();
 
     let mut total_blocks = 0;
     let mut total_slots = 0;
@@ -788,6 +1608,7 @@ fn classify_producers(
         );
         trace!("Validator {} produced {}", validator_identity, msg);
         reason_msg.insert(validator_identity, msg);
+        block_production.insert(validator_identity, (blocks, slots, skip_rate));
 
         if skip_rate.saturating_sub(config.quality_block_producer_percentage)
             > cluster_average_skip_rate
@@ -817,6 +1638,7 @@ fn classify_producers(
         quality_block_producers,
         poor_block_producers,
         reason_msg,
+        block_production,
         cluster_average_skip_rate,
         too_many_poor_block_producers,
     ))
@@ -896,12 +1718,12 @@ fn get_confirmed_blocks(
     }
 }
 
-/// Split validators into quality/poor lists based on their block production over the given `epoch`
-fn classify_block_producers(
+/// Fetch the confirmed blocks and leader schedule of the given `epoch`, for use by
+/// `classify_producers()`
+fn get_block_production(
     rpc_client: &RpcClient,
-    config: &Config,
     epoch: Epoch,
-) -> BoxResult<ClassifyResult> {
+) -> BoxResult<(Slot, HashSet<Slot>, HashMap<String, Vec<usize>>)> {
     let epoch_schedule = rpc_client.get_epoch_schedule()?;
     let first_slot_in_epoch = epoch_schedule.get_first_slot_in_epoch(epoch);
     let last_slot_in_epoch = epoch_schedule.get_last_slot_in_epoch(epoch);
@@ -916,22 +1738,20 @@ fn classify_block_producers(
         )?
         .unwrap();
 
-    classify_producers(
-        first_slot_in_epoch,
-        confirmed_blocks,
-        leader_schedule,
-        config,
-    )
+    Ok((first_slot_in_epoch, confirmed_blocks, leader_schedule))
 }
 
 // Look for self stake, where the stake withdraw authority matches the vote account withdraw
 // authority
-fn get_self_stake_by_vote_account(
+/// The self stake and the stake delegated by `pool_stake_authority` of each vote account
+fn get_stake_by_vote_account(
     rpc_client: &RpcClient,
     epoch: Epoch,
     vote_account_info: &[VoteAccountInfo],
-) -> BoxResult<HashMap<Pubkey, u64>> {
+    pool_stake_authority: &Pubkey,
+) -> BoxResult<(HashMap<Pubkey, u64>, HashMap<Pubkey, u64>)> {
     let mut self_stake_by_vote_account = HashMap::new();
+    let mut pool_stake_by_vote_account = HashMap::new();
 
     info!("Building list of authorized voters...");
 
@@ -958,34 +1778,34 @@ fn get_self_stake_by_vote_account(
     for (_stake_pubkey, stake_account) in all_stake_accounts {
         if let Ok(StakeState::Stake(meta, stake)) = stake_account.state() {
             let vote_address = &stake.delegation.voter_pubkey;
-            if let Some(vote_account_authorized_withdrawer) =
-                authorized_withdrawer.get(vote_address)
-            {
-                if *vote_account_authorized_withdrawer == meta.authorized.withdrawer {
-                    let effective_stake = stake
-                        .delegation
-                        .stake_activating_and_deactivating(epoch, Some(&stake_history), true)
-                        .0;
-                    if effective_stake > 0 {
-                        *self_stake_by_vote_account.entry(*vote_address).or_default() +=
-                            effective_stake;
-                    }
-                }
+            let effective_stake = stake
+                .delegation
+                .stake_activating_and_deactivating(epoch, Some(&stake_history), true)
+                .0;
+            if effective_stake == 0 {
+                continue;
+            }
+
+            if meta.authorized.staker == *pool_stake_authority {
+                *pool_stake_by_vote_account.entry(*vote_address).or_default() += effective_stake;
+            } else if authorized_withdrawer.get(vote_address) == Some(&meta.authorized.withdrawer) {
+                *self_stake_by_vote_account.entry(*vote_address).or_default() += effective_stake;
             }
         }
     }
 
-    Ok(self_stake_by_vote_account)
+    Ok((self_stake_by_vote_account, pool_stake_by_vote_account))
 }
 
 fn get_testnet_participation(config: &Config) -> BoxResult<Option<HashMap<Pubkey, bool>>> {
     if let Some((n, m)) = &config.min_testnet_participation {
         assert_eq!(config.cluster, Cluster::MainnetBeta);
-        let latest_testnet_epoch_classification =
-            EpochClassification::load_latest(&config.cluster_db_path_for(Cluster::Testnet))?
-                .ok_or("Unable to load testnet epoch classification")?
-                .1
-                .into_current();
+        let latest_testnet_epoch_classification = config
+            .epoch_classification_db_for(Cluster::Testnet)?
+            .load_latest()?
+            .ok_or("Unable to load testnet epoch classification")?
+            .1
+            .into_current();
 
         let testnet_participation = latest_testnet_epoch_classification
             .validator_classifications
@@ -1022,20 +1842,90 @@ fn get_testnet_participation(config: &Config) -> BoxResult<Option<HashMap<Pubkey
     }
 }
 
-fn classify(
+/// Gather everything `classify()` needs to know about the cluster for `epoch`
+fn get_cluster_snapshot(
     rpc_client: &RpcClient,
     config: &Config,
     epoch: Epoch,
-    validator_list: &ValidatorList,
-    identity_to_participant: &IdentityToParticipant,
-    previous_epoch_validator_classifications: Option<&ValidatorClassificationByIdentity>,
-) -> BoxResult<EpochClassificationV1> {
+    validator_list: ValidatorList,
+    identity_to_participant: IdentityToParticipant,
+    pool_stake_authority: &Pubkey,
+) -> BoxResult<ClusterSnapshot> {
     let last_epoch = epoch - 1;
 
     let testnet_participation = get_testnet_participation(config)?;
 
     let data_centers = match data_center_info::get(&config.cluster.to_string()) {
-        Ok(data_centers) => {
+        Ok(data_centers) => Some(data_centers),
+        Err(err) => {
+            if config.max_infrastructure_concentration.is_some() {
+                return Err(err);
+            }
+            warn!("infrastructure concentration skipped: {}", err);
+            None
+        }
+    };
+
+    let (vote_account_info, total_active_stake) = get_vote_account_info(&rpc_client, last_epoch)?;
+
+    let (self_stake_by_vote_account, pool_stake_by_vote_account) =
+        get_stake_by_vote_account(rpc_client, epoch, &vote_account_info, pool_stake_authority)?;
+
+    let cluster_node_versions = rpc_client
+        .get_cluster_nodes()?
+        .into_iter()
+        .filter_map(|rpc_contact_info| {
+            Pubkey::from_str(&rpc_contact_info.pubkey)
+                .ok()
+                .zip(rpc_contact_info.version)
+        })
+        .collect();
+
+    let (first_slot_in_epoch, confirmed_blocks, leader_schedule) =
+        get_block_production(rpc_client, last_epoch)?;
+
+    Ok(ClusterSnapshot {
+        epoch,
+        validator_list,
+        identity_to_participant,
+        data_centers,
+        vote_account_info,
+        total_active_stake,
+        self_stake_by_vote_account,
+        pool_stake_by_vote_account,
+        cluster_node_versions,
+        first_slot_in_epoch,
+        confirmed_blocks,
+        leader_schedule,
+        testnet_participation,
+    })
+}
+
+fn classify(
+    config: &Config,
+    snapshot: ClusterSnapshot,
+    previous_epoch_validator_classifications: Option<&ValidatorClassificationByIdentity>,
+    criteria: &[Box<dyn ValidatorCriterion>],
+) -> BoxResult<EpochClassificationV2> {
+    let ClusterSnapshot {
+        epoch,
+        validator_list,
+        identity_to_participant,
+        data_centers,
+        vote_account_info,
+        total_active_stake,
+        self_stake_by_vote_account,
+        pool_stake_by_vote_account,
+        cluster_node_versions,
+        first_slot_in_epoch,
+        confirmed_blocks,
+        leader_schedule,
+        testnet_participation,
+    } = snapshot;
+    let last_epoch = epoch - 1;
+
+    let data_centers = match data_centers {
+        Some(data_centers) => {
             // Sanity check the infrastructure stake percent data.  More than 35% indicates there's
             // probably a bug in the data source. Abort if so.
             let max_infrastucture_stake_percent = data_centers
@@ -1054,11 +1944,10 @@ fn classify(
             }
             data_centers
         }
-        Err(err) => {
+        None => {
             if config.max_infrastructure_concentration.is_some() {
-                return Err(err);
+                return Err("Data center information is not available".into());
             }
-            warn!("infrastructure concentration skipped: {}", err);
             data_center_info::DataCenters::default()
         }
     };
@@ -1078,26 +1967,16 @@ fn classify(
         .flat_map(|(v, sp)| v.into_iter().map(move |v| (v, sp)))
         .collect::<HashMap<_, _>>();
 
-    let (vote_account_info, total_active_stake) = get_vote_account_info(&rpc_client, last_epoch)?;
-
-    let self_stake_by_vote_account =
-        get_self_stake_by_vote_account(rpc_client, epoch, &vote_account_info)?;
-
     let (cluster_nodes_with_old_version, min_release_version): (HashMap<String, _>, _) =
         match config.min_release_version {
             Some(ref min_release_version) => (
-                rpc_client
-                    .get_cluster_nodes()?
-                    .into_iter()
-                    .filter_map(|rpc_contact_info| {
-                        if let Ok(identity) = Pubkey::from_str(&rpc_contact_info.pubkey) {
-                            if validator_list.contains(&identity) {
-                                if let Some(ref version) = rpc_contact_info.version {
-                                    if let Ok(semver) = semver::Version::parse(version) {
-                                        if semver < *min_release_version {
-                                            return Some((rpc_contact_info.pubkey, semver));
-                                        }
-                                    }
+                cluster_node_versions
+                    .iter()
+                    .filter_map(|(identity, version)| {
+                        if validator_list.contains(identity) {
+                            if let Ok(semver) = semver::Version::parse(version) {
+                                if semver < *min_release_version {
+                                    return Some((identity.to_string(), semver));
                                 }
                             }
                         }
@@ -1120,19 +1999,15 @@ fn classify(
         quality_block_producers,
         poor_block_producers,
         block_producer_classification_reason,
+        block_production,
         cluster_average_skip_rate,
         too_many_poor_block_producers,
-    ) = classify_block_producers(&rpc_client, &config, last_epoch)?;
-
-    let not_in_leader_schedule: ValidatorList = validator_list
-        .difference(
-            &quality_block_producers
-                .intersection(&poor_block_producers)
-                .cloned()
-                .collect(),
-        )
-        .cloned()
-        .collect();
+    ) = classify_producers(
+        first_slot_in_epoch,
+        confirmed_blocks,
+        leader_schedule,
+        config,
+    )?;
 
     let too_many_old_validators = cluster_nodes_with_old_version.len()
         > (poor_block_producers.len() + quality_block_producers.len())
@@ -1186,6 +2061,17 @@ fn classify(
         ));
     }
 
+    metrics::set_gauge(
+        "cluster_average_skip_rate_percent",
+        "Cluster average skip rate over the previous epoch",
+        cluster_average_skip_rate as f64,
+    );
+    metrics::set_gauge(
+        "poor_voter_percent",
+        "Percentage of validators classified as poor voters over the previous epoch",
+        poor_voter_percentage as f64,
+    );
+
     if cluster_average_skip_rate > config.bad_cluster_average_skip_rate {
         notes.push("Cluster average skip rate is poor".to_string());
     }
@@ -1208,244 +2094,175 @@ fn classify(
         ));
     }
 
-    let validator_classifications = if too_many_poor_voters
-        || too_many_old_validators
-        || too_many_poor_block_producers
-    {
-        notes.push("Stake adjustments skipped this epoch".to_string());
-        None
-    } else {
-        let mut validator_classifications = HashMap::new();
-
-        for VoteAccountInfo {
-            identity,
-            vote_address,
-            commission,
-            active_stake,
-            epoch_credits,
-        } in vote_account_info
-        {
-            if !validator_list.contains(&identity) {
-                continue;
-            }
-
-            let participant = identity_to_participant.get(&identity).cloned();
+    let validator_classifications =
+        if too_many_poor_voters || too_many_old_validators || too_many_poor_block_producers {
+            notes.push("Stake adjustments skipped this epoch".to_string());
+            None
+        } else {
+            let cluster_context = ClusterContext {
+                config,
+                last_epoch,
+                infrastructure_concentration_too_high,
+                testnet_participation,
+                poor_voters,
+                cluster_nodes_with_old_version,
+                quality_block_producers,
+                poor_block_producers,
+                block_producer_classification_reason,
+            };
+            let data_center_stake_percent = data_centers
+                .info
+                .iter()
+                .map(|dci| (dci.id.clone(), dci.stake_percent))
+                .collect::<HashMap<_, _>>();
+            let mut validator_classifications = HashMap::new();
 
-            let current_data_center = data_centers
-                .by_identity
-                .get(&identity)
-                .cloned()
-                .unwrap_or_default();
+            for VoteAccountInfo {
+                identity,
+                vote_address,
+                commission,
+                active_stake,
+                epoch_credits,
+            } in vote_account_info
+            {
+                if !validator_list.contains(&identity) {
+                    continue;
+                }
 
-            let previous_classification = previous_epoch_validator_classifications
-                .map(|p| p.get(&identity))
-                .flatten();
+                let participant = identity_to_participant.get(&identity).cloned();
 
-            let mut previous_data_center_residency = previous_classification
-                .map(|vc| vc.data_center_residency.clone())
-                .flatten()
-                .unwrap_or_default();
+                let current_data_center = data_centers
+                    .by_identity
+                    .get(&identity)
+                    .cloned()
+                    .unwrap_or_default();
 
-            let previous_stake_state = previous_classification
-                .map(|vc| vc.stake_state)
-                .unwrap_or_default();
+                let previous_classification = previous_epoch_validator_classifications
+                    .map(|p| p.get(&identity))
+                    .flatten();
 
-            let self_stake = self_stake_by_vote_account
-                .get(&vote_address)
-                .cloned()
-                .unwrap_or_default();
+                let mut previous_data_center_residency = previous_classification
+                    .map(|vc| vc.data_center_residency.clone())
+                    .flatten()
+                    .unwrap_or_default();
 
-            let block_producer_classification_reason_msg = block_producer_classification_reason
-                .get(&identity)
-                .cloned()
-                .unwrap_or_default();
-            let vote_credits_msg =
-                format!("{} credits earned in epoch {}", epoch_credits, last_epoch);
-
-            let mut validator_notes = vec![];
-
-            let infrastructure_concentration_destake_reason = infrastructure_concentration_too_high
-                .get(&identity)
-                .map(|concentration| {
-                    config.infrastructure_concentration_affects.memo(
-                        &identity,
-                        !previous_data_center_residency.contains_key(&current_data_center),
-                        *concentration,
-                    )
-                })
-                .and_then(|affect| match affect {
-                    InfrastructureConcentrationAffectKind::Destake(reason) => Some(reason),
-                    InfrastructureConcentrationAffectKind::Warn(reason) => {
-                        validator_notes.push(reason);
-                        None
-                    }
-                });
+                let previous_stake_state = previous_classification
+                    .map(|vc| vc.stake_state)
+                    .unwrap_or_default();
 
-            let insufficent_self_stake_msg =
-                format!("Insufficient self stake: {}", Sol(self_stake));
-            if !config.enforce_min_self_stake && self_stake < config.min_self_stake_lamports {
-                validator_notes.push(insufficent_self_stake_msg.clone());
-            }
+                let self_stake = self_stake_by_vote_account
+                    .get(&vote_address)
+                    .cloned()
+                    .unwrap_or_default();
 
-            let insufficent_testnet_participation = testnet_participation
-                .as_ref()
-                .map(|testnet_participation| {
-                    if let Some(participant) = participant {
-                        if !testnet_participation.get(&participant).unwrap_or(&true) {
-                            let note = "Insufficient testnet participation".to_string();
-                            if config.enforce_testnet_participation {
-                                return Some(note);
-                            } else {
-                                validator_notes.push(note);
-                            }
-                        }
-                    }
-                    None
-                })
-                .flatten();
+                let validator_context = ValidatorContext {
+                    identity,
+                    vote_address,
+                    commission,
+                    active_stake,
+                    epoch_credits,
+                    self_stake,
+                    participant,
+                    new_data_center: !previous_data_center_residency
+                        .contains_key(&current_data_center),
+                    previous_stake_state,
+                };
 
-            let (stake_state, reason) = if let Some(reason) =
-                infrastructure_concentration_destake_reason
-            {
-                (ValidatorStakeState::None, reason)
-            } else if config.enforce_min_self_stake && self_stake < config.min_self_stake_lamports {
-                (ValidatorStakeState::None, insufficent_self_stake_msg)
-            } else if active_stake > config.max_active_stake_lamports {
-                (
-                    ValidatorStakeState::None,
-                    format!("active stake is too high: {}", Sol(active_stake)),
-                )
-            } else if commission > config.max_commission {
-                (
-                    ValidatorStakeState::None,
-                    format!("commission is too high: {}% commission", commission),
-                )
-            } else if let Some(insufficent_testnet_participation) =
-                insufficent_testnet_participation
-            {
-                (ValidatorStakeState::None, insufficent_testnet_participation)
-            } else if poor_voters.contains(&identity) {
-                (
-                    ValidatorStakeState::None,
-                    format!("insufficient vote credits: {}", vote_credits_msg),
-                )
-            } else if cluster_nodes_with_old_version.contains_key(&identity.to_string()) {
-                (
-                    ValidatorStakeState::None,
-                    format!(
-                        "Outdated solana release: {}",
-                        cluster_nodes_with_old_version
-                            .get(&identity.to_string())
-                            .unwrap()
-                    ),
-                )
-            } else if quality_block_producers.contains(&identity) {
-                (
-                    ValidatorStakeState::Bonus,
-                    format!(
-                        "good block production during epoch {}: {}",
-                        last_epoch, block_producer_classification_reason_msg
-                    ),
-                )
-            } else if poor_block_producers.contains(&identity) {
-                (
-                    ValidatorStakeState::Baseline,
-                    format!(
-                        "poor block production during epoch {}: {}",
-                        last_epoch, block_producer_classification_reason_msg
-                    ),
-                )
-            } else {
-                assert!(!poor_voters.contains(&identity));
-                assert!(not_in_leader_schedule.contains(&identity));
-                (
-                    // If the validator is not in the leader schedule but was Bonus previously,
-                    // maintain Bonus.
-                    //
-                    // Destaking due to delinquency will not be reflected in the leader schedule
-                    // until 2 epochs later, which point the validator may have recovered and
-                    // there's no need to punish the validator further by reducing it to the
-                    // Baseline level.
-                    if previous_stake_state == ValidatorStakeState::Bonus {
-                        ValidatorStakeState::Bonus
-                    } else {
-                        ValidatorStakeState::Baseline
-                    },
-                    format!("no leader slots; {}", vote_credits_msg),
-                )
-            };
+                let (stake_state, reason, validator_notes) =
+                    evaluate_criteria(criteria, &cluster_context, &validator_context);
 
-            // Data center seniority increases with Bonus stake and decreases
-            // otherwise
-            previous_data_center_residency
-                .entry(current_data_center.clone())
-                .or_default();
+                // Data center seniority increases with Bonus stake and decreases
+                // otherwise
+                previous_data_center_residency
+                    .entry(current_data_center.clone())
+                    .or_default();
 
-            let data_center_residency = previous_data_center_residency
-                .into_iter()
-                .map(|(data_center, seniority)| {
-                    if data_center == current_data_center
-                        && stake_state == ValidatorStakeState::Bonus
-                    {
-                        (data_center, seniority.saturating_add(1))
-                    } else {
-                        (data_center, seniority.saturating_sub(1))
-                    }
-                })
-                .filter(|(_, i)| *i > 0)
-                .collect::<HashMap<_, _>>();
+                let data_center_residency = previous_data_center_residency
+                    .into_iter()
+                    .map(|(data_center, seniority)| {
+                        if data_center == current_data_center
+                            && stake_state == ValidatorStakeState::Bonus
+                        {
+                            (data_center, seniority.saturating_add(1))
+                        } else {
+                            (data_center, seniority.saturating_sub(1))
+                        }
+                    })
+                    .filter(|(_, i)| *i > 0)
+                    .collect::<HashMap<_, _>>();
 
-            debug!(
-                "\nidentity: {} ({:?})\n\
+                debug!(
+                    "\nidentity: {} ({:?})\n\
                     - vote address: {}\n\
                     - stake state: {:?}, data center: {:?} (seniority: {}), self stake: {}\n\
                     - {}",
-                identity,
-                participant,
-                vote_address,
-                stake_state,
-                current_data_center,
-                data_center_residency
-                    .get(&current_data_center)
-                    .cloned()
-                    .unwrap_or_default(),
-                Sol(self_stake),
-                reason
-            );
+                    identity,
+                    participant,
+                    vote_address,
+                    stake_state,
+                    current_data_center,
+                    data_center_residency
+                        .get(&current_data_center)
+                        .cloned()
+                        .unwrap_or_default(),
+                    Sol(self_stake),
+                    reason
+                );
 
-            let mut stake_states = previous_classification
-                .map(|vc| vc.stake_states.clone())
-                .flatten()
-                .unwrap_or_default();
-            stake_states.insert(0, (stake_state, reason.clone()));
+                let mut stake_states = previous_classification
+                    .map(|vc| vc.stake_states.clone())
+                    .flatten()
+                    .unwrap_or_default();
+                stake_states.insert(0, (stake_state, reason.clone()));
 
-            validator_classifications.insert(
-                identity,
-                ValidatorClassification {
+                let mut validator_classification = ValidatorClassification {
                     identity,
                     vote_address,
                     stake_state,
                     stake_states: Some(stake_states),
                     stake_action: None,
                     stake_state_reason: reason,
+                    stake_tier: None,
                     notes: validator_notes,
                     data_center_residency: Some(data_center_residency),
                     current_data_center: Some(current_data_center.clone()),
                     participant,
                     prioritize_funding_in_next_epoch: None,
-                },
-            );
-        }
-        notes.push(format!(
-            "{} validators processed",
-            validator_classifications.len()
-        ));
+                    metrics: Some(ValidatorMetrics {
+                        self_stake: Some(self_stake),
+                        epoch_credits: Some(epoch_credits),
+                        blocks: block_production.get(&identity).map(|bp| bp.0),
+                        leader_slots: block_production.get(&identity).map(|bp| bp.1),
+                        skip_rate: block_production.get(&identity).map(|bp| bp.2),
+                        active_stake: Some(active_stake),
+                        pool_stake: Some(
+                            pool_stake_by_vote_account
+                                .get(&vote_address)
+                                .cloned()
+                                .unwrap_or_default(),
+                        ),
+                        commission: Some(commission),
+                        release_version: cluster_node_versions.get(&identity).cloned(),
+                        data_center_stake_percent: data_center_stake_percent
+                            .get(&current_data_center)
+                            .cloned(),
+                    }),
+                };
+                validator_classification.stake_tier =
+                    tiers::assign(&config.stake_tiers, &validator_classification)
+                        .map(|stake_tier| stake_tier.name.clone());
+                validator_classifications.insert(identity, validator_classification);
+            }
+            notes.push(format!(
+                "{} validators processed",
+                validator_classifications.len()
+            ));
 
-        Some(validator_classifications)
-    };
+            Some(validator_classifications)
+        };
     notes.push(format!("Active stake: {}", Sol(total_active_stake)));
 
-    Ok(EpochClassificationV1 {
+    Ok(EpochClassificationV2 {
         data_center_info: data_centers.info,
         validator_classifications,
         notes,
@@ -1455,8 +2272,109 @@ fn classify(
 fn main() -> BoxResult<()> {
     solana_logger::setup_with_default("solana=info");
 
-    let (config, rpc_client, mut stake_pool) = get_config()?;
+    let (config, rpc_client, command) = get_config()?;
+
+    let mut stake_pool = match command {
+        Command::Run(stake_pool) => stake_pool,
+        Command::Daemon(mut stake_pool, daemon_args) => {
+            return daemon::run(&rpc_client, &daemon_args, || {
+                run_with_metrics(&config, &rpc_client, stake_pool.as_mut())
+            });
+        }
+        Command::ClassifyFromSnapshot(snapshot_path) => {
+            return classify_from_snapshot(&config, &snapshot_path);
+        }
+        Command::Simulate(simulate_args) => return simulate::run(&config, &simulate_args),
+        Command::MigrateDb(to) => {
+            if to == config.db_backend {
+                return Err(format!("The staking history is already stored in {}", to).into());
+            }
+            let epochs = migrate(
+                config.epoch_classification_db()?.as_ref(),
+                to.open(config.cluster_db_path())?.as_ref(),
+            )?;
+            println!(
+                "Migrated {} epochs from {} to {}",
+                epochs, config.db_backend, to
+            );
+            return Ok(());
+        }
+        Command::ValidatorHistory(identity) => {
+            for (epoch, validator_classification) in config
+                .epoch_classification_db()?
+                .load_validator_history(&identity)?
+            {
+                println!("Epoch {}:", epoch);
+                print!("{}", serde_yaml::to_string(&validator_classification)?);
+            }
+            return Ok(());
+        }
+        Command::ServeDb(address) => {
+            return api::serve(config.epoch_classification_db()?.as_ref(), address);
+        }
+        Command::ExecutePlan(mut stake_pool, stake_plan) => {
+            return execute_plan(&config, &rpc_client, stake_pool.as_mut(), &stake_plan);
+        }
+        Command::SignOffline(path, signer) => {
+            let signed = offline_signing::sign(&path, signer.as_ref())?;
+            println!("Signed {} transactions of {}", signed, path.display());
+            return Ok(());
+        }
+        Command::BroadcastOffline(path) => return offline_signing::broadcast(&rpc_client, &path),
+        Command::ShowConfig(profile) => {
+            print!(
+                "{}",
+                serde_yaml::to_string(&EffectiveConfig {
+                    cluster: config.cluster,
+                    profile: &profile,
+                })?
+            );
+            return Ok(());
+        }
+    };
+
+    run_with_metrics(&config, &rpc_client, stake_pool.as_mut())
+}
 
+/// `run()`, then write the metrics of the run if requested
+fn run_with_metrics(
+    config: &Config,
+    rpc_client: &RpcClient,
+    stake_pool: &mut dyn GenericStakePool,
+) -> BoxResult<()> {
+    let run_start = Instant::now();
+    let result = run(config, rpc_client, stake_pool);
+
+    if let Some(ref metrics_file) = config.metrics_file {
+        metrics::set_gauge(
+            "run_duration_seconds",
+            "Duration of the run",
+            run_start.elapsed().as_secs_f64(),
+        );
+        metrics::set_gauge(
+            "run_success",
+            "1 if the run completed successfully, 0 otherwise",
+            if result.is_ok() { 1. } else { 0. },
+        );
+        metrics::set_gauge(
+            "last_run_timestamp_seconds",
+            "Time the run ended, in seconds since the Unix epoch",
+            SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64(),
+        );
+        if let Err(err) = metrics::write(metrics_file, &[("cluster", &config.cluster.to_string())])
+        {
+            error!("Unable to write metrics: {}", err);
+        }
+    }
+    result
+}
+
+/// Classify the current epoch and apply the result to `stake_pool`
+fn run(
+    config: &Config,
+    rpc_client: &RpcClient,
+    stake_pool: &mut dyn GenericStakePool,
+) -> BoxResult<()> {
     info!("Loading participants...");
     let participants = get_participants_with_state(
         &RpcClient::new("https://api.mainnet-beta.solana.com".to_string()),
@@ -1471,7 +2389,7 @@ fn main() -> BoxResult<()> {
         .map(
             |(
                 participant,
-                Participant {
+                ParticipantV2 {
                     mainnet_identity,
                     testnet_identity,
                     ..
@@ -1486,7 +2404,14 @@ fn main() -> BoxResult<()> {
         .unzip();
 
     info!("{} participants loaded", participants.len());
-    assert!(participants.len() > 450); // Hard coded sanity check...
+    // Hard coded sanity check. An error rather than a panic so that a daemon retries the run
+    if participants.len() <= 450 {
+        return Err(format!(
+            "Only {} participants loaded, the RPC response may be incomplete",
+            participants.len()
+        )
+        .into());
+    }
 
     let (validator_list, identity_to_participant) = match config.cluster {
         Cluster::MainnetBeta => (
@@ -1499,11 +2424,29 @@ fn main() -> BoxResult<()> {
         ),
     };
 
-    let notifier = if config.dry_run {
-        Notifier::new("DRYRUN")
-    } else {
-        Notifier::default()
-    };
+    // Validators opt in to be notified by registering a contact with the program
+    let registered_contacts = participants
+        .values()
+        .filter_map(|participant| {
+            let identity = match config.cluster {
+                Cluster::MainnetBeta => participant.mainnet_identity,
+                Cluster::Testnet => participant.testnet_identity,
+            };
+            participant
+                .contact()
+                .map(|contact| (identity, contact.to_string()))
+        })
+        .collect::<HashMap<_, _>>();
+    let notifications = Notifications::new(
+        config.dry_run,
+        &config.notification_sinks,
+        &registered_contacts,
+        config.validator_contacts_path.as_deref(),
+        &SmtpSettings {
+            server: config.smtp_server.clone(),
+            from: config.smtp_from.clone(),
+        },
+    )?;
 
     let epoch = rpc_client.get_epoch_info()?.epoch;
     info!("Epoch: {:?}", epoch);
@@ -1512,40 +2455,66 @@ fn main() -> BoxResult<()> {
     }
 
     info!("Data directory: {}", config.cluster_db_path().display());
+    let db = config.epoch_classification_db()?;
 
-    let previous_epoch_classification =
-        EpochClassification::load_previous(epoch, &config.cluster_db_path())?
-            .map(|p| p.1)
-            .unwrap_or_default()
-            .into_current();
+    let previous_epoch_classification = db
+        .load_previous(epoch)?
+        .map(|p| p.1)
+        .unwrap_or_default()
+        .into_current();
+
+    let mut journal = if config.journal {
+        Journal::load(&config.cluster_db_path(), epoch)?
+    } else {
+        Journal::new(epoch)
+    };
 
     let (mut epoch_classification, first_time) =
-        if EpochClassification::exists(epoch, &config.cluster_db_path()) {
+        if let Some((epoch_classification, first_time)) = journal.classification() {
+            info!("Resuming with the classification of the interrupted run");
+            (epoch_classification.into_current(), first_time)
+        } else if db.exists(epoch)? {
             info!("Classification for epoch {} already exists", epoch);
-            (
-                EpochClassification::load(epoch, &config.cluster_db_path())?.into_current(),
-                false,
-            )
+            (db.load(epoch)?.into_current(), false)
         } else {
             if config.require_classification {
                 return Err(format!("Classification for epoch {} does not exist", epoch).into());
             }
+            let criteria = criteria_from_names(&config.criteria)?;
+            let snapshot = get_cluster_snapshot(
+                rpc_client,
+                config,
+                epoch,
+                validator_list,
+                identity_to_participant,
+                &stake_pool.stake_authority(),
+            )?;
+            if let Some(ref save_snapshot_path) = config.save_snapshot_path {
+                let filename = snapshot.save(save_snapshot_path)?;
+                info!("Wrote {}", filename.display());
+            }
             (
                 classify(
-                    &rpc_client,
-                    &config,
-                    epoch,
-                    &validator_list,
-                    &identity_to_participant,
+                    config,
+                    snapshot,
                     previous_epoch_classification
                         .validator_classifications
                         .as_ref(),
+                    &criteria,
                 )?,
                 true,
             )
         };
+    if !journal.is_completed(RunPhase::Classified) {
+        journal.classified(
+            EpochClassification::new(epoch_classification.clone()),
+            first_time,
+        )?;
+    }
 
-    let mut notifications = epoch_classification.notes.clone();
+    let mut notes = epoch_classification.notes.clone();
+    let mut validator_notes = vec![];
+    let mut stake_changes = vec![];
 
     if let Some(ref mut validator_classifications) = epoch_classification.validator_classifications
     {
@@ -1553,8 +2522,22 @@ fn main() -> BoxResult<()> {
             .validator_classifications
             .unwrap_or_default();
 
-        let mut validator_stake_change_notes = vec![];
-        let mut validator_notes = vec![];
+        // Stake states to delegate for, with downgrades deferred per `config.downgrade_epochs`
+        let held_stake_states = validator_classifications
+            .values()
+            .map(|vc| (vc.identity, vc.held_stake_state(config.downgrade_epochs)))
+            .collect::<HashMap<_, _>>();
+
+        let bonus_validators = validator_classifications
+            .values()
+            .filter(|vc| held_stake_states[&vc.identity] == ValidatorStakeState::Bonus)
+            .collect::<Vec<_>>();
+        let bonus_weights = bonus_validators
+            .iter()
+            .map(|vc| vc.identity)
+            .zip(bonus_weighting_from_name(&config.bonus_weighting)?.weights(&bonus_validators))
+            .collect::<HashMap<_, _>>();
+
         let desired_validator_stake: Vec<_> = validator_classifications
             .values()
             .map(|vc| {
@@ -1564,37 +2547,71 @@ fn main() -> BoxResult<()> {
                         .map(|note| format!("Note: {}: {}", vc.identity, note)),
                 );
 
-                let stake_state_changed = match previous_validator_classifications
+                let previous_stake_state = previous_validator_classifications
                     .get(&vc.identity)
-                    .map(|prev_vc| prev_vc.stake_state)
-                {
-                    Some(previous_stake_state) => previous_stake_state != vc.stake_state,
-                    None => true,
-                };
-
-                if stake_state_changed {
-                    validator_stake_change_notes.push(format!(
-                        "* {:?} stake: {}: {}",
-                        vc.stake_state, vc.identity, vc.stake_state_reason
-                    ));
+                    .map(|prev_vc| prev_vc.stake_state);
+
+                if previous_stake_state != Some(vc.stake_state) {
+                    stake_changes.push(StakeChange {
+                        identity: vc.identity,
+                        previous_stake_state,
+                        stake_state: vc.stake_state,
+                        reason: vc.stake_state_reason.clone(),
+                        notes: vc.notes.clone(),
+                    });
                 }
 
+                let stake_state = held_stake_states[&vc.identity];
+                let stake_tier = tiers::find(&config.stake_tiers, vc.stake_tier.as_deref())
+                    .filter(|stake_tier| stake_tier.stake_state == stake_state);
                 ValidatorStake {
                     identity: vc.identity,
                     vote_address: vc.vote_address,
-                    stake_state: vc.stake_state,
+                    stake_state,
                     priority: previous_validator_classifications
                         .get(&vc.identity)
                         .map(|prev_vc| prev_vc.prioritize_funding_in_next_epoch)
                         .unwrap_or_default()
                         .unwrap_or_default(),
+                    bonus_weight: bonus_weights.get(&vc.identity).cloned().unwrap_or_default()
+                        * stake_tier
+                            .and_then(|stake_tier| stake_tier.bonus_share)
+                            .unwrap_or(1.),
+                    stake_amount: stake_tier
+                        .and_then(|stake_tier| stake_tier.stake_amount)
+                        .map(sol_to_lamports),
                 }
             })
             .collect();
 
-        let (stake_pool_notes, validator_stake_actions, unfunded_validators) =
-            stake_pool.apply(&rpc_client, config.dry_run, &desired_validator_stake)?;
-        notifications.extend(stake_pool_notes.clone());
+        for stake_state in &[
+            ValidatorStakeState::None,
+            ValidatorStakeState::Baseline,
+            ValidatorStakeState::Bonus,
+        ] {
+            metrics::set_gauge_with_labels(
+                "validators",
+                "Validators by stake state",
+                &[("stake_state", &format!("{:?}", stake_state))],
+                desired_validator_stake
+                    .iter()
+                    .filter(|vs| vs.stake_state == *stake_state)
+                    .count() as f64,
+            );
+        }
+
+        let (stake_pool_notes, validator_stake_actions, unfunded_validators, stake_plan) =
+            stake_pool.apply(
+                rpc_client,
+                config.dry_run,
+                &desired_validator_stake,
+                &mut journal,
+            )?;
+        if let Some(ref export_plan_path) = config.export_plan_path {
+            stake_plan.save(export_plan_path)?;
+            info!("Wrote {}", export_plan_path.display());
+        }
+        notes.extend(stake_pool_notes.clone());
         epoch_classification.notes.extend(stake_pool_notes);
 
         for identity in unfunded_validators {
@@ -1604,182 +2621,343 @@ fn main() -> BoxResult<()> {
         }
 
         for (identity, stake_action) in validator_stake_actions {
-            validator_classifications
-                .entry(identity)
-                .and_modify(|e| e.stake_action = Some(stake_action));
+            validator_classifications.entry(identity).and_modify(|e| {
+                let held_stake_state = e.held_stake_state(config.downgrade_epochs);
+                e.stake_action = Some(if held_stake_state != e.stake_state {
+                    format!(
+                        "{} (holding {:?} stake until {:?} for {} epochs)",
+                        stake_action, held_stake_state, e.stake_state, config.downgrade_epochs
+                    )
+                } else {
+                    stake_action
+                })
+            });
         }
 
         validator_notes.sort();
-        notifications.extend(validator_notes);
-
-        validator_stake_change_notes.sort();
-        notifications.extend(validator_stake_change_notes);
+        stake_changes.sort_by(|a, b| {
+            (a.stake_state as u8, a.identity).cmp(&(b.stake_state as u8, b.identity))
+        });
     }
 
-    if first_time {
-        EpochClassification::new(epoch_classification).save(epoch, &config.cluster_db_path())?;
-        generate_markdown(epoch, &config)?;
+    if first_time && config.sign_offline {
+        info!(
+            "Epoch {} is not saved or notified until the offline transactions are broadcast",
+            epoch
+        );
+    } else if first_time {
+        db.save(epoch, &EpochClassification::new(epoch_classification))?;
+        generate_reports(epoch, config, db.as_ref())?;
 
-        // Only notify the user if this is the first run for this epoch
-        for notification in notifications {
-            info!("notification: {}", notification);
-            notifier.send(&notification);
+        for note in notes.iter().chain(validator_notes.iter()) {
+            info!("notification: {}", note);
+        }
+        for stake_change in &stake_changes {
+            info!("notification: {}", stake_change.summary());
         }
+
+        // Only notify the user if this is the first run for this epoch
+        let title = format!(
+            "{} epoch {}{}",
+            config.cluster,
+            epoch,
+            if config.dry_run { " (dry run)" } else { "" }
+        );
+        notifications.send(
+            &title,
+            &digest(&title, &notes, validator_notes.len(), &stake_changes),
+            &stake_changes,
+        );
+    } else if config.rebuild_reports {
+        generate_reports(epoch, config, db.as_ref())?;
     }
 
-    Ok(())
+    journal.completed()
 }
 
-fn generate_markdown(epoch: Epoch, config: &Config) -> BoxResult<()> {
-    let markdown_path = match config.markdown_path.as_ref() {
-        Some(d) => d,
-        None => return Ok(()),
-    };
-    fs::create_dir_all(&markdown_path)?;
+/// Verify a `StakePlan` against the current state of the stake pool and, unless a dry run,
+/// submit it
+fn execute_plan(
+    config: &Config,
+    rpc_client: &RpcClient,
+    stake_pool: &mut dyn GenericStakePool,
+    stake_plan: &StakePlan,
+) -> BoxResult<()> {
+    println!(
+        "Stake plan for epoch {} of {}:",
+        stake_plan.epoch, stake_plan.stake_pool
+    );
+    for change in stake_plan.operations() {
+        println!(
+            "* {:?} {}: {} ({:?})",
+            change.operation,
+            change.identity,
+            Sol(change.amount),
+            change.stake_state
+        );
+    }
+
+    stake_pool.execute_plan(rpc_client, config.dry_run, stake_plan)?;
+    if config.dry_run {
+        println!(
+            "Stake plan verified, {} operations not submitted (dry run)",
+            stake_plan.operations().count()
+        );
+    } else {
+        println!(
+            "Stake plan executed: {} operations",
+            stake_plan.operations().count()
+        );
+    }
+    Ok(())
+}
 
-    let mut list = vec![(
+/// Classify a recorded `ClusterSnapshot` and print the result.  No network access is performed
+/// and the classification database is not modified
+fn classify_from_snapshot(config: &Config, snapshot_path: &Path) -> BoxResult<()> {
+    let snapshot = ClusterSnapshot::load(snapshot_path)
+        .map_err(|err| format!("Unable to load {}: {}", snapshot_path.display(), err))?;
+    let epoch = snapshot.epoch;
+    info!(
+        "Classifying epoch {} from {}",
         epoch,
-        EpochClassification::load(epoch, &config.cluster_db_path())?.into_current(),
-    )];
+        snapshot_path.display()
+    );
 
-    let cluster_md = match config.cluster {
-        Cluster::MainnetBeta => "Mainnet",
-        Cluster::Testnet => "Testnet",
-    };
+    let previous_epoch_classification = config
+        .epoch_classification_db()?
+        .load_previous(epoch)?
+        .map(|p| p.1)
+        .unwrap_or_default()
+        .into_current();
 
-    while let Some((epoch, epoch_classification)) =
-        EpochClassification::load_previous(list.last().unwrap().0, &config.cluster_db_path())?
-    {
-        list.push((epoch, epoch_classification.into_current()));
-    }
+    let criteria = criteria_from_names(&config.criteria)?;
+    let epoch_classification = classify(
+        config,
+        snapshot,
+        previous_epoch_classification
+            .validator_classifications
+            .as_ref(),
+        &criteria,
+    )?;
 
-    let validator_summary_csv = {
-        let mut validator_summary_csv = vec![];
-
-        let mut csv = vec!["Identity".to_string()];
-        let mut validator_stakes: HashMap<Pubkey, HashMap<Epoch, ValidatorStakeState>> =
-            HashMap::default();
-        let mut validator_epochs = vec![];
-        for (epoch, epoch_classification) in list.iter() {
-            csv.push(format!("Epoch {}", epoch));
-            validator_epochs.push(epoch);
-            if let Some(ref validator_classifications) =
-                epoch_classification.validator_classifications
-            {
-                for (identity, classification) in validator_classifications {
-                    validator_stakes
-                        .entry(*identity)
-                        .or_default()
-                        .insert(*epoch, classification.stake_state);
-                }
+    println!(
+        "{}",
+        serde_yaml::to_string(&EpochClassification::new(epoch_classification))?
+    );
+    Ok(())
+}
+
+/// The stake state of each validator by epoch, for the classified epochs in `list` ordered newest
+/// first
+fn validator_summary_csv(list: &[(Epoch, EpochClassificationV2)]) -> String {
+    let mut validator_summary_csv = vec![];
+
+    let mut csv = vec!["Identity".to_string()];
+    let mut validator_stakes: HashMap<Pubkey, HashMap<Epoch, String>> = HashMap::default();
+    let mut validator_epochs = vec![];
+    for (epoch, epoch_classification) in list.iter() {
+        csv.push(format!("Epoch {}", epoch));
+        validator_epochs.push(epoch);
+        if let Some(ref validator_classifications) = epoch_classification.validator_classifications
+        {
+            for (identity, classification) in validator_classifications {
+                validator_stakes
+                    .entry(*identity)
+                    .or_default()
+                    .insert(*epoch, classification.stake_level());
             }
         }
-        validator_summary_csv.push(csv.join(","));
-
-        let mut validator_stakes = validator_stakes.into_iter().collect::<Vec<_>>();
-        validator_stakes.sort_by(|a, b| a.0.cmp(&b.0));
-        for (identity, epoch_stakes) in validator_stakes {
-            let mut csv = vec![identity.to_string()];
-            for epoch in &validator_epochs {
-                if let Some(stake_state) = epoch_stakes.get(epoch) {
-                    csv.push(format!("{:?}", stake_state));
-                } else {
-                    csv.push("-".to_string());
-                }
+    }
+    validator_summary_csv.push(csv.join(","));
+
+    let mut validator_stakes = validator_stakes.into_iter().collect::<Vec<_>>();
+    validator_stakes.sort_by(|a, b| a.0.cmp(&b.0));
+    for (identity, epoch_stakes) in validator_stakes {
+        let mut csv = vec![identity.to_string()];
+        for epoch in &validator_epochs {
+            if let Some(stake_level) = epoch_stakes.get(epoch) {
+                csv.push(stake_level.clone());
+            } else {
+                csv.push("-".to_string());
             }
-            validator_summary_csv.push(csv.join(","));
         }
-        validator_summary_csv.join("\n")
-    };
-    let filename = config.cluster_db_path().join("validator-summary.csv");
-    info!("Writing {}", filename.display());
-    let mut file = File::create(filename)?;
-    file.write_all(&validator_summary_csv.into_bytes())?;
+        validator_summary_csv.push(csv.join(","));
+    }
+    validator_summary_csv.join("\n")
+}
 
-    let mut validators_markdown: HashMap<_, Vec<_>> = HashMap::default();
-    let mut cluster_markdown = vec![];
-    for (epoch, epoch_classification) in list.iter() {
-        cluster_markdown.push(format!("### Epoch {}", epoch));
-        for note in &epoch_classification.notes {
-            cluster_markdown.push(format!("* {}", note));
+/// Add the stake states of the newly classified `epoch` to a `validator_summary_csv()`
+fn update_validator_summary_csv(
+    csv: &str,
+    epoch: Epoch,
+    epoch_classification: &EpochClassificationV2,
+) -> BoxResult<String> {
+    let mut lines = csv.lines();
+    let mut header = lines
+        .next()
+        .ok_or("Empty validator summary")?
+        .split(',')
+        .map(|column| column.to_string())
+        .collect::<Vec<_>>();
+
+    // Rows by identity, without the identity column
+    let mut validator_stakes = lines
+        .map(|line| {
+            let mut row = line.split(',').map(|cell| cell.to_string());
+            let identity = row.next().unwrap_or_default();
+            Pubkey::from_str(&identity)
+                .map(|identity| (identity, row.collect::<Vec<_>>()))
+                .map_err(|_| format!("Invalid identity in validator summary: {}", identity))
+        })
+        .collect::<Result<BTreeMap<_, _>, _>>()?;
+
+    // A repeated run for the epoch replaces its column
+    let epoch_column = format!("Epoch {}", epoch);
+    let replace = header.get(1) == Some(&epoch_column);
+    if !replace {
+        header.insert(1, epoch_column);
+    }
+    for row in validator_stakes.values_mut() {
+        if replace {
+            row[0] = "-".to_string();
+        } else {
+            row.insert(0, "-".to_string());
         }
+    }
+    if let Some(ref validator_classifications) = epoch_classification.validator_classifications {
+        for (identity, classification) in validator_classifications {
+            validator_stakes
+                .entry(*identity)
+                .or_insert_with(|| vec!["-".to_string(); header.len() - 1])[0] =
+                classification.stake_level();
+        }
+    }
 
-        if let Some(ref validator_classifications) = epoch_classification.validator_classifications
-        {
-            let mut validator_classifications =
-                validator_classifications.iter().collect::<Vec<_>>();
-            validator_classifications.sort_by(|a, b| a.0.cmp(&b.0));
-            for (identity, classification) in validator_classifications {
-                let validator_markdown = validators_markdown.entry(identity).or_default();
-
-                validator_markdown.push(format!(
-                    "### [[{1} Epoch {0}|{1}#Epoch-{0}]]",
-                    epoch, cluster_md
-                ));
-                let stake_state_streak = classification.stake_state_streak();
-                validator_markdown.push(format!(
-                    "* Stake level: **{:?}**{}",
-                    classification.stake_state,
-                    if stake_state_streak > 1 {
-                        format!(" (for {} epochs)", stake_state_streak)
-                    } else {
-                        "".to_string()
-                    }
-                ));
-                validator_markdown.push(format!(
-                    "* Stake reason: {}",
-                    classification.stake_state_reason
-                ));
-                if let Some(ref stake_action) = classification.stake_action {
-                    validator_markdown.push(format!("* Staking activity: {}", stake_action));
-                }
+    let mut validator_summary_csv = vec![header.join(",")];
+    for (identity, row) in validator_stakes {
+        validator_summary_csv.push(format!("{},{}", identity, row.join(",")));
+    }
+    Ok(validator_summary_csv.join("\n"))
+}
 
-                validator_markdown.push(format!(
-                    "* Vote account address: {}",
-                    classification.vote_address
-                ));
-                if let (Some(current_data_center), Some(data_center_residency)) = (
-                    classification.current_data_center.as_ref(),
-                    classification.data_center_residency.as_ref(),
-                ) {
-                    validator_markdown.push(format!("* Data Center: {}", current_data_center));
-
-                    if data_center_residency.len() > 1
-                        || (data_center_residency.len() == 1
-                            && !data_center_residency.contains_key(&current_data_center))
-                    {
-                        let data_center_residency = data_center_residency
-                            .keys()
-                            .cloned()
-                            .map(|data_center| data_center.to_string())
-                            .collect::<Vec<_>>();
-                        validator_markdown.push(format!(
-                            "* Resident Data Center(s): {}",
-                            data_center_residency.join(", ")
-                        ));
-                    }
-                }
+/// The metrics of each validator classified in an epoch
+fn validator_metrics_csv(epoch_classification: &EpochClassificationV2) -> String {
+    let mut validator_metrics_csv = vec![[
+        "Identity",
+        "Stake State",
+        "Blocks",
+        "Leader Slots",
+        "Skip Rate",
+        "Vote Credits",
+        "Self Stake (lamports)",
+        "Active Stake (lamports)",
+        "Commission",
+        "Release Version",
+        "Data Center Stake Percent",
+    ]
+    .join(",")];
+
+    fn optional<T: ToString>(value: Option<T>) -> String {
+        value.map(|value| value.to_string()).unwrap_or_default()
+    }
 
-                for note in &classification.notes {
-                    validator_markdown.push(format!("* {}", note));
-                }
-            }
+    if let Some(ref validator_classifications) = epoch_classification.validator_classifications {
+        let mut validator_classifications = validator_classifications.iter().collect::<Vec<_>>();
+        validator_classifications.sort_by(|a, b| a.0.cmp(&b.0));
+        for (identity, classification) in validator_classifications {
+            let metrics = classification.metrics.clone().unwrap_or_default();
+            validator_metrics_csv.push(
+                [
+                    identity.to_string(),
+                    format!("{:?}", classification.stake_state),
+                    optional(metrics.blocks),
+                    optional(metrics.leader_slots),
+                    optional(metrics.skip_rate),
+                    optional(metrics.epoch_credits),
+                    optional(metrics.self_stake),
+                    optional(metrics.active_stake),
+                    optional(metrics.commission),
+                    optional(metrics.release_version),
+                    optional(metrics.data_center_stake_percent),
+                ]
+                .join(","),
+            );
         }
     }
+    validator_metrics_csv.join("\n")
+}
 
-    for (identity, validator_markdown) in validators_markdown {
-        let markdown = validator_markdown.join("\n");
-        let filename = markdown_path.join(format!("Validator-{}.md", identity));
-        info!("Writing {}", filename.display());
-        let mut file = File::create(filename)?;
-        file.write_all(&markdown.into_bytes())?;
+fn generate_reports(
+    epoch: Epoch,
+    config: &Config,
+    db: &dyn EpochClassificationDb,
+) -> BoxResult<()> {
+    if config.markdown_path.is_none() && config.html_report_path.is_none() {
+        return Ok(());
     }
 
-    let markdown = cluster_markdown.join("\n");
-    let filename = markdown_path.join(format!("{}.md", cluster_md));
+    let epoch_classification = db.load(epoch)?.into_current();
+
+    let cluster_md = match config.cluster {
+        Cluster::MainnetBeta => "Mainnet",
+        Cluster::Testnet => "Testnet",
+    };
+    let reports = report::Reports {
+        cluster: cluster_md,
+        markdown_path: config.markdown_path.as_deref(),
+        html_path: config.html_report_path.as_deref(),
+        templates_path: config.report_templates_path.as_deref(),
+        index_path: config
+            .cluster_db_path()
+            .join(report::Reports::INDEX_FILE_NAME),
+    };
+    let validator_summary_filename = config.cluster_db_path().join("validator-summary.csv");
+
+    let index = if config.rebuild_reports || !validator_summary_filename.exists() {
+        None
+    } else {
+        reports.load_index()
+    };
+
+    // Paused epochs, without validator classifications, are reported like any other
+    let epochs = db.epochs()?;
+    let mut list = vec![(epoch, epoch_classification.clone())];
+    let validator_summary_csv = match index {
+        Some(index) => {
+            // Only the epochs classified since the reports were last updated are loaded
+            let indexed_epoch = index.epoch();
+            for previous_epoch in epochs.into_iter().rev().filter(|e| *e < epoch) {
+                if indexed_epoch.map_or(false, |indexed_epoch| previous_epoch <= indexed_epoch) {
+                    break;
+                }
+                list.push((previous_epoch, db.load(previous_epoch)?.into_current()));
+            }
+            reports.update(index, &list)?;
+            list.iter().rev().try_fold(
+                fs::read_to_string(&validator_summary_filename)?,
+                |csv, (epoch, epoch_classification)| {
+                    update_validator_summary_csv(&csv, *epoch, epoch_classification)
+                },
+            )?
+        }
+        None => {
+            info!("Generating the reports of every epoch");
+            for previous_epoch in epochs.into_iter().rev().filter(|e| *e < epoch) {
+                list.push((previous_epoch, db.load(previous_epoch)?.into_current()));
+            }
+            reports.rebuild(&list)?;
+            validator_summary_csv(&list)
+        }
+    };
+    info!("Writing {}", validator_summary_filename.display());
+    let mut file = File::create(validator_summary_filename)?;
+    file.write_all(&validator_summary_csv.into_bytes())?;
+
+    let filename = config.cluster_db_path().join("validator-metrics.csv");
     info!("Writing {}", filename.display());
     let mut file = File::create(filename)?;
-    file.write_all(&markdown.into_bytes())?;
+    file.write_all(&validator_metrics_csv(&epoch_classification).into_bytes())?;
 
     Ok(())
 }
@@ -1814,8 +2992,14 @@ mod test {
         leader_schedule.insert(l3.to_string(), (20..30).collect());
         leader_schedule.insert(l4.to_string(), (30..40).collect());
         leader_schedule.insert(l5.to_string(), (40..50).collect());
-        let (quality, poor, _reason_msg, cluster_average_skip_rate, too_many_poor_block_producers) =
-            classify_producers(0, confirmed_blocks, leader_schedule, &config).unwrap();
+        let (
+            quality,
+            poor,
+            _reason_msg,
+            _block_production,
+            cluster_average_skip_rate,
+            too_many_poor_block_producers,
+        ) = classify_producers(0, confirmed_blocks, leader_schedule, &config).unwrap();
         assert_eq!(cluster_average_skip_rate, 58);
         assert!(quality.contains(&l1));
         assert!(quality.contains(&l5));
@@ -1847,11 +3031,212 @@ mod test {
         leader_schedule.insert(l3.to_string(), (20..30).collect());
         leader_schedule.insert(l4.to_string(), (30..40).collect());
         leader_schedule.insert(l5.to_string(), (40..50).collect());
-        let (quality, poor, _reason_msg, cluster_average_skip_rate, too_many_poor_block_producers) =
-            classify_producers(0, confirmed_blocks, leader_schedule, &config).unwrap();
+        let (
+            quality,
+            poor,
+            _reason_msg,
+            _block_production,
+            cluster_average_skip_rate,
+            too_many_poor_block_producers,
+        ) = classify_producers(0, confirmed_blocks, leader_schedule, &config).unwrap();
         assert_eq!(cluster_average_skip_rate, 0);
         assert!(poor.is_empty());
         assert_eq!(quality.len(), 5);
         assert!(!too_many_poor_block_producers);
     }
+
+    #[test]
+    fn test_classify_from_snapshot() {
+        solana_logger::setup();
+        let config = Config {
+            max_commission: 10,
+            max_poor_block_producer_percentage: 50,
+            max_infrastructure_concentration: None,
+            ..Config::default_for_test()
+        };
+
+        let good_producer = Pubkey::new_unique();
+        let poor_producer = Pubkey::new_unique();
+        let high_commission = Pubkey::new_unique();
+
+        let mut leader_schedule = HashMap::new();
+        leader_schedule.insert(good_producer.to_string(), (0..10).collect());
+        leader_schedule.insert(poor_producer.to_string(), (10..20).collect());
+        leader_schedule.insert(high_commission.to_string(), (20..30).collect());
+        let confirmed_blocks = (0..10).chain(10..12).chain(20..30).collect();
+
+        let vote_account_info = [
+            (good_producer, 0),
+            (poor_producer, 0),
+            (high_commission, 100),
+        ]
+        .iter()
+        .map(|(identity, commission)| VoteAccountInfo {
+            identity: *identity,
+            vote_address: Pubkey::new_unique(),
+            commission: *commission,
+            active_stake: 0,
+            epoch_credits: 1_000,
+        })
+        .collect();
+
+        let snapshot = ClusterSnapshot {
+            epoch: 10,
+            validator_list: [good_producer, poor_producer, high_commission]
+                .iter()
+                .cloned()
+                .collect(),
+            vote_account_info,
+            first_slot_in_epoch: 0,
+            confirmed_blocks,
+            leader_schedule,
+            ..ClusterSnapshot::default()
+        };
+
+        let criteria = criteria_from_names(&config.criteria).unwrap();
+        let validator_classifications = classify(&config, snapshot, None, &criteria)
+            .unwrap()
+            .validator_classifications
+            .unwrap();
+
+        assert_eq!(validator_classifications.len(), 3);
+        assert_eq!(
+            validator_classifications[&good_producer].stake_state,
+            ValidatorStakeState::Bonus
+        );
+        assert_eq!(
+            validator_classifications[&poor_producer].stake_state,
+            ValidatorStakeState::Baseline
+        );
+        assert_eq!(
+            validator_classifications[&high_commission].stake_state,
+            ValidatorStakeState::None
+        );
+    }
+
+    #[test]
+    fn test_update_validator_summary_csv() {
+        let l1 = Pubkey::new_unique();
+        let l2 = Pubkey::new_unique();
+        let epoch_classification =
+            |validators: &[(Pubkey, ValidatorStakeState)]| EpochClassificationV2 {
+                validator_classifications: Some(
+                    validators
+                        .iter()
+                        .map(|(identity, stake_state)| {
+                            (
+                                *identity,
+                                ValidatorClassification {
+                                    identity: *identity,
+                                    stake_state: *stake_state,
+                                    ..ValidatorClassification::default()
+                                },
+                            )
+                        })
+                        .collect(),
+                ),
+                ..EpochClassificationV2::default()
+            };
+        let list = vec![
+            (
+                11,
+                epoch_classification(&[(l2, ValidatorStakeState::Bonus)]),
+            ),
+            (
+                10,
+                epoch_classification(&[(l1, ValidatorStakeState::Baseline)]),
+            ),
+        ];
+
+        // Adding an epoch to the summary matches the summary of every epoch
+        let csv = validator_summary_csv(&list[1..]);
+        let csv = update_validator_summary_csv(&csv, 11, &list[0].1).unwrap();
+        assert_eq!(csv, validator_summary_csv(&list));
+        assert_eq!(csv.lines().next(), Some("Identity,Epoch 11,Epoch 10"));
+
+        // A repeated run for the epoch replaces its column
+        assert_eq!(
+            update_validator_summary_csv(&csv, 11, &list[0].1).unwrap(),
+            csv
+        );
+    }
+
+    #[test]
+    fn test_generate_reports_with_paused_epoch() {
+        let path = std::env::temp_dir().join(format!("reports-{}", Pubkey::new_unique()));
+        let identity = Pubkey::new_unique();
+        let classified = EpochClassificationV2 {
+            validator_classifications: Some(
+                vec![(
+                    identity,
+                    ValidatorClassification {
+                        identity,
+                        stake_state: ValidatorStakeState::Bonus,
+                        ..ValidatorClassification::default()
+                    },
+                )]
+                .into_iter()
+                .collect(),
+            ),
+            ..EpochClassificationV2::default()
+        };
+        let paused = EpochClassificationV2 {
+            notes: vec!["Stake delegation paused".to_string()],
+            validator_classifications: None,
+            ..EpochClassificationV2::default()
+        };
+        let config_for = |name: &str, rebuild_reports: bool| Config {
+            db_path: path.join(name),
+            markdown_path: Some(path.join(name).join("md")),
+            rebuild_reports,
+            ..Config::default_for_test()
+        };
+
+        // Reports updated as epochs are classified, with a paused epoch in between...
+        let incremental_config = config_for("incremental", false);
+        let db = incremental_config.epoch_classification_db().unwrap();
+        db.save(10, &EpochClassification::new(classified.clone()))
+            .unwrap();
+        generate_reports(10, &incremental_config, db.as_ref()).unwrap();
+        db.save(11, &EpochClassification::new(paused.clone()))
+            .unwrap();
+        db.save(12, &EpochClassification::new(classified.clone()))
+            .unwrap();
+        generate_reports(12, &incremental_config, db.as_ref()).unwrap();
+
+        // ...match the reports generated from the whole history
+        let full_config = config_for("full", true);
+        let db = full_config.epoch_classification_db().unwrap();
+        for (epoch, epoch_classification) in &[(10, &classified), (11, &paused), (12, &classified)]
+        {
+            db.save(
+                *epoch,
+                &EpochClassification::new((*epoch_classification).clone()),
+            )
+            .unwrap();
+        }
+        generate_reports(12, &full_config, db.as_ref()).unwrap();
+
+        let validator_summary = |config: &Config| {
+            fs::read_to_string(config.cluster_db_path().join("validator-summary.csv")).unwrap()
+        };
+        let cluster_markdown = |config: &Config| {
+            fs::read_to_string(config.markdown_path.as_ref().unwrap().join("Mainnet.md")).unwrap()
+        };
+        assert_eq!(
+            validator_summary(&incremental_config),
+            validator_summary(&full_config)
+        );
+        assert_eq!(
+            validator_summary(&full_config).lines().next(),
+            Some("Identity,Epoch 12,Epoch 11,Epoch 10")
+        );
+        assert_eq!(
+            cluster_markdown(&incremental_config),
+            cluster_markdown(&full_config)
+        );
+        assert!(cluster_markdown(&full_config).contains("Stake delegation paused"));
+
+        fs::remove_dir_all(&path).unwrap();
+    }
 }
@@ -0,0 +1,185 @@
+use {
+    crate::BoxResult,
+    std::{cell::RefCell, collections::BTreeMap, fs, path::Path},
+};
+
+/// Prefix of every metric name
+const NAMESPACE: &str = "stake_o_matic";
+
+#[derive(Clone, Copy)]
+enum MetricType {
+    Counter,
+    Gauge,
+}
+
+impl MetricType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MetricType::Counter => "counter",
+            MetricType::Gauge => "gauge",
+        }
+    }
+}
+
+struct Metric {
+    help: &'static str,
+    metric_type: MetricType,
+
+    // Value by label set, formatted as `name="value",...`
+    samples: BTreeMap<String, f64>,
+}
+
+thread_local! {
+    // Values recorded during the run, by metric name
+    static METRICS: RefCell<BTreeMap<&'static str, Metric>> = RefCell::new(BTreeMap::new());
+}
+
+fn format_labels(labels: &[(&str, &str)]) -> String {
+    labels
+        .iter()
+        .map(|(name, value)| {
+            format!(
+                "{}=\"{}\"",
+                name,
+                value
+                    .replace('\\', "\\\\")
+                    .replace('"', "\\\"")
+                    .replace('\n', "\\n")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn record<F>(
+    name: &'static str,
+    help: &'static str,
+    metric_type: MetricType,
+    labels: &[(&str, &str)],
+    update: F,
+) where
+    F: FnOnce(&mut f64),
+{
+    METRICS.with(|metrics| {
+        let mut metrics = metrics.borrow_mut();
+        let metric = metrics.entry(name).or_insert_with(|| Metric {
+            help,
+            metric_type,
+            samples: BTreeMap::new(),
+        });
+        update(metric.samples.entry(format_labels(labels)).or_default());
+    });
+}
+
+pub fn set_gauge(name: &'static str, help: &'static str, value: f64) {
+    set_gauge_with_labels(name, help, &[], value);
+}
+
+pub fn set_gauge_with_labels(
+    name: &'static str,
+    help: &'static str,
+    labels: &[(&str, &str)],
+    value: f64,
+) {
+    record(name, help, MetricType::Gauge, labels, |sample| {
+        *sample = value
+    });
+}
+
+pub fn increment_counter(
+    name: &'static str,
+    help: &'static str,
+    labels: &[(&str, &str)],
+    value: u64,
+) {
+    record(name, help, MetricType::Counter, labels, |sample| {
+        *sample += value as f64
+    });
+}
+
+/// The recorded metrics in the Prometheus text exposition format, with `common_labels` added to
+/// every sample
+pub fn format(common_labels: &[(&str, &str)]) -> String {
+    let common_labels = format_labels(common_labels);
+    METRICS.with(|metrics| {
+        let mut text = String::new();
+        for (name, metric) in metrics.borrow().iter() {
+            text += &format!("# HELP {}_{} {}\n", NAMESPACE, name, metric.help);
+            text += &format!(
+                "# TYPE {}_{} {}\n",
+                NAMESPACE,
+                name,
+                metric.metric_type.as_str()
+            );
+            for (labels, value) in &metric.samples {
+                let labels = [common_labels.as_str(), labels.as_str()]
+                    .iter()
+                    .filter(|labels| !labels.is_empty())
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(",");
+                if labels.is_empty() {
+                    text += &format!("{}_{} {}\n", NAMESPACE, name, value);
+                } else {
+                    text += &format!("{}_{}{{{}}} {}\n", NAMESPACE, name, labels, value);
+                }
+            }
+        }
+        text
+    })
+}
+
+/// Write the recorded metrics to `path` for the node_exporter textfile collector
+pub fn write(path: &Path, common_labels: &[(&str, &str)]) -> BoxResult<()> {
+    // The collector may read the file at any time, so never expose a partially written file
+    let temp_path = path.with_extension("prom.tmp");
+    fs::write(&temp_path, format(common_labels))
+        .map_err(|err| format!("Unable to write {}: {}", temp_path.display(), err))?;
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        set_gauge("run_duration_seconds", "Duration of the run", 1.5);
+        set_gauge_with_labels(
+            "validators",
+            "Validators by stake state",
+            &[("stake_state", "Bonus")],
+            3.,
+        );
+        increment_counter(
+            "transactions_total",
+            "Transactions sent",
+            &[("result", "failed")],
+            1,
+        );
+        increment_counter(
+            "transactions_total",
+            "Transactions sent",
+            &[("result", "failed")],
+            2,
+        );
+
+        assert_eq!(
+            format(&[("cluster", "testnet")]),
+            "# HELP stake_o_matic_run_duration_seconds Duration of the run\n\
+             # TYPE stake_o_matic_run_duration_seconds gauge\n\
+             stake_o_matic_run_duration_seconds{cluster=\"testnet\"} 1.5\n\
+             # HELP stake_o_matic_transactions_total Transactions sent\n\
+             # TYPE stake_o_matic_transactions_total counter\n\
+             stake_o_matic_transactions_total{cluster=\"testnet\",result=\"failed\"} 3\n\
+             # HELP stake_o_matic_validators Validators by stake state\n\
+             # TYPE stake_o_matic_validators gauge\n\
+             stake_o_matic_validators{cluster=\"testnet\",stake_state=\"Bonus\"} 3\n"
+        );
+        assert_eq!(
+            format(&[]).lines().nth(2),
+            Some("stake_o_matic_run_duration_seconds 1.5")
+        );
+    }
+}
@@ -0,0 +1,489 @@
+use {
+    crate::{
+        rpc_client_utils::{
+            get_signature_statuses, transaction_completed, SendAndConfirmTransactionResult,
+        },
+        BoxResult,
+    },
+    log::*,
+    serde::{Deserialize, Serialize},
+    solana_client::{nonce_utils, rpc_client::RpcClient},
+    solana_sdk::{
+        hash::Hash,
+        instruction::{AccountMeta, Instruction},
+        message::Message,
+        pubkey::Pubkey,
+        signature::Signer,
+        transaction::Transaction,
+    },
+    std::{
+        collections::{BTreeMap, HashSet, VecDeque},
+        fs,
+        path::PathBuf,
+        str::FromStr,
+        thread::sleep,
+        time::{Duration, Instant},
+    },
+};
+
+// Resend a transaction that has not been confirmed after this long
+const RESEND_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Fetch the current blockhash stored in `nonce_account`, ensuring `nonce_authority` may advance
+/// it
+pub fn get_nonce_blockhash(
+    rpc_client: &RpcClient,
+    nonce_account: &Pubkey,
+    nonce_authority: &Pubkey,
+) -> BoxResult<Hash> {
+    let nonce_data = nonce_utils::data_from_account(
+        &nonce_utils::get_account_with_commitment(
+            rpc_client,
+            nonce_account,
+            rpc_client.commitment(),
+        )
+        .map_err(|err| format!("Nonce account {}: {}", nonce_account, err))?,
+    )
+    .map_err(|err| format!("Nonce account {}: {}", nonce_account, err))?;
+
+    if nonce_data.authority != *nonce_authority {
+        return Err(format!(
+            "Nonce account {} is not authorized to {}",
+            nonce_account, nonce_authority
+        )
+        .into());
+    }
+    Ok(nonce_data.blockhash)
+}
+
+/// Rewrite an unsigned transaction to first advance `nonce_account`, using `nonce_blockhash` in
+/// place of a recent blockhash.  The transaction then remains valid until the nonce is advanced
+pub fn nonce_transaction(
+    transaction: &Transaction,
+    nonce_account: &Pubkey,
+    nonce_authority: &Pubkey,
+    nonce_blockhash: Hash,
+) -> Transaction {
+    let mut message = Message::new_with_nonce(
+        decompile_instructions(&transaction.message),
+        transaction.message.account_keys.first(),
+        nonce_account,
+        nonce_authority,
+    );
+    message.recent_blockhash = nonce_blockhash;
+    Transaction::new_unsigned(message)
+}
+
+// Send `transaction`, returning false if it was rejected, for example by the preflight checks
+fn send_transaction(rpc_client: &RpcClient, transaction: &Transaction) -> bool {
+    match rpc_client.send_transaction(transaction) {
+        Ok(_) => true,
+        Err(err) => {
+            warn!("{}: failed to send: {}", transaction.signatures[0], err);
+            false
+        }
+    }
+}
+
+// Once the nonce of a transaction has advanced it can no longer execute, so its status, searched
+// for in the transaction history, is final: Some(success) if it executed, None if it never will
+fn executed_transaction_status(
+    rpc_client: &RpcClient,
+    transaction: &Transaction,
+) -> BoxResult<Option<bool>> {
+    Ok(rpc_client
+        .get_signature_status_with_commitment_and_history(
+            &transaction.signatures[0],
+            rpc_client.commitment(),
+            true,
+        )?
+        .map(|result| result.is_ok()))
+}
+
+// Recover the instructions of a compiled `Message`
+fn decompile_instructions(message: &Message) -> Vec<Instruction> {
+    let num_signed_accounts = message.header.num_required_signatures as usize;
+    let num_writable_signed_accounts =
+        num_signed_accounts - message.header.num_readonly_signed_accounts as usize;
+    let num_writable_accounts =
+        message.account_keys.len() - message.header.num_readonly_unsigned_accounts as usize;
+
+    message
+        .instructions
+        .iter()
+        .map(|instruction| Instruction {
+            program_id: message.account_keys[instruction.program_id_index as usize],
+            accounts: instruction
+                .accounts
+                .iter()
+                .map(|index| {
+                    let index = *index as usize;
+                    let is_signer = index < num_signed_accounts;
+                    let is_writable = if is_signer {
+                        index < num_writable_signed_accounts
+                    } else {
+                        index < num_writable_accounts
+                    };
+                    AccountMeta {
+                        pubkey: message.account_keys[index],
+                        is_signer,
+                        is_writable,
+                    }
+                })
+                .collect(),
+            data: instruction.data.clone(),
+        })
+        .collect()
+}
+
+/// A transaction sent with a durable nonce that has not been confirmed yet
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct InFlightTransaction {
+    /// Value of the nonce the transaction was signed with.  Once the nonce account holds another
+    /// value the transaction can no longer execute
+    nonce_blockhash: String,
+
+    /// Base58 encoded, bincode serialized, signed `Transaction`
+    transaction: String,
+}
+
+impl InFlightTransaction {
+    fn new(nonce_blockhash: Hash, transaction: &Transaction) -> BoxResult<Self> {
+        Ok(Self {
+            nonce_blockhash: nonce_blockhash.to_string(),
+            transaction: bs58::encode(bincode::serialize(transaction)?).into_string(),
+        })
+    }
+
+    fn nonce_blockhash(&self) -> BoxResult<Hash> {
+        Ok(Hash::from_str(&self.nonce_blockhash)
+            .map_err(|err| format!("Invalid nonce {}: {:?}", self.nonce_blockhash, err))?)
+    }
+
+    fn transaction(&self) -> BoxResult<Transaction> {
+        Ok(bincode::deserialize(
+            &bs58::decode(&self.transaction).into_vec()?,
+        )?)
+    }
+}
+
+// In-flight transactions by nonce account address
+type InFlightTransactions = BTreeMap<String, InFlightTransaction>;
+
+/// A pool of durable nonce accounts, with the staker as nonce authority.  Each transaction is
+/// sent with a nonce account of its own, so it can't expire while waiting for confirmation and
+/// is simply resent.  A nonce account is reused once its transaction has executed.
+///
+/// In-flight transactions are recorded by nonce account in `state_path`, so that transactions of
+/// an interrupted run are resumed rather than left to execute at an unexpected time
+#[derive(Debug)]
+pub struct NoncePool {
+    nonce_accounts: Vec<Pubkey>,
+    state_path: PathBuf,
+}
+
+impl NoncePool {
+    pub fn new(nonce_accounts: Vec<Pubkey>, state_path: PathBuf) -> Self {
+        Self {
+            nonce_accounts,
+            state_path,
+        }
+    }
+
+    fn load(&self) -> BoxResult<InFlightTransactions> {
+        if !self.state_path.exists() {
+            return Ok(InFlightTransactions::default());
+        }
+        let file = fs::File::open(&self.state_path)?;
+        Ok(serde_json::from_reader(file).map_err(|err| {
+            format!(
+                "Invalid in-flight transactions {}: {}",
+                self.state_path.display(),
+                err
+            )
+        })?)
+    }
+
+    fn save(&self, in_flight_transactions: &InFlightTransactions) -> BoxResult<()> {
+        if let Some(parent) = self.state_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = fs::File::create(&self.state_path)?;
+        serde_json::to_writer_pretty(file, in_flight_transactions)?;
+        Ok(())
+    }
+
+    /// Wait until the in-flight transactions left by a previous run have executed, or their
+    /// nonce has advanced, so that stake is planned from balances that include them
+    pub fn settle_in_flight_transactions<S: Signer>(
+        &self,
+        rpc_client: &RpcClient,
+        authorized_staker: &S,
+    ) -> BoxResult<SendAndConfirmTransactionResult> {
+        self.send_and_confirm_transactions(rpc_client, false, vec![], authorized_staker)
+    }
+
+    /// Sign `transactions` with durable nonces from the pool and send them, at most one
+    /// transaction per nonce account at a time.  In-flight transactions left by a previous run
+    /// are resent first if their nonce has not advanced.  A transaction that can't be sent is
+    /// failed rather than failing the others
+    pub fn send_and_confirm_transactions<S: Signer>(
+        &self,
+        rpc_client: &RpcClient,
+        dry_run: bool,
+        transactions: Vec<Transaction>,
+        authorized_staker: &S,
+    ) -> BoxResult<SendAndConfirmTransactionResult> {
+        if self.nonce_accounts.is_empty() {
+            return Err("The nonce pool is empty".into());
+        }
+        let nonce_authority = authorized_staker.pubkey();
+
+        // (nonce account, (transaction, last sent))
+        let mut pending_transactions = BTreeMap::<Pubkey, (Transaction, Instant)>::new();
+        let mut succeeded_transactions = HashSet::new();
+        let mut failed_transactions = HashSet::new();
+        let mut in_flight_transactions = if dry_run {
+            InFlightTransactions::default()
+        } else {
+            self.load()?
+        };
+        for (nonce_account, in_flight_transaction) in in_flight_transactions.clone() {
+            let nonce_account = Pubkey::from_str(&nonce_account)
+                .map_err(|err| format!("Invalid nonce account {}: {}", nonce_account, err))?;
+            let transaction = in_flight_transaction.transaction()?;
+            if get_nonce_blockhash(rpc_client, &nonce_account, &nonce_authority)?
+                == in_flight_transaction.nonce_blockhash()?
+            {
+                warn!(
+                    "Resuming transaction {} of nonce account {}",
+                    transaction.signatures[0], nonce_account
+                );
+                if send_transaction(rpc_client, &transaction) {
+                    pending_transactions.insert(nonce_account, (transaction, Instant::now()));
+                } else {
+                    failed_transactions.insert(transaction.signatures[0]);
+                    in_flight_transactions.remove(&nonce_account.to_string());
+                }
+            } else {
+                let signature = transaction.signatures[0];
+                match executed_transaction_status(rpc_client, &transaction)? {
+                    Some(success) => {
+                        info!(
+                            "Transaction {} of nonce account {} already executed. success={}",
+                            signature, nonce_account, success
+                        );
+                        if success {
+                            succeeded_transactions.insert(signature);
+                        } else {
+                            failed_transactions.insert(signature);
+                        }
+                    }
+                    None => {
+                        warn!(
+                            "Transaction {} of nonce account {} did not execute before the nonce advanced",
+                            signature, nonce_account
+                        );
+                        failed_transactions.insert(signature);
+                    }
+                }
+                in_flight_transactions.remove(&nonce_account.to_string());
+            }
+        }
+        if !dry_run {
+            self.save(&in_flight_transactions)?;
+        }
+
+        let mut queued_transactions = VecDeque::from(transactions);
+        loop {
+            for nonce_account in &self.nonce_accounts {
+                if queued_transactions.is_empty() {
+                    break;
+                }
+                if pending_transactions.contains_key(nonce_account) {
+                    continue;
+                }
+
+                let nonce_blockhash =
+                    get_nonce_blockhash(rpc_client, nonce_account, &nonce_authority)?;
+                let mut transaction = nonce_transaction(
+                    &queued_transactions.pop_front().unwrap(),
+                    nonce_account,
+                    &nonce_authority,
+                    nonce_blockhash,
+                );
+                transaction.sign(&[authorized_staker], nonce_blockhash);
+
+                if !dry_run {
+                    in_flight_transactions.insert(
+                        nonce_account.to_string(),
+                        InFlightTransaction::new(nonce_blockhash, &transaction)?,
+                    );
+                    self.save(&in_flight_transactions)?;
+                    if !send_transaction(rpc_client, &transaction) {
+                        failed_transactions.insert(transaction.signatures[0]);
+                        in_flight_transactions.remove(&nonce_account.to_string());
+                        self.save(&in_flight_transactions)?;
+                        continue;
+                    }
+                }
+                pending_transactions.insert(*nonce_account, (transaction, Instant::now()));
+            }
+
+            if pending_transactions.is_empty() {
+                break;
+            }
+
+            let statuses = get_signature_statuses(
+                rpc_client,
+                &pending_transactions
+                    .values()
+                    .map(|(transaction, _)| transaction.signatures[0])
+                    .collect::<Vec<_>>(),
+            )?;
+
+            let mut still_pending_transactions = BTreeMap::new();
+            for ((nonce_account, (transaction, last_sent)), status) in
+                pending_transactions.into_iter().zip(statuses.into_iter())
+            {
+                let signature = transaction.signatures[0];
+                trace!("{}: status={:?}", signature, status);
+                let completed = if dry_run {
+                    Some(true)
+                } else {
+                    transaction_completed(rpc_client, &status)
+                };
+
+                match completed {
+                    Some(success) => {
+                        info!("{}: completed. success={}", signature, success);
+                        if success {
+                            succeeded_transactions.insert(signature);
+                        } else {
+                            failed_transactions.insert(signature);
+                        }
+                        in_flight_transactions.remove(&nonce_account.to_string());
+                    }
+                    None if last_sent.elapsed() > RESEND_INTERVAL => {
+                        if get_nonce_blockhash(rpc_client, &nonce_account, &nonce_authority)?
+                            == transaction.message.recent_blockhash
+                        {
+                            warn!("{}: resending", signature);
+                            if send_transaction(rpc_client, &transaction) {
+                                still_pending_transactions
+                                    .insert(nonce_account, (transaction, Instant::now()));
+                            } else {
+                                failed_transactions.insert(signature);
+                                in_flight_transactions.remove(&nonce_account.to_string());
+                            }
+                        } else {
+                            // The nonce advanced, possibly by the transaction itself executing
+                            // since its status was fetched
+                            match executed_transaction_status(rpc_client, &transaction)? {
+                                Some(success) => {
+                                    info!("{}: completed. success={}", signature, success);
+                                    if success {
+                                        succeeded_transactions.insert(signature);
+                                    } else {
+                                        failed_transactions.insert(signature);
+                                    }
+                                }
+                                None => {
+                                    warn!("{}: nonce {} advanced", signature, nonce_account);
+                                    failed_transactions.insert(signature);
+                                }
+                            }
+                            in_flight_transactions.remove(&nonce_account.to_string());
+                        }
+                    }
+                    None => {
+                        still_pending_transactions.insert(nonce_account, (transaction, last_sent));
+                    }
+                }
+            }
+            pending_transactions = still_pending_transactions;
+
+            if !dry_run {
+                self.save(&in_flight_transactions)?;
+            }
+            sleep(Duration::from_millis(500));
+        }
+
+        Ok(SendAndConfirmTransactionResult {
+            succeeded: succeeded_transactions,
+            failed: failed_transactions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        solana_sdk::{signature::Keypair, system_instruction},
+    };
+
+    #[test]
+    fn test_decompile_instructions() {
+        let payer = Pubkey::new_unique();
+        let instructions = vec![
+            system_instruction::transfer(&payer, &Pubkey::new_unique(), 42),
+            system_instruction::assign(&Pubkey::new_unique(), &Pubkey::new_unique()),
+        ];
+        let message = Message::new(&instructions, Some(&payer));
+        assert_eq!(
+            Message::new(&decompile_instructions(&message), Some(&payer)),
+            message
+        );
+    }
+
+    #[test]
+    fn test_nonce_transaction() {
+        let staker = Keypair::new();
+        let nonce_account = Pubkey::new_unique();
+        let nonce_blockhash = Hash::new_unique();
+        let transaction = Transaction::new_with_payer(
+            &[system_instruction::transfer(
+                &staker.pubkey(),
+                &Pubkey::new_unique(),
+                42,
+            )],
+            Some(&staker.pubkey()),
+        );
+
+        let transaction = nonce_transaction(
+            &transaction,
+            &nonce_account,
+            &staker.pubkey(),
+            nonce_blockhash,
+        );
+        assert_eq!(transaction.message.account_keys[0], staker.pubkey());
+        assert_eq!(transaction.message.recent_blockhash, nonce_blockhash);
+        assert_eq!(transaction.message.instructions.len(), 2);
+        assert_eq!(
+            decompile_instructions(&transaction.message)[0],
+            system_instruction::advance_nonce_account(&nonce_account, &staker.pubkey())
+        );
+    }
+
+    #[test]
+    fn test_in_flight_transaction() {
+        let nonce_blockhash = Hash::new_unique();
+        let transaction = Transaction::new_with_payer(
+            &[system_instruction::transfer(
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                42,
+            )],
+            None,
+        );
+        let in_flight_transaction =
+            InFlightTransaction::new(nonce_blockhash, &transaction).unwrap();
+        assert_eq!(
+            in_flight_transaction.nonce_blockhash().unwrap(),
+            nonce_blockhash
+        );
+        assert_eq!(in_flight_transaction.transaction().unwrap(), transaction);
+    }
+}
@@ -0,0 +1,582 @@
+use {
+    crate::{generic_stake_pool::ValidatorStakeState, BoxResult},
+    log::*,
+    serde_json::json,
+    solana_notifier::Notifier,
+    solana_sdk::pubkey::Pubkey,
+    std::{
+        collections::HashMap,
+        env, fs,
+        io::{BufRead, BufReader, Write},
+        net::TcpStream,
+        path::{Path, PathBuf},
+        str::FromStr,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+};
+
+/// Stake state changes listed individually in the digest, the remainder are only counted
+const MAX_DIGEST_STAKE_CHANGES: usize = 10;
+
+/// A destination for notifications
+trait NotificationSink {
+    fn send(&self, message: &str) -> BoxResult<()>;
+}
+
+/// The Slack, Discord and Telegram channels configured through the environment
+struct SolanaNotifierSink(Notifier);
+
+impl NotificationSink for SolanaNotifierSink {
+    fn send(&self, message: &str) -> BoxResult<()> {
+        self.0.send(message);
+        Ok(())
+    }
+}
+
+/// POSTs `{"text": message}`, as accepted by Slack and Mattermost incoming webhooks
+struct WebhookSink {
+    url: String,
+}
+
+impl NotificationSink for WebhookSink {
+    fn send(&self, message: &str) -> BoxResult<()> {
+        reqwest::blocking::Client::new()
+            .post(&self.url)
+            .json(&json!({ "text": message }))
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Appends messages to a file
+struct FileSink {
+    path: PathBuf,
+}
+
+impl NotificationSink for FileSink {
+    fn send(&self, message: &str) -> BoxResult<()> {
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|err| format!("Unable to open {}: {}", self.path.display(), err))?;
+        writeln!(file, "{}\n", message)?;
+        Ok(())
+    }
+}
+
+/// Sends a text message to a Matrix room, with the access token from `MATRIX_ACCESS_TOKEN`
+struct MatrixSink {
+    homeserver: String,
+    room_id: String,
+    access_token: String,
+}
+
+impl NotificationSink for MatrixSink {
+    fn send(&self, message: &str) -> BoxResult<()> {
+        let transaction_id = SystemTime::now()
+            .duration_since(UNIX_EPOCH)?
+            .as_nanos()
+            .to_string();
+        let mut url = reqwest::Url::parse(&self.homeserver)?;
+        url.path_segments_mut()
+            .map_err(|_| format!("Invalid Matrix homeserver: {}", self.homeserver))?
+            .extend(&[
+                "_matrix",
+                "client",
+                "r0",
+                "rooms",
+                &self.room_id,
+                "send",
+                "m.room.message",
+                &transaction_id,
+            ]);
+        reqwest::blocking::Client::new()
+            .put(url)
+            .bearer_auth(&self.access_token)
+            .json(&json!({ "msgtype": "m.text", "body": message }))
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Sends an email through an SMTP relay that accepts unauthenticated mail, such as a local MTA.
+/// The first line of the message is used as the subject
+struct EmailSink {
+    server: String,
+    from: String,
+    to: String,
+}
+
+impl EmailSink {
+    fn reply(reader: &mut impl BufRead, expected_code: u16) -> BoxResult<()> {
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                return Err("SMTP connection closed".into());
+            }
+            // A `-` after the code marks a continued multi-line reply
+            if line.as_bytes().get(3) == Some(&b'-') {
+                continue;
+            }
+            return match line.get(..3).and_then(|code| code.parse::<u16>().ok()) {
+                Some(code) if code == expected_code => Ok(()),
+                _ => Err(format!("Unexpected SMTP reply: {}", line.trim_end()).into()),
+            };
+        }
+    }
+
+    fn command(
+        stream: &mut TcpStream,
+        reader: &mut impl BufRead,
+        command: &str,
+        expected_code: u16,
+    ) -> BoxResult<()> {
+        write!(stream, "{}\r\n", command)?;
+        Self::reply(reader, expected_code)
+    }
+}
+
+// Drop the characters of `value` that could end a header line
+fn header_value(value: &str) -> String {
+    value.chars().filter(|c| !c.is_control()).collect()
+}
+
+/// The address of an email contact, given as `mailto:ADDRESS` or a bare `ADDRESS`. The address is
+/// used in SMTP commands and headers, so anything that could end a line or the address is
+/// rejected
+pub fn email_address(contact: &str) -> Option<&str> {
+    let address = contact.strip_prefix("mailto:").unwrap_or(contact);
+    let mut parts = address.splitn(2, '@');
+    let local_part = parts.next().unwrap_or_default();
+    let domain = parts.next().unwrap_or_default();
+    if local_part.is_empty()
+        || domain.is_empty()
+        || domain.contains('@')
+        || address
+            .chars()
+            .any(|c| c.is_control() || c.is_whitespace() || c == '<' || c == '>')
+    {
+        None
+    } else {
+        Some(address)
+    }
+}
+
+impl NotificationSink for EmailSink {
+    fn send(&self, message: &str) -> BoxResult<()> {
+        let mut stream = TcpStream::connect(&self.server)
+            .map_err(|err| format!("Unable to connect to {}: {}", self.server, err))?;
+        stream.set_read_timeout(Some(Duration::from_secs(30)))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+
+        Self::reply(&mut reader, 220)?;
+        Self::command(&mut stream, &mut reader, "HELO localhost", 250)?;
+        Self::command(
+            &mut stream,
+            &mut reader,
+            &format!("MAIL FROM:<{}>", self.from),
+            250,
+        )?;
+        Self::command(
+            &mut stream,
+            &mut reader,
+            &format!("RCPT TO:<{}>", self.to),
+            250,
+        )?;
+        Self::command(&mut stream, &mut reader, "DATA", 354)?;
+
+        let subject = message.lines().next().unwrap_or_default();
+        write!(
+            stream,
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\n\r\n",
+            header_value(&self.from),
+            header_value(&self.to),
+            header_value(subject)
+        )?;
+        for line in message.lines() {
+            // Dot-stuffing, so that a line of the message never ends the data
+            if line.starts_with('.') {
+                write!(stream, ".")?;
+            }
+            write!(stream, "{}\r\n", line.replace('\r', ""))?;
+        }
+        Self::command(&mut stream, &mut reader, ".", 250)?;
+        Self::command(&mut stream, &mut reader, "QUIT", 221)
+    }
+}
+
+/// Settings for `mailto:` notification sinks
+pub struct SmtpSettings {
+    /// SMTP relay, as HOST:PORT
+    pub server: String,
+    pub from: String,
+}
+
+/// Check that `spec` names a notification sink, for use as a clap validator
+pub fn is_notification_sink(spec: String) -> Result<(), String> {
+    if ["http://", "https://", "file:", "mailto:", "matrix://"]
+        .iter()
+        .any(|prefix| spec.starts_with(prefix))
+    {
+        Ok(())
+    } else {
+        Err(format!(
+            "Unknown notification sink: {}. \
+             Expected an http(s) webhook URL, file:PATH, mailto:ADDRESS or matrix://HOMESERVER/ROOM",
+            spec
+        ))
+    }
+}
+
+fn email_sink(to: &str, smtp: &SmtpSettings) -> Box<dyn NotificationSink> {
+    Box::new(EmailSink {
+        server: smtp.server.clone(),
+        from: smtp.from.clone(),
+        to: to.to_string(),
+    })
+}
+
+fn parse_sink(spec: &str, smtp: &SmtpSettings) -> BoxResult<Box<dyn NotificationSink>> {
+    is_notification_sink(spec.to_string())?;
+
+    Ok(if let Some(path) = spec.strip_prefix("file:") {
+        Box::new(FileSink {
+            path: PathBuf::from(path),
+        })
+    } else if spec.starts_with("mailto:") {
+        email_sink(
+            email_address(spec).ok_or_else(|| format!("Invalid email address: {}", spec))?,
+            smtp,
+        )
+    } else if let Some(homeserver_and_room) = spec.strip_prefix("matrix://") {
+        let mut parts = homeserver_and_room.splitn(2, '/');
+        let homeserver = parts.next().unwrap_or_default();
+        let room_id = parts
+            .next()
+            .filter(|room_id| !room_id.is_empty())
+            .ok_or_else(|| format!("Matrix room missing from {}", spec))?;
+        Box::new(MatrixSink {
+            homeserver: format!("https://{}", homeserver),
+            room_id: room_id.to_string(),
+            access_token: env::var("MATRIX_ACCESS_TOKEN")
+                .map_err(|_| format!("MATRIX_ACCESS_TOKEN is required by {}", spec))?,
+        })
+    } else {
+        Box::new(WebhookSink {
+            url: spec.to_string(),
+        })
+    })
+}
+
+/// A change in the stake state of a validator since the previous epoch
+#[derive(Debug, Clone)]
+pub struct StakeChange {
+    pub identity: Pubkey,
+
+    /// `None` for a validator that was not classified in the previous epoch
+    pub previous_stake_state: Option<ValidatorStakeState>,
+    pub stake_state: ValidatorStakeState,
+    pub reason: String,
+
+    /// Notes of the validator's classification
+    pub notes: Vec<String>,
+}
+
+impl StakeChange {
+    pub fn summary(&self) -> String {
+        format!(
+            "* {:?} stake: {}: {}",
+            self.stake_state, self.identity, self.reason
+        )
+    }
+
+    /// The message sent to the contacts of the validator
+    fn message(&self, title: &str) -> String {
+        let mut message = format!(
+            "{}\nValidator {} stake state changed from {} to {:?}: {}",
+            title,
+            self.identity,
+            self.previous_stake_state
+                .map(|stake_state| format!("{:?}", stake_state))
+                .unwrap_or_else(|| "unclassified".to_string()),
+            self.stake_state,
+            self.reason
+        );
+        for note in &self.notes {
+            message += &format!("\nNote: {}", note);
+        }
+        message
+    }
+}
+
+/// Summarize an epoch for operators in a single message
+pub fn digest(
+    title: &str,
+    notes: &[String],
+    validator_note_count: usize,
+    stake_changes: &[StakeChange],
+) -> String {
+    let mut lines = vec![title.to_string()];
+    lines.extend(notes.iter().cloned());
+
+    if !stake_changes.is_empty() {
+        let count = |stake_state| {
+            stake_changes
+                .iter()
+                .filter(|stake_change| stake_change.stake_state == stake_state)
+                .count()
+        };
+        lines.push(format!(
+            "Stake state changes: {} (Bonus: {}, Baseline: {}, None: {})",
+            stake_changes.len(),
+            count(ValidatorStakeState::Bonus),
+            count(ValidatorStakeState::Baseline),
+            count(ValidatorStakeState::None)
+        ));
+        lines.extend(
+            stake_changes
+                .iter()
+                .take(MAX_DIGEST_STAKE_CHANGES)
+                .map(StakeChange::summary),
+        );
+        if stake_changes.len() > MAX_DIGEST_STAKE_CHANGES {
+            lines.push(format!(
+                "...and {} more",
+                stake_changes.len() - MAX_DIGEST_STAKE_CHANGES
+            ));
+        }
+    }
+    if validator_note_count > 0 {
+        lines.push(format!("Validator notes: {}", validator_note_count));
+    }
+    lines.join("\n")
+}
+
+/// The operator notification sinks, plus the sinks of validators that opted in to be notified
+/// of their own stake state changes by registering a contact with the program
+pub struct Notifications {
+    sinks: Vec<Box<dyn NotificationSink>>,
+    validator_sinks: HashMap<Pubkey, Vec<Box<dyn NotificationSink>>>,
+}
+
+impl Notifications {
+    /// `registered_contacts` maps validator identities to the contact registered with the
+    /// program, of which only email addresses are used.  `validator_contacts` is a YAML file
+    /// mapping validator identities to a list of additional sinks.  Validators are never
+    /// notified of a dry run
+    pub fn new(
+        dry_run: bool,
+        sink_specs: &[String],
+        registered_contacts: &HashMap<Pubkey, String>,
+        validator_contacts: Option<&Path>,
+        smtp: &SmtpSettings,
+    ) -> BoxResult<Self> {
+        let mut sinks: Vec<Box<dyn NotificationSink>> =
+            vec![Box::new(SolanaNotifierSink(if dry_run {
+                Notifier::new("DRYRUN")
+            } else {
+                Notifier::default()
+            }))];
+        for spec in sink_specs {
+            sinks.push(parse_sink(spec, smtp)?);
+        }
+
+        let mut validator_sinks: HashMap<_, Vec<_>> = HashMap::new();
+        if !dry_run {
+            for (identity, contact) in registered_contacts {
+                match email_address(contact) {
+                    Some(address) => validator_sinks
+                        .entry(*identity)
+                        .or_default()
+                        .push(email_sink(address, smtp)),
+                    None => debug!(
+                        "Registered contact of {} is not an email address: {}",
+                        identity, contact
+                    ),
+                }
+            }
+        }
+        if let Some(validator_contacts) = validator_contacts.filter(|_| !dry_run) {
+            let contacts: HashMap<String, Vec<String>> =
+                serde_yaml::from_reader(fs::File::open(validator_contacts).map_err(|err| {
+                    format!("Unable to open {}: {}", validator_contacts.display(), err)
+                })?)
+                .map_err(|err| {
+                    format!(
+                        "Invalid validator contacts {}: {}",
+                        validator_contacts.display(),
+                        err
+                    )
+                })?;
+            for (identity, specs) in contacts {
+                let identity = Pubkey::from_str(&identity)
+                    .map_err(|_| format!("Invalid validator identity: {}", identity))?;
+                for spec in specs {
+                    validator_sinks
+                        .entry(identity)
+                        .or_default()
+                        .push(parse_sink(&spec, smtp)?);
+                }
+            }
+        }
+
+        Ok(Self {
+            sinks,
+            validator_sinks,
+        })
+    }
+
+    fn send_to(sinks: &[Box<dyn NotificationSink>], message: &str) {
+        for sink in sinks {
+            if let Err(err) = sink.send(message) {
+                warn!("Unable to send notification: {}", err);
+            }
+        }
+    }
+
+    /// Send the epoch digest to the operators, and each stake state change to the contacts of
+    /// its validator
+    pub fn send(&self, title: &str, digest: &str, stake_changes: &[StakeChange]) {
+        Self::send_to(&self.sinks, digest);
+
+        for stake_change in stake_changes {
+            if let Some(sinks) = self.validator_sinks.get(&stake_change.identity) {
+                info!(
+                    "Notifying {} of its stake state change",
+                    stake_change.identity
+                );
+                Self::send_to(sinks, &stake_change.message(title));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_digest() {
+        let stake_changes = (0..MAX_DIGEST_STAKE_CHANGES + 2)
+            .map(|i| StakeChange {
+                identity: Pubkey::new_unique(),
+                previous_stake_state: None,
+                stake_state: if i == 0 {
+                    ValidatorStakeState::Bonus
+                } else {
+                    ValidatorStakeState::None
+                },
+                reason: "reason".to_string(),
+                notes: vec![],
+            })
+            .collect::<Vec<_>>();
+
+        let digest = digest("Epoch 42", &["note".to_string()], 3, &stake_changes);
+        let lines = digest.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "Epoch 42");
+        assert_eq!(lines[1], "note");
+        assert_eq!(
+            lines[2],
+            "Stake state changes: 12 (Bonus: 1, Baseline: 0, None: 11)"
+        );
+        assert_eq!(lines[3], stake_changes[0].summary());
+        assert_eq!(lines[3 + MAX_DIGEST_STAKE_CHANGES], "...and 2 more");
+        assert_eq!(lines.last(), Some(&"Validator notes: 3"));
+
+        assert_eq!(digest("Epoch 42", &[], 0, &[]), "Epoch 42");
+    }
+
+    #[test]
+    fn test_email_address() {
+        assert_eq!(email_address("a@example.com"), Some("a@example.com"));
+        assert_eq!(email_address("mailto:a@example.com"), Some("a@example.com"));
+        assert_eq!(email_address("https://example.com"), None);
+        assert_eq!(email_address("@example.com"), None);
+        assert_eq!(email_address("a@b@example.com"), None);
+        assert_eq!(email_address("a@example.com\r\nBcc: b@example.com"), None);
+        assert_eq!(email_address("a@example.com>"), None);
+    }
+
+    #[test]
+    fn test_notifications() {
+        let path = std::env::temp_dir().join(format!("notifications-{}", Pubkey::new_unique()));
+        fs::create_dir_all(&path).unwrap();
+        let operator_file = path.join("operators.log");
+        let validator_file = path.join("validator.log");
+        let contacts_file = path.join("contacts.yml");
+        let smtp = SmtpSettings {
+            server: "127.0.0.1:25".to_string(),
+            from: "stake-o-matic@localhost".to_string(),
+        };
+
+        assert!(is_notification_sink("ftp://example.com".to_string()).is_err());
+        assert!(parse_sink("matrix://example.com", &smtp).is_err());
+        assert!(parse_sink("mailto:a@example.com\r\nBcc: b@example.com", &smtp).is_err());
+
+        let stake_change = StakeChange {
+            identity: Pubkey::new_unique(),
+            previous_stake_state: Some(ValidatorStakeState::Bonus),
+            stake_state: ValidatorStakeState::Baseline,
+            reason: "reason".to_string(),
+            notes: vec!["note".to_string()],
+        };
+        fs::write(
+            &contacts_file,
+            format!(
+                "{}:\n  - file:{}\n",
+                stake_change.identity,
+                validator_file.display()
+            ),
+        )
+        .unwrap();
+
+        let operator_sink = format!("file:{}", operator_file.display());
+        let registered_contacts = vec![
+            (stake_change.identity, "https://example.com".to_string()),
+            (
+                Pubkey::new_unique(),
+                "mailto:validator@example.com".to_string(),
+            ),
+        ]
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+        let notifications = Notifications::new(
+            false,
+            &[operator_sink.clone()],
+            &registered_contacts,
+            Some(&contacts_file),
+            &smtp,
+        )
+        .unwrap();
+        // Only email addresses are used of the registered contacts
+        assert_eq!(notifications.validator_sinks.len(), 2);
+        assert_eq!(
+            notifications.validator_sinks[&stake_change.identity].len(),
+            1
+        );
+        notifications.send("Epoch 42", "digest", &[stake_change.clone()]);
+        assert_eq!(fs::read_to_string(&operator_file).unwrap(), "digest\n\n");
+        assert_eq!(
+            fs::read_to_string(&validator_file).unwrap(),
+            format!("{}\n\n", stake_change.message("Epoch 42"))
+        );
+
+        // Validators are not notified of a dry run
+        fs::remove_file(&validator_file).unwrap();
+        Notifications::new(
+            true,
+            &[operator_sink],
+            &registered_contacts,
+            Some(&contacts_file),
+            &smtp,
+        )
+        .unwrap()
+        .send("Epoch 42", "digest", &[stake_change]);
+        assert!(!validator_file.exists());
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
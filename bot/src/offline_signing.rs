@@ -0,0 +1,271 @@
+use {
+    crate::{
+        nonce_pool::{get_nonce_blockhash, nonce_transaction},
+        rpc_client_utils::{send_and_confirm_signed_transactions, Staker},
+        BoxResult,
+    },
+    log::*,
+    serde::{Deserialize, Serialize},
+    solana_client::rpc_client::RpcClient,
+    solana_sdk::{
+        pubkey::Pubkey,
+        signature::{Signature, Signer, SignerError},
+        transaction::Transaction,
+    },
+    std::{
+        cell::RefCell,
+        fs,
+        path::{Path, PathBuf},
+    },
+};
+
+/// A transaction awaiting signatures
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct OfflineTransaction {
+    /// Durable nonce account advanced by the transaction
+    pub nonce_account: String,
+
+    /// Base58 encoded, bincode serialized `Transaction`
+    pub transaction: String,
+}
+
+impl OfflineTransaction {
+    fn new(nonce_account: Pubkey, transaction: &Transaction) -> BoxResult<Self> {
+        Ok(Self {
+            nonce_account: nonce_account.to_string(),
+            transaction: Self::encode(transaction)?,
+        })
+    }
+
+    fn encode(transaction: &Transaction) -> BoxResult<String> {
+        Ok(bs58::encode(bincode::serialize(transaction)?).into_string())
+    }
+
+    fn decode(&self) -> BoxResult<Transaction> {
+        Ok(bincode::deserialize(
+            &bs58::decode(&self.transaction).into_vec()?,
+        )?)
+    }
+}
+
+/// The file of transactions written by an `OfflineStaker`
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct OfflineTransactions {
+    pub transactions: Vec<OfflineTransaction>,
+}
+
+impl OfflineTransactions {
+    pub fn load<P: AsRef<Path>>(path: P) -> BoxResult<Self> {
+        let file = fs::File::open(&path)
+            .map_err(|err| format!("Unable to open {}: {}", path.as_ref().display(), err))?;
+        Ok(serde_json::from_reader(file).map_err(|err| {
+            format!(
+                "Invalid offline transactions {}: {}",
+                path.as_ref().display(),
+                err
+            )
+        })?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> BoxResult<()> {
+        let file = fs::File::create(&path)
+            .map_err(|err| format!("Unable to create {}: {}", path.as_ref().display(), err))?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+}
+
+/// A staker whose key is not present on this host.  Rather than signing and sending
+/// transactions, the first batch of transactions is written to `path` as `OfflineTransactions`.  Later batches are planned from chain state that does not yet include
+/// the first, so they are left to a later run once the file has been broadcast.  Every
+/// transaction uses its own durable nonce account, authorized to the staker, so that it remains
+/// valid until the signatures are collected
+#[derive(Debug)]
+pub struct OfflineStaker {
+    pubkey: Pubkey,
+    path: PathBuf,
+    nonce_accounts: Vec<Pubkey>,
+    transactions: RefCell<OfflineTransactions>,
+}
+
+impl OfflineStaker {
+    pub fn new(pubkey: Pubkey, path: PathBuf, nonce_accounts: Vec<Pubkey>) -> Self {
+        Self {
+            pubkey,
+            path,
+            nonce_accounts,
+            transactions: RefCell::new(OfflineTransactions::default()),
+        }
+    }
+
+    /// Rewrite `transactions` to advance a durable nonce and write them to the file, unless a
+    /// batch was already written by this run
+    pub fn add_transactions(
+        &self,
+        rpc_client: &RpcClient,
+        transactions: Vec<Transaction>,
+    ) -> BoxResult<()> {
+        if transactions.is_empty() {
+            return Ok(());
+        }
+
+        let mut offline_transactions = self.transactions.borrow_mut();
+        if !offline_transactions.transactions.is_empty() {
+            warn!(
+                "Deferring {} transactions: run again once {} has been broadcast",
+                transactions.len(),
+                self.path.display()
+            );
+            return Ok(());
+        }
+        if transactions.len() > self.nonce_accounts.len() {
+            return Err(format!(
+                "Not enough nonce accounts: {} transactions, but only {} nonce accounts",
+                transactions.len(),
+                self.nonce_accounts.len()
+            )
+            .into());
+        }
+
+        for (transaction, nonce_account) in transactions.into_iter().zip(self.nonce_accounts.iter())
+        {
+            let nonce_blockhash = get_nonce_blockhash(rpc_client, nonce_account, &self.pubkey)?;
+            offline_transactions
+                .transactions
+                .push(OfflineTransaction::new(
+                    *nonce_account,
+                    &nonce_transaction(&transaction, nonce_account, &self.pubkey, nonce_blockhash),
+                )?);
+        }
+
+        offline_transactions.save(&self.path)?;
+        info!(
+            "Wrote {} offline transactions to {}",
+            offline_transactions.transactions.len(),
+            self.path.display()
+        );
+        Ok(())
+    }
+}
+
+impl Signer for OfflineStaker {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        Ok(self.pubkey)
+    }
+
+    fn try_sign_message(&self, _message: &[u8]) -> Result<Signature, SignerError> {
+        Err(SignerError::Custom(
+            "The authorized staker signs offline".to_string(),
+        ))
+    }
+}
+
+impl Staker for OfflineStaker {
+    fn offline(&self) -> Option<&OfflineStaker> {
+        Some(self)
+    }
+}
+
+/// Add the signatures of `signer` to every transaction of the file at `path` that requires it.
+/// Returns the number of transactions signed
+pub fn sign(path: &Path, signer: &dyn Signer) -> BoxResult<usize> {
+    let mut offline_transactions = OfflineTransactions::load(path)?;
+    let signer_pubkey = signer.try_pubkey()?;
+
+    let mut signed = 0;
+    for offline_transaction in offline_transactions.transactions.iter_mut() {
+        let mut transaction = offline_transaction.decode()?;
+        let num_required_signatures = transaction.message.header.num_required_signatures as usize;
+        if !transaction.message.account_keys[..num_required_signatures].contains(&signer_pubkey) {
+            continue;
+        }
+
+        let recent_blockhash = transaction.message.recent_blockhash;
+        transaction.try_partial_sign(&vec![signer], recent_blockhash)?;
+        offline_transaction.transaction = OfflineTransaction::encode(&transaction)?;
+        signed += 1;
+    }
+
+    offline_transactions.save(path)?;
+    Ok(signed)
+}
+
+/// Broadcast the fully signed transactions of the file at `path`
+pub fn broadcast(rpc_client: &RpcClient, path: &Path) -> BoxResult<()> {
+    let offline_transactions = OfflineTransactions::load(path)?;
+
+    let mut transactions = vec![];
+    for offline_transaction in &offline_transactions.transactions {
+        let transaction = offline_transaction.decode()?;
+        if !transaction.is_signed() {
+            return Err(format!(
+                "Transaction using nonce account {} is missing signatures",
+                offline_transaction.nonce_account
+            )
+            .into());
+        }
+        transactions.push(transaction);
+    }
+
+    info!("Broadcasting {} transactions", transactions.len());
+    let result = send_and_confirm_signed_transactions(rpc_client, transactions)?;
+    if !result.failed.is_empty() {
+        return Err(format!("{} transactions failed to execute", result.failed.len()).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        solana_sdk::{message::Message, signature::Keypair, system_instruction},
+    };
+
+    #[test]
+    fn test_sign() {
+        let path = std::env::temp_dir().join(format!("offline-{}.json", Pubkey::new_unique()));
+        let staker = Keypair::new();
+        let other_signer = Keypair::new();
+        let nonce_account = Pubkey::new_unique();
+
+        let transaction = Transaction::new_unsigned(Message::new_with_nonce(
+            vec![system_instruction::transfer(
+                &staker.pubkey(),
+                &Pubkey::new_unique(),
+                42,
+            )],
+            Some(&staker.pubkey()),
+            &nonce_account,
+            &staker.pubkey(),
+        ));
+        OfflineTransactions {
+            transactions: vec![OfflineTransaction::new(nonce_account, &transaction).unwrap()],
+        }
+        .save(&path)
+        .unwrap();
+
+        assert_eq!(sign(&path, &other_signer).unwrap(), 0);
+        assert_eq!(sign(&path, &staker).unwrap(), 1);
+        let offline_transactions = OfflineTransactions::load(&path).unwrap();
+        assert!(offline_transactions.transactions[0]
+            .decode()
+            .unwrap()
+            .is_signed());
+
+        // A run writes a single batch
+        let offline_staker = OfflineStaker::new(staker.pubkey(), path.clone(), vec![]);
+        *offline_staker.transactions.borrow_mut() = offline_transactions;
+        offline_staker
+            .add_transactions(
+                &RpcClient::new_mock("succeeds".to_string()),
+                vec![transaction],
+            )
+            .unwrap();
+        assert_eq!(offline_staker.transactions.borrow().transactions.len(), 1);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
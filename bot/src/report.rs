@@ -0,0 +1,801 @@
+use {
+    crate::{
+        db::{EpochClassificationV2, ValidatorClassification},
+        generic_stake_pool::ValidatorStakeState,
+        BoxResult,
+    },
+    handlebars::Handlebars,
+    log::*,
+    serde::{Deserialize, Serialize},
+    solana_sdk::{clock::Epoch, native_token::Sol},
+    std::{
+        collections::{BTreeMap, HashSet},
+        fs::{self, File},
+        io::Write,
+        path::{Path, PathBuf},
+    },
+};
+
+// Built-in templates by name.  A file named `{name}.hbs` in the report templates directory
+// overrides the template of the same name
+const MARKDOWN_TEMPLATES: &[(&str, &str)] = &[
+    ("cluster.md", include_str!("../templates/cluster.md.hbs")),
+    (
+        "validator.md",
+        include_str!("../templates/validator.md.hbs"),
+    ),
+];
+const HTML_TEMPLATES: &[(&str, &str)] = &[
+    ("index.html", include_str!("../templates/index.html.hbs")),
+    ("epoch.html", include_str!("../templates/epoch.html.hbs")),
+    (
+        "validator.html",
+        include_str!("../templates/validator.html.hbs"),
+    ),
+    ("style.css", include_str!("../templates/style.css.hbs")),
+];
+
+// Dimensions of the SVG charts, in pixels
+const CHART_HEIGHT: usize = 100;
+const BAR_WIDTH: usize = 8;
+const BAR_SPACING: usize = 2;
+
+// The models below are rendered by the templates.  Optional values are formatted ahead of time
+// so that a zero, which is falsy to `{{#if}}`, is still rendered
+
+#[derive(Clone, Deserialize, Serialize)]
+struct BlockProduction {
+    blocks: usize,
+    leader_slots: usize,
+    skip_rate: usize,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct ValidatorEpochReport {
+    epoch: Epoch,
+    identity: String,
+    stake_state: ValidatorStakeState,
+    stake_tier: Option<String>,
+    // `None` unless the stake level has been held for more than one epoch
+    stake_state_streak: Option<usize>,
+    stake_state_reason: String,
+    stake_action: Option<String>,
+    vote_address: String,
+    block_production: Option<BlockProduction>,
+    epoch_credits: Option<String>,
+    self_stake: Option<String>,
+    active_stake: Option<String>,
+    commission: Option<String>,
+    release_version: Option<String>,
+    data_center_stake_percent: Option<String>,
+    data_center: Option<String>,
+    // `None` unless the validator has resided outside of its current data center
+    resident_data_centers: Option<String>,
+    notes: Vec<String>,
+}
+
+impl ValidatorEpochReport {
+    fn new(epoch: Epoch, classification: &ValidatorClassification) -> Self {
+        let stake_state_streak = classification.stake_state_streak();
+        let metrics = classification.metrics.clone().unwrap_or_default();
+        let (data_center, resident_data_centers) = match (
+            classification.current_data_center.as_ref(),
+            classification.data_center_residency.as_ref(),
+        ) {
+            (Some(current_data_center), Some(data_center_residency)) => {
+                let resident_data_centers = if data_center_residency.len() > 1
+                    || (data_center_residency.len() == 1
+                        && !data_center_residency.contains_key(current_data_center))
+                {
+                    let mut data_center_residency = data_center_residency
+                        .keys()
+                        .map(|data_center| data_center.to_string())
+                        .collect::<Vec<_>>();
+                    data_center_residency.sort();
+                    Some(data_center_residency.join(", "))
+                } else {
+                    None
+                };
+                (Some(current_data_center.to_string()), resident_data_centers)
+            }
+            _ => (None, None),
+        };
+
+        Self {
+            epoch,
+            identity: classification.identity.to_string(),
+            stake_state: classification.stake_state,
+            stake_tier: classification.stake_tier.clone(),
+            stake_state_streak: if stake_state_streak > 1 {
+                Some(stake_state_streak)
+            } else {
+                None
+            },
+            stake_state_reason: classification.stake_state_reason.clone(),
+            stake_action: classification.stake_action.clone(),
+            vote_address: classification.vote_address.to_string(),
+            block_production: match (metrics.blocks, metrics.leader_slots, metrics.skip_rate) {
+                (Some(blocks), Some(leader_slots), Some(skip_rate)) => Some(BlockProduction {
+                    blocks,
+                    leader_slots,
+                    skip_rate,
+                }),
+                _ => None,
+            },
+            epoch_credits: metrics.epoch_credits.map(|value| value.to_string()),
+            self_stake: metrics.self_stake.map(|value| Sol(value).to_string()),
+            active_stake: metrics.active_stake.map(|value| Sol(value).to_string()),
+            commission: metrics.commission.map(|value| value.to_string()),
+            release_version: metrics.release_version,
+            data_center_stake_percent: metrics
+                .data_center_stake_percent
+                .map(|value| format!("{:.1}", value)),
+            data_center,
+            resident_data_centers,
+            notes: classification.notes.clone(),
+        }
+    }
+}
+
+#[derive(Default, Deserialize, Serialize)]
+struct StakeStateCounts {
+    none: usize,
+    baseline: usize,
+    bonus: usize,
+}
+
+#[derive(Deserialize, Serialize)]
+struct EpochSummary {
+    epoch: Epoch,
+    notes: Vec<String>,
+    stake_states: StakeStateCounts,
+}
+
+impl EpochSummary {
+    fn new(epoch: Epoch, epoch_classification: &EpochClassificationV2) -> Self {
+        let mut stake_states = StakeStateCounts::default();
+        for classification in epoch_classification
+            .validator_classifications
+            .iter()
+            .flat_map(|validator_classifications| validator_classifications.values())
+        {
+            match classification.stake_state {
+                ValidatorStakeState::None => stake_states.none += 1,
+                ValidatorStakeState::Baseline => stake_states.baseline += 1,
+                ValidatorStakeState::Bonus => stake_states.bonus += 1,
+            }
+        }
+
+        Self {
+            epoch,
+            notes: epoch_classification.notes.clone(),
+            stake_states,
+        }
+    }
+}
+
+/// The validators classified in `epoch`, ordered by identity.  Empty if stake adjustments were
+/// paused for the epoch
+fn validator_epoch_reports(
+    epoch: Epoch,
+    epoch_classification: &EpochClassificationV2,
+) -> Vec<ValidatorEpochReport> {
+    let mut validators = epoch_classification
+        .validator_classifications
+        .iter()
+        .flat_map(|validator_classifications| validator_classifications.values())
+        .collect::<Vec<_>>();
+    validators.sort_by_key(|classification| classification.identity);
+    validators
+        .into_iter()
+        .map(|classification| ValidatorEpochReport::new(epoch, classification))
+        .collect()
+}
+
+/// Summaries of the reported epochs and validators, newest first, from which the reports are
+/// updated without loading the classification of every epoch
+#[derive(Default, Deserialize, Serialize)]
+pub struct ReportIndex {
+    // The outputs the reports were rendered into
+    #[serde(default)]
+    markdown_path: Option<PathBuf>,
+    #[serde(default)]
+    html_path: Option<PathBuf>,
+
+    epochs: Vec<EpochSummary>,
+    #[serde(default)]
+    validators: BTreeMap<String, Vec<ValidatorEpochReport>>,
+}
+
+impl ReportIndex {
+    /// The newest reported epoch
+    pub fn epoch(&self) -> Option<Epoch> {
+        self.epochs.first().map(|summary| summary.epoch)
+    }
+}
+
+#[derive(Serialize)]
+struct ChartBar {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    stake_state: ValidatorStakeState,
+    title: String,
+}
+
+#[derive(Serialize)]
+struct Chart {
+    width: usize,
+    height: usize,
+    bars: Vec<ChartBar>,
+}
+
+impl Chart {
+    /// A chart of `columns` bars, each a stack of `(stake state, height)` segments from the
+    /// bottom up
+    fn new(columns: Vec<(String, Vec<(ValidatorStakeState, usize)>)>) -> Self {
+        let mut bars = vec![];
+        for (i, (title, segments)) in columns.iter().enumerate() {
+            let mut bottom = CHART_HEIGHT;
+            for (stake_state, height) in segments {
+                if *height == 0 {
+                    continue;
+                }
+                bottom -= height;
+                bars.push(ChartBar {
+                    x: i * (BAR_WIDTH + BAR_SPACING),
+                    y: bottom,
+                    width: BAR_WIDTH,
+                    height: *height,
+                    stake_state: *stake_state,
+                    title: title.clone(),
+                });
+            }
+        }
+        Self {
+            width: (columns.len() * (BAR_WIDTH + BAR_SPACING)).max(1),
+            height: CHART_HEIGHT,
+            bars,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ClusterReport<'a> {
+    cluster: &'a str,
+    // Newest first
+    epochs: &'a [EpochSummary],
+    // Validators by stake level, oldest epoch first
+    chart: &'a Chart,
+}
+
+#[derive(Serialize)]
+struct EpochPage<'a> {
+    cluster: &'a str,
+    #[serde(flatten)]
+    summary: &'a EpochSummary,
+    validators: &'a [ValidatorEpochReport],
+}
+
+#[derive(Serialize)]
+struct ValidatorReport<'a> {
+    cluster: &'a str,
+    identity: String,
+    // Newest first
+    epochs: Vec<&'a ValidatorEpochReport>,
+    // Stake level, oldest epoch first
+    chart: Chart,
+}
+
+/// Height of a validator's bar in its stake level chart
+fn stake_state_height(stake_state: ValidatorStakeState) -> usize {
+    match stake_state {
+        ValidatorStakeState::None => CHART_HEIGHT / 10,
+        ValidatorStakeState::Baseline => CHART_HEIGHT / 2,
+        ValidatorStakeState::Bonus => CHART_HEIGHT,
+    }
+}
+
+fn validator_report<'a>(
+    cluster: &'a str,
+    identity: &str,
+    epochs: &[&'a ValidatorEpochReport],
+) -> ValidatorReport<'a> {
+    ValidatorReport {
+        cluster,
+        identity: identity.to_string(),
+        epochs: epochs.to_vec(),
+        chart: Chart::new(
+            epochs
+                .iter()
+                .rev()
+                .map(|validator| {
+                    (
+                        format!("Epoch {}: {:?}", validator.epoch, validator.stake_state),
+                        vec![(
+                            validator.stake_state,
+                            stake_state_height(validator.stake_state),
+                        )],
+                    )
+                })
+                .collect(),
+        ),
+    }
+}
+
+fn registry(
+    templates: &[(&'static str, &'static str)],
+    templates_path: Option<&Path>,
+    escape_html: bool,
+) -> BoxResult<Handlebars<'static>> {
+    let mut handlebars = Handlebars::new();
+    if !escape_html {
+        handlebars.register_escape_fn(handlebars::no_escape);
+    }
+    for (name, template) in templates {
+        let custom_template = templates_path
+            .map(|templates_path| templates_path.join(format!("{}.hbs", name)))
+            .filter(|filename| filename.exists());
+        match custom_template {
+            Some(filename) => {
+                info!("Using report template {}", filename.display());
+                handlebars
+                    .register_template_file(name, &filename)
+                    .map_err(|err| format!("Invalid template {}: {}", filename.display(), err))?;
+            }
+            None => handlebars.register_template_string(name, template)?,
+        }
+    }
+    Ok(handlebars)
+}
+
+fn render<T: Serialize>(
+    handlebars: &Handlebars,
+    name: &str,
+    data: &T,
+    filename: &Path,
+) -> BoxResult<()> {
+    let contents = handlebars
+        .render(name, data)
+        .map_err(|err| format!("Unable to render {}: {}", name, err))?;
+    info!("Writing {}", filename.display());
+    let mut file = File::create(filename)?;
+    file.write_all(&contents.into_bytes())?;
+    Ok(())
+}
+
+/// The reports of the staking history of a cluster:
+/// * Into `markdown_path`, the `{cluster}.md` epoch notes and a `Validator-{identity}.md` page per
+///   validator, laid out for the wiki
+/// * Into `html_path`, a static site of an `index.html` epoch index, an `epoch-{epoch}.html` page
+///   per epoch and a `validator-{identity}.html` page per validator
+///
+/// The built-in templates are overridden by those found in `templates_path`
+pub struct Reports<'a> {
+    pub cluster: &'a str,
+    pub markdown_path: Option<&'a Path>,
+    pub html_path: Option<&'a Path>,
+    pub templates_path: Option<&'a Path>,
+
+    /// Summaries of the reported epochs, maintained for `update()`
+    pub index_path: PathBuf,
+}
+
+impl Reports<'_> {
+    pub const INDEX_FILE_NAME: &'static str = "report-index.yml";
+
+    /// The index of the previously rendered reports.  `None` unless they were rendered into the
+    /// same outputs, and so may be updated incrementally
+    pub fn load_index(&self) -> Option<ReportIndex> {
+        let file = File::open(&self.index_path).ok()?;
+        let index: ReportIndex = match serde_yaml::from_reader(file) {
+            Ok(index) => index,
+            Err(err) => {
+                warn!(
+                    "Invalid report index {}: {}",
+                    self.index_path.display(),
+                    err
+                );
+                return None;
+            }
+        };
+        if index.markdown_path.as_deref() != self.markdown_path
+            || index.html_path.as_deref() != self.html_path
+        {
+            info!("The reports were previously rendered into other outputs");
+            return None;
+        }
+        Some(index)
+    }
+
+    /// Render every report of the classified epochs in `list`, ordered newest first
+    pub fn rebuild(&self, list: &[(Epoch, EpochClassificationV2)]) -> BoxResult<()> {
+        let epoch_validators = list
+            .iter()
+            .map(|(epoch, epoch_classification)| {
+                validator_epoch_reports(*epoch, epoch_classification)
+            })
+            .collect::<Vec<_>>();
+        let mut index = ReportIndex {
+            markdown_path: self.markdown_path.map(Path::to_path_buf),
+            html_path: self.html_path.map(Path::to_path_buf),
+            epochs: list
+                .iter()
+                .map(|(epoch, epoch_classification)| {
+                    EpochSummary::new(*epoch, epoch_classification)
+                })
+                .collect(),
+            validators: BTreeMap::new(),
+        };
+        for validator in epoch_validators.iter().flatten() {
+            index
+                .validators
+                .entry(validator.identity.clone())
+                .or_default()
+                .push(validator.clone());
+        }
+
+        let validators: BTreeMap<&str, Vec<&ValidatorEpochReport>> = index
+            .validators
+            .iter()
+            .map(|(identity, history)| (identity.as_str(), history.iter().collect()))
+            .collect();
+        self.render(
+            &index,
+            index
+                .epochs
+                .iter()
+                .zip(epoch_validators.iter())
+                .map(|(summary, validators)| (summary, validators.as_slice())),
+            &validators,
+        )?;
+        self.save_index(&index)
+    }
+
+    /// Add the classified epochs in `list`, ordered newest first, to the reports of `index`:
+    /// their sections of the cluster reports, their epoch pages and the pages of the validators
+    /// classified in them.  The pages of other validators and epochs are left untouched
+    pub fn update(
+        &self,
+        mut index: ReportIndex,
+        list: &[(Epoch, EpochClassificationV2)],
+    ) -> BoxResult<()> {
+        let epoch_validators = list
+            .iter()
+            .map(|(epoch, epoch_classification)| {
+                validator_epoch_reports(*epoch, epoch_classification)
+            })
+            .collect::<Vec<_>>();
+
+        let mut identities = HashSet::new();
+        for ((epoch, epoch_classification), validators) in list.iter().zip(&epoch_validators) {
+            index.epochs.retain(|summary| summary.epoch != *epoch);
+            index
+                .epochs
+                .push(EpochSummary::new(*epoch, epoch_classification));
+            for validator in validators {
+                let history = index
+                    .validators
+                    .entry(validator.identity.clone())
+                    .or_default();
+                history.retain(|report| report.epoch != *epoch);
+                history.push(validator.clone());
+                identities.insert(validator.identity.as_str());
+            }
+        }
+        index.epochs.sort_by(|a, b| b.epoch.cmp(&a.epoch));
+        for identity in &identities {
+            index
+                .validators
+                .get_mut(*identity)
+                .unwrap()
+                .sort_by(|a, b| b.epoch.cmp(&a.epoch));
+        }
+
+        let validators: BTreeMap<&str, Vec<&ValidatorEpochReport>> = index
+            .validators
+            .iter()
+            .filter(|(identity, _)| identities.contains(identity.as_str()))
+            .map(|(identity, history)| (identity.as_str(), history.iter().collect()))
+            .collect();
+        let epoch_pages = list
+            .iter()
+            .zip(&epoch_validators)
+            .map(|((epoch, _), validators)| {
+                let summary = index
+                    .epochs
+                    .iter()
+                    .find(|summary| summary.epoch == *epoch)
+                    .unwrap();
+                (summary, validators.as_slice())
+            })
+            .collect::<Vec<_>>();
+        self.render(&index, epoch_pages, &validators)?;
+        self.save_index(&index)
+    }
+
+    fn save_index(&self, index: &ReportIndex) -> BoxResult<()> {
+        let temp_path = self.index_path.with_extension("yml.tmp");
+        serde_yaml::to_writer(File::create(&temp_path)?, index)?;
+        fs::rename(&temp_path, &self.index_path)?;
+        Ok(())
+    }
+
+    /// Render the cluster reports of every epoch in `index`, the pages of `epochs` and the pages
+    /// of `validators`
+    fn render<'a, I>(
+        &self,
+        index: &ReportIndex,
+        epochs: I,
+        validators: &BTreeMap<&str, Vec<&ValidatorEpochReport>>,
+    ) -> BoxResult<()>
+    where
+        I: IntoIterator<Item = (&'a EpochSummary, &'a [ValidatorEpochReport])>,
+    {
+        let cluster = self.cluster;
+        let max_validators = index
+            .epochs
+            .iter()
+            .map(|summary| {
+                let stake_states = &summary.stake_states;
+                stake_states.bonus + stake_states.baseline + stake_states.none
+            })
+            .max()
+            .unwrap_or_default()
+            .max(1);
+        let scale = |count: usize| count * CHART_HEIGHT / max_validators;
+        let cluster_report = ClusterReport {
+            cluster,
+            epochs: &index.epochs,
+            chart: &Chart::new(
+                index
+                    .epochs
+                    .iter()
+                    .rev()
+                    .map(|summary| {
+                        let stake_states = &summary.stake_states;
+                        (
+                            format!(
+                                "Epoch {}: {} Bonus, {} Baseline, {} None",
+                                summary.epoch,
+                                stake_states.bonus,
+                                stake_states.baseline,
+                                stake_states.none
+                            ),
+                            vec![
+                                (ValidatorStakeState::Bonus, scale(stake_states.bonus)),
+                                (ValidatorStakeState::Baseline, scale(stake_states.baseline)),
+                                (ValidatorStakeState::None, scale(stake_states.none)),
+                            ],
+                        )
+                    })
+                    .collect(),
+            ),
+        };
+
+        if let Some(markdown_path) = self.markdown_path {
+            fs::create_dir_all(markdown_path)?;
+            let handlebars = registry(MARKDOWN_TEMPLATES, self.templates_path, false)?;
+
+            for (identity, validator_epochs) in validators {
+                render(
+                    &handlebars,
+                    "validator.md",
+                    &validator_report(cluster, identity, validator_epochs),
+                    &markdown_path.join(format!("Validator-{}.md", identity)),
+                )?;
+            }
+            render(
+                &handlebars,
+                "cluster.md",
+                &cluster_report,
+                &markdown_path.join(format!("{}.md", cluster)),
+            )?;
+        }
+
+        if let Some(html_path) = self.html_path {
+            fs::create_dir_all(html_path)?;
+            let handlebars = registry(HTML_TEMPLATES, self.templates_path, true)?;
+
+            for (identity, validator_epochs) in validators {
+                render(
+                    &handlebars,
+                    "validator.html",
+                    &validator_report(cluster, identity, validator_epochs),
+                    &html_path.join(format!("validator-{}.html", identity)),
+                )?;
+            }
+            for (summary, validators) in epochs {
+                render(
+                    &handlebars,
+                    "epoch.html",
+                    &EpochPage {
+                        cluster,
+                        summary,
+                        validators,
+                    },
+                    &html_path.join(format!("epoch-{}.html", summary.epoch)),
+                )?;
+            }
+            render(
+                &handlebars,
+                "index.html",
+                &cluster_report,
+                &html_path.join("index.html"),
+            )?;
+            render(&handlebars, "style.css", &(), &html_path.join("style.css"))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{data_center_info::DataCenterId, db::ValidatorMetrics},
+        solana_sdk::pubkey::Pubkey,
+        std::str::FromStr,
+    };
+
+    #[test]
+    fn test_reports() {
+        let path = std::env::temp_dir().join(format!("report-{}", Pubkey::new_unique()));
+        fs::create_dir_all(&path).unwrap();
+        let identity = Pubkey::new_unique();
+        let vote_address = Pubkey::new_unique();
+        let data_center = DataCenterId::from_str("123-Amsterdam").unwrap();
+
+        let epoch_classification = |stake_state, stake_states| EpochClassificationV2 {
+            notes: vec![format!("{:?} epoch", stake_state)],
+            validator_classifications: Some(
+                vec![(
+                    identity,
+                    ValidatorClassification {
+                        identity,
+                        vote_address,
+                        stake_state,
+                        stake_state_reason: "Good".to_string(),
+                        stake_states: Some(stake_states),
+                        current_data_center: Some(data_center.clone()),
+                        data_center_residency: Some(
+                            vec![(data_center.clone(), 1)].into_iter().collect(),
+                        ),
+                        metrics: Some(ValidatorMetrics {
+                            commission: Some(0),
+                            blocks: Some(9),
+                            leader_slots: Some(10),
+                            skip_rate: Some(10),
+                            ..ValidatorMetrics::default()
+                        }),
+                        ..ValidatorClassification::default()
+                    },
+                )]
+                .into_iter()
+                .collect(),
+            ),
+            ..EpochClassificationV2::default()
+        };
+        let list = vec![
+            (
+                11,
+                epoch_classification(
+                    ValidatorStakeState::Bonus,
+                    vec![
+                        (ValidatorStakeState::Bonus, String::new()),
+                        (ValidatorStakeState::Bonus, String::new()),
+                    ],
+                ),
+            ),
+            (
+                10,
+                epoch_classification(
+                    ValidatorStakeState::Bonus,
+                    vec![(ValidatorStakeState::Bonus, String::new())],
+                ),
+            ),
+        ];
+
+        let markdown_path = path.join("md");
+        let html_path = path.join("html");
+        let reports = Reports {
+            cluster: "Testnet",
+            markdown_path: Some(markdown_path.as_path()),
+            html_path: Some(html_path.as_path()),
+            templates_path: None,
+            index_path: path.join(Reports::INDEX_FILE_NAME),
+        };
+        assert!(reports.load_index().is_none());
+        reports.rebuild(&list).unwrap();
+        assert_eq!(reports.load_index().unwrap().epoch(), Some(11));
+
+        // The built-in markdown templates reproduce the wiki layout
+        let cluster_markdown = fs::read_to_string(markdown_path.join("Testnet.md")).unwrap();
+        assert_eq!(
+            cluster_markdown,
+            "### Epoch 11\n* Bonus epoch\n### Epoch 10\n* Bonus epoch\n"
+        );
+        let validator_markdown_path = markdown_path.join(format!("Validator-{}.md", identity));
+        let validator_markdown = fs::read_to_string(&validator_markdown_path).unwrap();
+        assert_eq!(
+            validator_markdown.lines().take(7).collect::<Vec<_>>(),
+            vec![
+                "### [[Testnet Epoch 11|Testnet#Epoch-11]]".to_string(),
+                "* Stake level: **Bonus** (for 2 epochs)".to_string(),
+                "* Stake reason: Good".to_string(),
+                format!("* Vote account address: {}", vote_address),
+                "* Block production: 9 blocks in 10 leader slots, 10% skip rate".to_string(),
+                "* Commission: 0%".to_string(),
+                "* Data Center: 123-Amsterdam".to_string(),
+            ]
+        );
+        assert!(validator_markdown
+            .contains("### [[Testnet Epoch 10|Testnet#Epoch-10]]\n* Stake level: **Bonus**\n"));
+
+        assert!(fs::read_to_string(html_path.join("index.html"))
+            .unwrap()
+            .contains("<a href=\"epoch-10.html\">10</a>"));
+        assert!(fs::read_to_string(html_path.join("epoch-11.html"))
+            .unwrap()
+            .contains(&format!("validator-{}.html", identity)));
+        assert!(
+            fs::read_to_string(html_path.join(format!("validator-{}.html", identity)))
+                .unwrap()
+                .contains("class=\"Bonus\"")
+        );
+        assert!(html_path.join("style.css").exists());
+
+        // Reports rendered into other outputs are rebuilt rather than updated
+        assert!(Reports {
+            html_path: None,
+            index_path: reports.index_path.clone(),
+            ..reports
+        }
+        .load_index()
+        .is_none());
+
+        // An update after epoch 10 was reported produces the same reports as a rebuild
+        fs::remove_dir_all(&markdown_path).unwrap();
+        fs::remove_dir_all(&html_path).unwrap();
+        reports.rebuild(&list[1..]).unwrap();
+        fs::remove_file(html_path.join("epoch-10.html")).unwrap();
+        reports
+            .update(reports.load_index().unwrap(), &list[..1])
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(markdown_path.join("Testnet.md")).unwrap(),
+            cluster_markdown
+        );
+        assert_eq!(
+            fs::read_to_string(&validator_markdown_path).unwrap(),
+            validator_markdown
+        );
+        assert!(html_path.join("epoch-11.html").exists());
+        // Pages of earlier epochs are not rendered again
+        assert!(!html_path.join("epoch-10.html").exists());
+
+        // A template in the templates directory overrides the built-in template
+        let templates_path = path.join("templates");
+        fs::create_dir_all(&templates_path).unwrap();
+        fs::write(
+            templates_path.join("cluster.md.hbs"),
+            "{{#each epochs}}{{epoch}} {{/each}}",
+        )
+        .unwrap();
+        Reports {
+            html_path: None,
+            templates_path: Some(templates_path.as_path()),
+            ..reports
+        }
+        .rebuild(&list)
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(markdown_path.join("Testnet.md")).unwrap(),
+            "11 10 "
+        );
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -1,6 +1,8 @@
 use {
+    crate::{metrics, nonce_pool::NoncePool, offline_signing::OfflineStaker},
     log::*,
     reqwest::StatusCode,
+    serde::{Deserialize, Serialize},
     solana_client::{
         client_error,
         rpc_client::RpcClient,
@@ -14,15 +16,16 @@ use {
         clock::Epoch,
         native_token::*,
         pubkey::Pubkey,
-        signature::{Keypair, Signature, Signer},
+        signature::{Keypair, Signature, Signer, SignerError},
         transaction::Transaction,
     },
+    solana_transaction_status::TransactionStatus,
     std::{
         collections::{HashMap, HashSet},
         error,
         str::FromStr,
         thread::sleep,
-        time::Duration,
+        time::{Duration, Instant},
     },
 };
 
@@ -89,17 +92,148 @@ pub fn simulate_transactions(
     Ok(simulated_transactions)
 }
 
+/// Signer of the stake pool transactions
+pub trait Staker: Signer {
+    /// If Some(), the staker key is not available and transactions are written to disk for
+    /// offline signing instead of being signed and sent
+    fn offline(&self) -> Option<&OfflineStaker> {
+        None
+    }
+
+    /// If Some(), transactions are sent with durable nonces from this pool rather than with a
+    /// recent blockhash
+    fn nonce_pool(&self) -> Option<&NoncePool> {
+        None
+    }
+}
+
+impl Staker for Keypair {}
+
+/// The authorized staker selected on the command-line
+#[derive(Debug)]
+pub enum AuthorizedStaker {
+    Keypair(Keypair),
+    NoncePool(Keypair, NoncePool),
+    Offline(OfflineStaker),
+}
+
+impl Signer for AuthorizedStaker {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        match self {
+            Self::Keypair(keypair) | Self::NoncePool(keypair, _) => keypair.try_pubkey(),
+            Self::Offline(offline_staker) => offline_staker.try_pubkey(),
+        }
+    }
+
+    fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        match self {
+            Self::Keypair(keypair) | Self::NoncePool(keypair, _) => {
+                keypair.try_sign_message(message)
+            }
+            Self::Offline(offline_staker) => offline_staker.try_sign_message(message),
+        }
+    }
+}
+
+impl Staker for AuthorizedStaker {
+    fn offline(&self) -> Option<&OfflineStaker> {
+        match self {
+            Self::Offline(offline_staker) => Some(offline_staker),
+            _ => None,
+        }
+    }
+
+    fn nonce_pool(&self) -> Option<&NoncePool> {
+        match self {
+            Self::NoncePool(_, nonce_pool) => Some(nonce_pool),
+            _ => None,
+        }
+    }
+}
+
 pub struct SendAndConfirmTransactionResult {
     pub succeeded: HashSet<Signature>,
     pub failed: HashSet<Signature>,
 }
 
+impl SendAndConfirmTransactionResult {
+    fn record_metrics(&self) {
+        for (result, signatures) in &[("succeeded", &self.succeeded), ("failed", &self.failed)] {
+            metrics::increment_counter(
+                "transactions_total",
+                "Transactions sent by the authorized staker",
+                &[("result", result)],
+                signatures.len() as u64,
+            );
+        }
+    }
+}
+
+pub fn get_signature_statuses(
+    rpc_client: &RpcClient,
+    signatures: &[Signature],
+) -> Result<Vec<Option<TransactionStatus>>, Box<dyn error::Error>> {
+    let mut statuses = vec![];
+    for signatures_chunk in signatures.chunks(MAX_GET_SIGNATURE_STATUSES_QUERY_ITEMS - 1) {
+        trace!("checking {} pending transactions", signatures_chunk.len());
+        statuses.extend(
+            rpc_client
+                .get_signature_statuses(signatures_chunk)?
+                .value
+                .into_iter(),
+        )
+    }
+    assert_eq!(statuses.len(), signatures.len());
+    Ok(statuses)
+}
+
+// Some(success) once a transaction status satisfies the commitment of `rpc_client`
+pub fn transaction_completed(
+    rpc_client: &RpcClient,
+    status: &Option<TransactionStatus>,
+) -> Option<bool> {
+    match status {
+        Some(status) if status.satisfies_commitment(rpc_client.commitment()) => {
+            Some(status.err.is_none())
+        }
+        _ => None,
+    }
+}
+
+/// Wait for transactions left in flight by an interrupted run, so that stake is not planned
+/// from balances that they are yet to change
+pub fn settle_in_flight_transactions(
+    rpc_client: &RpcClient,
+    dry_run: bool,
+    authorized_staker: &impl Staker,
+) -> Result<(), Box<dyn error::Error>> {
+    if let Some(nonce_pool) = authorized_staker.nonce_pool() {
+        if dry_run {
+            return Ok(());
+        }
+        let result = nonce_pool.settle_in_flight_transactions(rpc_client, authorized_staker)?;
+        result.record_metrics();
+        if !result.failed.is_empty() {
+            warn!("{} in-flight transactions failed", result.failed.len());
+        }
+    }
+    Ok(())
+}
+
 pub fn send_and_confirm_transactions(
     rpc_client: &RpcClient,
     dry_run: bool,
     transactions: Vec<Transaction>,
-    authorized_staker: &Keypair,
+    authorized_staker: &impl Staker,
 ) -> Result<SendAndConfirmTransactionResult, Box<dyn error::Error>> {
+    if let Some(offline_staker) = authorized_staker.offline() {
+        offline_staker.add_transactions(rpc_client, transactions)?;
+        return Ok(SendAndConfirmTransactionResult {
+            succeeded: HashSet::new(),
+            failed: HashSet::new(),
+        });
+    }
+
     let authorized_staker_balance = rpc_client.get_balance(&authorized_staker.pubkey())?;
     info!(
         "Authorized staker balance: {} SOL",
@@ -117,6 +251,19 @@ pub fn send_and_confirm_transactions(
         return Err("Authorized staker has insufficient funds".into());
     }
 
+    if let Some(nonce_pool) = authorized_staker.nonce_pool() {
+        let result = nonce_pool.send_and_confirm_transactions(
+            rpc_client,
+            dry_run,
+            transactions,
+            authorized_staker,
+        )?;
+        if !dry_run {
+            result.record_metrics();
+        }
+        return Ok(result);
+    }
+
     let mut pending_transactions = vec![];
     for mut transaction in transactions {
         transaction.sign(&[authorized_staker], blockhash);
@@ -156,25 +303,13 @@ pub fn send_and_confirm_transactions(
             }
         }
 
-        let mut statuses = vec![];
-        for pending_signatures_chunk in pending_transactions
-            .iter()
-            .map(|transaction| transaction.signatures[0])
-            .collect::<Vec<_>>()
-            .chunks(MAX_GET_SIGNATURE_STATUSES_QUERY_ITEMS - 1)
-        {
-            trace!(
-                "checking {} pending transactions",
-                pending_signatures_chunk.len()
-            );
-            statuses.extend(
-                rpc_client
-                    .get_signature_statuses(pending_signatures_chunk)?
-                    .value
-                    .into_iter(),
-            )
-        }
-        assert_eq!(statuses.len(), pending_transactions.len());
+        let statuses = get_signature_statuses(
+            rpc_client,
+            &pending_transactions
+                .iter()
+                .map(|transaction| transaction.signatures[0])
+                .collect::<Vec<_>>(),
+        )?;
 
         let mut still_pending_transactions = vec![];
         for (transaction, status) in pending_transactions.into_iter().zip(statuses.into_iter()) {
@@ -182,14 +317,8 @@ pub fn send_and_confirm_transactions(
             trace!("{}: status={:?}", signature, status);
             let completed = if dry_run {
                 Some(true)
-            } else if let Some(status) = &status {
-                if status.satisfies_commitment(rpc_client.commitment()) {
-                    Some(status.err.is_none())
-                } else {
-                    None
-                }
             } else {
-                None
+                transaction_completed(rpc_client, &status)
             };
 
             if let Some(success) = completed {
@@ -207,12 +336,90 @@ pub fn send_and_confirm_transactions(
         sleep(Duration::from_millis(500));
     }
 
+    let result = SendAndConfirmTransactionResult {
+        succeeded: succeeded_transactions,
+        failed: failed_transactions,
+    };
+    if !dry_run {
+        result.record_metrics();
+    }
+    Ok(result)
+}
+
+/// Send transactions that have already been signed, such as transactions with a durable nonce
+/// signed offline, and wait for them to be confirmed.  Pending transactions are resent
+/// periodically, and reported as failed after `MAX_RESENDS`
+pub fn send_and_confirm_signed_transactions(
+    rpc_client: &RpcClient,
+    transactions: Vec<Transaction>,
+) -> Result<SendAndConfirmTransactionResult, Box<dyn error::Error>> {
+    const RESEND_INTERVAL: Duration = Duration::from_secs(30);
+    const MAX_RESENDS: usize = 10;
+
+    info!("{} signed transactions to send", transactions.len());
+    for transaction in &transactions {
+        rpc_client.send_transaction(transaction)?;
+    }
+
+    let mut pending_transactions = transactions;
+    let mut succeeded_transactions = HashSet::new();
+    let mut failed_transactions = HashSet::new();
+    let mut resends = 0;
+    let mut last_send = Instant::now();
+    while !pending_transactions.is_empty() {
+        if last_send.elapsed() > RESEND_INTERVAL {
+            if resends == MAX_RESENDS {
+                for transaction in pending_transactions {
+                    warn!("{}: not confirmed", transaction.signatures[0]);
+                    failed_transactions.insert(transaction.signatures[0]);
+                }
+                break;
+            }
+            resends += 1;
+            warn!(
+                "Resending {} pending transactions",
+                pending_transactions.len()
+            );
+            for transaction in &pending_transactions {
+                rpc_client.send_transaction(transaction)?;
+            }
+            last_send = Instant::now();
+        }
+
+        let statuses = get_signature_statuses(
+            rpc_client,
+            &pending_transactions
+                .iter()
+                .map(|transaction| transaction.signatures[0])
+                .collect::<Vec<_>>(),
+        )?;
+
+        let mut still_pending_transactions = vec![];
+        for (transaction, status) in pending_transactions.into_iter().zip(statuses.into_iter()) {
+            let signature = transaction.signatures[0];
+            match transaction_completed(rpc_client, &status) {
+                Some(success) => {
+                    info!("{}: completed. success={}", signature, success);
+                    if success {
+                        succeeded_transactions.insert(signature);
+                    } else {
+                        failed_transactions.insert(signature);
+                    }
+                }
+                None => still_pending_transactions.push(transaction),
+            }
+        }
+        pending_transactions = still_pending_transactions;
+        sleep(Duration::from_millis(500));
+    }
+
     Ok(SendAndConfirmTransactionResult {
         succeeded: succeeded_transactions,
         failed: failed_transactions,
     })
 }
 
+#[derive(Deserialize, Serialize)]
 pub struct VoteAccountInfo {
     pub identity: Pubkey,
     pub vote_address: Pubkey,
@@ -0,0 +1,411 @@
+use {
+    crate::{
+        classify,
+        cluster_snapshot::ClusterSnapshot,
+        criteria::criteria_from_names,
+        data_center_info::DataCenters,
+        db::{EpochClassificationV2, ValidatorClassificationByIdentity},
+        generic_stake_pool::ValidatorStakeState,
+        rpc_client_utils::VoteAccountInfo,
+        BoxResult, Config,
+    },
+    log::*,
+    solana_sdk::{
+        clock::{Epoch, Slot},
+        native_token::Sol,
+    },
+    std::{collections::BTreeSet, path::PathBuf},
+};
+
+pub struct SimulateArgs {
+    /// Directory of recorded `ClusterSnapshot`s
+    pub snapshot_path: PathBuf,
+
+    /// Range of epochs to replay. `None` selects the latest classified epoch
+    pub first_epoch: Option<Epoch>,
+    pub last_epoch: Option<Epoch>,
+
+    /// Stake pool size of the epochs that did not record theirs, and the baseline stake amount,
+    /// used to derive the bonus stake amount
+    pub stake_pool_size: Option<u64>,
+    pub baseline_stake_amount: u64,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct StakeStateCounts {
+    none: usize,
+    baseline: usize,
+    bonus: usize,
+}
+
+impl StakeStateCounts {
+    fn new(epoch_classification: &EpochClassificationV2) -> Self {
+        let mut counts = Self::default();
+        if let Some(ref validator_classifications) = epoch_classification.validator_classifications
+        {
+            for vc in validator_classifications.values() {
+                match vc.stake_state {
+                    ValidatorStakeState::None => counts.none += 1,
+                    ValidatorStakeState::Baseline => counts.baseline += 1,
+                    ValidatorStakeState::Bonus => counts.bonus += 1,
+                }
+            }
+        }
+        counts
+    }
+
+    // Mirrors the bonus stake computation of `StakePoolOMatic::apply()`
+    fn bonus_stake_amount(&self, stake_pool_size: u64, baseline_stake_amount: u64) -> u64 {
+        if self.bonus == 0 {
+            0
+        } else {
+            stake_pool_size.saturating_sub(self.baseline as u64 * baseline_stake_amount)
+                / self.bonus as u64
+        }
+    }
+}
+
+fn stake_state_name(stake_state: Option<ValidatorStakeState>) -> String {
+    stake_state
+        .map(|stake_state| format!("{:?}", stake_state))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn report(
+    epoch: Epoch,
+    stored: &EpochClassificationV2,
+    simulated: &EpochClassificationV2,
+    args: &SimulateArgs,
+) {
+    println!("Epoch {}", epoch);
+
+    let stored_classifications = stored.validator_classifications.clone().unwrap_or_default();
+    let simulated_classifications = simulated
+        .validator_classifications
+        .clone()
+        .unwrap_or_default();
+
+    let identities = stored_classifications
+        .keys()
+        .chain(simulated_classifications.keys())
+        .collect::<BTreeSet<_>>();
+
+    let mut changed = 0;
+    for identity in identities {
+        let stored_vc = stored_classifications.get(identity);
+        let simulated_vc = simulated_classifications.get(identity);
+        let stored_stake_state = stored_vc.map(|vc| vc.stake_state);
+        let simulated_stake_state = simulated_vc.map(|vc| vc.stake_state);
+
+        if stored_stake_state != simulated_stake_state {
+            changed += 1;
+            println!(
+                "* {}: {} -> {}{}",
+                identity,
+                stake_state_name(stored_stake_state),
+                stake_state_name(simulated_stake_state),
+                simulated_vc
+                    .map(|vc| format!(": {}", vc.stake_state_reason))
+                    .unwrap_or_default()
+            );
+        }
+    }
+
+    let stored_counts = StakeStateCounts::new(stored);
+    let simulated_counts = StakeStateCounts::new(simulated);
+    println!("* Validators with a different stake state: {}", changed);
+    println!(
+        "* None validators: {} -> {}",
+        stored_counts.none, simulated_counts.none
+    );
+    println!(
+        "* Baseline validators: {} -> {}",
+        stored_counts.baseline, simulated_counts.baseline
+    );
+    println!(
+        "* Bonus validators: {} -> {}",
+        stored_counts.bonus, simulated_counts.bonus
+    );
+    match stored.stake_pool_size().or(args.stake_pool_size) {
+        Some(stake_pool_size) => println!(
+            "* Bonus stake amount: {} -> {}",
+            Sol(stored_counts.bonus_stake_amount(stake_pool_size, args.baseline_stake_amount)),
+            Sol(simulated_counts.bonus_stake_amount(stake_pool_size, args.baseline_stake_amount))
+        ),
+        None => println!(
+            "* Bonus stake amount: unknown, the stake pool size was not recorded (see --stake-pool-size)"
+        ),
+    }
+    println!();
+}
+
+/// Rebuild the inputs of `classify()` from the metrics stored with the classification of
+/// `epoch`, for epochs without a recorded `ClusterSnapshot`.  Only the classified validators
+/// were stored, so the cluster averages of vote credits and skip rate are taken over them alone,
+/// and testnet participation is not checked.  `None` if the epoch was paused or the metrics of a
+/// validator are incomplete
+fn snapshot_from_classification(
+    epoch: Epoch,
+    stored: &EpochClassificationV2,
+) -> Option<ClusterSnapshot> {
+    let mut snapshot = ClusterSnapshot {
+        epoch,
+        ..ClusterSnapshot::default()
+    };
+    let mut data_centers = DataCenters {
+        info: stored.data_center_info.clone(),
+        ..DataCenters::default()
+    };
+
+    // Leader slots are laid out back to back, with the produced blocks first
+    let mut next_leader_slot = 0;
+    for vc in stored.validator_classifications.as_ref()?.values() {
+        let metrics = vc.metrics.as_ref()?;
+        let active_stake = metrics.active_stake?;
+
+        snapshot.validator_list.insert(vc.identity);
+        if let Some(participant) = vc.participant {
+            snapshot
+                .identity_to_participant
+                .insert(vc.identity, participant);
+        }
+        if let Some(ref current_data_center) = vc.current_data_center {
+            data_centers
+                .by_identity
+                .insert(vc.identity, current_data_center.clone());
+        }
+        snapshot.vote_account_info.push(VoteAccountInfo {
+            identity: vc.identity,
+            vote_address: vc.vote_address,
+            commission: metrics.commission?,
+            active_stake,
+            epoch_credits: metrics.epoch_credits?,
+        });
+        snapshot.total_active_stake += active_stake;
+        snapshot
+            .self_stake_by_vote_account
+            .insert(vc.vote_address, metrics.self_stake?);
+        if let Some(pool_stake) = metrics.pool_stake {
+            snapshot
+                .pool_stake_by_vote_account
+                .insert(vc.vote_address, pool_stake);
+        }
+        if let Some(ref release_version) = metrics.release_version {
+            snapshot
+                .cluster_node_versions
+                .insert(vc.identity, release_version.clone());
+        }
+        if let (Some(blocks), Some(leader_slots)) = (metrics.blocks, metrics.leader_slots) {
+            let relative_slots =
+                (next_leader_slot..next_leader_slot + leader_slots).collect::<Vec<_>>();
+            snapshot.confirmed_blocks.extend(
+                relative_slots
+                    .iter()
+                    .take(blocks)
+                    .map(|relative_slot| *relative_slot as Slot),
+            );
+            snapshot
+                .leader_schedule
+                .insert(vc.identity.to_string(), relative_slots);
+            next_leader_slot += leader_slots;
+        }
+    }
+
+    // Block production can't be classified without any leader slots
+    if next_leader_slot == 0 {
+        return None;
+    }
+    if !data_centers.info.is_empty() {
+        snapshot.data_centers = Some(data_centers);
+    }
+    Some(snapshot)
+}
+
+/// Replay the stored classifications of `args.first_epoch..=args.last_epoch` under `config`.
+/// Epochs are reclassified from their recorded `ClusterSnapshot`, or else from the metrics
+/// stored with their classification. Epochs that can't be reclassified either way are not
+/// simulated, and their stored classification only seeds the stake state history of the epochs
+/// that follow. Fails if no epoch in the range can be simulated
+pub fn run(config: &Config, args: &SimulateArgs) -> BoxResult<()> {
+    let db = config.epoch_classification_db()?;
+    let latest_epoch = db
+        .load_latest()?
+        .ok_or_else(|| {
+            format!(
+                "No epoch classifications found at {}",
+                config.cluster_db_path().display()
+            )
+        })?
+        .0;
+    let first_epoch = args.first_epoch.unwrap_or(latest_epoch);
+    let last_epoch = args.last_epoch.unwrap_or(latest_epoch);
+    if first_epoch > last_epoch {
+        return Err(format!("Invalid epoch range: {}..={}", first_epoch, last_epoch).into());
+    }
+
+    let criteria = criteria_from_names(&config.criteria)?;
+
+    let mut previous_validator_classifications: Option<ValidatorClassificationByIdentity> = db
+        .load_previous(first_epoch)?
+        .map(|(_, previous)| previous.into_current().validator_classifications)
+        .flatten();
+
+    let mut simulated_epochs = 0;
+    for epoch in first_epoch..=last_epoch {
+        if !db.exists(epoch)? {
+            continue;
+        }
+        let stored = db.load(epoch)?.into_current();
+
+        let snapshot_file = ClusterSnapshot::file_name(epoch, &args.snapshot_path);
+        let simulated = if snapshot_file.exists() {
+            info!("Replaying epoch {} from {}", epoch, snapshot_file.display());
+            Some(classify(
+                config,
+                ClusterSnapshot::load(&snapshot_file)?,
+                previous_validator_classifications.as_ref(),
+                &criteria,
+            )?)
+        } else if let Some(snapshot) = snapshot_from_classification(epoch, &stored) {
+            info!("Replaying epoch {} from its stored metrics", epoch);
+            match classify(
+                config,
+                snapshot,
+                previous_validator_classifications.as_ref(),
+                &criteria,
+            ) {
+                Ok(simulated) => Some(simulated),
+                Err(err) => {
+                    println!("Epoch {}: not simulated, {}\n", epoch, err);
+                    None
+                }
+            }
+        } else {
+            println!(
+                "Epoch {}: not simulated, no recorded cluster snapshot in {} and no stored metrics\n",
+                epoch,
+                args.snapshot_path.display()
+            );
+            None
+        };
+
+        let classification = match simulated {
+            Some(simulated) => {
+                report(epoch, &stored, &simulated, args);
+                simulated_epochs += 1;
+                simulated
+            }
+            None => stored,
+        };
+
+        if classification.validator_classifications.is_some() {
+            previous_validator_classifications = classification.validator_classifications;
+        }
+    }
+
+    if simulated_epochs == 0 {
+        return Err(format!(
+            "No epoch in {}..={} has a recorded cluster snapshot or stored metrics to simulate",
+            first_epoch, last_epoch
+        )
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::db::{ValidatorClassification, ValidatorMetrics},
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_bonus_stake_amount() {
+        let counts = StakeStateCounts {
+            none: 1,
+            baseline: 2,
+            bonus: 4,
+        };
+        assert_eq!(counts.bonus_stake_amount(1_000, 100), 200);
+        assert_eq!(counts.bonus_stake_amount(100, 100), 0);
+        assert_eq!(
+            StakeStateCounts::default().bonus_stake_amount(1_000, 100),
+            0
+        );
+    }
+
+    #[test]
+    fn test_snapshot_from_classification() {
+        let producer = Pubkey::new_unique();
+        let non_leader = Pubkey::new_unique();
+        let metrics = ValidatorMetrics {
+            self_stake: Some(100),
+            epoch_credits: Some(1_000),
+            active_stake: Some(500),
+            pool_stake: Some(200),
+            commission: Some(10),
+            release_version: Some("1.7.1".to_string()),
+            ..ValidatorMetrics::default()
+        };
+        let vc = |identity, metrics| ValidatorClassification {
+            identity,
+            vote_address: Pubkey::new_unique(),
+            metrics,
+            ..ValidatorClassification::default()
+        };
+
+        let mut stored = EpochClassificationV2 {
+            validator_classifications: Some(
+                vec![
+                    vc(
+                        producer,
+                        Some(ValidatorMetrics {
+                            blocks: Some(3),
+                            leader_slots: Some(4),
+                            ..metrics.clone()
+                        }),
+                    ),
+                    vc(non_leader, Some(metrics)),
+                ]
+                .into_iter()
+                .map(|vc| (vc.identity, vc))
+                .collect(),
+            ),
+            ..EpochClassificationV2::default()
+        };
+
+        let snapshot = snapshot_from_classification(10, &stored).unwrap();
+        assert_eq!(snapshot.epoch, 10);
+        assert_eq!(snapshot.validator_list.len(), 2);
+        assert_eq!(snapshot.vote_account_info.len(), 2);
+        assert_eq!(snapshot.total_active_stake, 1_000);
+        assert_eq!(snapshot.pool_stake_by_vote_account.len(), 2);
+        assert_eq!(snapshot.cluster_node_versions[&non_leader], "1.7.1");
+        assert_eq!(
+            snapshot.leader_schedule[&producer.to_string()],
+            vec![0, 1, 2, 3]
+        );
+        assert!(!snapshot
+            .leader_schedule
+            .contains_key(&non_leader.to_string()));
+        assert_eq!(snapshot.confirmed_blocks.len(), 3);
+        assert!(!snapshot.confirmed_blocks.contains(&3));
+        assert!(snapshot.data_centers.is_none());
+
+        // Incomplete metrics can't be replayed
+        stored
+            .validator_classifications
+            .as_mut()
+            .unwrap()
+            .get_mut(&non_leader)
+            .unwrap()
+            .metrics = None;
+        assert!(snapshot_from_classification(10, &stored).is_none());
+
+        // Neither can a paused epoch
+        stored.validator_classifications = None;
+        assert!(snapshot_from_classification(10, &stored).is_none());
+    }
+}
@@ -0,0 +1,323 @@
+use {
+    crate::db::{EpochClassification, EpochClassificationDb, ValidatorClassification},
+    rusqlite::{params, Connection, OptionalExtension, NO_PARAMS},
+    solana_sdk::{clock::Epoch, pubkey::Pubkey},
+    std::{fs, io, path::Path},
+};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS epoch_classification (
+        epoch INTEGER PRIMARY KEY,
+        -- 0 if `validator_classifications` is `None`
+        has_validator_classifications INTEGER NOT NULL,
+        -- YAML encoded `EpochClassification` with `validator_classifications` removed
+        classification TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS validator_classification (
+        identity TEXT NOT NULL,
+        epoch INTEGER NOT NULL REFERENCES epoch_classification(epoch) ON DELETE CASCADE,
+        stake_state TEXT NOT NULL,
+        -- YAML encoded `ValidatorClassification`
+        classification TEXT NOT NULL,
+        PRIMARY KEY (identity, epoch)
+    );
+    CREATE INDEX IF NOT EXISTS validator_classification_epoch
+        ON validator_classification(epoch);
+";
+
+fn sqlite_error(err: rusqlite::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, format!("{:?}", err))
+}
+
+fn yaml_error(err: serde_yaml::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, format!("{:?}", err))
+}
+
+/// An `EpochClassificationDb` stored in a single SQLite database. Validator classifications are
+/// stored one row per validator per epoch so that the history of a validator can be queried
+/// without loading every epoch
+pub struct SqliteDb {
+    connection: Connection,
+}
+
+impl SqliteDb {
+    pub const FILE_NAME: &'static str = "classifications.sqlite3";
+
+    /// Open, creating if necessary, the database within the directory `path`
+    pub fn open<P>(path: P) -> Result<Self, io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        fs::create_dir_all(&path)?;
+        Self::new(Connection::open(path.as_ref().join(Self::FILE_NAME)).map_err(sqlite_error)?)
+    }
+
+    fn new(connection: Connection) -> Result<Self, io::Error> {
+        connection
+            .execute_batch(&format!("PRAGMA foreign_keys = ON; {}", SCHEMA))
+            .map_err(sqlite_error)?;
+        Ok(Self { connection })
+    }
+}
+
+impl EpochClassificationDb for SqliteDb {
+    fn epochs(&self) -> Result<Vec<Epoch>, io::Error> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT epoch FROM epoch_classification ORDER BY epoch")
+            .map_err(sqlite_error)?;
+        let epochs = statement
+            .query_map(NO_PARAMS, |row| row.get::<_, i64>(0))
+            .map_err(sqlite_error)?
+            .map(|epoch| epoch.map(|epoch| epoch as Epoch))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(sqlite_error)?;
+        Ok(epochs)
+    }
+
+    fn exists(&self, epoch: Epoch) -> Result<bool, io::Error> {
+        self.connection
+            .query_row(
+                "SELECT 1 FROM epoch_classification WHERE epoch = ?1",
+                params![epoch as i64],
+                |_| Ok(()),
+            )
+            .optional()
+            .map(|row| row.is_some())
+            .map_err(sqlite_error)
+    }
+
+    fn load(&self, epoch: Epoch) -> Result<EpochClassification, io::Error> {
+        let (has_validator_classifications, classification) = self
+            .connection
+            .query_row(
+                "SELECT has_validator_classifications, classification \
+                 FROM epoch_classification WHERE epoch = ?1",
+                params![epoch as i64],
+                |row| Ok((row.get::<_, bool>(0)?, row.get::<_, String>(1)?)),
+            )
+            .optional()
+            .map_err(sqlite_error)?
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("No classification for epoch {}", epoch),
+                )
+            })?;
+
+        let mut epoch_classification = serde_yaml::from_str::<EpochClassification>(&classification)
+            .map_err(yaml_error)?
+            .into_current();
+
+        if has_validator_classifications {
+            let mut statement = self
+                .connection
+                .prepare("SELECT classification FROM validator_classification WHERE epoch = ?1")
+                .map_err(sqlite_error)?;
+            let validator_classifications = statement
+                .query_map(params![epoch as i64], |row| row.get::<_, String>(0))
+                .map_err(sqlite_error)?
+                .map(|classification| {
+                    let classification = classification.map_err(sqlite_error)?;
+                    let validator_classification =
+                        serde_yaml::from_str::<ValidatorClassification>(&classification)
+                            .map_err(yaml_error)?;
+                    Ok((validator_classification.identity, validator_classification))
+                })
+                .collect::<Result<_, io::Error>>()?;
+            epoch_classification.validator_classifications = Some(validator_classifications);
+        }
+
+        Ok(EpochClassification::new(epoch_classification))
+    }
+
+    fn save(
+        &self,
+        epoch: Epoch,
+        epoch_classification: &EpochClassification,
+    ) -> Result<(), io::Error> {
+        let mut epoch_classification = epoch_classification.clone().into_current();
+        let validator_classifications = epoch_classification.validator_classifications.take();
+
+        let transaction = self
+            .connection
+            .unchecked_transaction()
+            .map_err(sqlite_error)?;
+        transaction
+            .execute(
+                "INSERT OR REPLACE INTO epoch_classification \
+                 (epoch, has_validator_classifications, classification) VALUES (?1, ?2, ?3)",
+                params![
+                    epoch as i64,
+                    validator_classifications.is_some(),
+                    serde_yaml::to_string(&EpochClassification::new(epoch_classification))
+                        .map_err(yaml_error)?,
+                ],
+            )
+            .map_err(sqlite_error)?;
+        transaction
+            .execute(
+                "DELETE FROM validator_classification WHERE epoch = ?1",
+                params![epoch as i64],
+            )
+            .map_err(sqlite_error)?;
+        for validator_classification in validator_classifications.iter().flat_map(|v| v.values()) {
+            transaction
+                .execute(
+                    "INSERT INTO validator_classification \
+                     (identity, epoch, stake_state, classification) VALUES (?1, ?2, ?3, ?4)",
+                    params![
+                        validator_classification.identity.to_string(),
+                        epoch as i64,
+                        format!("{:?}", validator_classification.stake_state),
+                        serde_yaml::to_string(validator_classification).map_err(yaml_error)?,
+                    ],
+                )
+                .map_err(sqlite_error)?;
+        }
+        transaction.commit().map_err(sqlite_error)
+    }
+
+    fn load_validator_history(
+        &self,
+        identity: &Pubkey,
+    ) -> Result<Vec<(Epoch, ValidatorClassification)>, io::Error> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT epoch, classification FROM validator_classification \
+                 WHERE identity = ?1 ORDER BY epoch DESC",
+            )
+            .map_err(sqlite_error)?;
+        let history = statement
+            .query_map(params![identity.to_string()], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(sqlite_error)?
+            .map(|row| {
+                let (epoch, classification) = row.map_err(sqlite_error)?;
+                Ok((
+                    epoch as Epoch,
+                    serde_yaml::from_str(&classification).map_err(yaml_error)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, io::Error>>()?;
+        Ok(history)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            db::{migrate, EpochClassificationV2},
+            generic_stake_pool::ValidatorStakeState,
+        },
+    };
+
+    fn open_in_memory() -> SqliteDb {
+        SqliteDb::new(Connection::open_in_memory().unwrap()).unwrap()
+    }
+
+    fn epoch_classification(validators: &[(Pubkey, ValidatorStakeState)]) -> EpochClassification {
+        EpochClassification::new(EpochClassificationV2 {
+            validator_classifications: Some(
+                validators
+                    .iter()
+                    .map(|(identity, stake_state)| {
+                        (
+                            *identity,
+                            ValidatorClassification {
+                                identity: *identity,
+                                stake_state: *stake_state,
+                                ..ValidatorClassification::default()
+                            },
+                        )
+                    })
+                    .collect(),
+            ),
+            notes: vec!["note".to_string()],
+            ..EpochClassificationV2::default()
+        })
+    }
+
+    #[test]
+    fn test_sqlite_db() {
+        let db = open_in_memory();
+        let validator = Pubkey::new_unique();
+        let other_validator = Pubkey::new_unique();
+
+        assert_eq!(db.epochs().unwrap(), Vec::<Epoch>::new());
+        assert!(db.load_latest().unwrap().is_none());
+        assert!(db.load(1).is_err());
+
+        db.save(
+            1,
+            &epoch_classification(&[(validator, ValidatorStakeState::Baseline)]),
+        )
+        .unwrap();
+        db.save(
+            2,
+            &epoch_classification(&[
+                (validator, ValidatorStakeState::Bonus),
+                (other_validator, ValidatorStakeState::None),
+            ]),
+        )
+        .unwrap();
+        db.save(3, &EpochClassification::default()).unwrap();
+
+        assert_eq!(db.epochs().unwrap(), vec![1, 2, 3]);
+        assert!(db.exists(3).unwrap());
+        assert!(!db.exists(4).unwrap());
+
+        let epoch_classification = db.load(2).unwrap().into_current();
+        assert_eq!(epoch_classification.notes, vec!["note".to_string()]);
+        assert_eq!(
+            epoch_classification
+                .validator_classifications
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(db
+            .load(3)
+            .unwrap()
+            .into_current()
+            .validator_classifications
+            .is_none());
+
+        // Epoch 3 has no validator classifications
+        assert_eq!(db.load_latest().unwrap().unwrap().0, 2);
+        assert_eq!(db.load_previous(2).unwrap().unwrap().0, 1);
+
+        let history = db
+            .load_validator_history(&validator)
+            .unwrap()
+            .into_iter()
+            .map(|(epoch, vc)| (epoch, vc.stake_state))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            history,
+            vec![
+                (2, ValidatorStakeState::Bonus),
+                (1, ValidatorStakeState::Baseline)
+            ]
+        );
+
+        // Saving an epoch again replaces its validator classifications
+        db.save(2, &epoch_classification(&[])).unwrap();
+        assert_eq!(
+            db.load_validator_history(&other_validator).unwrap().len(),
+            0
+        );
+
+        let other_db = open_in_memory();
+        assert_eq!(migrate(&db, &other_db).unwrap(), 3);
+        assert_eq!(other_db.epochs().unwrap(), vec![1, 2, 3]);
+        assert_eq!(
+            other_db.load_validator_history(&validator).unwrap().len(),
+            1
+        );
+    }
+}
@@ -1,14 +1,20 @@
 use {
     crate::{
+        bonus::{bonus_stake_amounts, describe_bonus_stake_amounts, BonusStakeLimits},
         generic_stake_pool::*,
-        rpc_client_utils::{get_all_stake, send_and_confirm_transactions},
+        journal::{Journal, RunPhase},
+        metrics,
+        rpc_client_utils::{
+            get_all_stake, send_and_confirm_transactions, settle_in_flight_transactions,
+            SendAndConfirmTransactionResult, Staker,
+        },
     },
     borsh::BorshDeserialize,
     log::*,
     solana_client::{rpc_client::RpcClient, rpc_response::StakeActivationState},
     solana_sdk::{
         borsh::try_from_slice_unchecked,
-        native_token::{Sol, LAMPORTS_PER_SOL},
+        native_token::{lamports_to_sol, Sol, LAMPORTS_PER_SOL},
         pubkey::Pubkey,
         signature::{Keypair, Signer},
         system_instruction,
@@ -17,6 +23,7 @@ use {
     solana_stake_program::{stake_instruction, stake_state::StakeState},
     spl_stake_pool::{
         self, find_stake_program_address, find_transient_stake_program_address,
+        find_withdraw_authority_program_address,
         stake_program::split_only,
         state::{StakePool, StakeStatus, ValidatorList},
     },
@@ -73,20 +80,24 @@ fn staker_transient_stake_address(authorized_staker: Pubkey, vote_address: Pubke
 }
 
 #[derive(Debug)]
-pub struct StakePoolOMatic {
-    authorized_staker: Keypair,
+pub struct StakePoolOMatic<S = Keypair> {
+    authorized_staker: S,
     baseline_stake_amount: u64,
+    bonus_stake_limits: BonusStakeLimits,
+    max_stake_change: Option<MaxStakeChange>,
     stake_pool_address: Pubkey,
     stake_pool: StakePool,
     validator_list: ValidatorList,
 }
 
-pub fn new(
+pub fn new<S: Staker>(
     rpc_client: &RpcClient,
-    authorized_staker: Keypair,
+    authorized_staker: S,
     stake_pool_address: Pubkey,
     baseline_stake_amount: u64,
-) -> Result<StakePoolOMatic, Box<dyn error::Error>> {
+    bonus_stake_limits: BonusStakeLimits,
+    max_stake_change: Option<MaxStakeChange>,
+) -> Result<StakePoolOMatic<S>, Box<dyn error::Error>> {
     if baseline_stake_amount < MIN_STAKE_CHANGE_AMOUNT {
         return Err(format!(
             "baseline stake amount too small: {}",
@@ -110,13 +121,15 @@ pub fn new(
     Ok(StakePoolOMatic {
         authorized_staker,
         baseline_stake_amount,
+        bonus_stake_limits,
+        max_stake_change,
         stake_pool_address,
         stake_pool,
         validator_list,
     })
 }
 
-impl StakePoolOMatic {
+impl<S: Staker> StakePoolOMatic<S> {
     /// Perform the double update, required at the start of an epoch:
     /// * call into the stake pool program to update the accounting of lamports
     /// * update the StakePool and ValidatorList objects based on the accounting
@@ -150,15 +163,25 @@ impl StakePoolOMatic {
     }
 }
 
-impl GenericStakePool for StakePoolOMatic {
+impl<S: Staker> GenericStakePool for StakePoolOMatic<S> {
+    fn stake_authority(&self) -> Pubkey {
+        find_withdraw_authority_program_address(&spl_stake_pool::id(), &self.stake_pool_address).0
+    }
+
     fn apply(
         &mut self,
         rpc_client: &RpcClient,
         dry_run: bool,
         desired_validator_stake: &[ValidatorStake],
-    ) -> Result<(EpochStakeNotes, ValidatorStakeActions, UnfundedValidators), Box<dyn error::Error>>
-    {
-        let mut validator_stake_actions = HashMap::default();
+        journal: &mut Journal,
+    ) -> Result<ApplyResult, Box<dyn error::Error>> {
+        if let Some(apply_result) = journal.apply_result() {
+            info!("Stake distribution already sent");
+            return Ok(apply_result.clone());
+        }
+        settle_in_flight_transactions(rpc_client, dry_run, &self.authorized_staker)?;
+
+        let mut validator_stake_actions = journal.validator_stake_actions().clone();
         let mut no_stake_node_count = 0;
         let mut bonus_stake_node_count = 0;
         let mut baseline_stake_node_count = 0;
@@ -193,25 +216,31 @@ impl GenericStakePool for StakePoolOMatic {
             .iter()
             .map(|x| x.vote_account_address)
             .collect();
-        info!("Remove validators no longer present in the desired list");
-        remove_validators_from_pool(
-            rpc_client,
-            &self.authorized_staker,
-            &self.stake_pool_address,
-            &self.stake_pool,
-            &self.validator_list,
-            &all_vote_addresses - &inuse_vote_addresses,
-        )?;
+        if !journal.is_completed(RunPhase::ValidatorsRemoved) {
+            info!("Remove validators no longer present in the desired list");
+            remove_validators_from_pool(
+                rpc_client,
+                &self.authorized_staker,
+                &self.stake_pool_address,
+                &self.stake_pool,
+                &self.validator_list,
+                &all_vote_addresses - &inuse_vote_addresses,
+            )?;
+            journal.complete_stake_phase(RunPhase::ValidatorsRemoved, &validator_stake_actions)?;
+        }
 
-        info!("Add new validators to pool if active");
-        add_validators_to_pool(
-            rpc_client,
-            &self.authorized_staker,
-            desired_validator_stake,
-            &self.stake_pool_address,
-            &self.stake_pool,
-            &self.validator_list,
-        )?;
+        if !journal.is_completed(RunPhase::ValidatorsAdded) {
+            info!("Add new validators to pool if active");
+            add_validators_to_pool(
+                rpc_client,
+                &self.authorized_staker,
+                desired_validator_stake,
+                &self.stake_pool_address,
+                &self.stake_pool,
+                &self.validator_list,
+            )?;
+            journal.complete_stake_phase(RunPhase::ValidatorsAdded, &validator_stake_actions)?;
+        }
         self.update(rpc_client)?;
 
         info!("Add unmerged transient stake accounts to the busy set");
@@ -222,14 +251,18 @@ impl GenericStakePool for StakePoolOMatic {
             &mut validator_stake_actions,
         )?;
 
-        info!("Create validator stake accounts if needed");
-        create_validator_stake_accounts(
-            rpc_client,
-            &self.authorized_staker,
-            desired_validator_stake,
-            &self.stake_pool_address,
-            &mut validator_stake_actions,
-        )?;
+        if !journal.is_completed(RunPhase::StakeAccountsCreated) {
+            info!("Create validator stake accounts if needed");
+            create_validator_stake_accounts(
+                rpc_client,
+                &self.authorized_staker,
+                desired_validator_stake,
+                &self.stake_pool_address,
+                &mut validator_stake_actions,
+            )?;
+            journal
+                .complete_stake_phase(RunPhase::StakeAccountsCreated, &validator_stake_actions)?;
+        }
 
         let total_stake_amount = self.stake_pool.total_stake_lamports;
         info!(
@@ -237,11 +270,15 @@ impl GenericStakePool for StakePoolOMatic {
             Sol(total_stake_amount)
         );
 
-        let total_baseline_stake_amount = baseline_stake_node_count * self.baseline_stake_amount;
+        // Includes the fixed amounts of stake tiers, which take precedence over the bonus
+        let total_baseline_stake_amount = desired_validator_stake
+            .iter()
+            .filter_map(|vs| vs.fixed_stake_amount(self.baseline_stake_amount))
+            .sum::<u64>();
         info!("Baseline node count: {}", baseline_stake_node_count);
         info!("Baseline stake amount: {}", Sol(self.baseline_stake_amount));
         info!(
-            "Total baseline stake amount: {}",
+            "Total baseline and stake tier amount: {}",
             Sol(total_baseline_stake_amount)
         );
 
@@ -257,13 +294,19 @@ impl GenericStakePool for StakePoolOMatic {
             Sol(total_bonus_stake_amount)
         );
 
-        let bonus_stake_amount = if bonus_stake_node_count == 0 {
-            0
-        } else {
-            total_bonus_stake_amount / (bonus_stake_node_count as u64)
-        };
-
-        info!("Bonus stake amount: {}", Sol(bonus_stake_amount));
+        let bonus_stake_amounts = bonus_stake_amounts(
+            total_bonus_stake_amount,
+            &desired_validator_stake
+                .iter()
+                .filter(|vs| {
+                    vs.stake_state == ValidatorStakeState::Bonus && vs.stake_amount.is_none()
+                })
+                .map(|vs| (vs.identity, vs.bonus_weight))
+                .collect::<Vec<_>>(),
+            &self.bonus_stake_limits,
+        );
+        let bonus_stake_amount = describe_bonus_stake_amounts(&bonus_stake_amounts);
+        info!("Bonus stake amount: {}", bonus_stake_amount);
 
         let reserve_stake_balance = get_available_stake_balance(
             rpc_client,
@@ -289,24 +332,47 @@ impl GenericStakePool for StakePoolOMatic {
                 Sol(reserve_stake_balance)
             ),
             format!("Baseline stake amount: {}", Sol(self.baseline_stake_amount)),
-            format!("Bonus stake amount: {}", Sol(bonus_stake_amount)),
+            format!("Bonus stake amount: {}", bonus_stake_amount),
             format!(
                 "Validators by stake level: None={}, Baseline={}, Bonus={}",
                 no_stake_node_count, baseline_stake_node_count, bonus_stake_node_count
             ),
         ];
 
+        metrics::set_gauge(
+            "stake_pool_size_sol",
+            "Stake pool balance",
+            lamports_to_sol(total_stake_amount),
+        );
+        metrics::set_gauge(
+            "reserve_balance_sol",
+            "Reserve stake available for delegation",
+            lamports_to_sol(reserve_stake_balance),
+        );
+        metrics::set_gauge(
+            "baseline_stake_amount_sol",
+            "Stake delegated to each Baseline validator",
+            lamports_to_sol(self.baseline_stake_amount),
+        );
+        metrics::set_gauge(
+            "bonus_stake_amount_sol",
+            "Average stake delegated to each Bonus validator",
+            lamports_to_sol(
+                bonus_stake_amounts.values().sum::<u64>() / bonus_stake_amounts.len().max(1) as u64,
+            ),
+        );
+
         let busy_validators = validator_stake_actions
             .keys()
             .cloned()
             .collect::<HashSet<_>>();
         let mut unfunded_validators = HashSet::default();
+        let mut stake_plan = StakePlan {
+            stake_pool: self.stake_pool_address,
+            epoch: rpc_client.get_epoch_info()?.epoch,
+            changes: vec![],
+        };
         distribute_validator_stake(
-            rpc_client,
-            dry_run,
-            &self.authorized_staker,
-            &self.stake_pool_address,
-            &self.stake_pool,
             &self.validator_list,
             desired_validator_stake
                 .iter()
@@ -314,11 +380,78 @@ impl GenericStakePool for StakePoolOMatic {
                 .cloned(),
             reserve_stake_balance,
             self.baseline_stake_amount,
-            bonus_stake_amount,
+            &bonus_stake_amounts,
+            self.max_stake_change,
             &mut validator_stake_actions,
             &mut unfunded_validators,
+            &mut stake_plan,
+        );
+
+        let apply_result = (
+            notes,
+            validator_stake_actions,
+            unfunded_validators,
+            stake_plan,
+        );
+        if !dry_run {
+            let result = send_stake_plan(
+                rpc_client,
+                &self.authorized_staker,
+                &self.stake_pool_address,
+                &self.stake_pool,
+                &apply_result.3,
+            )?;
+            journal.distribution_sent(
+                result.succeeded.into_iter().chain(result.failed),
+                &apply_result,
+            )?;
+        }
+        Ok(apply_result)
+    }
+
+    fn execute_plan(
+        &mut self,
+        rpc_client: &RpcClient,
+        dry_run: bool,
+        stake_plan: &StakePlan,
+    ) -> Result<(), Box<dyn error::Error>> {
+        settle_in_flight_transactions(rpc_client, dry_run, &self.authorized_staker)?;
+        self.update(rpc_client)?;
+        let validator_list = &self.validator_list;
+        stake_plan.verify(
+            &self.stake_pool_address,
+            rpc_client.get_epoch_info()?.epoch,
+            |change| {
+                validator_list
+                    .find(&change.vote_address)
+                    .map(|validator_entry| validator_entry.stake_lamports)
+                    .ok_or_else(|| {
+                        format!(
+                            "Vote address {} found in the stake plan, but not in stake pool",
+                            change.vote_address
+                        )
+                        .into()
+                    })
+            },
         )?;
-        Ok((notes, validator_stake_actions, unfunded_validators))
+
+        if dry_run {
+            return Ok(());
+        }
+        if send_stake_plan(
+            rpc_client,
+            &self.authorized_staker,
+            &self.stake_pool_address,
+            &self.stake_pool,
+            stake_plan,
+        )?
+        .failed
+        .is_empty()
+        {
+            Ok(())
+        } else {
+            Err("One or more transactions failed to execute".into())
+        }
     }
 }
 
@@ -395,7 +528,7 @@ fn add_unmerged_transient_stake_accounts(
 /// and withdraws the entirety back to the staker.
 fn withdraw_inactive_stakes_to_staker(
     rpc_client: &RpcClient,
-    authorized_staker: &Keypair,
+    authorized_staker: &impl Staker,
 ) -> Result<(), Box<dyn error::Error>> {
     let mut transactions = vec![];
     let (all_stake_addresses, _all_stake_total_amount) =
@@ -449,7 +582,7 @@ fn withdraw_inactive_stakes_to_staker(
 /// once per epoch to perform any operations on the stake pool.
 fn update_stake_pool(
     rpc_client: &RpcClient,
-    payer: &Keypair,
+    payer: &impl Staker,
     stake_pool_address: &Pubkey,
     stake_pool: &StakePool,
     validator_list: &ValidatorList,
@@ -492,7 +625,7 @@ fn update_stake_pool(
 /// this also deactivates the stake, to be reclaimed in the next epoch.
 fn remove_validators_from_pool(
     rpc_client: &RpcClient,
-    authorized_staker: &Keypair,
+    authorized_staker: &impl Staker,
     stake_pool_address: &Pubkey,
     stake_pool: &StakePool,
     validator_list: &ValidatorList,
@@ -564,7 +697,7 @@ fn remove_validators_from_pool(
 /// included yet in the stake pool
 fn add_validators_to_pool(
     rpc_client: &RpcClient,
-    authorized_staker: &Keypair,
+    authorized_staker: &impl Staker,
     desired_validator_stake: &[ValidatorStake],
     stake_pool_address: &Pubkey,
     stake_pool: &StakePool,
@@ -666,7 +799,7 @@ fn add_validators_to_pool(
 /// busy validators.
 fn create_validator_stake_accounts(
     rpc_client: &RpcClient,
-    authorized_staker: &Keypair,
+    authorized_staker: &impl Staker,
     desired_validator_stake: &[ValidatorStake],
     stake_pool_address: &Pubkey,
     validator_stake_actions: &mut ValidatorStakeActions,
@@ -792,20 +925,16 @@ fn create_validator_stake_accounts(
 
 #[allow(clippy::too_many_arguments)]
 fn distribute_validator_stake<V>(
-    rpc_client: &RpcClient,
-    dry_run: bool,
-    authorized_staker: &Keypair,
-    stake_pool_address: &Pubkey,
-    stake_pool: &StakePool,
     validator_list: &ValidatorList,
     desired_validator_stake: V,
     mut reserve_stake_balance: u64,
     baseline_stake_amount: u64,
-    bonus_stake_amount: u64,
+    bonus_stake_amounts: &HashMap<Pubkey, u64>,
+    max_stake_change: Option<MaxStakeChange>,
     validator_stake_actions: &mut ValidatorStakeActions,
     unfunded_validators: &mut HashSet<Pubkey>,
-) -> Result<bool, Box<dyn error::Error>>
-where
+    stake_plan: &mut StakePlan,
+) where
     V: IntoIterator<Item = ValidatorStake>,
 {
     // Prioritize funding smaller stake accounts to maximize the number of accounts that will be
@@ -844,7 +973,6 @@ where
     baseline_stake.sort_by_key(|k| k.0);
     bonus_stake.sort_by_key(|k| k.0);
 
-    let mut transactions = vec![];
     for (
         balance,
         ValidatorStake {
@@ -852,6 +980,8 @@ where
             stake_state,
             vote_address,
             priority,
+            stake_amount,
+            ..
         },
     ) in priority_stake
         .into_iter()
@@ -859,36 +989,59 @@ where
         .chain(baseline_stake)
         .chain(bonus_stake)
     {
-        let desired_balance = match stake_state {
+        let final_balance = match stake_state {
             ValidatorStakeState::None => 0,
-            ValidatorStakeState::Baseline => baseline_stake_amount,
-            ValidatorStakeState::Bonus => bonus_stake_amount,
+            ValidatorStakeState::Baseline => stake_amount.unwrap_or(baseline_stake_amount),
+            ValidatorStakeState::Bonus => stake_amount.unwrap_or_else(|| {
+                bonus_stake_amounts
+                    .get(&identity)
+                    .cloned()
+                    .unwrap_or_default()
+            }),
         };
+        let desired_balance = max_stake_change
+            .map(|max_stake_change| {
+                max_stake_change.limit(
+                    balance,
+                    final_balance,
+                    baseline_stake_amount,
+                    MIN_STAKE_CHANGE_AMOUNT,
+                )
+            })
+            .unwrap_or(final_balance);
+        if desired_balance != final_balance {
+            info!(
+                "{}: stake change limited, moving towards {}",
+                identity,
+                Sol(final_balance)
+            );
+        }
 
         #[allow(clippy::comparison_chain)]
-        let op_msg = if balance > desired_balance {
+        let (operation, amount, reason) = if balance > desired_balance {
             let amount_to_remove = balance - desired_balance;
             if amount_to_remove < MIN_STAKE_CHANGE_AMOUNT {
-                format!("not removing {} (amount too small)", Sol(amount_to_remove))
+                (
+                    StakeOperation::None,
+                    0,
+                    format!("not removing {} (amount too small)", Sol(amount_to_remove)),
+                )
             } else {
-                transactions.push(Transaction::new_with_payer(
-                    &[
-                        spl_stake_pool::instruction::decrease_validator_stake_with_vote(
-                            stake_pool,
-                            stake_pool_address,
-                            &vote_address,
-                            amount_to_remove,
-                        ),
-                    ],
-                    Some(&authorized_staker.pubkey()),
-                ));
-                format!("removing {}", Sol(amount_to_remove))
+                (
+                    StakeOperation::Decrease,
+                    amount_to_remove,
+                    format!("removing {}", Sol(amount_to_remove)),
+                )
             }
         } else if balance < desired_balance {
             let mut amount_to_add = desired_balance - balance;
 
             if amount_to_add < MIN_STAKE_CHANGE_AMOUNT {
-                format!("not adding {} (amount too small)", Sol(amount_to_add))
+                (
+                    StakeOperation::None,
+                    0,
+                    format!("not adding {} (amount too small)", Sol(amount_to_add)),
+                )
             } else {
                 if amount_to_add > reserve_stake_balance {
                     trace!(
@@ -904,58 +1057,91 @@ where
                         warn!("Failed to fund a priority node");
                     }
                     unfunded_validators.insert(identity);
-                    "reserve depleted".to_string()
+                    (StakeOperation::None, 0, "reserve depleted".to_string())
                 } else {
                     reserve_stake_balance -= amount_to_add;
                     info!("adding {} stake", Sol(amount_to_add));
-
-                    transactions.push(Transaction::new_with_payer(
-                        &[
-                            spl_stake_pool::instruction::increase_validator_stake_with_vote(
-                                stake_pool,
-                                stake_pool_address,
-                                &vote_address,
-                                amount_to_add,
-                            ),
-                        ],
-                        Some(&authorized_staker.pubkey()),
-                    ));
-                    format!("adding {}", Sol(amount_to_add))
+                    (
+                        StakeOperation::Increase,
+                        amount_to_add,
+                        format!("adding {}", Sol(amount_to_add)),
+                    )
                 }
             }
         } else {
-            "no change".to_string()
+            (StakeOperation::None, 0, "no change".to_string())
         };
 
-        let action = format!(
-            "target stake amount: {}, current stake amount: {} - {}",
-            Sol(desired_balance),
-            Sol(balance),
-            op_msg,
-        );
+        let change = PlannedStakeChange {
+            identity,
+            vote_address,
+            stake_state,
+            priority,
+            current_balance: balance,
+            desired_balance,
+            final_balance: if desired_balance != final_balance {
+                Some(final_balance)
+            } else {
+                None
+            },
+            operation,
+            amount,
+            reason,
+        };
+        let action = change.action();
         info!(
             "{} ({:?},priority={}) | {}",
             identity, stake_state, priority, action
         );
         validator_stake_actions.insert(identity, action);
+        stake_plan.changes.push(change);
     }
     info!(
         "Reserve stake available balance after updates: {}",
         Sol(reserve_stake_balance)
     );
+}
 
-    let ok = if dry_run {
-        true
-    } else {
-        send_and_confirm_transactions(rpc_client, false, transactions, authorized_staker)?
-            .failed
-            .is_empty()
-    };
+/// Send the increase and decrease instructions of `stake_plan`
+fn send_stake_plan(
+    rpc_client: &RpcClient,
+    authorized_staker: &impl Staker,
+    stake_pool_address: &Pubkey,
+    stake_pool: &StakePool,
+    stake_plan: &StakePlan,
+) -> Result<SendAndConfirmTransactionResult, Box<dyn error::Error>> {
+    let transactions = stake_plan
+        .operations()
+        .map(|change| {
+            let instruction = match change.operation {
+                StakeOperation::Increase => {
+                    spl_stake_pool::instruction::increase_validator_stake_with_vote(
+                        stake_pool,
+                        stake_pool_address,
+                        &change.vote_address,
+                        change.amount,
+                    )
+                }
+                StakeOperation::Decrease => {
+                    spl_stake_pool::instruction::decrease_validator_stake_with_vote(
+                        stake_pool,
+                        stake_pool_address,
+                        &change.vote_address,
+                        change.amount,
+                    )
+                }
+                StakeOperation::None => unreachable!(),
+            };
+            Transaction::new_with_payer(&[instruction], Some(&authorized_staker.pubkey()))
+        })
+        .collect::<Vec<_>>();
+
+    let result = send_and_confirm_transactions(rpc_client, false, transactions, authorized_staker)?;
 
-    if !ok {
+    if !result.failed.is_empty() {
         error!("One or more transactions failed to execute")
     }
-    Ok(ok)
+    Ok(result)
 }
 
 #[cfg(test)]
@@ -970,7 +1156,7 @@ mod test {
             signature::{Keypair, Signer},
         },
         solana_validator::test_validator::*,
-        spl_stake_pool::find_withdraw_authority_program_address,
+        spl_stake_pool::state::ValidatorStakeInfo,
     };
 
     fn num_stake_accounts(rpc_client: &RpcClient, authority: Pubkey) -> usize {
@@ -1017,17 +1203,29 @@ mod test {
                 vote_address: vap.vote_address,
                 stake_state,
                 priority: false,
+                bonus_weight: 1.,
+                stake_amount: None,
             })
             .collect::<Vec<_>>();
 
         stake_o_matic
-            .apply(rpc_client, false, &desired_validator_stake)
+            .apply(
+                rpc_client,
+                false,
+                &desired_validator_stake,
+                &mut Journal::default(),
+            )
             .unwrap();
 
         assert!(num_stake_accounts(rpc_client, pool_withdraw_authority) > 1 + validators.len());
         let _epoch = wait_for_next_epoch(&rpc_client).unwrap();
         stake_o_matic
-            .apply(rpc_client, false, &desired_validator_stake)
+            .apply(
+                rpc_client,
+                false,
+                &desired_validator_stake,
+                &mut Journal::default(),
+            )
             .unwrap();
 
         assert_eq!(
@@ -1048,6 +1246,63 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_distribute_validator_stake_with_max_stake_change() {
+        let sol = sol_to_lamports;
+        let identity = Pubkey::new_unique();
+        let vote_address = Pubkey::new_unique();
+        let mut validator_list = ValidatorList::new(1);
+        validator_list.validators.push(ValidatorStakeInfo {
+            status: StakeStatus::Active,
+            vote_account_address: vote_address,
+            stake_lamports: sol(10.),
+            last_update_epoch: 0,
+        });
+
+        let distribute = |max_stake_change| {
+            let mut stake_plan = StakePlan::default();
+            distribute_validator_stake(
+                &validator_list,
+                vec![ValidatorStake {
+                    identity,
+                    vote_address,
+                    stake_state: ValidatorStakeState::Baseline,
+                    priority: false,
+                    bonus_weight: 1.,
+                    stake_amount: None,
+                }],
+                sol(1_000.),
+                sol(100.),
+                &HashMap::new(),
+                Some(max_stake_change),
+                &mut HashMap::new(),
+                &mut HashSet::new(),
+                &mut stake_plan,
+            );
+            let change = stake_plan.changes.pop().unwrap();
+            assert_eq!(change.final_balance, Some(sol(100.)));
+            (change.operation, change.amount)
+        };
+
+        assert_eq!(
+            distribute(MaxStakeChange::Amount(sol(5.))),
+            (StakeOperation::Increase, sol(5.))
+        );
+        assert_eq!(
+            distribute(MaxStakeChange::Percentage(2.)),
+            (StakeOperation::Increase, sol(2.))
+        );
+        // Limits below the minimum stake change still move the validator towards its balance
+        assert_eq!(
+            distribute(MaxStakeChange::Amount(sol(0.5))),
+            (StakeOperation::Increase, MIN_STAKE_CHANGE_AMOUNT)
+        );
+        assert_eq!(
+            distribute(MaxStakeChange::Percentage(0.1)),
+            (StakeOperation::Increase, MIN_STAKE_CHANGE_AMOUNT)
+        );
+    }
+
     #[test]
     fn this_test_is_too_big_and_slow() {
         solana_logger::setup_with_default("solana_stake_o_matic=info");
@@ -1151,6 +1406,8 @@ mod test {
             authorized_staker,
             stake_pool.pubkey(),
             baseline_stake_amount,
+            BonusStakeLimits::default(),
+            None,
         )
         .unwrap();
 
@@ -1170,8 +1427,11 @@ mod test {
                         vote_address: vap.vote_address,
                         stake_state: ValidatorStakeState::None,
                         priority: false,
+                        bonus_weight: 1.,
+                        stake_amount: None,
                     })
                     .collect::<Vec<_>>(),
+                &mut Journal::default(),
             )
             .unwrap();
 
@@ -1247,8 +1507,11 @@ mod test {
                         vote_address: vap.vote_address,
                         stake_state: ValidatorStakeState::None,
                         priority: false,
+                        bonus_weight: 1.,
+                        stake_amount: None,
                     })
                     .collect::<Vec<_>>(),
+                &mut Journal::default(),
             )
             .unwrap();
 
@@ -1283,8 +1546,11 @@ mod test {
                         vote_address: vap.vote_address,
                         stake_state: ValidatorStakeState::None,
                         priority: false,
+                        bonus_weight: 1.,
+                        stake_amount: None,
                     })
                     .collect::<Vec<_>>(),
+                &mut Journal::default(),
             )
             .unwrap();
         let _epoch = wait_for_next_epoch(&rpc_client).unwrap();
@@ -1320,27 +1586,43 @@ mod test {
                 vote_address: validators[0].vote_address,
                 stake_state: ValidatorStakeState::None,
                 priority: false,
+                bonus_weight: 1.,
+                stake_amount: None,
             },
             ValidatorStake {
                 identity: validators[1].identity,
                 vote_address: validators[1].vote_address,
                 stake_state: ValidatorStakeState::Baseline,
                 priority: false,
+                bonus_weight: 1.,
+                stake_amount: None,
             },
             ValidatorStake {
                 identity: validators[2].identity,
                 vote_address: validators[2].vote_address,
                 stake_state: ValidatorStakeState::Bonus,
                 priority: false,
+                bonus_weight: 1.,
+                stake_amount: None,
             },
         ];
 
         stake_o_matic
-            .apply(&rpc_client, false, &desired_validator_stake)
+            .apply(
+                &rpc_client,
+                false,
+                &desired_validator_stake,
+                &mut Journal::default(),
+            )
             .unwrap();
         let _epoch = wait_for_next_epoch(&rpc_client).unwrap();
         stake_o_matic
-            .apply(&rpc_client, false, &desired_validator_stake)
+            .apply(
+                &rpc_client,
+                false,
+                &desired_validator_stake,
+                &mut Journal::default(),
+            )
             .unwrap();
 
         info!("Check after first epoch");
@@ -1366,7 +1648,12 @@ mod test {
         info!("Check after second epoch");
         let _epoch = wait_for_next_epoch(&rpc_client).unwrap();
         stake_o_matic
-            .apply(&rpc_client, false, &desired_validator_stake)
+            .apply(
+                &rpc_client,
+                false,
+                &desired_validator_stake,
+                &mut Journal::default(),
+            )
             .unwrap();
 
         assert_eq!(
@@ -1390,10 +1677,14 @@ mod test {
         info!("remove all validators");
 
         // deactivate all validator stake and remove from pool
-        stake_o_matic.apply(&rpc_client, false, &[]).unwrap();
+        stake_o_matic
+            .apply(&rpc_client, false, &[], &mut Journal::default())
+            .unwrap();
         let _epoch = wait_for_next_epoch(&rpc_client).unwrap();
         // withdraw removed validator stake into the staker
-        stake_o_matic.apply(&rpc_client, false, &[]).unwrap();
+        stake_o_matic
+            .apply(&rpc_client, false, &[], &mut Journal::default())
+            .unwrap();
         // all stake has been returned to the reserve account
         assert_reserve_account_only(
             MIN_STAKE_RESERVE_BALANCE + stake_rent_exemption + total_stake_amount,
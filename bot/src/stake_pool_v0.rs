@@ -1,13 +1,19 @@
 use {
     crate::{
+        bonus::{bonus_stake_amounts, describe_bonus_stake_amounts, BonusStakeLimits},
         generic_stake_pool::*,
-        rpc_client_utils::{get_all_stake, send_and_confirm_transactions},
+        journal::{Journal, RunPhase},
+        metrics,
+        rpc_client_utils::{
+            get_all_stake, send_and_confirm_transactions, settle_in_flight_transactions,
+            SendAndConfirmTransactionResult, Staker,
+        },
     },
     log::*,
     solana_client::{rpc_client::RpcClient, rpc_response::StakeActivationState},
     solana_sdk::{
         account::Account,
-        native_token::{Sol, LAMPORTS_PER_SOL},
+        native_token::{lamports_to_sol, Sol, LAMPORTS_PER_SOL},
         pubkey::Pubkey,
         signature::{Keypair, Signer},
         transaction::Transaction,
@@ -27,20 +33,24 @@ pub const MIN_STAKE_ACCOUNT_BALANCE: u64 = LAMPORTS_PER_SOL;
 const MIN_STAKE_CHANGE_AMOUNT: u64 = MIN_STAKE_ACCOUNT_BALANCE;
 
 #[derive(Debug)]
-pub struct StakePool {
-    authorized_staker: Keypair,
+pub struct StakePool<S = Keypair> {
+    authorized_staker: S,
     baseline_stake_amount: u64,
+    bonus_stake_limits: BonusStakeLimits,
+    max_stake_change: Option<MaxStakeChange>,
     reserve_stake_address: Pubkey,
     min_reserve_stake_balance: u64,
 }
 
-pub fn new(
+pub fn new<S: Staker>(
     _rpc_client: &RpcClient,
-    authorized_staker: Keypair,
+    authorized_staker: S,
     baseline_stake_amount: u64,
+    bonus_stake_limits: BonusStakeLimits,
+    max_stake_change: Option<MaxStakeChange>,
     reserve_stake_address: Pubkey,
     min_reserve_stake_balance: u64,
-) -> Result<StakePool, Box<dyn error::Error>> {
+) -> Result<StakePool<S>, Box<dyn error::Error>> {
     if baseline_stake_amount < MIN_STAKE_CHANGE_AMOUNT {
         return Err(format!(
             "baseline stake amount too small: {}",
@@ -60,6 +70,8 @@ pub fn new(
     Ok(StakePool {
         authorized_staker,
         baseline_stake_amount,
+        bonus_stake_limits,
+        max_stake_change,
         reserve_stake_address,
         min_reserve_stake_balance,
     })
@@ -91,15 +103,25 @@ fn validator_transient_stake_address(authorized_staker: Pubkey, vote_address: Pu
     .unwrap()
 }
 
-impl GenericStakePool for StakePool {
+impl<S: Staker> GenericStakePool for StakePool<S> {
+    fn stake_authority(&self) -> Pubkey {
+        self.authorized_staker.pubkey()
+    }
+
     fn apply(
         &mut self,
         rpc_client: &RpcClient,
         dry_run: bool,
         desired_validator_stake: &[ValidatorStake],
-    ) -> Result<(EpochStakeNotes, ValidatorStakeActions, UnfundedValidators), Box<dyn error::Error>>
-    {
-        let mut validator_stake_actions = HashMap::default();
+        journal: &mut Journal,
+    ) -> Result<ApplyResult, Box<dyn error::Error>> {
+        if let Some(apply_result) = journal.apply_result() {
+            info!("Stake distribution already sent");
+            return Ok(apply_result.clone());
+        }
+        settle_in_flight_transactions(rpc_client, dry_run, &self.authorized_staker)?;
+
+        let mut validator_stake_actions = journal.validator_stake_actions().clone();
 
         let mut inuse_stake_addresses = HashSet::default();
         inuse_stake_addresses.insert(self.reserve_stake_address);
@@ -132,13 +154,16 @@ impl GenericStakePool for StakePool {
         let (all_stake_addresses, all_stake_total_amount) =
             get_all_stake(rpc_client, self.authorized_staker.pubkey())?;
 
-        info!("Merge orphaned stake into the reserve");
-        merge_orphaned_stake_accounts(
-            rpc_client,
-            &self.authorized_staker,
-            &all_stake_addresses - &inuse_stake_addresses,
-            self.reserve_stake_address,
-        )?;
+        if !journal.is_completed(RunPhase::ValidatorsRemoved) {
+            info!("Merge orphaned stake into the reserve");
+            merge_orphaned_stake_accounts(
+                rpc_client,
+                &self.authorized_staker,
+                &all_stake_addresses - &inuse_stake_addresses,
+                self.reserve_stake_address,
+            )?;
+            journal.complete_stake_phase(RunPhase::ValidatorsRemoved, &validator_stake_actions)?;
+        }
 
         info!("Merge transient stake back into either the reserve or validator stake");
         merge_transient_stake_accounts(
@@ -149,15 +174,19 @@ impl GenericStakePool for StakePool {
             &mut validator_stake_actions,
         )?;
 
-        info!("Create validator stake accounts if needed");
-        create_validator_stake_accounts(
-            rpc_client,
-            &self.authorized_staker,
-            desired_validator_stake,
-            self.reserve_stake_address,
-            self.min_reserve_stake_balance,
-            &mut validator_stake_actions,
-        )?;
+        if !journal.is_completed(RunPhase::StakeAccountsCreated) {
+            info!("Create validator stake accounts if needed");
+            create_validator_stake_accounts(
+                rpc_client,
+                &self.authorized_staker,
+                desired_validator_stake,
+                self.reserve_stake_address,
+                self.min_reserve_stake_balance,
+                &mut validator_stake_actions,
+            )?;
+            journal
+                .complete_stake_phase(RunPhase::StakeAccountsCreated, &validator_stake_actions)?;
+        }
 
         // `total_stake_amount` excludes the amount that always remains in the reserve account
         let total_stake_amount = all_stake_total_amount - self.min_reserve_stake_balance;
@@ -168,11 +197,15 @@ impl GenericStakePool for StakePool {
         info!("Min node count: {}", min_stake_node_count);
         info!("Min stake amount: {}", Sol(total_min_stake_amount));
 
-        let total_baseline_stake_amount = baseline_stake_node_count * self.baseline_stake_amount;
+        // Includes the fixed amounts of stake tiers, which take precedence over the bonus
+        let total_baseline_stake_amount = desired_validator_stake
+            .iter()
+            .filter_map(|vs| vs.fixed_stake_amount(self.baseline_stake_amount))
+            .sum::<u64>();
         info!("Baseline node count: {}", baseline_stake_node_count);
         info!("Baseline stake amount: {}", Sol(self.baseline_stake_amount));
         info!(
-            "Total baseline stake amount: {}",
+            "Total baseline and stake tier amount: {}",
             Sol(total_baseline_stake_amount)
         );
 
@@ -188,13 +221,19 @@ impl GenericStakePool for StakePool {
             Sol(total_bonus_stake_amount)
         );
 
-        let bonus_stake_amount = if bonus_stake_node_count == 0 {
-            0
-        } else {
-            total_bonus_stake_amount / (bonus_stake_node_count as u64)
-        };
-
-        info!("Bonus stake amount: {}", Sol(bonus_stake_amount));
+        let bonus_stake_amounts = bonus_stake_amounts(
+            total_bonus_stake_amount,
+            &desired_validator_stake
+                .iter()
+                .filter(|vs| {
+                    vs.stake_state == ValidatorStakeState::Bonus && vs.stake_amount.is_none()
+                })
+                .map(|vs| (vs.identity, vs.bonus_weight))
+                .collect::<Vec<_>>(),
+            &self.bonus_stake_limits,
+        );
+        let bonus_stake_amount = describe_bonus_stake_amounts(&bonus_stake_amounts);
+        info!("Bonus stake amount: {}", bonus_stake_amount);
 
         let reserve_stake_balance = get_available_stake_balance(
             rpc_client,
@@ -220,34 +259,106 @@ impl GenericStakePool for StakePool {
                 Sol(reserve_stake_balance)
             ),
             format!("Baseline stake amount: {}", Sol(self.baseline_stake_amount)),
-            format!("Bonus stake amount: {}", Sol(bonus_stake_amount)),
+            format!("Bonus stake amount: {}", bonus_stake_amount),
             format!(
                 "Validators by stake level: None={}, Baseline={}, Bonus={}",
                 min_stake_node_count, baseline_stake_node_count, bonus_stake_node_count
             ),
         ];
 
+        metrics::set_gauge(
+            "stake_pool_size_sol",
+            "Stake pool balance",
+            lamports_to_sol(total_stake_amount),
+        );
+        metrics::set_gauge(
+            "reserve_balance_sol",
+            "Reserve stake available for delegation",
+            lamports_to_sol(reserve_stake_balance),
+        );
+        metrics::set_gauge(
+            "baseline_stake_amount_sol",
+            "Stake delegated to each Baseline validator",
+            lamports_to_sol(self.baseline_stake_amount),
+        );
+        metrics::set_gauge(
+            "bonus_stake_amount_sol",
+            "Average stake delegated to each Bonus validator",
+            lamports_to_sol(
+                bonus_stake_amounts.values().sum::<u64>() / bonus_stake_amounts.len().max(1) as u64,
+            ),
+        );
+
         let busy_validators = validator_stake_actions
             .keys()
             .cloned()
             .collect::<HashSet<_>>();
         let mut unfunded_validators = HashSet::default();
+        let mut stake_plan = StakePlan {
+            stake_pool: self.reserve_stake_address,
+            epoch: rpc_client.get_epoch_info()?.epoch,
+            changes: vec![],
+        };
         distribute_validator_stake(
             rpc_client,
-            dry_run,
             &self.authorized_staker,
             desired_validator_stake
                 .iter()
                 .filter(|vs| !busy_validators.contains(&vs.identity))
                 .cloned(),
-            self.reserve_stake_address,
             reserve_stake_balance,
             self.baseline_stake_amount,
-            bonus_stake_amount,
+            &bonus_stake_amounts,
+            self.max_stake_change,
             &mut validator_stake_actions,
             &mut unfunded_validators,
+            &mut stake_plan,
         )?;
-        Ok((notes, validator_stake_actions, unfunded_validators))
+
+        let apply_result = (
+            notes,
+            validator_stake_actions,
+            unfunded_validators,
+            stake_plan,
+        );
+        if !dry_run {
+            let result = send_stake_plan(
+                rpc_client,
+                &self.authorized_staker,
+                self.reserve_stake_address,
+                &apply_result.3,
+            )?;
+            journal.distribution_sent(result.succeeded, &apply_result)?;
+        }
+        Ok(apply_result)
+    }
+
+    fn execute_plan(
+        &mut self,
+        rpc_client: &RpcClient,
+        dry_run: bool,
+        stake_plan: &StakePlan,
+    ) -> Result<(), Box<dyn error::Error>> {
+        settle_in_flight_transactions(rpc_client, dry_run, &self.authorized_staker)?;
+        let authorized_staker = self.authorized_staker.pubkey();
+        stake_plan.verify(
+            &self.reserve_stake_address,
+            rpc_client.get_epoch_info()?.epoch,
+            |change| {
+                get_validator_stake_balance(rpc_client, authorized_staker, change.vote_address)
+            },
+        )?;
+
+        if dry_run {
+            return Ok(());
+        }
+        send_stake_plan(
+            rpc_client,
+            &self.authorized_staker,
+            self.reserve_stake_address,
+            stake_plan,
+        )?;
+        Ok(())
     }
 }
 
@@ -278,7 +389,7 @@ fn get_available_stake_balance(
 
 fn merge_orphaned_stake_accounts(
     rpc_client: &RpcClient,
-    authorized_staker: &Keypair,
+    authorized_staker: &impl Staker,
     source_stake_addresses: HashSet<Pubkey>,
     reserve_stake_address: Pubkey,
 ) -> Result<(), Box<dyn error::Error>> {
@@ -335,7 +446,7 @@ fn merge_orphaned_stake_accounts(
 
 fn merge_transient_stake_accounts(
     rpc_client: &RpcClient,
-    authorized_staker: &Keypair,
+    authorized_staker: &impl Staker,
     desired_validator_stake: &[ValidatorStake],
     reserve_stake_address: Pubkey,
     validator_stake_actions: &mut ValidatorStakeActions,
@@ -470,7 +581,7 @@ fn stake_accounts_have_same_credits_observed(
 
 fn create_validator_stake_accounts(
     rpc_client: &RpcClient,
-    authorized_staker: &Keypair,
+    authorized_staker: &impl Staker,
     desired_validator_stake: &[ValidatorStake],
     reserve_stake_address: Pubkey,
     min_reserve_stake_balance: u64,
@@ -601,18 +712,43 @@ fn create_validator_stake_accounts(
     }
 }
 
+// Get the combined balance of the stake and transient stake accounts of a validator
+fn get_validator_stake_balance(
+    rpc_client: &RpcClient,
+    authorized_staker: Pubkey,
+    vote_address: Pubkey,
+) -> Result<u64, Box<dyn error::Error>> {
+    let stake_address = validator_stake_address(authorized_staker, vote_address);
+    let transient_stake_address =
+        validator_transient_stake_address(authorized_staker, vote_address);
+
+    Ok(rpc_client.get_balance(&stake_address).map_err(|err| {
+        format!(
+            "Unable to get stake account balance: {}: {}",
+            stake_address, err
+        )
+    })? + rpc_client
+        .get_balance(&transient_stake_address)
+        .map_err(|err| {
+            format!(
+                "Unable to get transient stake account balance: {}: {}",
+                transient_stake_address, err
+            )
+        })?)
+}
+
 #[allow(clippy::too_many_arguments)]
 fn distribute_validator_stake<V>(
     rpc_client: &RpcClient,
-    dry_run: bool,
-    authorized_staker: &Keypair,
+    authorized_staker: &impl Staker,
     desired_validator_stake: V,
-    reserve_stake_address: Pubkey,
     mut reserve_stake_balance: u64,
     baseline_stake_amount: u64,
-    bonus_stake_amount: u64,
+    bonus_stake_amounts: &HashMap<Pubkey, u64>,
+    max_stake_change: Option<MaxStakeChange>,
     validator_stake_actions: &mut ValidatorStakeActions,
     unfunded_validators: &mut HashSet<Pubkey>,
+    stake_plan: &mut StakePlan,
 ) -> Result<(), Box<dyn error::Error>>
 where
     V: IntoIterator<Item = ValidatorStake>,
@@ -626,26 +762,11 @@ where
     let mut bonus_stake = vec![];
 
     for validator_stake in desired_validator_stake {
-        let stake_address =
-            validator_stake_address(authorized_staker.pubkey(), validator_stake.vote_address);
-        let transient_stake_address = validator_transient_stake_address(
+        let balance = get_validator_stake_balance(
+            rpc_client,
             authorized_staker.pubkey(),
             validator_stake.vote_address,
-        );
-
-        let balance = rpc_client.get_balance(&stake_address).map_err(|err| {
-            format!(
-                "Unable to get stake account balance: {}: {}",
-                stake_address, err
-            )
-        })? + rpc_client
-            .get_balance(&transient_stake_address)
-            .map_err(|err| {
-                format!(
-                    "Unable to get transient stake account balance: {}: {}",
-                    transient_stake_address, err
-                )
-            })?;
+        )?;
 
         let list = if validator_stake.priority {
             &mut priority_stake
@@ -656,12 +777,7 @@ where
                 ValidatorStakeState::Bonus => &mut bonus_stake,
             }
         };
-        list.push((
-            balance,
-            stake_address,
-            transient_stake_address,
-            validator_stake,
-        ));
+        list.push((balance, validator_stake));
     }
 
     // Sort from lowest to highest balance
@@ -670,16 +786,15 @@ where
     baseline_stake.sort_by_key(|k| k.0);
     bonus_stake.sort_by_key(|k| k.0);
 
-    let mut transactions = vec![];
     for (
         balance,
-        stake_address,
-        transient_stake_address,
         ValidatorStake {
             identity,
             stake_state,
             vote_address,
             priority,
+            stake_amount,
+            ..
         },
     ) in priority_stake
         .into_iter()
@@ -687,43 +802,59 @@ where
         .chain(baseline_stake)
         .chain(bonus_stake)
     {
-        let desired_balance = match stake_state {
+        let final_balance = match stake_state {
             ValidatorStakeState::None => MIN_STAKE_ACCOUNT_BALANCE,
-            ValidatorStakeState::Baseline => baseline_stake_amount,
-            ValidatorStakeState::Bonus => bonus_stake_amount,
+            ValidatorStakeState::Baseline => stake_amount.unwrap_or(baseline_stake_amount),
+            ValidatorStakeState::Bonus => stake_amount.unwrap_or_else(|| {
+                bonus_stake_amounts
+                    .get(&identity)
+                    .cloned()
+                    .unwrap_or_default()
+            }),
         };
-        let transient_stake_address_seed = validator_transient_stake_address_seed(vote_address);
+        let desired_balance = max_stake_change
+            .map(|max_stake_change| {
+                max_stake_change.limit(
+                    balance,
+                    final_balance,
+                    baseline_stake_amount,
+                    MIN_STAKE_CHANGE_AMOUNT,
+                )
+            })
+            .unwrap_or(final_balance);
+        if desired_balance != final_balance {
+            info!(
+                "{}: stake change limited, moving towards {}",
+                identity,
+                Sol(final_balance)
+            );
+        }
 
         #[allow(clippy::comparison_chain)]
-        let op_msg = if balance > desired_balance {
+        let (operation, amount, reason) = if balance > desired_balance {
             let amount_to_remove = balance - desired_balance;
             if amount_to_remove < MIN_STAKE_CHANGE_AMOUNT {
-                format!("not removing {} (amount too small)", Sol(amount_to_remove))
+                (
+                    StakeOperation::None,
+                    0,
+                    format!("not removing {} (amount too small)", Sol(amount_to_remove)),
+                )
             } else {
-                let mut instructions = stake_instruction::split_with_seed(
-                    &stake_address,
-                    &authorized_staker.pubkey(),
+                (
+                    StakeOperation::Decrease,
                     amount_to_remove,
-                    &transient_stake_address,
-                    &authorized_staker.pubkey(),
-                    &transient_stake_address_seed,
-                );
-                instructions.push(stake_instruction::deactivate_stake(
-                    &transient_stake_address,
-                    &authorized_staker.pubkey(),
-                ));
-
-                transactions.push(Transaction::new_with_payer(
-                    &instructions,
-                    Some(&authorized_staker.pubkey()),
-                ));
-                format!("removing {}", Sol(amount_to_remove))
+                    format!("removing {}", Sol(amount_to_remove)),
+                )
             }
         } else if balance < desired_balance {
             let mut amount_to_add = desired_balance - balance;
 
             if amount_to_add < MIN_STAKE_CHANGE_AMOUNT {
-                format!("not adding {} (amount too small)", Sol(amount_to_add))
+                (
+                    StakeOperation::None,
+                    0,
+                    format!("not adding {} (amount too small)", Sol(amount_to_add)),
+                )
             } else {
                 if amount_to_add > reserve_stake_balance {
                     trace!(
@@ -739,64 +870,112 @@ where
                         warn!("Failed to fund a priority node");
                     }
                     unfunded_validators.insert(identity);
-                    "reserve depleted".to_string()
+                    (StakeOperation::None, 0, "reserve depleted".to_string())
                 } else {
                     reserve_stake_balance -= amount_to_add;
-
-                    let mut instructions = stake_instruction::split_with_seed(
-                        &reserve_stake_address,
-                        &authorized_staker.pubkey(),
+                    (
+                        StakeOperation::Increase,
                         amount_to_add,
-                        &transient_stake_address,
-                        &authorized_staker.pubkey(),
-                        &transient_stake_address_seed,
-                    );
-                    instructions.push(stake_instruction::delegate_stake(
-                        &transient_stake_address,
-                        &authorized_staker.pubkey(),
-                        &vote_address,
-                    ));
-
-                    transactions.push(Transaction::new_with_payer(
-                        &instructions,
-                        Some(&authorized_staker.pubkey()),
-                    ));
-                    format!("adding {}", Sol(amount_to_add))
+                        format!("adding {}", Sol(amount_to_add)),
+                    )
                 }
             }
         } else {
-            "no change".to_string()
+            (StakeOperation::None, 0, "no change".to_string())
         };
 
-        let action = format!(
-            "target stake amount: {}, current stake amount: {} - {}",
-            Sol(desired_balance),
-            Sol(balance),
-            op_msg,
-        );
+        let change = PlannedStakeChange {
+            identity,
+            vote_address,
+            stake_state,
+            priority,
+            current_balance: balance,
+            desired_balance,
+            final_balance: if desired_balance != final_balance {
+                Some(final_balance)
+            } else {
+                None
+            },
+            operation,
+            amount,
+            reason,
+        };
+        let action = change.action();
         info!(
             "{} ({:?},priority={}) | {}",
             identity, stake_state, priority, action
         );
         validator_stake_actions.insert(identity, action);
+        stake_plan.changes.push(change);
     }
     info!(
         "Reserve stake available balance after updates: {}",
         Sol(reserve_stake_balance)
     );
+    Ok(())
+}
 
-    let ok = if dry_run {
-        true
-    } else {
-        send_and_confirm_transactions(rpc_client, false, transactions, authorized_staker)?
-            .failed
-            .is_empty()
-    };
+/// Send the increase and decrease instructions of `stake_plan`
+fn send_stake_plan(
+    rpc_client: &RpcClient,
+    authorized_staker: &impl Staker,
+    reserve_stake_address: Pubkey,
+    stake_plan: &StakePlan,
+) -> Result<SendAndConfirmTransactionResult, Box<dyn error::Error>> {
+    let transactions = stake_plan
+        .operations()
+        .map(|change| {
+            let stake_address =
+                validator_stake_address(authorized_staker.pubkey(), change.vote_address);
+            let transient_stake_address =
+                validator_transient_stake_address(authorized_staker.pubkey(), change.vote_address);
+            let transient_stake_address_seed =
+                validator_transient_stake_address_seed(change.vote_address);
 
-    if !ok {
+            let instructions = match change.operation {
+                StakeOperation::Increase => {
+                    let mut instructions = stake_instruction::split_with_seed(
+                        &reserve_stake_address,
+                        &authorized_staker.pubkey(),
+                        change.amount,
+                        &transient_stake_address,
+                        &authorized_staker.pubkey(),
+                        &transient_stake_address_seed,
+                    );
+                    instructions.push(stake_instruction::delegate_stake(
+                        &transient_stake_address,
+                        &authorized_staker.pubkey(),
+                        &change.vote_address,
+                    ));
+                    instructions
+                }
+                StakeOperation::Decrease => {
+                    let mut instructions = stake_instruction::split_with_seed(
+                        &stake_address,
+                        &authorized_staker.pubkey(),
+                        change.amount,
+                        &transient_stake_address,
+                        &authorized_staker.pubkey(),
+                        &transient_stake_address_seed,
+                    );
+                    instructions.push(stake_instruction::deactivate_stake(
+                        &transient_stake_address,
+                        &authorized_staker.pubkey(),
+                    ));
+                    instructions
+                }
+                StakeOperation::None => unreachable!(),
+            };
+            Transaction::new_with_payer(&instructions, Some(&authorized_staker.pubkey()))
+        })
+        .collect::<Vec<_>>();
+
+    let result = send_and_confirm_transactions(rpc_client, false, transactions, authorized_staker)?;
+
+    if !result.failed.is_empty() {
         Err("One or more transactions failed to execute".into())
     } else {
-        Ok(())
+        Ok(result)
     }
 }
 
@@ -845,11 +1024,18 @@ mod test {
                 vote_address: vap.vote_address,
                 stake_state,
                 priority: false,
+                bonus_weight: 1.,
+                stake_amount: None,
             })
             .collect::<Vec<_>>();
 
         stake_pool
-            .apply(rpc_client, false, &desired_validator_stake)
+            .apply(
+                rpc_client,
+                false,
+                &desired_validator_stake,
+                &mut Journal::default(),
+            )
             .unwrap();
 
         assert_eq!(
@@ -858,7 +1044,12 @@ mod test {
         );
         let _epoch = wait_for_next_epoch(&rpc_client).unwrap();
         stake_pool
-            .apply(rpc_client, false, &desired_validator_stake)
+            .apply(
+                rpc_client,
+                false,
+                &desired_validator_stake,
+                &mut Journal::default(),
+            )
             .unwrap();
 
         assert_eq!(
@@ -960,6 +1151,8 @@ mod test {
             &rpc_client,
             authorized_staker,
             baseline_stake_amount,
+            BonusStakeLimits::default(),
+            None,
             reserve_stake_address,
             min_reserve_stake_balance,
         )
@@ -979,8 +1172,11 @@ mod test {
                         vote_address: vap.vote_address,
                         stake_state: ValidatorStakeState::None,
                         priority: false,
+                        bonus_weight: 1.,
+                        stake_amount: None,
                     })
                     .collect::<Vec<_>>(),
+                &mut Journal::default(),
             )
             .unwrap();
 
@@ -1042,27 +1238,43 @@ mod test {
                 vote_address: validators[0].vote_address,
                 stake_state: ValidatorStakeState::None,
                 priority: false,
+                bonus_weight: 1.,
+                stake_amount: None,
             },
             ValidatorStake {
                 identity: validators[1].identity,
                 vote_address: validators[1].vote_address,
                 stake_state: ValidatorStakeState::Baseline,
                 priority: false,
+                bonus_weight: 1.,
+                stake_amount: None,
             },
             ValidatorStake {
                 identity: validators[2].identity,
                 vote_address: validators[2].vote_address,
                 stake_state: ValidatorStakeState::Bonus,
                 priority: false,
+                bonus_weight: 1.,
+                stake_amount: None,
             },
         ];
 
         stake_pool
-            .apply(&rpc_client, false, &desired_validator_stake)
+            .apply(
+                &rpc_client,
+                false,
+                &desired_validator_stake,
+                &mut Journal::default(),
+            )
             .unwrap();
         let _epoch = wait_for_next_epoch(&rpc_client).unwrap();
         stake_pool
-            .apply(&rpc_client, false, &desired_validator_stake)
+            .apply(
+                &rpc_client,
+                false,
+                &desired_validator_stake,
+                &mut Journal::default(),
+            )
             .unwrap();
 
         // after the first epoch, validators 0 and 1 are at their target levels but validator 2
@@ -1090,7 +1302,12 @@ mod test {
 
         let _epoch = wait_for_next_epoch(&rpc_client).unwrap();
         stake_pool
-            .apply(&rpc_client, false, &desired_validator_stake)
+            .apply(
+                &rpc_client,
+                false,
+                &desired_validator_stake,
+                &mut Journal::default(),
+            )
             .unwrap();
 
         assert_eq!(
@@ -1118,10 +1335,14 @@ mod test {
         info!("remove all validators");
 
         // deactivate all validator stake
-        stake_pool.apply(&rpc_client, false, &[]).unwrap();
+        stake_pool
+            .apply(&rpc_client, false, &[], &mut Journal::default())
+            .unwrap();
         let _epoch = wait_for_next_epoch(&rpc_client).unwrap();
         // merge deactivated validator stake back into the reserve
-        stake_pool.apply(&rpc_client, false, &[]).unwrap();
+        stake_pool
+            .apply(&rpc_client, false, &[], &mut Journal::default())
+            .unwrap();
         // all stake has returned to the reserve account
         assert_reserve_account_only();
     }
@@ -0,0 +1,166 @@
+use {
+    crate::{db::ValidatorClassification, generic_stake_pool::ValidatorStakeState},
+    serde::{Deserialize, Serialize},
+    std::{fs::File, io, path::Path},
+};
+
+/// A graduated stake level within the Baseline or Bonus stake state, such as a "probation" level
+/// for validators that only recently earned the Baseline.
+///
+/// A validator is placed in the first listed tier of its stake state whose entry criteria it
+/// meets.  Validators that meet no tier receive the stake of their plain stake state
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct StakeTier {
+    pub name: String,
+
+    /// The stake state refined by the tier, `Baseline` or `Bonus`
+    pub stake_state: ValidatorStakeState,
+
+    /// Fixed amount of SOL delegated to validators in the tier, in place of the baseline stake
+    /// amount or their share of the bonus stake
+    pub stake_amount: Option<f64>,
+
+    /// Multiplier of the bonus weight of Bonus validators in the tier
+    pub bonus_share: Option<f64>,
+
+    /// Entry criteria: the validator has held its stake state for at least, or at most, this
+    /// many consecutive epochs
+    pub min_streak: Option<usize>,
+    pub max_streak: Option<usize>,
+
+    /// Entry criteria: the validator was staked for at least `n` of the last `m` epochs
+    pub staked_for: Option<(/*n:*/ usize, /*m:*/ usize)>,
+}
+
+impl StakeTier {
+    fn admits(&self, validator: &ValidatorClassification) -> bool {
+        let streak = validator.stake_state_streak();
+        self.stake_state == validator.stake_state
+            && self.min_streak.map(|min| streak >= min).unwrap_or(true)
+            && self.max_streak.map(|max| streak <= max).unwrap_or(true)
+            && self
+                .staked_for
+                .map(|(n, m)| validator.staked_for(n, m))
+                .unwrap_or(true)
+    }
+}
+
+/// Load a list of stake tiers from a YAML file
+pub fn load<P>(file_name: P) -> Result<Vec<StakeTier>, io::Error>
+where
+    P: AsRef<Path>,
+{
+    let invalid = |err: String| {
+        io::Error::new(
+            io::ErrorKind::Other,
+            format!("{}: {}", file_name.as_ref().display(), err),
+        )
+    };
+
+    let file = File::open(&file_name).map_err(|err| {
+        io::Error::new(
+            err.kind(),
+            format!("{}: {}", file_name.as_ref().display(), err),
+        )
+    })?;
+    let stake_tiers: Vec<StakeTier> =
+        serde_yaml::from_reader(file).map_err(|err| invalid(err.to_string()))?;
+
+    for stake_tier in &stake_tiers {
+        if stake_tier.stake_state == ValidatorStakeState::None {
+            return Err(invalid(format!(
+                "stake tier {} cannot refine the None stake state",
+                stake_tier.name
+            )));
+        }
+        if stake_tier.stake_amount.is_some() && stake_tier.bonus_share.is_some() {
+            return Err(invalid(format!(
+                "stake tier {} sets both a stake amount and a bonus share",
+                stake_tier.name
+            )));
+        }
+    }
+    Ok(stake_tiers)
+}
+
+/// The tier of `validator`, if any
+pub fn assign<'a>(
+    stake_tiers: &'a [StakeTier],
+    validator: &ValidatorClassification,
+) -> Option<&'a StakeTier> {
+    stake_tiers
+        .iter()
+        .find(|stake_tier| stake_tier.admits(validator))
+}
+
+/// The tier named by a classification
+pub fn find<'a>(stake_tiers: &'a [StakeTier], name: Option<&str>) -> Option<&'a StakeTier> {
+    name.and_then(|name| {
+        stake_tiers
+            .iter()
+            .find(|stake_tier| stake_tier.name == name)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, solana_sdk::pubkey::Pubkey, std::io::Write};
+
+    #[test]
+    fn test_stake_tiers() {
+        let path = std::env::temp_dir().join(format!("stake-tiers-{}.yml", Pubkey::new_unique()));
+        File::create(&path)
+            .unwrap()
+            .write_all(
+                b"- name: probation\n  stake-state: Baseline\n  stake-amount: 1000\n  max-streak: 3\n\
+                  - name: veteran\n  stake-state: Bonus\n  bonus-share: 2\n  staked-for: [10, 12]\n",
+            )
+            .unwrap();
+        let stake_tiers = load(&path).unwrap();
+        assert_eq!(stake_tiers.len(), 2);
+        assert_eq!(stake_tiers[0].stake_amount, Some(1000.));
+
+        let validator = |stake_states: &[ValidatorStakeState]| ValidatorClassification {
+            stake_state: stake_states[0],
+            stake_states: Some(
+                stake_states
+                    .iter()
+                    .map(|stake_state| (*stake_state, String::new()))
+                    .collect(),
+            ),
+            ..ValidatorClassification::default()
+        };
+        let tier_name = |stake_states: &[ValidatorStakeState]| {
+            assign(&stake_tiers, &validator(stake_states)).map(|tier| tier.name.as_str())
+        };
+
+        use ValidatorStakeState::{Baseline, Bonus};
+        assert_eq!(
+            tier_name(&[Baseline, ValidatorStakeState::None]),
+            Some("probation")
+        );
+        assert_eq!(tier_name(&[Baseline; 4]), None);
+        assert_eq!(tier_name(&[Bonus; 9]), None);
+        assert_eq!(tier_name(&[Bonus; 12]), Some("veteran"));
+        assert_eq!(tier_name(&[ValidatorStakeState::None; 12]), None);
+
+        assert_eq!(
+            find(&stake_tiers, Some("veteran")).map(|tier| tier.bonus_share),
+            Some(Some(2.))
+        );
+        assert!(find(&stake_tiers, Some("unknown")).is_none());
+
+        File::create(&path)
+            .unwrap()
+            .write_all(b"- name: zero\n  stake-state: None\n")
+            .unwrap();
+        assert!(load(&path).is_err());
+        File::create(&path)
+            .unwrap()
+            .write_all(b"- name: both\n  stake-state: Bonus\n  stake-amount: 1\n  bonus-share: 2\n")
+            .unwrap();
+        assert!(load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
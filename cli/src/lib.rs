@@ -4,16 +4,48 @@ use {
         rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
         rpc_filter::*,
     },
-    solana_foundation_delegation_program_registry::state::{Participant, ParticipantState},
-    solana_sdk::{program_pack::Pack, pubkey::Pubkey},
+    solana_foundation_delegation_program_registry::state::{
+        config_address, config_decision_address, decision_address, participant_address,
+        testnet_index_address, Config, Participant, ParticipantState, ParticipantV2,
+        PendingConfigDecision, PendingDecision, TestnetIndex,
+    },
+    solana_sdk::{account::Account, program_pack::Pack, pubkey::Pubkey},
     std::collections::HashMap,
 };
 
-pub fn get_participants_with_state(
+/// Program accounts of `data_size` bytes
+fn get_program_accounts_with_size(
     rpc_client: &RpcClient,
-    state: Option<ParticipantState>,
-) -> Result<HashMap<Pubkey, Participant>, Box<dyn std::error::Error>> {
-    let accounts = rpc_client.get_program_accounts_with_config(
+    data_size: usize,
+) -> Result<Vec<(Pubkey, Account)>, Box<dyn std::error::Error>> {
+    get_program_accounts_with_filters(rpc_client, vec![RpcFilterType::DataSize(data_size as u64)])
+}
+
+/// Program accounts of `data_size` bytes holding `pubkey` at `offset`
+fn get_program_accounts_with_pubkey(
+    rpc_client: &RpcClient,
+    data_size: usize,
+    offset: usize,
+    pubkey: &Pubkey,
+) -> Result<Vec<(Pubkey, Account)>, Box<dyn std::error::Error>> {
+    get_program_accounts_with_filters(
+        rpc_client,
+        vec![
+            RpcFilterType::DataSize(data_size as u64),
+            RpcFilterType::Memcmp(Memcmp {
+                offset,
+                bytes: MemcmpEncodedBytes::Binary(pubkey.to_string()),
+                encoding: Some(MemcmpEncoding::Binary),
+            }),
+        ],
+    )
+}
+
+fn get_program_accounts_with_filters(
+    rpc_client: &RpcClient,
+    filters: Vec<RpcFilterType>,
+) -> Result<Vec<(Pubkey, Account)>, Box<dyn std::error::Error>> {
+    Ok(rpc_client.get_program_accounts_with_config(
         &solana_foundation_delegation_program_registry::id(),
         RpcProgramAccountsConfig {
             account_config: RpcAccountInfoConfig {
@@ -21,17 +53,87 @@ pub fn get_participants_with_state(
                 commitment: Some(rpc_client.commitment()), // TODO: Remove this line after updating to solana v1.6.10
                 ..RpcAccountInfoConfig::default()
             },
-            filters: Some(vec![RpcFilterType::DataSize(
-                Participant::get_packed_len() as u64
-            )]),
+            filters: Some(filters),
             ..RpcProgramAccountsConfig::default()
         },
-    )?;
+    )?)
+}
+
+/// The registry program account at `address`, if any
+fn get_program_account(
+    rpc_client: &RpcClient,
+    address: &Pubkey,
+) -> Result<Option<Account>, Box<dyn std::error::Error>> {
+    Ok(rpc_client
+        .get_account_with_commitment(address, rpc_client.commitment())?
+        .value
+        .filter(|account| account.owner == solana_foundation_delegation_program_registry::id()))
+}
+
+/// The participant registering `identity`, and its address.  Participants at their program
+/// address are found by derivation from the identity, participants not yet migrated there by
+/// filtering program accounts on the identity
+pub fn get_participant_by_identity(
+    rpc_client: &RpcClient,
+    identity: Pubkey,
+) -> Result<Option<(Pubkey, ParticipantV2)>, Box<dyn std::error::Error>> {
+    let address = participant_address(&identity);
+    if let Some(account) = get_program_account(rpc_client, &address)? {
+        let participant = ParticipantV2::unpack_from_slice(&account.data)?;
+        if participant.mainnet_identity == identity {
+            return Ok(Some((address, participant)));
+        }
+    }
+
+    if let Some(account) = get_program_account(rpc_client, &testnet_index_address(&identity))? {
+        let address = TestnetIndex::unpack_from_slice(&account.data)?.participant;
+        if let Some(account) = get_program_account(rpc_client, &address)? {
+            let participant = ParticipantV2::unpack_from_slice(&account.data)?;
+            if participant.testnet_identity == identity {
+                return Ok(Some((address, participant)));
+            }
+        }
+    }
+
+    // Identities follow the version byte in the current layout
+    let mut participants = HashMap::new();
+    for (data_size, offset) in &[
+        (Participant::get_packed_len(), 0),
+        (ParticipantV2::get_packed_len(), 1),
+    ] {
+        for offset in &[*offset, offset + 32] {
+            for (address, account) in
+                get_program_accounts_with_pubkey(rpc_client, *data_size, *offset, &identity)?
+            {
+                if let Ok(participant) = ParticipantV2::unpack_any(&account.data) {
+                    participants.insert(address, participant);
+                }
+            }
+        }
+    }
+
+    if participants.len() > 1 {
+        Err(format!("{} matches multiple participants", identity).into())
+    } else {
+        Ok(participants.into_iter().next())
+    }
+}
+
+/// Participants in either account layout.  Participants in the original layout have no metadata
+pub fn get_participants_with_state(
+    rpc_client: &RpcClient,
+    state: Option<ParticipantState>,
+) -> Result<HashMap<Pubkey, ParticipantV2>, Box<dyn std::error::Error>> {
+    let mut accounts = get_program_accounts_with_size(rpc_client, Participant::get_packed_len())?;
+    accounts.extend(get_program_accounts_with_size(
+        rpc_client,
+        ParticipantV2::get_packed_len(),
+    )?);
 
     Ok(accounts
         .into_iter()
         .filter_map(|(address, account)| {
-            Participant::unpack_from_slice(&account.data)
+            ParticipantV2::unpack_any(&account.data)
                 .ok()
                 .map(|p| (address, p))
         })
@@ -46,6 +148,63 @@ pub fn get_participants_with_state(
 
 pub fn get_participants(
     rpc_client: &RpcClient,
-) -> Result<HashMap<Pubkey, Participant>, Box<dyn std::error::Error>> {
+) -> Result<HashMap<Pubkey, ParticipantV2>, Box<dyn std::error::Error>> {
     get_participants_with_state(rpc_client, None)
 }
+
+/// The registry configuration, or `None` if it has not been initialized
+pub fn get_config(rpc_client: &RpcClient) -> Result<Option<Config>, Box<dyn std::error::Error>> {
+    let account = rpc_client
+        .get_account_with_commitment(&config_address(), rpc_client.commitment())?
+        .value;
+
+    match account {
+        Some(account) if account.owner == solana_foundation_delegation_program_registry::id() => {
+            Ok(Some(Config::unpack_from_slice(&account.data)?))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// The decision pending on the participant at `participant_address`, if any.  Cleared decision
+/// accounts are not pending
+pub fn get_pending_decision(
+    rpc_client: &RpcClient,
+    participant_address: &Pubkey,
+) -> Result<Option<PendingDecision>, Box<dyn std::error::Error>> {
+    match get_program_account(rpc_client, &decision_address(participant_address))? {
+        Some(account) if account.data.iter().any(|byte| *byte != 0) => {
+            Ok(Some(PendingDecision::unpack_from_slice(&account.data)?))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// The config change pending a vote of the admins, if any
+pub fn get_pending_config_decision(
+    rpc_client: &RpcClient,
+) -> Result<Option<PendingConfigDecision>, Box<dyn std::error::Error>> {
+    match get_program_account(rpc_client, &config_decision_address())? {
+        Some(account) if account.data.iter().any(|byte| *byte != 0) => Ok(Some(
+            PendingConfigDecision::unpack_from_slice(&account.data)?,
+        )),
+        _ => Ok(None),
+    }
+}
+
+/// Participant decisions that are still short of the admin vote threshold, by account address
+pub fn get_pending_decisions(
+    rpc_client: &RpcClient,
+) -> Result<HashMap<Pubkey, PendingDecision>, Box<dyn std::error::Error>> {
+    let accounts = get_program_accounts_with_size(rpc_client, PendingDecision::get_packed_len())?;
+
+    Ok(accounts
+        .into_iter()
+        .filter(|(_, account)| account.data.iter().any(|byte| *byte != 0))
+        .filter_map(|(address, account)| {
+            PendingDecision::unpack_from_slice(&account.data)
+                .ok()
+                .map(|d| (address, d))
+        })
+        .collect())
+}
@@ -4,33 +4,29 @@ use {
         SubCommand,
     },
     solana_clap_utils::{
-        input_parsers::{pubkey_of, signer_of},
+        input_parsers::{pubkey_of, pubkeys_of, signer_of},
         input_validators::{
-            is_url_or_moniker, is_valid_pubkey, is_valid_signer, normalize_to_url_if_moniker,
+            is_parsable, is_url_or_moniker, is_valid_pubkey, is_valid_signer,
+            normalize_to_url_if_moniker,
         },
         keypair::DefaultSigner,
     },
     solana_client::rpc_client::RpcClient,
-    solana_foundation_delegation_program_cli::{get_participants, get_participants_with_state},
-    solana_foundation_delegation_program_registry::state::{Participant, ParticipantState},
+    solana_foundation_delegation_program_cli::{
+        get_config, get_participant_by_identity, get_participants_with_state,
+        get_pending_config_decision, get_pending_decision, get_pending_decisions,
+    },
+    solana_foundation_delegation_program_registry::state::{
+        admin_set, contact_field, ConfigDecision, Participant, ParticipantDecision,
+        ParticipantState, ParticipantV2, TestnetIndex, PARTICIPANT_VERSION,
+    },
     solana_remote_wallet::remote_wallet::RemoteWalletManager,
     solana_sdk::{
-        commitment_config::CommitmentConfig,
-        message::Message,
-        native_token::Sol,
-        program_pack::Pack,
-        pubkey::Pubkey,
-        signature::{Keypair, Signer},
-        signers::Signers,
-        system_instruction,
+        commitment_config::CommitmentConfig, instruction::Instruction, message::Message,
+        native_token::Sol, program_pack::Pack, pubkey::Pubkey, signature::Signer, signers::Signers,
         transaction::Transaction,
     },
-    std::{
-        collections::{HashMap, HashSet},
-        ops::Deref,
-        process::exit,
-        sync::Arc,
-    },
+    std::{ops::Deref, process::exit, sync::Arc},
 };
 
 struct Config {
@@ -76,35 +72,32 @@ fn send_and_confirm_message<T: Signers>(
     Ok(())
 }
 
-fn get_participants_with_identity(
+/// Fail if any of `identities` is registered
+fn check_unregistered(
     rpc_client: &RpcClient,
-    identities: HashSet<&Pubkey>,
-) -> Result<HashMap<Pubkey, Participant>, Box<dyn std::error::Error>> {
-    let mut participants = get_participants(rpc_client)?;
-    participants.retain(|_, p| {
-        identities.contains(&p.testnet_identity) || identities.contains(&p.mainnet_identity)
-    });
-    Ok(participants)
+    identities: &[Pubkey],
+) -> Result<(), Box<dyn std::error::Error>> {
+    for identity in identities {
+        if get_participant_by_identity(rpc_client, *identity)?.is_some() {
+            return Err(format!("A registration already exists with {}", identity).into());
+        }
+    }
+    Ok(())
 }
 
-fn get_participant_by_identity(
-    rpc_client: &RpcClient,
-    identity: Pubkey,
-) -> Result<Option<(Pubkey, Participant)>, Box<dyn std::error::Error>> {
-    let participant = get_participants(rpc_client)?
-        .into_iter()
-        .filter(|(_, p)| p.testnet_identity == identity || p.mainnet_identity == identity)
-        .collect::<Vec<_>>();
-
-    if participant.len() > 1 {
-        Err(format!("{} matches multiple participants", identity).into())
-    } else {
-        Ok(participant.into_iter().next())
-    }
+/// Lamports of the accounts created for a registration
+fn registration_rent(rpc_client: &RpcClient) -> Result<u64, Box<dyn std::error::Error>> {
+    Ok(
+        rpc_client.get_minimum_balance_for_rent_exemption(ParticipantV2::get_packed_len())?
+            + rpc_client.get_minimum_balance_for_rent_exemption(TestnetIndex::get_packed_len())?,
+    )
 }
 
-fn print_participant(participant: &Participant) {
+fn print_participant(participant: &ParticipantV2) {
     println!("State: {:?}", participant.state);
+    if participant.state == ParticipantState::Rejected && participant.rejection_reason != 0 {
+        println!("Rejection Reason: {}", participant.rejection_reason);
+    }
     println!(
         "Mainnet Validator Identity: {}",
         participant.mainnet_identity
@@ -113,6 +106,89 @@ fn print_participant(participant: &Participant) {
         "Testnet Validator Identity: {}",
         participant.testnet_identity
     );
+    if participant.applied_at != 0 {
+        println!("Applied At: {} (unix time)", participant.applied_at);
+    }
+    if participant.decided_at != 0 {
+        println!("Decided At: {} (unix time)", participant.decided_at);
+    }
+    if let Some(contact) = participant.contact() {
+        println!("Contact: {}", contact);
+    }
+    if participant.version < PARTICIPANT_VERSION {
+        println!(
+            "Account Version: {} (run `migrate` to upgrade)",
+            participant.version
+        );
+    }
+}
+
+/// The instruction carrying out `decision` on a participant: the decision itself, or a vote for it
+/// when the registry requires more than one admin to agree
+fn participant_decision_instruction(
+    config: &Config,
+    rpc_client: &RpcClient,
+    admin: Pubkey,
+    participant_address: Pubkey,
+    decision: ParticipantDecision,
+) -> Result<Instruction, Box<dyn std::error::Error>> {
+    let registry_config = get_config(rpc_client)?.ok_or("Registry config is not initialized")?;
+
+    if registry_config.required_votes() > 1 {
+        println!(
+            "Voting, {} admins must agree on the decision",
+            registry_config.required_votes()
+        );
+        let creator = match get_pending_decision(rpc_client, &participant_address)? {
+            Some(pending_decision) if pending_decision.decision != decision => {
+                return Err(format!(
+                    "A different decision is pending on {}: {:?}",
+                    participant_address, pending_decision.decision
+                )
+                .into());
+            }
+            Some(pending_decision) => pending_decision.creator,
+            None => admin,
+        };
+        return Ok(
+            solana_foundation_delegation_program_registry::instruction::vote(
+                participant_address,
+                admin,
+                config.default_signer.pubkey(),
+                decision,
+                creator,
+            ),
+        );
+    }
+
+    Ok(match decision {
+        ParticipantDecision::Approve => {
+            solana_foundation_delegation_program_registry::instruction::approve(
+                participant_address,
+                admin,
+            )
+        }
+        ParticipantDecision::Reject => {
+            solana_foundation_delegation_program_registry::instruction::reject(
+                participant_address,
+                admin,
+            )
+        }
+        ParticipantDecision::Rewrite(participant) => {
+            solana_foundation_delegation_program_registry::instruction::rewrite(
+                participant_address,
+                admin,
+                participant,
+            )
+        }
+        ParticipantDecision::RejectWithReason(rejection_reason) => {
+            solana_foundation_delegation_program_registry::instruction::reject_with_reason(
+                participant_address,
+                admin,
+                rejection_reason,
+            )
+        }
+    })
 }
 
 fn process_status(
@@ -139,56 +215,54 @@ fn process_apply(
     rpc_client: &RpcClient,
     mainnet_identity: Box<dyn Signer>,
     testnet_identity: Box<dyn Signer>,
+    contact: Option<String>,
     confirm: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let participants = get_participants_with_identity(
+    let contact = contact.map(|contact| contact_field(&contact)).transpose()?;
+
+    check_unregistered(
         rpc_client,
-        [mainnet_identity.pubkey(), testnet_identity.pubkey()]
-            .iter()
-            .collect::<HashSet<_>>(),
+        &[mainnet_identity.pubkey(), testnet_identity.pubkey()],
     )?;
 
-    if !participants.is_empty() {
-        return Err("Registration already exists".into());
-    }
-
     println!("Mainnet Validator Identity: {}", mainnet_identity.pubkey());
     println!("Testnet Validator Identity: {}", testnet_identity.pubkey());
 
     if !confirm {
         println!(
-            "\nWarning: Your mainnet and testnet identities cannot be changed after applying. \
+            "\nWarning: After applying, an identity can only be changed with `update-identity`, \
+                    which requires both the current and the new identity keypair. \
                     Add the --confirm flag to continue"
         );
         return Ok(());
     }
 
-    let rent = rpc_client.get_minimum_balance_for_rent_exemption(Participant::get_packed_len())?;
-    let participant: Box<dyn Signer> = Box::new(Keypair::new());
+    let rent = registration_rent(rpc_client)?;
 
-    let message = Message::new(
-        &[
-            system_instruction::create_account(
-                &config.default_signer.pubkey(),
-                &participant.pubkey(),
-                rent,
-                Participant::get_packed_len() as u64,
-                &solana_foundation_delegation_program_registry::id(),
-            ),
-            solana_foundation_delegation_program_registry::instruction::apply(
-                participant.pubkey(),
+    let mut instructions = vec![
+        solana_foundation_delegation_program_registry::instruction::apply(
+            mainnet_identity.pubkey(),
+            testnet_identity.pubkey(),
+            config.default_signer.pubkey(),
+        ),
+    ];
+    if let Some(contact) = contact {
+        instructions.push(
+            solana_foundation_delegation_program_registry::instruction::set_contact(
+                solana_foundation_delegation_program_registry::state::participant_address(
+                    &mainnet_identity.pubkey(),
+                ),
                 mainnet_identity.pubkey(),
-                testnet_identity.pubkey(),
+                contact,
             ),
-        ],
-        Some(&config.default_signer.pubkey()),
-    );
+        );
+    }
+    let message = Message::new(&instructions, Some(&config.default_signer.pubkey()));
 
     send_and_confirm_message(
         rpc_client,
         message,
         [
-            participant.deref(),
             mainnet_identity.deref(),
             testnet_identity.deref(),
             config.default_signer.deref(),
@@ -223,6 +297,9 @@ fn process_withdraw(
                 participant_address,
                 identity.pubkey(),
                 config.default_signer.pubkey(),
+                participant.testnet_identity,
+                get_pending_decision(rpc_client, &participant_address)?
+                    .map(|pending_decision| pending_decision.creator),
             ),
         ],
         Some(&config.default_signer.pubkey()),
@@ -236,6 +313,145 @@ fn process_withdraw(
     )
 }
 
+fn process_set_contact(
+    config: &Config,
+    rpc_client: &RpcClient,
+    identity: Box<dyn Signer>,
+    contact: String,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (participant_address, participant) =
+        get_participant_by_identity(rpc_client, identity.pubkey())?
+            .ok_or_else(|| format!("Registration not found for {}", identity.pubkey()))?;
+
+    if participant.version < PARTICIPANT_VERSION {
+        return Err("Registration must be migrated before publishing a contact".into());
+    }
+
+    let message = Message::new(
+        &[
+            solana_foundation_delegation_program_registry::instruction::set_contact(
+                participant_address,
+                identity.pubkey(),
+                contact_field(&contact)?,
+            ),
+        ],
+        Some(&config.default_signer.pubkey()),
+    );
+
+    send_and_confirm_message(
+        rpc_client,
+        message,
+        [identity.deref(), config.default_signer.deref()],
+        None,
+    )
+}
+
+fn process_migrate(
+    config: &Config,
+    rpc_client: &RpcClient,
+    identity: Box<dyn Signer>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (participant_address, participant) =
+        get_participant_by_identity(rpc_client, identity.pubkey())?
+            .ok_or_else(|| format!("Registration not found for {}", identity.pubkey()))?;
+
+    if participant_address
+        == solana_foundation_delegation_program_registry::state::participant_address(
+            &participant.mainnet_identity,
+        )
+    {
+        return Err("Registration is already migrated".into());
+    }
+
+    let rent = registration_rent(rpc_client)?;
+
+    let message = Message::new(
+        &[
+            solana_foundation_delegation_program_registry::instruction::migrate(
+                participant_address,
+                participant.mainnet_identity,
+                participant.testnet_identity,
+                identity.pubkey(),
+                config.default_signer.pubkey(),
+            ),
+        ],
+        Some(&config.default_signer.pubkey()),
+    );
+
+    send_and_confirm_message(
+        rpc_client,
+        message,
+        [identity.deref(), config.default_signer.deref()],
+        Some(rent),
+    )
+}
+
+fn process_update_identity(
+    config: &Config,
+    rpc_client: &RpcClient,
+    identity: Box<dyn Signer>,
+    new_identity: Box<dyn Signer>,
+    confirm: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (participant_address, participant) =
+        get_participant_by_identity(rpc_client, identity.pubkey())?
+            .ok_or_else(|| format!("Registration not found for {}", identity.pubkey()))?;
+
+    check_unregistered(rpc_client, &[new_identity.pubkey()])?;
+    if participant_address
+        != solana_foundation_delegation_program_registry::state::participant_address(
+            &participant.mainnet_identity,
+        )
+    {
+        return Err(format!(
+            "Registration must be migrated first, run `{} migrate`",
+            crate_name!()
+        )
+        .into());
+    }
+
+    print_participant(&participant);
+    println!(
+        "\nReplacing {} {} with {}",
+        if identity.pubkey() == participant.mainnet_identity {
+            "Mainnet Validator Identity"
+        } else {
+            "Testnet Validator Identity"
+        },
+        identity.pubkey(),
+        new_identity.pubkey()
+    );
+
+    if !confirm {
+        println!("\nAdd the --confirm flag to continue");
+        return Ok(());
+    }
+
+    let message = Message::new(
+        &[
+            solana_foundation_delegation_program_registry::instruction::update_identity(
+                participant.mainnet_identity,
+                participant.testnet_identity,
+                identity.pubkey(),
+                new_identity.pubkey(),
+                config.default_signer.pubkey(),
+            ),
+        ],
+        Some(&config.default_signer.pubkey()),
+    );
+
+    send_and_confirm_message(
+        rpc_client,
+        message,
+        [
+            identity.deref(),
+            new_identity.deref(),
+            config.default_signer.deref(),
+        ],
+        None,
+    )
+}
+
 fn process_list(
     config: &Config,
     rpc_client: &RpcClient,
@@ -268,12 +484,13 @@ fn process_admin_approve(
     println!("Approving...");
 
     let message = Message::new(
-        &[
-            solana_foundation_delegation_program_registry::instruction::approve(
-                participant_address,
-                admin_signer.pubkey(),
-            ),
-        ],
+        &[participant_decision_instruction(
+            config,
+            rpc_client,
+            admin_signer.pubkey(),
+            participant_address,
+            ParticipantDecision::Approve,
+        )?],
         Some(&config.default_signer.pubkey()),
     );
 
@@ -290,6 +507,7 @@ fn process_admin_reject(
     rpc_client: &RpcClient,
     admin_signer: Box<dyn Signer>,
     identity: Pubkey,
+    rejection_reason: Option<u8>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let (participant_address, participant) = get_participant_by_identity(rpc_client, identity)?
         .ok_or_else(|| format!("Registration not found for {}", identity))?;
@@ -298,12 +516,16 @@ fn process_admin_reject(
     println!("Rejecting...");
 
     let message = Message::new(
-        &[
-            solana_foundation_delegation_program_registry::instruction::reject(
-                participant_address,
-                admin_signer.pubkey(),
-            ),
-        ],
+        &[participant_decision_instruction(
+            config,
+            rpc_client,
+            admin_signer.pubkey(),
+            participant_address,
+            match rejection_reason {
+                Some(rejection_reason) => ParticipantDecision::RejectWithReason(rejection_reason),
+                None => ParticipantDecision::Reject,
+            },
+        )?],
         Some(&config.default_signer.pubkey()),
     );
 
@@ -322,38 +544,50 @@ fn process_admin_import(
     mainnet_identity: Pubkey,
     testnet_identity: Pubkey,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let participants = get_participants_with_identity(
+    let participant_address =
+        solana_foundation_delegation_program_registry::state::participant_address(
+            &mainnet_identity,
+        );
+    let approve = participant_decision_instruction(
+        config,
         rpc_client,
-        [mainnet_identity, testnet_identity]
-            .iter()
-            .collect::<HashSet<_>>(),
+        admin_signer.pubkey(),
+        participant_address,
+        ParticipantDecision::Approve,
     )?;
 
-    if !participants.is_empty() {
-        return Err("A registration already exists with the provided identity".into());
+    // With an admin vote threshold, another admin may already have imported the registration
+    for identity in &[mainnet_identity, testnet_identity] {
+        if let Some((address, participant)) = get_participant_by_identity(rpc_client, *identity)? {
+            if address == participant_address
+                && participant.mainnet_identity == mainnet_identity
+                && participant.testnet_identity == testnet_identity
+                && participant.state == ParticipantState::Pending
+            {
+                println!("Approving the pending import into {}", participant_address);
+                let message = Message::new(&[approve], Some(&config.default_signer.pubkey()));
+                return send_and_confirm_message(
+                    rpc_client,
+                    message,
+                    [admin_signer.deref(), config.default_signer.deref()],
+                    None,
+                );
+            }
+            return Err("A registration already exists with the provided identity".into());
+        }
     }
 
-    let rent = rpc_client.get_minimum_balance_for_rent_exemption(Participant::get_packed_len())?;
-    let participant: Box<dyn Signer> = Box::new(Keypair::new());
+    let rent = registration_rent(rpc_client)?;
 
     let message = Message::new(
         &[
-            system_instruction::create_account(
-                &config.default_signer.pubkey(),
-                &participant.pubkey(),
-                rent,
-                Participant::get_packed_len() as u64,
-                &solana_foundation_delegation_program_registry::id(),
-            ),
-            solana_foundation_delegation_program_registry::instruction::rewrite(
-                participant.pubkey(),
+            solana_foundation_delegation_program_registry::instruction::import(
+                mainnet_identity,
+                testnet_identity,
+                config.default_signer.pubkey(),
                 admin_signer.pubkey(),
-                Participant {
-                    state: ParticipantState::Approved,
-                    testnet_identity,
-                    mainnet_identity,
-                },
             ),
+            approve,
         ],
         Some(&config.default_signer.pubkey()),
     );
@@ -361,11 +595,7 @@ fn process_admin_import(
     send_and_confirm_message(
         rpc_client,
         message,
-        [
-            participant.deref(),
-            admin_signer.deref(),
-            config.default_signer.deref(),
-        ],
+        [admin_signer.deref(), config.default_signer.deref()],
         Some(rent),
     )
 }
@@ -376,13 +606,296 @@ fn process_admin_rewrite(
     admin_signer: Box<dyn Signer>,
     participant_address: Pubkey,
     participant: Participant,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let message = Message::new(
+        &[participant_decision_instruction(
+            config,
+            rpc_client,
+            admin_signer.pubkey(),
+            participant_address,
+            ParticipantDecision::Rewrite(participant),
+        )?],
+        Some(&config.default_signer.pubkey()),
+    );
+
+    send_and_confirm_message(
+        rpc_client,
+        message,
+        [admin_signer.deref(), config.default_signer.deref()],
+        None,
+    )
+}
+
+fn process_admins(rpc_client: &RpcClient) -> Result<(), Box<dyn std::error::Error>> {
+    let config = get_config(rpc_client)?.ok_or("Registry config is not initialized")?;
+
+    for admin in config.admins() {
+        println!("Admin: {}", admin);
+    }
+    println!(
+        "Threshold: {} of {} admins",
+        config.required_votes(),
+        config.admins().count()
+    );
+    if config.proposed_admin != Pubkey::default() {
+        println!(
+            "Proposed Admin: {} (replacing {})",
+            config.proposed_admin, config.proposing_admin
+        );
+    }
+    Ok(())
+}
+
+fn process_decisions(rpc_client: &RpcClient) -> Result<(), Box<dyn std::error::Error>> {
+    let config = get_config(rpc_client)?.ok_or("Registry config is not initialized")?;
+    let pending_decisions = get_pending_decisions(rpc_client)?;
+
+    if let Some(pending_decision) = get_pending_config_decision(rpc_client)? {
+        match &pending_decision.decision {
+            ConfigDecision::SetAdmins(admins) => {
+                println!("Config Decision: Set Admins");
+                for admin in admins.iter().filter(|admin| **admin != Pubkey::default()) {
+                    println!("  Admin: {}", admin);
+                }
+            }
+            ConfigDecision::SetThreshold(threshold) => {
+                println!("Config Decision: Set Threshold to {}", threshold)
+            }
+        }
+        println!(
+            "Votes: {} of {}",
+            pending_decision.votes_of(&config),
+            config.required_votes()
+        );
+        println!("Created By: {}", pending_decision.creator);
+        for voter in pending_decision.voters() {
+            println!("Voted: {}", voter);
+        }
+        println!();
+    }
+
+    for pending_decision in pending_decisions.values() {
+        println!("Participant: {}", pending_decision.participant);
+        match &pending_decision.decision {
+            ParticipantDecision::Approve => println!("Decision: Approve"),
+            ParticipantDecision::Reject => println!("Decision: Reject"),
+            ParticipantDecision::Rewrite(participant) => {
+                println!("Decision: Rewrite");
+                println!("  State: {:?}", participant.state);
+                println!(
+                    "  Mainnet Validator Identity: {}",
+                    participant.mainnet_identity
+                );
+                println!(
+                    "  Testnet Validator Identity: {}",
+                    participant.testnet_identity
+                );
+            }
+            ParticipantDecision::RejectWithReason(rejection_reason) => {
+                println!("Decision: Reject, reason {}", rejection_reason)
+            }
+        }
+        println!(
+            "Votes: {} of {}",
+            pending_decision.votes_of(&config),
+            config.required_votes()
+        );
+        println!("Created By: {}", pending_decision.creator);
+        for voter in pending_decision.voters() {
+            println!("Voted: {}", voter);
+        }
+        println!();
+    }
+    println!("{} pending decisions found", pending_decisions.len());
+    Ok(())
+}
+
+/// Check that `admin` may sign the admin subcommand `sub_command`
+fn check_admin_authority(
+    rpc_client: &RpcClient,
+    sub_command: &str,
+    admin: Pubkey,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config = get_config(rpc_client)?;
+    let authorized = match (sub_command, config) {
+        ("initialize-config", None) => {
+            admin == solana_foundation_delegation_program_registry::admin::id()
+        }
+        ("initialize-config", Some(_)) => {
+            return Err("Registry config is already initialized".into());
+        }
+        (_, None) => return Err("Registry config is not initialized".into()),
+        ("accept-admin", Some(config)) => config.proposed_admin == admin,
+        (_, Some(config)) => config.is_admin(&admin),
+    };
+
+    if authorized {
+        Ok(())
+    } else {
+        Err("Invalid admin authority".into())
+    }
+}
+
+fn process_admin_initialize_config(
+    config: &Config,
+    rpc_client: &RpcClient,
+    admin_signer: Box<dyn Signer>,
+    admins: Vec<Pubkey>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let message = Message::new(
         &[
-            solana_foundation_delegation_program_registry::instruction::rewrite(
-                participant_address,
+            solana_foundation_delegation_program_registry::instruction::initialize_config(
+                admin_signer.pubkey(),
+                config.default_signer.pubkey(),
+                admin_set(&admins)?,
+            ),
+        ],
+        Some(&config.default_signer.pubkey()),
+    );
+
+    send_and_confirm_message(
+        rpc_client,
+        message,
+        [admin_signer.deref(), config.default_signer.deref()],
+        None,
+    )
+}
+
+/// The instruction carrying out `decision` on the config: the change itself, or a vote for it
+/// when the registry requires more than one admin to agree
+fn config_decision_instruction(
+    config: &Config,
+    rpc_client: &RpcClient,
+    admin: Pubkey,
+    decision: ConfigDecision,
+) -> Result<Instruction, Box<dyn std::error::Error>> {
+    let registry_config = get_config(rpc_client)?.ok_or("Registry config is not initialized")?;
+
+    if registry_config.required_votes() > 1 {
+        println!(
+            "Voting, {} admins must agree on the change",
+            registry_config.required_votes()
+        );
+        let creator = match get_pending_config_decision(rpc_client)? {
+            Some(pending_decision) if pending_decision.decision != decision => {
+                return Err(format!(
+                    "A different config change is pending: {:?}",
+                    pending_decision.decision
+                )
+                .into());
+            }
+            Some(pending_decision) => pending_decision.creator,
+            None => admin,
+        };
+        return Ok(
+            solana_foundation_delegation_program_registry::instruction::vote_config(
+                admin,
+                config.default_signer.pubkey(),
+                decision,
+                creator,
+            ),
+        );
+    }
+
+    Ok(match decision {
+        ConfigDecision::SetAdmins(admins) => {
+            solana_foundation_delegation_program_registry::instruction::set_admins(admin, admins)
+        }
+        ConfigDecision::SetThreshold(threshold) => {
+            solana_foundation_delegation_program_registry::instruction::set_threshold(
+                admin, threshold,
+            )
+        }
+    })
+}
+
+fn process_admin_set_admins(
+    config: &Config,
+    rpc_client: &RpcClient,
+    admin_signer: Box<dyn Signer>,
+    admins: Vec<Pubkey>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let message = Message::new(
+        &[config_decision_instruction(
+            config,
+            rpc_client,
+            admin_signer.pubkey(),
+            ConfigDecision::SetAdmins(admin_set(&admins)?),
+        )?],
+        Some(&config.default_signer.pubkey()),
+    );
+
+    send_and_confirm_message(
+        rpc_client,
+        message,
+        [admin_signer.deref(), config.default_signer.deref()],
+        None,
+    )
+}
+
+fn process_admin_set_threshold(
+    config: &Config,
+    rpc_client: &RpcClient,
+    admin_signer: Box<dyn Signer>,
+    threshold: u8,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let message = Message::new(
+        &[config_decision_instruction(
+            config,
+            rpc_client,
+            admin_signer.pubkey(),
+            ConfigDecision::SetThreshold(threshold),
+        )?],
+        Some(&config.default_signer.pubkey()),
+    );
+
+    send_and_confirm_message(
+        rpc_client,
+        message,
+        [admin_signer.deref(), config.default_signer.deref()],
+        None,
+    )
+}
+
+fn process_admin_propose_admin(
+    config: &Config,
+    rpc_client: &RpcClient,
+    admin_signer: Box<dyn Signer>,
+    new_admin: Pubkey,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!(
+        "Proposing {} to replace {}. The new admin must run `admin accept-admin` to take over",
+        new_admin,
+        admin_signer.pubkey()
+    );
+
+    let message = Message::new(
+        &[
+            solana_foundation_delegation_program_registry::instruction::propose_admin(
+                admin_signer.pubkey(),
+                new_admin,
+            ),
+        ],
+        Some(&config.default_signer.pubkey()),
+    );
+
+    send_and_confirm_message(
+        rpc_client,
+        message,
+        [admin_signer.deref(), config.default_signer.deref()],
+        None,
+    )
+}
+
+fn process_admin_accept_admin(
+    config: &Config,
+    rpc_client: &RpcClient,
+    admin_signer: Box<dyn Signer>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let message = Message::new(
+        &[
+            solana_foundation_delegation_program_registry::instruction::accept_admin(
                 admin_signer.pubkey(),
-                participant,
             ),
         ],
         Some(&config.default_signer.pubkey()),
@@ -469,6 +982,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         .required(true)
                         .help("Testnet validator identity"),
                 )
+                .arg(
+                    Arg::with_name("contact")
+                        .long("contact")
+                        .value_name("CONTACT")
+                        .takes_value(true)
+                        .help("Contact or URL to publish with the registration"),
+                )
                 .arg(
                     Arg::with_name("confirm")
                         .long("confirm")
@@ -506,6 +1026,67 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         .help("Add the --confirm flag to continue when you're ready to continue"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("set-contact")
+                .about("Publish a contact or URL with your registration")
+                .arg(
+                    Arg::with_name("identity")
+                        .validator(is_valid_signer)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .index(1)
+                        .required(true)
+                        .help("Testnet or Mainnet validator identity"),
+                )
+                .arg(
+                    Arg::with_name("contact")
+                        .value_name("CONTACT")
+                        .takes_value(true)
+                        .index(2)
+                        .required(true)
+                        .help("Contact or URL"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("migrate")
+                .about("Move your registration into the account derived from its Mainnet Validator Identity")
+                .arg(
+                    Arg::with_name("identity")
+                        .validator(is_valid_signer)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .index(1)
+                        .required(true)
+                        .help("Testnet or Mainnet validator identity"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("update-identity")
+                .about("Replace the mainnet or testnet identity of your registration")
+                .arg(
+                    Arg::with_name("identity")
+                        .validator(is_valid_signer)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .index(1)
+                        .required(true)
+                        .help("Current testnet or mainnet validator identity"),
+                )
+                .arg(
+                    Arg::with_name("new_identity")
+                        .validator(is_valid_signer)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .index(2)
+                        .required(true)
+                        .help("New validator identity"),
+                )
+                .arg(
+                    Arg::with_name("confirm")
+                        .long("confirm")
+                        .help("Add the --confirm flag when you're ready to continue"),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("list")
                 .about("List registrations")
@@ -518,6 +1099,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         .help("Restrict the list to registrations in the specified state"),
                 ),
         )
+        .subcommand(SubCommand::with_name("admins").about("Display the registry admins"))
+        .subcommand(
+            SubCommand::with_name("decisions")
+                .about("Display the participant and config decisions awaiting admin votes"),
+        )
         .subcommand(
             SubCommand::with_name("admin")
                 .about("Administration commands")
@@ -555,6 +1141,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                 .index(1)
                                 .required(true)
                                 .help("Testnet or Mainnet validator identity"),
+                        )
+                        .arg(
+                            Arg::with_name("reason")
+                                .long("reason")
+                                .validator(is_parsable::<u8>)
+                                .value_name("CODE")
+                                .takes_value(true)
+                                .help("Rejection reason code to record with the participant"),
                         ),
                 )
                 .subcommand(
@@ -618,6 +1212,64 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                 .possible_values(&["pending", "rejected", "approved"])
                                 .help("New participant state"),
                         ),
+                )
+                .subcommand(
+                    SubCommand::with_name("initialize-config")
+                        .about("Create the on-chain admin set, using the bootstrap authority")
+                        .arg(
+                            Arg::with_name("admins")
+                                .validator(is_valid_pubkey)
+                                .value_name("ADDRESS")
+                                .takes_value(true)
+                                .index(1)
+                                .multiple(true)
+                                .required(true)
+                                .help("Admin addresses"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("set-admins")
+                        .about("Replace the admin set")
+                        .arg(
+                            Arg::with_name("admins")
+                                .validator(is_valid_pubkey)
+                                .value_name("ADDRESS")
+                                .takes_value(true)
+                                .index(1)
+                                .multiple(true)
+                                .required(true)
+                                .help("Admin addresses"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("set-threshold")
+                        .about("Set the number of admins that must vote for a participant or config decision")
+                        .arg(
+                            Arg::with_name("threshold")
+                                .validator(is_parsable::<u8>)
+                                .value_name("COUNT")
+                                .takes_value(true)
+                                .index(1)
+                                .required(true)
+                                .help("Number of admin votes, 1 to decide with a single admin"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("propose-admin")
+                        .about("Propose a new admin to replace the authority")
+                        .arg(
+                            Arg::with_name("new_admin")
+                                .validator(is_valid_pubkey)
+                                .value_name("ADDRESS")
+                                .takes_value(true)
+                                .index(1)
+                                .required(true)
+                                .help("Proposed admin address"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("accept-admin")
+                        .about("Accept a proposal naming the authority as the new admin"),
                 ),
         )
         .get_matches();
@@ -691,6 +1343,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 &rpc_client,
                 mainnet_identity_signer,
                 testnet_identity_signer,
+                arg_matches
+                    .value_of("contact")
+                    .map(|contact| contact.to_string()),
                 confirm,
             )?;
         }
@@ -711,6 +1366,59 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
             process_withdraw(&config, &rpc_client, identity_signer, confirm)?;
         }
+        ("set-contact", Some(arg_matches)) => {
+            let identity_signer = match signer_of(arg_matches, "identity", &mut wallet_manager) {
+                Err(err) => {
+                    eprintln!("Failed to parse identity: {}", err);
+                    exit(1);
+                }
+                Ok((Some(signer), _)) => signer,
+                _ => unreachable!(),
+            };
+            let contact = value_t_or_exit!(arg_matches, "contact", String);
+
+            process_set_contact(&config, &rpc_client, identity_signer, contact)?;
+        }
+        ("migrate", Some(arg_matches)) => {
+            let identity_signer = match signer_of(arg_matches, "identity", &mut wallet_manager) {
+                Err(err) => {
+                    eprintln!("Failed to parse identity: {}", err);
+                    exit(1);
+                }
+                Ok((Some(signer), _)) => signer,
+                _ => unreachable!(),
+            };
+
+            process_migrate(&config, &rpc_client, identity_signer)?;
+        }
+        ("update-identity", Some(arg_matches)) => {
+            let confirm = arg_matches.is_present("confirm");
+            let identity_signer = match signer_of(arg_matches, "identity", &mut wallet_manager) {
+                Err(err) => {
+                    eprintln!("Failed to parse identity: {}", err);
+                    exit(1);
+                }
+                Ok((Some(signer), _)) => signer,
+                _ => unreachable!(),
+            };
+            let new_identity_signer =
+                match signer_of(arg_matches, "new_identity", &mut wallet_manager) {
+                    Err(err) => {
+                        eprintln!("Failed to parse new identity: {}", err);
+                        exit(1);
+                    }
+                    Ok((Some(signer), _)) => signer,
+                    _ => unreachable!(),
+                };
+
+            process_update_identity(
+                &config,
+                &rpc_client,
+                identity_signer,
+                new_identity_signer,
+                confirm,
+            )?;
+        }
         ("list", Some(arg_matches)) => {
             let state = match value_t_or_exit!(arg_matches, "state", String).as_str() {
                 "all" => None,
@@ -722,6 +1430,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
             process_list(&config, &rpc_client, state)?;
         }
+        ("admins", Some(_)) => {
+            process_admins(&rpc_client)?;
+        }
+        ("decisions", Some(_)) => {
+            process_decisions(&rpc_client)?;
+        }
         ("admin", Some(admin_matches)) => {
             let admin_signer = match signer_of(admin_matches, "authority", &mut wallet_manager) {
                 Err(err) => {
@@ -732,8 +1446,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 _ => unreachable!(),
             };
 
-            if admin_signer.pubkey() != solana_foundation_delegation_program_registry::admin::id() {
-                eprintln!("Invalid admin authority");
+            if let Err(err) = check_admin_authority(
+                &rpc_client,
+                admin_matches.subcommand_name().unwrap_or_default(),
+                admin_signer.pubkey(),
+            ) {
+                eprintln!("{}", err);
                 exit(1);
             }
 
@@ -744,7 +1462,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
                 ("reject", Some(arg_matches)) => {
                     let participant = pubkey_of(arg_matches, "participant").unwrap();
-                    process_admin_reject(&config, &rpc_client, admin_signer, participant)?;
+                    let rejection_reason = arg_matches
+                        .value_of("reason")
+                        .map(|reason| reason.parse::<u8>().unwrap());
+                    process_admin_reject(
+                        &config,
+                        &rpc_client,
+                        admin_signer,
+                        participant,
+                        rejection_reason,
+                    )?;
                 }
                 ("import", Some(arg_matches)) => {
                     let testnet_identity = pubkey_of(arg_matches, "testnet").unwrap();
@@ -779,6 +1506,25 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         },
                     )?;
                 }
+                ("initialize-config", Some(arg_matches)) => {
+                    let admins = pubkeys_of(arg_matches, "admins").unwrap();
+                    process_admin_initialize_config(&config, &rpc_client, admin_signer, admins)?;
+                }
+                ("set-admins", Some(arg_matches)) => {
+                    let admins = pubkeys_of(arg_matches, "admins").unwrap();
+                    process_admin_set_admins(&config, &rpc_client, admin_signer, admins)?;
+                }
+                ("set-threshold", Some(arg_matches)) => {
+                    let threshold = value_t_or_exit!(arg_matches, "threshold", u8);
+                    process_admin_set_threshold(&config, &rpc_client, admin_signer, threshold)?;
+                }
+                ("propose-admin", Some(arg_matches)) => {
+                    let new_admin = pubkey_of(arg_matches, "new_admin").unwrap();
+                    process_admin_propose_admin(&config, &rpc_client, admin_signer, new_admin)?;
+                }
+                ("accept-admin", Some(_)) => {
+                    process_admin_accept_admin(&config, &rpc_client, admin_signer)?;
+                }
                 _ => unreachable!(),
             }
         }
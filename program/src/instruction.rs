@@ -1,6 +1,13 @@
 //! Program instructions
 
-use crate::{id, state::Participant};
+use crate::{
+    id,
+    state::{
+        config_address, config_decision_address, decision_address, participant_address,
+        testnet_index_address, ConfigDecision, Participant, ParticipantDecision, MAX_ADMINS,
+        MAX_CONTACT_LEN,
+    },
+};
 use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
 use solana_program::{
     instruction::{AccountMeta, Instruction},
@@ -8,6 +15,7 @@ use solana_program::{
     program_error::ProgramError,
     program_pack::{Pack, Sealed},
     pubkey::Pubkey,
+    system_program,
 };
 
 /// Instructions supported by the Feature Proposal program
@@ -15,21 +23,35 @@ use solana_program::{
 pub enum RegistryInstruction {
     /// Apply for the program
     ///
-    /// On success the participant will be moved to the `ParticipantState::Pending` state
+    /// On success the participant account will be created in the `ParticipantState::Pending`
+    /// state.  Each identity can only be registered by one participant
     ///
-    /// 0. `[writable]` Uninitialized `Participant` account
-    /// 1. `[signer]` Mainnet validator identity
-    /// 2. `[signer]` Testnet validator identity
+    /// 0. `[writable]` `ParticipantV2` account, at `state::participant_address(mainnet_identity)`
+    /// 1. `[writable]` `TestnetIndex` account, at `state::testnet_index_address(testnet_identity)`
+    /// 2. `[signer]` Mainnet validator identity
+    /// 3. `[signer]` Testnet validator identity
+    /// 4. `[writable, signer]` Funding account
+    /// 5. `[]` System program
+    /// 6. `[]` `TestnetIndex` account at `state::testnet_index_address(mainnet_identity)`, which
+    ///         must not exist
+    /// 7. `[]` `ParticipantV2` account at `state::participant_address(testnet_identity)`, which
+    ///         must not exist
     Apply,
 
     /// Withdraw from the program
     ///
-    /// On success the participant account will be deleted and lamports in it refunded
+    /// On success the participant account will be deleted and lamports in it refunded.  A
+    /// pending decision on the participant is discarded
     ///
     /// 0. `[writable]` `Participant` account in the `ParticipantState::Pending` or
     ///                 `ParticipantState::Enrolled` state
     /// 1. `[signer]` Mainnet or Testnet validator identity
-    /// 2. `[writable]`  The account to receive the closed account's lamports.
+    /// 2. `[writable]`  The account to receive the closed accounts' lamports.
+    /// 3. `[writable]` `TestnetIndex` account of the participant, which is deleted along with a
+    ///                 participant account at its program address
+    /// 4. `[writable]` `PendingDecision` account of the participant, deleted if it exists
+    /// 5. `[writable]` The admin that created the pending decision, which receives the lamports
+    ///                 of the `PendingDecision` account.  Only required if the decision exists
     ///
     Withdraw,
 
@@ -39,6 +61,7 @@ pub enum RegistryInstruction {
     ///
     /// 0. `[writable]` `Participant` account in the `ParticipantState::Pending` state
     /// 1. `[signer]` Admin account
+    /// 2. `[]` `Config` account
     Approve,
 
     /// Reject a participant
@@ -48,18 +71,161 @@ pub enum RegistryInstruction {
     /// 0. `[writable]` `Participant` account in the `ParticipantState::Pending` or
     ///                 `ParticipantState::Enrolled` state
     /// 1. `[signer]` Admin account
+    /// 2. `[]` `Config` account
     Reject,
 
-    /// Bypass the normal workflow and rewrite a participant account to the provided state
+    /// Bypass the normal workflow and rewrite a participant account to the provided state.  The
+    /// identities of a participant account at its program address only change with
+    /// `UpdateIdentity`, and accounts at a random address must already be initialized
     ///
     /// 0. `[writable]` `Participant` account in any state
     /// 1. `[signer]` Admin account
+    /// 2. `[]` `Config` account
     Rewrite(Participant),
+
+    /// Create the `Config` account holding the admin set.  Unused entries of the admin set are
+    /// `Pubkey::default()`
+    ///
+    /// 0. `[writable]` `Config` account, at `state::config_address()`
+    /// 1. `[signer]` The bootstrap admin, `crate::admin::id()`
+    /// 2. `[writable, signer]` Funding account
+    /// 3. `[]` System program
+    InitializeConfig([Pubkey; MAX_ADMINS]),
+
+    /// Replace the admin set.  Unused entries of the admin set are `Pubkey::default()`.  Only
+    /// allowed while a single admin decides, otherwise admins vote with `VoteConfig`
+    ///
+    /// 0. `[writable]` `Config` account
+    /// 1. `[signer]` Admin account
+    SetAdmins([Pubkey; MAX_ADMINS]),
+
+    /// Propose a new admin to replace the signing admin.  The replacement takes effect once the
+    /// new admin signs `AcceptAdmin`.  Only one proposal is pending at a time, and only the
+    /// proposing admin may replace it until `SetAdmins` discards it
+    ///
+    /// 0. `[writable]` `Config` account
+    /// 1. `[signer]` Admin account
+    ProposeAdmin(Pubkey),
+
+    /// Accept a proposal of `ProposeAdmin`, replacing the proposing admin
+    ///
+    /// 0. `[writable]` `Config` account
+    /// 1. `[signer]` The proposed admin
+    AcceptAdmin,
+
+    /// Set the number of admins that must vote for a participant or config decision, see
+    /// `state::Config::threshold`.  Only allowed while a single admin decides, otherwise admins
+    /// vote with `VoteConfig`
+    ///
+    /// 0. `[writable]` `Config` account
+    /// 1. `[signer]` Admin account
+    SetThreshold(u8),
+
+    /// Vote for a participant decision.  The decision applies once `Config::threshold` admins
+    /// voted for it, and its `PendingDecision` account is then closed and cleared.  A vote for a
+    /// different decision than the pending one fails
+    ///
+    /// 0. `[writable]` `Participant` account
+    /// 1. `[signer]` Admin account
+    /// 2. `[]` `Config` account
+    /// 3. `[writable]` `PendingDecision` account, at `state::decision_address(participant)`
+    /// 4. `[writable, signer]` Funding account
+    /// 5. `[]` System program
+    /// 6. `[writable]` The admin that created the pending decision, or the voting admin if there
+    ///                 is none, which receives the lamports of the `PendingDecision` account once
+    ///                 it is closed
+    Vote(ParticipantDecision),
+
+    /// Reject a participant, recording an admin assigned reason code in the participant account
+    ///
+    /// 0. `[writable]` `Participant` account in the `ParticipantState::Pending` or
+    ///                 `ParticipantState::Enrolled` state
+    /// 1. `[signer]` Admin account
+    /// 2. `[]` `Config` account
+    RejectWithReason(u8),
+
+    /// Publish a contact or URL of the participant, UTF-8 padded with zeros.  Requires a
+    /// `ParticipantV2` account
+    ///
+    /// 0. `[writable]` `ParticipantV2` account
+    /// 1. `[signer]` Mainnet or Testnet validator identity
+    SetContact([u8; MAX_CONTACT_LEN]),
+
+    /// Move a participant from an account at a random address, in either layout, into a
+    /// `ParticipantV2` account at its program address.  The original account is deleted and its
+    /// lamports refunded
+    ///
+    /// 0. `[writable]` `Participant` account in any initialized state
+    /// 1. `[writable]` `ParticipantV2` account, at `state::participant_address(mainnet_identity)`
+    /// 2. `[writable]` `TestnetIndex` account, at `state::testnet_index_address(testnet_identity)`
+    /// 3. `[signer]` Mainnet or Testnet validator identity, or an admin
+    /// 4. `[]` `Config` account
+    /// 5. `[]` `PendingDecision` account of the `Participant` account, which must not exist
+    /// 6. `[writable, signer]` Funding account, which also receives the closed account's lamports
+    /// 7. `[]` System program
+    /// 8. `[]` `TestnetIndex` account at `state::testnet_index_address(mainnet_identity)`, which
+    ///         must not exist
+    /// 9. `[]` `ParticipantV2` account at `state::participant_address(testnet_identity)`, which
+    ///         must not exist
+    Migrate,
+
+    /// Replace the mainnet or testnet validator identity of a participant, keeping its state.
+    /// The identity matching the signing current identity is replaced.  A new mainnet identity
+    /// moves the participant account to the program address of that identity.  The new identity
+    /// must not be registered by any participant, in either role
+    ///
+    /// 0. `[writable]` `ParticipantV2` account at its program address, in any initialized state
+    /// 1. `[signer]` Current Mainnet or Testnet validator identity
+    /// 2. `[signer]` New validator identity
+    /// 3. `[writable]` `TestnetIndex` account of the participant
+    /// 4. `[writable]` The account reserving the new identity: `ParticipantV2` account at
+    ///                 `state::participant_address(new_identity)` for a mainnet identity,
+    ///                 `TestnetIndex` account at `state::testnet_index_address(new_identity)` for
+    ///                 a testnet identity
+    /// 5. `[]` The account that would reserve the new identity in the other role, which must not
+    ///         exist: `state::testnet_index_address(new_identity)` for a mainnet identity,
+    ///         `state::participant_address(new_identity)` for a testnet identity
+    /// 6. `[]` `PendingDecision` account of the participant, which must not exist
+    /// 7. `[writable, signer]` Funding account, which also receives the lamports of the account
+    ///                         reserving the replaced identity
+    /// 8. `[]` System program
+    UpdateIdentity,
+
+    /// Register a participant on behalf of its validators, in the `ParticipantState::Pending`
+    /// state for the admins to decide on
+    ///
+    /// 0. `[writable]` `ParticipantV2` account, at `state::participant_address(mainnet_identity)`
+    /// 1. `[writable]` `TestnetIndex` account, at `state::testnet_index_address(testnet_identity)`
+    /// 2. `[]` Mainnet validator identity
+    /// 3. `[]` Testnet validator identity
+    /// 4. `[writable, signer]` Funding account
+    /// 5. `[]` System program
+    /// 6. `[]` `TestnetIndex` account at `state::testnet_index_address(mainnet_identity)`, which
+    ///         must not exist
+    /// 7. `[]` `ParticipantV2` account at `state::participant_address(testnet_identity)`, which
+    ///         must not exist
+    /// 8. `[signer]` Admin account
+    /// 9. `[]` `Config` account
+    Import,
+
+    /// Vote for a change to the config.  The change applies once `Config::threshold` admins
+    /// voted for it, and its `PendingConfigDecision` account is then closed and cleared.  A vote
+    /// for a different change than the pending one fails
+    ///
+    /// 0. `[writable]` `Config` account
+    /// 1. `[signer]` Admin account
+    /// 2. `[writable]` `PendingConfigDecision` account, at `state::config_decision_address()`
+    /// 3. `[writable, signer]` Funding account
+    /// 4. `[]` System program
+    /// 5. `[writable]` The admin that created the pending decision, or the voting admin if there
+    ///                 is none, which receives the lamports of the `PendingConfigDecision`
+    ///                 account once it is closed
+    VoteConfig(ConfigDecision),
 }
 
 impl Sealed for RegistryInstruction {}
 impl Pack for RegistryInstruction {
-    const LEN: usize = 66; // see `test::get_packed_len()` for justification of "66"
+    const LEN: usize = 162; // see `test::get_packed_len()` for justification of "162"
 
     fn pack_into_slice(&self, dst: &mut [u8]) {
         let data = self.pack_into_vec();
@@ -86,30 +252,47 @@ impl RegistryInstruction {
 
 /// Create a `RegistryInstruction::Apply` instruction
 pub fn apply(
-    participant: Pubkey,
     mainnet_validator_identity: Pubkey,
     testnet_validator_identity: Pubkey,
+    funder: Pubkey,
 ) -> Instruction {
     Instruction {
         program_id: id(),
         accounts: vec![
-            AccountMeta::new(participant, false),
+            AccountMeta::new(participant_address(&mainnet_validator_identity), false),
+            AccountMeta::new(testnet_index_address(&testnet_validator_identity), false),
             AccountMeta::new_readonly(mainnet_validator_identity, true),
             AccountMeta::new_readonly(testnet_validator_identity, true),
+            AccountMeta::new(funder, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(testnet_index_address(&mainnet_validator_identity), false),
+            AccountMeta::new_readonly(participant_address(&testnet_validator_identity), false),
         ],
         data: RegistryInstruction::Apply.pack_into_vec(),
     }
 }
 
 /// Create a `RegistryInstruction::Withdraw` instruction
-pub fn withdraw(participant: Pubkey, validator_identity: Pubkey, refundee: Pubkey) -> Instruction {
+pub fn withdraw(
+    participant: Pubkey,
+    validator_identity: Pubkey,
+    refundee: Pubkey,
+    testnet_validator_identity: Pubkey,
+    decision_creator: Option<Pubkey>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(participant, false),
+        AccountMeta::new_readonly(validator_identity, true),
+        AccountMeta::new(refundee, false),
+        AccountMeta::new(testnet_index_address(&testnet_validator_identity), false),
+        AccountMeta::new(decision_address(&participant), false),
+    ];
+    if let Some(decision_creator) = decision_creator {
+        accounts.push(AccountMeta::new(decision_creator, false));
+    }
     Instruction {
         program_id: id(),
-        accounts: vec![
-            AccountMeta::new(participant, false),
-            AccountMeta::new_readonly(validator_identity, true),
-            AccountMeta::new(refundee, false),
-        ],
+        accounts,
         data: RegistryInstruction::Withdraw.pack_into_vec(),
     }
 }
@@ -121,6 +304,7 @@ pub fn approve(participant: Pubkey, admin: Pubkey) -> Instruction {
         accounts: vec![
             AccountMeta::new(participant, false),
             AccountMeta::new_readonly(admin, true),
+            AccountMeta::new_readonly(config_address(), false),
         ],
         data: RegistryInstruction::Approve.pack_into_vec(),
     }
@@ -133,6 +317,7 @@ pub fn reject(participant: Pubkey, admin: Pubkey) -> Instruction {
         accounts: vec![
             AccountMeta::new(participant, false),
             AccountMeta::new_readonly(admin, true),
+            AccountMeta::new_readonly(config_address(), false),
         ],
         data: RegistryInstruction::Reject.pack_into_vec(),
     }
@@ -145,11 +330,243 @@ pub fn rewrite(participant: Pubkey, admin: Pubkey, new_state: Participant) -> In
         accounts: vec![
             AccountMeta::new(participant, false),
             AccountMeta::new_readonly(admin, true),
+            AccountMeta::new_readonly(config_address(), false),
         ],
         data: RegistryInstruction::Rewrite(new_state).pack_into_vec(),
     }
 }
 
+/// Create a `RegistryInstruction::InitializeConfig` instruction
+pub fn initialize_config(
+    bootstrap_admin: Pubkey,
+    funder: Pubkey,
+    admins: [Pubkey; MAX_ADMINS],
+) -> Instruction {
+    Instruction {
+        program_id: id(),
+        accounts: vec![
+            AccountMeta::new(config_address(), false),
+            AccountMeta::new_readonly(bootstrap_admin, true),
+            AccountMeta::new(funder, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: RegistryInstruction::InitializeConfig(admins).pack_into_vec(),
+    }
+}
+
+/// Create a `RegistryInstruction::SetAdmins` instruction
+pub fn set_admins(admin: Pubkey, admins: [Pubkey; MAX_ADMINS]) -> Instruction {
+    Instruction {
+        program_id: id(),
+        accounts: vec![
+            AccountMeta::new(config_address(), false),
+            AccountMeta::new_readonly(admin, true),
+        ],
+        data: RegistryInstruction::SetAdmins(admins).pack_into_vec(),
+    }
+}
+
+/// Create a `RegistryInstruction::ProposeAdmin` instruction
+pub fn propose_admin(admin: Pubkey, new_admin: Pubkey) -> Instruction {
+    Instruction {
+        program_id: id(),
+        accounts: vec![
+            AccountMeta::new(config_address(), false),
+            AccountMeta::new_readonly(admin, true),
+        ],
+        data: RegistryInstruction::ProposeAdmin(new_admin).pack_into_vec(),
+    }
+}
+
+/// Create a `RegistryInstruction::AcceptAdmin` instruction
+pub fn accept_admin(new_admin: Pubkey) -> Instruction {
+    Instruction {
+        program_id: id(),
+        accounts: vec![
+            AccountMeta::new(config_address(), false),
+            AccountMeta::new_readonly(new_admin, true),
+        ],
+        data: RegistryInstruction::AcceptAdmin.pack_into_vec(),
+    }
+}
+
+/// Create a `RegistryInstruction::SetThreshold` instruction
+pub fn set_threshold(admin: Pubkey, threshold: u8) -> Instruction {
+    Instruction {
+        program_id: id(),
+        accounts: vec![
+            AccountMeta::new(config_address(), false),
+            AccountMeta::new_readonly(admin, true),
+        ],
+        data: RegistryInstruction::SetThreshold(threshold).pack_into_vec(),
+    }
+}
+
+/// Create a `RegistryInstruction::Vote` instruction
+pub fn vote(
+    participant: Pubkey,
+    admin: Pubkey,
+    funder: Pubkey,
+    decision: ParticipantDecision,
+    decision_creator: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: id(),
+        accounts: vec![
+            AccountMeta::new(participant, false),
+            AccountMeta::new_readonly(admin, true),
+            AccountMeta::new_readonly(config_address(), false),
+            AccountMeta::new(decision_address(&participant), false),
+            AccountMeta::new(funder, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(decision_creator, false),
+        ],
+        data: RegistryInstruction::Vote(decision).pack_into_vec(),
+    }
+}
+
+/// Create a `RegistryInstruction::VoteConfig` instruction
+pub fn vote_config(
+    admin: Pubkey,
+    funder: Pubkey,
+    decision: ConfigDecision,
+    decision_creator: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: id(),
+        accounts: vec![
+            AccountMeta::new(config_address(), false),
+            AccountMeta::new_readonly(admin, true),
+            AccountMeta::new(config_decision_address(), false),
+            AccountMeta::new(funder, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(decision_creator, false),
+        ],
+        data: RegistryInstruction::VoteConfig(decision).pack_into_vec(),
+    }
+}
+
+/// Create a `RegistryInstruction::RejectWithReason` instruction
+pub fn reject_with_reason(participant: Pubkey, admin: Pubkey, rejection_reason: u8) -> Instruction {
+    Instruction {
+        program_id: id(),
+        accounts: vec![
+            AccountMeta::new(participant, false),
+            AccountMeta::new_readonly(admin, true),
+            AccountMeta::new_readonly(config_address(), false),
+        ],
+        data: RegistryInstruction::RejectWithReason(rejection_reason).pack_into_vec(),
+    }
+}
+
+/// Create a `RegistryInstruction::SetContact` instruction
+pub fn set_contact(
+    participant: Pubkey,
+    validator_identity: Pubkey,
+    contact: [u8; MAX_CONTACT_LEN],
+) -> Instruction {
+    Instruction {
+        program_id: id(),
+        accounts: vec![
+            AccountMeta::new(participant, false),
+            AccountMeta::new_readonly(validator_identity, true),
+        ],
+        data: RegistryInstruction::SetContact(contact).pack_into_vec(),
+    }
+}
+
+/// Create a `RegistryInstruction::Migrate` instruction
+pub fn migrate(
+    participant: Pubkey,
+    mainnet_validator_identity: Pubkey,
+    testnet_validator_identity: Pubkey,
+    authority: Pubkey,
+    funder: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: id(),
+        accounts: vec![
+            AccountMeta::new(participant, false),
+            AccountMeta::new(participant_address(&mainnet_validator_identity), false),
+            AccountMeta::new(testnet_index_address(&testnet_validator_identity), false),
+            AccountMeta::new_readonly(authority, true),
+            AccountMeta::new_readonly(config_address(), false),
+            AccountMeta::new_readonly(decision_address(&participant), false),
+            AccountMeta::new(funder, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(testnet_index_address(&mainnet_validator_identity), false),
+            AccountMeta::new_readonly(participant_address(&testnet_validator_identity), false),
+        ],
+        data: RegistryInstruction::Migrate.pack_into_vec(),
+    }
+}
+
+/// Create a `RegistryInstruction::UpdateIdentity` instruction replacing `validator_identity`, one
+/// of the identities of the participant registering `mainnet_validator_identity` and
+/// `testnet_validator_identity`
+pub fn update_identity(
+    mainnet_validator_identity: Pubkey,
+    testnet_validator_identity: Pubkey,
+    validator_identity: Pubkey,
+    new_validator_identity: Pubkey,
+    funder: Pubkey,
+) -> Instruction {
+    let participant = participant_address(&mainnet_validator_identity);
+    let (new_identity_account, new_identity_other_account) =
+        if validator_identity == mainnet_validator_identity {
+            (
+                participant_address(&new_validator_identity),
+                testnet_index_address(&new_validator_identity),
+            )
+        } else {
+            (
+                testnet_index_address(&new_validator_identity),
+                participant_address(&new_validator_identity),
+            )
+        };
+
+    Instruction {
+        program_id: id(),
+        accounts: vec![
+            AccountMeta::new(participant, false),
+            AccountMeta::new_readonly(validator_identity, true),
+            AccountMeta::new_readonly(new_validator_identity, true),
+            AccountMeta::new(testnet_index_address(&testnet_validator_identity), false),
+            AccountMeta::new(new_identity_account, false),
+            AccountMeta::new_readonly(new_identity_other_account, false),
+            AccountMeta::new_readonly(decision_address(&participant), false),
+            AccountMeta::new(funder, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: RegistryInstruction::UpdateIdentity.pack_into_vec(),
+    }
+}
+
+/// Create a `RegistryInstruction::Import` instruction
+pub fn import(
+    mainnet_validator_identity: Pubkey,
+    testnet_validator_identity: Pubkey,
+    funder: Pubkey,
+    admin: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: id(),
+        accounts: vec![
+            AccountMeta::new(participant_address(&mainnet_validator_identity), false),
+            AccountMeta::new(testnet_index_address(&testnet_validator_identity), false),
+            AccountMeta::new_readonly(mainnet_validator_identity, false),
+            AccountMeta::new_readonly(testnet_validator_identity, false),
+            AccountMeta::new(funder, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(testnet_index_address(&mainnet_validator_identity), false),
+            AccountMeta::new_readonly(participant_address(&testnet_validator_identity), false),
+            AccountMeta::new_readonly(admin, true),
+            AccountMeta::new_readonly(config_address(), false),
+        ],
+        data: RegistryInstruction::Import.pack_into_vec(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use {super::*, crate::state::ParticipantState, solana_program::pubkey::Pubkey};
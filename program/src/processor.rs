@@ -3,11 +3,15 @@
 use crate::{instruction::*, state::*, *};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     entrypoint::ProgramResult,
     msg,
+    program::{invoke, invoke_signed},
     program_error::ProgramError,
     program_pack::Pack,
     pubkey::Pubkey,
+    system_instruction,
+    sysvar::{rent::Rent, Sysvar},
 };
 
 #[cfg(test)]
@@ -15,7 +19,8 @@ mod test_admin {
     solana_program::declare_id!("563B79TEFBRx8f6vwJH1XWo85MSsJRaV3E2EdmwUtjmG");
 }
 
-fn is_admin(address: &Pubkey) -> bool {
+/// The bootstrap admin may only create the `Config` account, which holds the admin set from then on
+fn is_bootstrap_admin(address: &Pubkey) -> bool {
     if crate::admin::id() == *address {
         return true;
     }
@@ -28,9 +33,32 @@ fn is_admin(address: &Pubkey) -> bool {
     false
 }
 
-fn authenticate_admin(admin_info: &AccountInfo) -> ProgramResult {
-    if !is_admin(admin_info.key) {
-        msg!("Error: {} is not the admin", admin_info.key);
+fn load_config(program_id: &Pubkey, config_info: &AccountInfo) -> Result<Config, ProgramError> {
+    if *config_info.key != config_address() {
+        msg!("Error: {} is not the config account", config_info.key);
+        return Err(ProgramError::InvalidArgument);
+    }
+    if config_info.owner != program_id {
+        msg!("Error: config account is not initialized");
+        return Err(ProgramError::UninitializedAccount);
+    }
+
+    let config = Config::unpack_from_slice(&config_info.data.borrow())?;
+    if !config.is_initialized() {
+        msg!("Error: config account is not initialized");
+        return Err(ProgramError::UninitializedAccount);
+    }
+    Ok(config)
+}
+
+fn authenticate_admin(
+    program_id: &Pubkey,
+    config_info: &AccountInfo,
+    admin_info: &AccountInfo,
+) -> Result<Config, ProgramError> {
+    let config = load_config(program_id, config_info)?;
+    if !config.is_admin(admin_info.key) {
+        msg!("Error: {} is not an admin", admin_info.key);
         return Err(ProgramError::InvalidArgument);
     }
     if !admin_info.is_signer {
@@ -38,97 +66,957 @@ fn authenticate_admin(admin_info: &AccountInfo) -> ProgramResult {
         return Err(ProgramError::MissingRequiredSignature);
     }
 
+    Ok(config)
+}
+
+/// Authenticate an admin deciding alone, which requires a config without a threshold
+fn authenticate_sole_admin(
+    program_id: &Pubkey,
+    config_info: &AccountInfo,
+    admin_info: &AccountInfo,
+) -> Result<Config, ProgramError> {
+    let config = authenticate_admin(program_id, config_info, admin_info)?;
+    if config.required_votes() > 1 {
+        msg!(
+            "Error: {} admin votes are required, use Vote or VoteConfig",
+            config.required_votes()
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(config)
+}
+
+/// Unpack the participant account `participant_info`, which the program must own in either
+/// participant layout
+fn load_participant(
+    program_id: &Pubkey,
+    participant_info: &AccountInfo,
+) -> Result<ParticipantV2, ProgramError> {
+    let data_len = participant_info.data_len();
+    if participant_info.owner != program_id
+        || (data_len != Participant::LEN && data_len != ParticipantV2::LEN)
+    {
+        msg!(
+            "Error: {} is not a participant account",
+            participant_info.key
+        );
+        return Err(ProgramError::InvalidAccountData);
+    }
+    ParticipantV2::unpack_any(&participant_info.data.borrow())
+}
+
+/// Authenticate a validator identity of `participant`
+fn authenticate_identity(
+    participant: &ParticipantV2,
+    identity_info: &AccountInfo,
+) -> ProgramResult {
+    if !identity_info.is_signer {
+        msg!("Error: {} is not a signer", identity_info.key);
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    if *identity_info.key != participant.testnet_identity
+        && *identity_info.key != participant.mainnet_identity
+    {
+        msg!("Error: {} is not authorized", identity_info.key);
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    Ok(())
+}
+
+/// Whether the program holds live state in `account_info`.  Accounts closed by
+/// `close_program_account` stay owned by the program until the end of the transaction, and for
+/// good if anyone funds them again, but their data is cleared
+fn is_live(program_id: &Pubkey, account_info: &AccountInfo) -> bool {
+    account_info.owner == program_id && account_info.data.borrow().iter().any(|byte| *byte != 0)
+}
+
+/// Create the program account `account_info` at the program address of `seeds`.  Creation fails
+/// if the program already holds live state in the account
+fn create_program_account<'a>(
+    program_id: &Pubkey,
+    account_info: &AccountInfo<'a>,
+    funder_info: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+    space: usize,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    let reopen = account_info.owner == program_id;
+    if reopen && (is_live(program_id, account_info) || account_info.data_len() != space) {
+        msg!("Error: {} already exists", account_info.key);
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    let rent = Rent::get()?.minimum_balance(space);
+    if !reopen && account_info.lamports() == 0 {
+        return invoke_signed(
+            &system_instruction::create_account(
+                funder_info.key,
+                account_info.key,
+                rent,
+                space as u64,
+                program_id,
+            ),
+            &[
+                funder_info.clone(),
+                account_info.clone(),
+                system_program_info.clone(),
+            ],
+            &[seeds],
+        );
+    }
+
+    // Anyone may fund a program address beforehand, which `create_account` refuses
+    let required_lamports = rent.saturating_sub(account_info.lamports());
+    if required_lamports > 0 {
+        invoke(
+            &system_instruction::transfer(funder_info.key, account_info.key, required_lamports),
+            &[
+                funder_info.clone(),
+                account_info.clone(),
+                system_program_info.clone(),
+            ],
+        )?;
+    }
+    if reopen {
+        // A closed account keeps its space and owner
+        return Ok(());
+    }
+    invoke_signed(
+        &system_instruction::allocate(account_info.key, space as u64),
+        &[account_info.clone(), system_program_info.clone()],
+        &[seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(account_info.key, program_id),
+        &[account_info.clone(), system_program_info.clone()],
+        &[seeds],
+    )
+}
+
+/// Check that `decision_info` is the `PendingDecision` account of `participant` and that no
+/// decision is pending
+fn check_no_pending_decision(
+    program_id: &Pubkey,
+    participant: &Pubkey,
+    decision_info: &AccountInfo,
+) -> ProgramResult {
+    if *decision_info.key != decision_address(participant) {
+        msg!(
+            "Error: {} is not the pending decision account",
+            decision_info.key
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+    if is_live(program_id, decision_info) {
+        msg!("Error: participant has a pending decision");
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(())
+}
+
+/// Check that `account_info` is `address`, the program address reserving `identity` in one of
+/// its roles, and that no participant registers `identity` in that role
+fn check_identity_not_reserved(
+    program_id: &Pubkey,
+    identity: &Pubkey,
+    account_info: &AccountInfo,
+    address: &Pubkey,
+) -> ProgramResult {
+    if account_info.key != address {
+        msg!(
+            "Error: {} is not the account reserving {}",
+            account_info.key,
+            identity
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+    if is_live(program_id, account_info) {
+        msg!("Error: {} is already registered", identity);
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
     Ok(())
 }
 
+/// Check that no participant registers the mainnet identity of `participant` as its testnet
+/// identity, or its testnet identity as its mainnet identity
+fn check_identities_not_reserved_in_other_roles(
+    program_id: &Pubkey,
+    participant: &ParticipantV2,
+    mainnet_identity_testnet_index_info: &AccountInfo,
+    testnet_identity_participant_info: &AccountInfo,
+) -> ProgramResult {
+    check_identity_not_reserved(
+        program_id,
+        &participant.mainnet_identity,
+        mainnet_identity_testnet_index_info,
+        &testnet_index_address(&participant.mainnet_identity),
+    )?;
+    check_identity_not_reserved(
+        program_id,
+        &participant.testnet_identity,
+        testnet_identity_participant_info,
+        &participant_address(&participant.testnet_identity),
+    )
+}
+
+/// Close the program account `account_info`, clearing its data and moving its lamports to
+/// `refundee_info`
+fn close_program_account(account_info: &AccountInfo, refundee_info: &AccountInfo) {
+    account_info
+        .data
+        .borrow_mut()
+        .iter_mut()
+        .for_each(|byte| *byte = 0);
+    **refundee_info.lamports.borrow_mut() += account_info.lamports();
+    **account_info.lamports.borrow_mut() = 0;
+}
+
 pub fn process_instruction(
-    _program_id: &Pubkey,
+    program_id: &Pubkey,
     accounts: &[AccountInfo],
     input: &[u8],
 ) -> ProgramResult {
     let instruction = RegistryInstruction::unpack_from_slice(input)?;
-    let account_info_iter = &mut accounts.iter();
-    let participant_info = next_account_info(account_info_iter)?;
-    let mut participant = Participant::unpack_from_slice(&participant_info.data.borrow())?;
 
     match instruction {
-        RegistryInstruction::Apply => {
-            msg!("Apply");
-            if participant.state != ParticipantState::Uninitialized {
-                msg!("Error: participant account is already initialized");
-                return Err(ProgramError::AccountAlreadyInitialized);
-            }
+        RegistryInstruction::InitializeConfig(_)
+        | RegistryInstruction::SetAdmins(_)
+        | RegistryInstruction::ProposeAdmin(_)
+        | RegistryInstruction::AcceptAdmin
+        | RegistryInstruction::SetThreshold(_)
+        | RegistryInstruction::VoteConfig(_) => {
+            process_config_instruction(program_id, accounts, instruction)
+        }
+        RegistryInstruction::Apply | RegistryInstruction::Import => {
+            process_apply(program_id, accounts, instruction)
+        }
+        RegistryInstruction::Migrate => process_migrate(program_id, accounts),
+        RegistryInstruction::UpdateIdentity => process_update_identity(program_id, accounts),
+        _ => process_participant_instruction(program_id, accounts, instruction),
+    }
+}
+
+fn process_config_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction: RegistryInstruction,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let config_info = next_account_info(account_info_iter)?;
 
-            let mainnet_identity_info = next_account_info(account_info_iter)?;
-            let testnet_identity_info = next_account_info(account_info_iter)?;
+    let config = match instruction {
+        RegistryInstruction::InitializeConfig(admins) => {
+            msg!("InitializeConfig");
+            let bootstrap_admin_info = next_account_info(account_info_iter)?;
+            let funder_info = next_account_info(account_info_iter)?;
+            let system_program_info = next_account_info(account_info_iter)?;
 
-            if !mainnet_identity_info.is_signer {
-                msg!("Error: {} is not a signer", mainnet_identity_info.key);
+            if !is_bootstrap_admin(bootstrap_admin_info.key) {
+                msg!("Error: {} is not the admin", bootstrap_admin_info.key);
+                return Err(ProgramError::InvalidArgument);
+            }
+            if !bootstrap_admin_info.is_signer {
+                msg!("Error: {} is not a signer", bootstrap_admin_info.key);
                 return Err(ProgramError::MissingRequiredSignature);
             }
-            if !testnet_identity_info.is_signer {
-                msg!("Error: {} is not a signer", testnet_identity_info.key);
+
+            let (config_address, bump_seed) = find_config_address();
+            if *config_info.key != config_address {
+                msg!("Error: {} is not the config account", config_info.key);
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            let config = Config {
+                admins: admin_set_of(&admins)?,
+                ..Config::default()
+            };
+            create_program_account(
+                program_id,
+                config_info,
+                funder_info,
+                system_program_info,
+                Config::get_packed_len(),
+                &[CONFIG_SEED, &[bump_seed]],
+            )?;
+            config
+        }
+        RegistryInstruction::SetAdmins(admins) => {
+            msg!("SetAdmins");
+            let mut config = authenticate_sole_admin(
+                program_id,
+                config_info,
+                next_account_info(account_info_iter)?,
+            )?;
+            ConfigDecision::SetAdmins(admins).apply(&mut config)?;
+            config
+        }
+        RegistryInstruction::SetThreshold(threshold) => {
+            msg!("SetThreshold");
+            let mut config = authenticate_sole_admin(
+                program_id,
+                config_info,
+                next_account_info(account_info_iter)?,
+            )?;
+            ConfigDecision::SetThreshold(threshold).apply(&mut config)?;
+            config
+        }
+        RegistryInstruction::VoteConfig(decision) => {
+            msg!("VoteConfig");
+            let admin_info = next_account_info(account_info_iter)?;
+            let mut config = authenticate_admin(program_id, config_info, admin_info)?;
+            let decision_info = next_account_info(account_info_iter)?;
+            let funder_info = next_account_info(account_info_iter)?;
+            let system_program_info = next_account_info(account_info_iter)?;
+            let creator_info = next_account_info(account_info_iter)?;
+
+            let (decision_address, bump_seed) = find_config_decision_address();
+            if *decision_info.key != decision_address {
+                msg!(
+                    "Error: {} is not the pending config decision account",
+                    decision_info.key
+                );
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            let mut pending_decision = if is_live(program_id, decision_info) {
+                let pending_decision =
+                    PendingConfigDecision::unpack_from_slice(&decision_info.data.borrow())?;
+                if pending_decision.decision != decision {
+                    msg!("Error: a different config decision is pending");
+                    return Err(ProgramError::InvalidArgument);
+                }
+                pending_decision
+            } else {
+                create_program_account(
+                    program_id,
+                    decision_info,
+                    funder_info,
+                    system_program_info,
+                    PendingConfigDecision::get_packed_len(),
+                    &[DECISION_SEED, config_info.key.as_ref(), &[bump_seed]],
+                )?;
+                PendingConfigDecision::new(decision, *admin_info.key)
+            };
+            if *creator_info.key != pending_decision.creator {
+                msg!(
+                    "Error: {} did not create the pending config decision",
+                    creator_info.key
+                );
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            pending_decision.vote(*admin_info.key, &config);
+            let votes = pending_decision.votes_of(&config);
+            msg!("{} of {} votes", votes, config.required_votes());
+            if votes < config.required_votes() {
+                pending_decision.pack_into_slice(&mut decision_info.data.borrow_mut());
+                return Ok(());
+            }
+
+            // The decision applies, and its account is closed
+            pending_decision.decision.apply(&mut config)?;
+            close_program_account(decision_info, creator_info);
+            config
+        }
+        RegistryInstruction::ProposeAdmin(new_admin) => {
+            msg!("ProposeAdmin");
+            let admin_info = next_account_info(account_info_iter)?;
+            let mut config = authenticate_admin(program_id, config_info, admin_info)?;
+            if new_admin == Pubkey::default() || config.is_admin(&new_admin) {
+                msg!("Error: {} cannot be proposed", new_admin);
+                return Err(ProgramError::InvalidArgument);
+            }
+            if config.proposed_admin != Pubkey::default()
+                && config.proposing_admin != *admin_info.key
+            {
+                msg!("Error: a proposal of {} is pending", config.proposing_admin);
+                return Err(ProgramError::InvalidArgument);
+            }
+            config.proposing_admin = *admin_info.key;
+            config.proposed_admin = new_admin;
+            config
+        }
+        RegistryInstruction::AcceptAdmin => {
+            msg!("AcceptAdmin");
+            let new_admin_info = next_account_info(account_info_iter)?;
+            let mut config = load_config(program_id, config_info)?;
+            if config.proposed_admin == Pubkey::default()
+                || config.proposed_admin != *new_admin_info.key
+            {
+                msg!("Error: {} was not proposed", new_admin_info.key);
+                return Err(ProgramError::InvalidArgument);
+            }
+            if !new_admin_info.is_signer {
+                msg!("Error: {} is not a signer", new_admin_info.key);
                 return Err(ProgramError::MissingRequiredSignature);
             }
 
-            participant.testnet_identity = *testnet_identity_info.key;
-            participant.mainnet_identity = *mainnet_identity_info.key;
-            participant.state = ParticipantState::Pending;
+            let proposing_admin = config.proposing_admin;
+            let admin = config
+                .admins
+                .iter_mut()
+                .find(|admin| **admin == proposing_admin)
+                .ok_or_else(|| {
+                    msg!("Error: {} is no longer an admin", proposing_admin);
+                    ProgramError::InvalidArgument
+                })?;
+            *admin = config.proposed_admin;
+            config.proposing_admin = Pubkey::default();
+            config.proposed_admin = Pubkey::default();
+            config
         }
+        _ => unreachable!(),
+    };
+
+    config.pack_into_slice(&mut config_info.data.borrow_mut());
+    Ok(())
+}
+
+fn process_participant_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction: RegistryInstruction,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let participant_info = next_account_info(account_info_iter)?;
+    let mut participant = load_participant(program_id, participant_info)?;
+    let previous_participant = participant.participant();
+    let at_program_address =
+        *participant_info.key == participant_address(&participant.mainnet_identity);
+    if participant.state == ParticipantState::Uninitialized && !at_program_address {
+        // Participants are only registered at their program addresses, by `Apply` or `Import`
+        msg!("Error: participant account is not initialized");
+        return Err(ProgramError::UninitializedAccount);
+    }
+
+    match instruction {
         RegistryInstruction::Withdraw => {
             msg!("Withdraw");
             let identity_info = next_account_info(account_info_iter)?;
             let refundee_info = next_account_info(account_info_iter)?;
+            let testnet_index_info = next_account_info(account_info_iter)?;
+            let decision_info = next_account_info(account_info_iter)?;
+            authenticate_identity(&participant, identity_info)?;
 
-            if !identity_info.is_signer {
-                msg!("Error: {} is not a signer", identity_info.key);
-                return Err(ProgramError::MissingRequiredSignature);
+            if *decision_info.key != decision_address(participant_info.key) {
+                msg!(
+                    "Error: {} is not the pending decision account",
+                    decision_info.key
+                );
+                return Err(ProgramError::InvalidArgument);
+            }
+            if is_live(program_id, decision_info) {
+                let pending_decision =
+                    PendingDecision::unpack_from_slice(&decision_info.data.borrow())?;
+                let creator_info = next_account_info(account_info_iter)?;
+                if *creator_info.key != pending_decision.creator {
+                    msg!(
+                        "Error: {} did not create the pending decision",
+                        creator_info.key
+                    );
+                    return Err(ProgramError::InvalidArgument);
+                }
+                msg!("Discarding the pending decision");
+                close_program_account(decision_info, creator_info);
             }
 
-            if *identity_info.key != participant.testnet_identity
-                && *identity_info.key != participant.mainnet_identity
-            {
-                msg!("Error: {} is not authorized", identity_info.key);
-                return Err(ProgramError::MissingRequiredSignature);
+            if at_program_address {
+                if *testnet_index_info.key != testnet_index_address(&participant.testnet_identity) {
+                    msg!(
+                        "Error: {} is not the testnet index account",
+                        testnet_index_info.key
+                    );
+                    return Err(ProgramError::InvalidArgument);
+                }
+                close_program_account(testnet_index_info, refundee_info);
             }
 
-            **refundee_info.lamports.borrow_mut() += participant_info.lamports();
-            **participant_info.lamports.borrow_mut() = 0;
+            // The participant account is closed rather than written back
+            close_program_account(participant_info, refundee_info);
+            return Ok(());
         }
         RegistryInstruction::Approve => {
             msg!("Approve");
-            authenticate_admin(next_account_info(account_info_iter)?)?;
+            let admin_info = next_account_info(account_info_iter)?;
+            authenticate_sole_admin(
+                program_id,
+                next_account_info(account_info_iter)?,
+                admin_info,
+            )?;
             participant.state = ParticipantState::Approved;
         }
         RegistryInstruction::Reject => {
             msg!("Reject");
-            authenticate_admin(next_account_info(account_info_iter)?)?;
+            let admin_info = next_account_info(account_info_iter)?;
+            authenticate_sole_admin(
+                program_id,
+                next_account_info(account_info_iter)?,
+                admin_info,
+            )?;
             participant.state = ParticipantState::Rejected;
         }
         RegistryInstruction::Rewrite(new_participant) => {
             msg!("Rewrite");
-            authenticate_admin(next_account_info(account_info_iter)?)?;
-            participant = new_participant;
+            let admin_info = next_account_info(account_info_iter)?;
+            authenticate_sole_admin(
+                program_id,
+                next_account_info(account_info_iter)?,
+                admin_info,
+            )?;
+            participant.rewrite(new_participant);
+        }
+        RegistryInstruction::Vote(decision) => {
+            msg!("Vote");
+            let admin_info = next_account_info(account_info_iter)?;
+            let config = authenticate_admin(
+                program_id,
+                next_account_info(account_info_iter)?,
+                admin_info,
+            )?;
+            let decision_info = next_account_info(account_info_iter)?;
+            let funder_info = next_account_info(account_info_iter)?;
+            let system_program_info = next_account_info(account_info_iter)?;
+            let creator_info = next_account_info(account_info_iter)?;
+
+            let (decision_address, bump_seed) = find_decision_address(participant_info.key);
+            if *decision_info.key != decision_address {
+                msg!(
+                    "Error: {} is not the pending decision account",
+                    decision_info.key
+                );
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            let mut pending_decision = if is_live(program_id, decision_info) {
+                let pending_decision =
+                    PendingDecision::unpack_from_slice(&decision_info.data.borrow())?;
+                if pending_decision.decision != decision {
+                    msg!("Error: a different decision is pending");
+                    return Err(ProgramError::InvalidArgument);
+                }
+                pending_decision
+            } else {
+                create_program_account(
+                    program_id,
+                    decision_info,
+                    funder_info,
+                    system_program_info,
+                    PendingDecision::get_packed_len(),
+                    &[DECISION_SEED, participant_info.key.as_ref(), &[bump_seed]],
+                )?;
+                PendingDecision::new(*participant_info.key, decision, *admin_info.key)
+            };
+            if *creator_info.key != pending_decision.creator {
+                msg!(
+                    "Error: {} did not create the pending decision",
+                    creator_info.key
+                );
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            pending_decision.vote(*admin_info.key, &config);
+            let votes = pending_decision.votes_of(&config);
+            msg!("{} of {} votes", votes, config.required_votes());
+            if votes < config.required_votes() {
+                pending_decision.pack_into_slice(&mut decision_info.data.borrow_mut());
+                return Ok(());
+            }
+
+            // The decision applies, and its account is closed
+            pending_decision.decision.apply(&mut participant);
+            close_program_account(decision_info, creator_info);
+        }
+        RegistryInstruction::RejectWithReason(rejection_reason) => {
+            msg!("RejectWithReason");
+            let admin_info = next_account_info(account_info_iter)?;
+            authenticate_sole_admin(
+                program_id,
+                next_account_info(account_info_iter)?,
+                admin_info,
+            )?;
+            participant.state = ParticipantState::Rejected;
+            participant.rejection_reason = rejection_reason;
+        }
+        RegistryInstruction::SetContact(contact) => {
+            msg!("SetContact");
+            authenticate_identity(&participant, next_account_info(account_info_iter)?)?;
+            if participant_info.data_len() == Participant::LEN {
+                msg!("Error: participant account has no contact, migrate it first");
+                return Err(ProgramError::InvalidAccountData);
+            }
+            check_contact_field(&contact)?;
+            participant.contact = contact;
+        }
+        _ => unreachable!(),
+    }
+
+    if at_program_address
+        && (participant.mainnet_identity != previous_participant.mainnet_identity
+            || participant.testnet_identity != previous_participant.testnet_identity)
+    {
+        msg!("Error: the identities of the participant only change with UpdateIdentity");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    if participant.state != previous_participant.state {
+        let now = Clock::get()?.unix_timestamp;
+        match participant.state {
+            ParticipantState::Pending => participant.applied_at = now,
+            ParticipantState::Approved | ParticipantState::Rejected => participant.decided_at = now,
+            ParticipantState::Uninitialized => {}
         }
     }
+    if participant.state != ParticipantState::Rejected {
+        participant.rejection_reason = 0;
+    }
+    participant.version = PARTICIPANT_VERSION;
 
     if participant.testnet_identity == participant.mainnet_identity {
         msg!("Error: mainnet and testnet identities must be unique",);
         Err(ProgramError::InvalidAccountData)
     } else {
-        participant.pack_into_slice(&mut participant_info.data.borrow_mut());
+        participant.pack_any(&mut participant_info.data.borrow_mut());
         Ok(())
     }
 }
 
+/// Register a participant at the program addresses of its identities
+fn process_apply(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction: RegistryInstruction,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let participant_info = next_account_info(account_info_iter)?;
+    let testnet_index_info = next_account_info(account_info_iter)?;
+    let mainnet_identity_info = next_account_info(account_info_iter)?;
+    let testnet_identity_info = next_account_info(account_info_iter)?;
+    let funder_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let mainnet_identity_testnet_index_info = next_account_info(account_info_iter)?;
+    let testnet_identity_participant_info = next_account_info(account_info_iter)?;
+
+    if instruction == RegistryInstruction::Import {
+        msg!("Import");
+        let admin_info = next_account_info(account_info_iter)?;
+        authenticate_admin(
+            program_id,
+            next_account_info(account_info_iter)?,
+            admin_info,
+        )?;
+    } else {
+        msg!("Apply");
+        if !mainnet_identity_info.is_signer {
+            msg!("Error: {} is not a signer", mainnet_identity_info.key);
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if !testnet_identity_info.is_signer {
+            msg!("Error: {} is not a signer", testnet_identity_info.key);
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+    }
+
+    let participant = ParticipantV2 {
+        testnet_identity: *testnet_identity_info.key,
+        mainnet_identity: *mainnet_identity_info.key,
+        state: ParticipantState::Pending,
+        applied_at: Clock::get()?.unix_timestamp,
+        ..ParticipantV2::default()
+    };
+    if participant.testnet_identity == participant.mainnet_identity {
+        msg!("Error: mainnet and testnet identities must be unique",);
+        return Err(ProgramError::InvalidAccountData);
+    }
+    check_identities_not_reserved_in_other_roles(
+        program_id,
+        &participant,
+        mainnet_identity_testnet_index_info,
+        testnet_identity_participant_info,
+    )?;
+
+    let (participant_address, participant_bump_seed) =
+        find_participant_address(&participant.mainnet_identity);
+    if *participant_info.key != participant_address {
+        msg!(
+            "Error: {} is not the participant account",
+            participant_info.key
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+    let (testnet_index_address, testnet_index_bump_seed) =
+        find_testnet_index_address(&participant.testnet_identity);
+    if *testnet_index_info.key != testnet_index_address {
+        msg!(
+            "Error: {} is not the testnet index account",
+            testnet_index_info.key
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    create_program_account(
+        program_id,
+        participant_info,
+        funder_info,
+        system_program_info,
+        ParticipantV2::get_packed_len(),
+        &[
+            PARTICIPANT_SEED,
+            participant.mainnet_identity.as_ref(),
+            &[participant_bump_seed],
+        ],
+    )?;
+    create_program_account(
+        program_id,
+        testnet_index_info,
+        funder_info,
+        system_program_info,
+        TestnetIndex::get_packed_len(),
+        &[
+            TESTNET_INDEX_SEED,
+            participant.testnet_identity.as_ref(),
+            &[testnet_index_bump_seed],
+        ],
+    )?;
+
+    participant.pack_into_slice(&mut participant_info.data.borrow_mut());
+    TestnetIndex {
+        participant: participant_address,
+    }
+    .pack_into_slice(&mut testnet_index_info.data.borrow_mut());
+    Ok(())
+}
+
+fn process_migrate(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    msg!("Migrate");
+    let account_info_iter = &mut accounts.iter();
+    let participant_info = next_account_info(account_info_iter)?;
+    let new_participant_info = next_account_info(account_info_iter)?;
+    let testnet_index_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let decision_info = next_account_info(account_info_iter)?;
+    let funder_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let mainnet_identity_testnet_index_info = next_account_info(account_info_iter)?;
+    let testnet_identity_participant_info = next_account_info(account_info_iter)?;
+
+    let mut participant = load_participant(program_id, participant_info)?;
+    if participant.state == ParticipantState::Uninitialized {
+        msg!("Error: participant account is not initialized");
+        return Err(ProgramError::UninitializedAccount);
+    }
+
+    if *authority_info.key == participant.testnet_identity
+        || *authority_info.key == participant.mainnet_identity
+    {
+        authenticate_identity(&participant, authority_info)?;
+    } else {
+        authenticate_admin(program_id, config_info, authority_info)?;
+    }
+    check_no_pending_decision(program_id, participant_info.key, decision_info)?;
+    check_identities_not_reserved_in_other_roles(
+        program_id,
+        &participant,
+        mainnet_identity_testnet_index_info,
+        testnet_identity_participant_info,
+    )?;
+
+    let (participant_address, participant_bump_seed) =
+        find_participant_address(&participant.mainnet_identity);
+    if *participant_info.key == participant_address {
+        msg!("Error: participant account is already migrated");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if *new_participant_info.key != participant_address {
+        msg!(
+            "Error: {} is not the participant account",
+            new_participant_info.key
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+    let (testnet_index_address, testnet_index_bump_seed) =
+        find_testnet_index_address(&participant.testnet_identity);
+    if *testnet_index_info.key != testnet_index_address {
+        msg!(
+            "Error: {} is not the testnet index account",
+            testnet_index_info.key
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    create_program_account(
+        program_id,
+        new_participant_info,
+        funder_info,
+        system_program_info,
+        ParticipantV2::get_packed_len(),
+        &[
+            PARTICIPANT_SEED,
+            participant.mainnet_identity.as_ref(),
+            &[participant_bump_seed],
+        ],
+    )?;
+    create_program_account(
+        program_id,
+        testnet_index_info,
+        funder_info,
+        system_program_info,
+        TestnetIndex::get_packed_len(),
+        &[
+            TESTNET_INDEX_SEED,
+            participant.testnet_identity.as_ref(),
+            &[testnet_index_bump_seed],
+        ],
+    )?;
+
+    participant.version = PARTICIPANT_VERSION;
+    participant.pack_into_slice(&mut new_participant_info.data.borrow_mut());
+    TestnetIndex {
+        participant: participant_address,
+    }
+    .pack_into_slice(&mut testnet_index_info.data.borrow_mut());
+
+    close_program_account(participant_info, funder_info);
+    Ok(())
+}
+
+fn process_update_identity(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    msg!("UpdateIdentity");
+    let account_info_iter = &mut accounts.iter();
+    let participant_info = next_account_info(account_info_iter)?;
+    let identity_info = next_account_info(account_info_iter)?;
+    let new_identity_info = next_account_info(account_info_iter)?;
+    let testnet_index_info = next_account_info(account_info_iter)?;
+    let new_identity_account_info = next_account_info(account_info_iter)?;
+    let new_identity_other_account_info = next_account_info(account_info_iter)?;
+    let decision_info = next_account_info(account_info_iter)?;
+    let funder_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    let mut participant = load_participant(program_id, participant_info)?;
+    if participant.state == ParticipantState::Uninitialized {
+        msg!("Error: participant account is not initialized");
+        return Err(ProgramError::UninitializedAccount);
+    }
+    if *participant_info.key != participant_address(&participant.mainnet_identity) {
+        msg!("Error: participant account must be migrated first");
+        return Err(ProgramError::InvalidArgument);
+    }
+    authenticate_identity(&participant, identity_info)?;
+    if !new_identity_info.is_signer {
+        msg!("Error: {} is not a signer", new_identity_info.key);
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if *testnet_index_info.key != testnet_index_address(&participant.testnet_identity) {
+        msg!(
+            "Error: {} is not the testnet index account",
+            testnet_index_info.key
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+    if *new_identity_info.key == participant.testnet_identity
+        || *new_identity_info.key == participant.mainnet_identity
+    {
+        msg!("Error: mainnet and testnet identities must be unique",);
+        return Err(ProgramError::InvalidAccountData);
+    }
+    check_no_pending_decision(program_id, participant_info.key, decision_info)?;
+
+    if *identity_info.key == participant.mainnet_identity {
+        // The participant account moves to the program address of the new identity, which must
+        // not be the testnet identity of another participant
+        check_identity_not_reserved(
+            program_id,
+            new_identity_info.key,
+            new_identity_other_account_info,
+            &testnet_index_address(new_identity_info.key),
+        )?;
+        participant.mainnet_identity = *new_identity_info.key;
+
+        let (participant_address, bump_seed) =
+            find_participant_address(&participant.mainnet_identity);
+        if *new_identity_account_info.key != participant_address {
+            msg!(
+                "Error: {} is not the participant account",
+                new_identity_account_info.key
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+        create_program_account(
+            program_id,
+            new_identity_account_info,
+            funder_info,
+            system_program_info,
+            ParticipantV2::get_packed_len(),
+            &[
+                PARTICIPANT_SEED,
+                participant.mainnet_identity.as_ref(),
+                &[bump_seed],
+            ],
+        )?;
+
+        participant.pack_into_slice(&mut new_identity_account_info.data.borrow_mut());
+        TestnetIndex {
+            participant: participant_address,
+        }
+        .pack_into_slice(&mut testnet_index_info.data.borrow_mut());
+
+        close_program_account(participant_info, funder_info);
+    } else {
+        // The testnet index moves to the program address of the new identity, which must not be
+        // the mainnet identity of another participant
+        check_identity_not_reserved(
+            program_id,
+            new_identity_info.key,
+            new_identity_other_account_info,
+            &participant_address(new_identity_info.key),
+        )?;
+        participant.testnet_identity = *new_identity_info.key;
+
+        let (testnet_index_address, bump_seed) =
+            find_testnet_index_address(&participant.testnet_identity);
+        if *new_identity_account_info.key != testnet_index_address {
+            msg!(
+                "Error: {} is not the testnet index account",
+                new_identity_account_info.key
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+        create_program_account(
+            program_id,
+            new_identity_account_info,
+            funder_info,
+            system_program_info,
+            TestnetIndex::get_packed_len(),
+            &[
+                TESTNET_INDEX_SEED,
+                participant.testnet_identity.as_ref(),
+                &[bump_seed],
+            ],
+        )?;
+
+        participant.pack_into_slice(&mut participant_info.data.borrow_mut());
+        TestnetIndex {
+            participant: *participant_info.key,
+        }
+        .pack_into_slice(&mut new_identity_account_info.data.borrow_mut());
+
+        close_program_account(testnet_index_info, funder_info);
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use {
         super::*,
         assert_matches::*,
-        solana_program::{system_instruction::create_account, sysvar::rent::Rent},
+        solana_program::sysvar::rent::Rent,
         solana_program_test::*,
         solana_sdk::{
+            account::Account,
+            hash::Hash,
             signature::{Keypair, Signer},
             transaction::Transaction,
         },
@@ -146,46 +1034,62 @@ mod test {
         keypair
     }
 
+    fn program_test() -> ProgramTest {
+        ProgramTest::new(
+            "solana_foundation_delegation_program_registry",
+            crate::id(),
+            processor!(process_instruction),
+        )
+    }
+
+    async fn initialize_test_config(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: Hash,
+        admins: &[Pubkey],
+    ) {
+        let mut transaction = Transaction::new_with_payer(
+            &[initialize_config(
+                test_admin::id(),
+                payer.pubkey(),
+                admin_set(admins).unwrap(),
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[payer, &test_admin_keypair()], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
+    }
+
     #[tokio::test]
     async fn test_signup() {
-        let program_id = crate::id();
-
-        let participant = Keypair::new();
         let mainnet_validator_identity = Keypair::new();
         let testnet_validator_identity = Keypair::new();
+        let participant = participant_address(&mainnet_validator_identity.pubkey());
 
-        let (mut banks_client, payer, recent_blockhash) = ProgramTest::new(
-            "solana_foundation_delegation_program_registry",
-            program_id,
-            processor!(process_instruction),
+        let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+        initialize_test_config(
+            &mut banks_client,
+            &payer,
+            recent_blockhash,
+            &[test_admin::id()],
         )
-        .start()
         .await;
 
-        let rent = Rent::default().minimum_balance(Participant::get_packed_len());
+        let rent = Rent::default().minimum_balance(ParticipantV2::get_packed_len())
+            + Rent::default().minimum_balance(TestnetIndex::get_packed_len());
 
-        // Create/Apply...
+        // Apply...
         let mut transaction = Transaction::new_with_payer(
-            &[
-                create_account(
-                    &payer.pubkey(),
-                    &participant.pubkey(),
-                    rent,
-                    Participant::get_packed_len() as u64,
-                    &program_id,
-                ),
-                apply(
-                    participant.pubkey(),
-                    mainnet_validator_identity.pubkey(),
-                    testnet_validator_identity.pubkey(),
-                ),
-            ],
+            &[apply(
+                mainnet_validator_identity.pubkey(),
+                testnet_validator_identity.pubkey(),
+                payer.pubkey(),
+            )],
             Some(&payer.pubkey()),
         );
         transaction.sign(
             &[
                 &payer,
-                &participant,
                 &mainnet_validator_identity,
                 &testnet_validator_identity,
             ],
@@ -194,40 +1098,61 @@ mod test {
         assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
 
         let participant_state = banks_client
-            .get_packed_account_data::<Participant>(participant.pubkey())
+            .get_packed_account_data::<ParticipantV2>(participant)
             .await
             .unwrap();
         assert_eq!(
-            participant_state,
+            participant_state.participant(),
             Participant {
                 state: ParticipantState::Pending,
                 testnet_identity: testnet_validator_identity.pubkey(),
                 mainnet_identity: mainnet_validator_identity.pubkey()
             }
         );
+        assert_ne!(participant_state.applied_at, 0);
+        assert_eq!(
+            banks_client
+                .get_packed_account_data::<TestnetIndex>(testnet_index_address(
+                    &testnet_validator_identity.pubkey()
+                ))
+                .await
+                .unwrap()
+                .participant,
+            participant
+        );
 
-        // Cannot Apply twice...
-        let mut transaction = Transaction::new_with_payer(
-            &[apply(
-                participant.pubkey(),
-                mainnet_validator_identity.pubkey(),
-                testnet_validator_identity.pubkey(),
-            )],
-            Some(&payer.pubkey()),
-        );
-        transaction.sign(
-            &[
-                &payer,
-                &mainnet_validator_identity,
-                &testnet_validator_identity,
-            ],
-            recent_blockhash,
-        );
-        assert_matches!(banks_client.process_transaction(transaction).await, Err(_));
+        // Cannot Apply with a registered identity...
+        let other_validator_identity = Keypair::new();
+        for (mainnet_validator_identity, testnet_validator_identity) in [
+            (&mainnet_validator_identity, &other_validator_identity),
+            (&other_validator_identity, &testnet_validator_identity),
+            (&testnet_validator_identity, &other_validator_identity),
+            (&other_validator_identity, &mainnet_validator_identity),
+        ]
+        .iter()
+        {
+            let mut transaction = Transaction::new_with_payer(
+                &[apply(
+                    mainnet_validator_identity.pubkey(),
+                    testnet_validator_identity.pubkey(),
+                    payer.pubkey(),
+                )],
+                Some(&payer.pubkey()),
+            );
+            transaction.sign(
+                &[
+                    &payer,
+                    *mainnet_validator_identity,
+                    *testnet_validator_identity,
+                ],
+                recent_blockhash,
+            );
+            assert_matches!(banks_client.process_transaction(transaction).await, Err(_));
+        }
 
         // Reject..
         let mut transaction = Transaction::new_with_payer(
-            &[reject(participant.pubkey(), test_admin::id())],
+            &[reject(participant, test_admin::id())],
             Some(&payer.pubkey()),
         );
         transaction.sign(&[&payer, &test_admin_keypair()], recent_blockhash);
@@ -235,7 +1160,7 @@ mod test {
 
         assert_eq!(
             banks_client
-                .get_packed_account_data::<Participant>(participant.pubkey())
+                .get_packed_account_data::<ParticipantV2>(participant)
                 .await
                 .unwrap()
                 .state,
@@ -244,7 +1169,7 @@ mod test {
 
         // Approve...
         let mut transaction = Transaction::new_with_payer(
-            &[approve(participant.pubkey(), test_admin::id())],
+            &[approve(participant, test_admin::id())],
             Some(&payer.pubkey()),
         );
         transaction.sign(&[&payer, &test_admin_keypair()], recent_blockhash);
@@ -252,7 +1177,7 @@ mod test {
 
         assert_eq!(
             banks_client
-                .get_packed_account_data::<Participant>(participant.pubkey())
+                .get_packed_account_data::<ParticipantV2>(participant)
                 .await
                 .unwrap()
                 .state,
@@ -261,10 +1186,7 @@ mod test {
 
         // Approve with wrong admin key, failure...
         let mut transaction = Transaction::new_with_payer(
-            &[approve(
-                participant.pubkey(),
-                testnet_validator_identity.pubkey(),
-            )],
+            &[approve(participant, testnet_validator_identity.pubkey())],
             Some(&payer.pubkey()),
         );
         transaction.sign(&[&payer, &testnet_validator_identity], recent_blockhash);
@@ -273,7 +1195,7 @@ mod test {
         // Rewrite with wrong admin key, failure...
         let mut transaction = Transaction::new_with_payer(
             &[rewrite(
-                participant.pubkey(),
+                participant,
                 testnet_validator_identity.pubkey(),
                 Participant::default(),
             )],
@@ -282,15 +1204,15 @@ mod test {
         transaction.sign(&[&payer, &testnet_validator_identity], recent_blockhash);
         assert_matches!(banks_client.process_transaction(transaction).await, Err(_));
 
-        // Rewrite with duplicate identities, failure...
+        // Rewrite with changed identities, failure...
         let mut transaction = Transaction::new_with_payer(
             &[rewrite(
-                participant.pubkey(),
+                participant,
                 test_admin::id(),
                 Participant {
                     state: ParticipantState::Pending,
                     testnet_identity: testnet_validator_identity.pubkey(),
-                    mainnet_identity: testnet_validator_identity.pubkey(),
+                    mainnet_identity: Pubkey::default(),
                 },
             )],
             Some(&payer.pubkey()),
@@ -301,12 +1223,12 @@ mod test {
         // Rewrite...
         let mut transaction = Transaction::new_with_payer(
             &[rewrite(
-                participant.pubkey(),
+                participant,
                 test_admin::id(),
                 Participant {
                     state: ParticipantState::Pending,
                     testnet_identity: testnet_validator_identity.pubkey(),
-                    mainnet_identity: Pubkey::default(),
+                    mainnet_identity: mainnet_validator_identity.pubkey(),
                 },
             )],
             Some(&payer.pubkey()),
@@ -314,20 +1236,16 @@ mod test {
         transaction.sign(&[&payer, &test_admin_keypair()], recent_blockhash);
         assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
 
-        let participant_state = banks_client
-            .get_packed_account_data::<Participant>(participant.pubkey())
-            .await
-            .unwrap();
         assert_eq!(
-            participant_state,
-            Participant {
-                state: ParticipantState::Pending,
-                testnet_identity: testnet_validator_identity.pubkey(),
-                mainnet_identity: Pubkey::default(),
-            }
+            banks_client
+                .get_packed_account_data::<ParticipantV2>(participant)
+                .await
+                .unwrap()
+                .state,
+            ParticipantState::Pending
         );
 
-        // Withdraw...
+        // Withdraw, with transfers trying to revive the closed accounts...
         assert_eq!(
             banks_client
                 .get_balance(testnet_validator_identity.pubkey())
@@ -335,19 +1253,27 @@ mod test {
                 .unwrap(),
             0
         );
-        assert_eq!(
-            banks_client
-                .get_balance(participant.pubkey())
-                .await
-                .unwrap(),
-            rent
-        );
+        let testnet_index = testnet_index_address(&testnet_validator_identity.pubkey());
         let mut transaction = Transaction::new_with_payer(
-            &[withdraw(
-                participant.pubkey(),
-                testnet_validator_identity.pubkey(),
-                testnet_validator_identity.pubkey(),
-            )],
+            &[
+                withdraw(
+                    participant,
+                    testnet_validator_identity.pubkey(),
+                    testnet_validator_identity.pubkey(),
+                    testnet_validator_identity.pubkey(),
+                    None,
+                ),
+                system_instruction::transfer(
+                    &payer.pubkey(),
+                    &participant,
+                    Rent::default().minimum_balance(ParticipantV2::get_packed_len()),
+                ),
+                system_instruction::transfer(
+                    &payer.pubkey(),
+                    &testnet_index,
+                    Rent::default().minimum_balance(TestnetIndex::get_packed_len()),
+                ),
+            ],
             Some(&payer.pubkey()),
         );
         transaction.sign(&[&payer, &testnet_validator_identity], recent_blockhash);
@@ -360,12 +1286,984 @@ mod test {
                 .unwrap(),
             rent
         );
+
+        // ...which hold no registration
         assert_eq!(
             banks_client
-                .get_balance(participant.pubkey())
+                .get_packed_account_data::<ParticipantV2>(participant)
                 .await
-                .unwrap(),
-            0
+                .unwrap()
+                .state,
+            ParticipantState::Uninitialized
+        );
+        assert_eq!(
+            banks_client
+                .get_packed_account_data::<TestnetIndex>(testnet_index)
+                .await
+                .unwrap()
+                .participant,
+            Pubkey::default()
+        );
+
+        // Apply again once withdrawn...
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                apply(
+                    mainnet_validator_identity.pubkey(),
+                    testnet_validator_identity.pubkey(),
+                    payer.pubkey(),
+                ),
+                rewrite(
+                    participant,
+                    test_admin::id(),
+                    Participant {
+                        state: ParticipantState::Approved,
+                        testnet_identity: testnet_validator_identity.pubkey(),
+                        mainnet_identity: mainnet_validator_identity.pubkey(),
+                    },
+                ),
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(
+            &[
+                &payer,
+                &mainnet_validator_identity,
+                &testnet_validator_identity,
+                &test_admin_keypair(),
+            ],
+            recent_blockhash,
+        );
+        assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_admins() {
+        let mainnet_validator_identity = Pubkey::new_unique();
+        let testnet_validator_identity = Pubkey::new_unique();
+        let participant = participant_address(&mainnet_validator_identity);
+        let admin = Keypair::new();
+        let new_admin = Keypair::new();
+
+        let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+
+        // Approve before the config is initialized, failure...
+        let mut transaction = Transaction::new_with_payer(
+            &[approve(participant, test_admin::id())],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &test_admin_keypair()], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Err(_));
+
+        // Initialize with a non-bootstrap admin, failure...
+        let mut transaction = Transaction::new_with_payer(
+            &[initialize_config(
+                admin.pubkey(),
+                payer.pubkey(),
+                admin_set(&[admin.pubkey()]).unwrap(),
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &admin], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Err(_));
+
+        // Anyone may fund the config account beforehand...
+        let mut transaction = Transaction::new_with_payer(
+            &[system_instruction::transfer(
+                &payer.pubkey(),
+                &config_address(),
+                1,
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
+
+        // ...which does not prevent initializing it
+        initialize_test_config(
+            &mut banks_client,
+            &payer,
+            recent_blockhash,
+            &[test_admin::id()],
+        )
+        .await;
+
+        // Cannot initialize twice...
+        let mut transaction = Transaction::new_with_payer(
+            &[initialize_config(
+                test_admin::id(),
+                payer.pubkey(),
+                admin_set(&[admin.pubkey()]).unwrap(),
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &test_admin_keypair()], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Err(_));
+
+        // Set admins...
+        let mut transaction = Transaction::new_with_payer(
+            &[set_admins(
+                test_admin::id(),
+                admin_set(&[admin.pubkey()]).unwrap(),
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &test_admin_keypair()], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
+
+        // The previous admin no longer imports...
+        let mut transaction = Transaction::new_with_payer(
+            &[import(
+                mainnet_validator_identity,
+                testnet_validator_identity,
+                payer.pubkey(),
+                test_admin::id(),
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &test_admin_keypair()], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Err(_));
+
+        // ...but the new admin does
+        let mut transaction = Transaction::new_with_payer(
+            &[import(
+                mainnet_validator_identity,
+                testnet_validator_identity,
+                payer.pubkey(),
+                admin.pubkey(),
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &admin], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
+
+        // Rewriting a fresh account at a random address, failure...
+        let random_account = Keypair::new();
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                system_instruction::create_account(
+                    &payer.pubkey(),
+                    &random_account.pubkey(),
+                    Rent::default().minimum_balance(Participant::get_packed_len()),
+                    Participant::get_packed_len() as u64,
+                    &crate::id(),
+                ),
+                rewrite(
+                    random_account.pubkey(),
+                    admin.pubkey(),
+                    Participant {
+                        state: ParticipantState::Approved,
+                        testnet_identity: Pubkey::new_unique(),
+                        mainnet_identity: Pubkey::new_unique(),
+                    },
+                ),
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &random_account, &admin], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Err(_));
+
+        // Program-owned accounts of another size are not participants, failure...
+        let mut transaction = Transaction::new_with_payer(
+            &[approve(config_address(), admin.pubkey())],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &admin], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Err(_));
+
+        // Propose a replacement admin...
+        let mut transaction = Transaction::new_with_payer(
+            &[propose_admin(admin.pubkey(), new_admin.pubkey())],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &admin], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
+
+        // Accept by another key, failure...
+        let mut transaction =
+            Transaction::new_with_payer(&[accept_admin(test_admin::id())], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &test_admin_keypair()], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Err(_));
+
+        // Accept...
+        let mut transaction =
+            Transaction::new_with_payer(&[accept_admin(new_admin.pubkey())], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &new_admin], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
+
+        let config = banks_client
+            .get_packed_account_data::<Config>(config_address())
+            .await
+            .unwrap();
+        assert_eq!(
+            config,
+            Config {
+                admins: admin_set(&[new_admin.pubkey()]).unwrap(),
+                ..Config::default()
+            }
+        );
+
+        // The replaced admin no longer approves...
+        let mut transaction = Transaction::new_with_payer(
+            &[approve(participant, admin.pubkey())],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &admin], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Err(_));
+
+        // ...but the new admin does
+        let mut transaction = Transaction::new_with_payer(
+            &[approve(participant, new_admin.pubkey())],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &new_admin], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
+
+        // A proposal of another admin is not replaced...
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                set_admins(
+                    new_admin.pubkey(),
+                    admin_set(&[new_admin.pubkey(), admin.pubkey()]).unwrap(),
+                ),
+                propose_admin(new_admin.pubkey(), Pubkey::new_unique()),
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &new_admin], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
+
+        let mut transaction = Transaction::new_with_payer(
+            &[propose_admin(admin.pubkey(), Pubkey::new_unique())],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &admin], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Err(_));
+
+        // ...but the proposing admin may replace its own
+        let mut transaction = Transaction::new_with_payer(
+            &[propose_admin(new_admin.pubkey(), Pubkey::new_unique())],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &new_admin], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_threshold() {
+        let mainnet_validator_identity = Keypair::new();
+        let testnet_validator_identity = Keypair::new();
+        let participant = participant_address(&mainnet_validator_identity.pubkey());
+        let other_admin = Keypair::new();
+
+        let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+        initialize_test_config(
+            &mut banks_client,
+            &payer,
+            recent_blockhash,
+            &[test_admin::id(), other_admin.pubkey()],
+        )
+        .await;
+
+        // A threshold above the number of admins, failure...
+        let mut transaction = Transaction::new_with_payer(
+            &[set_threshold(test_admin::id(), 3)],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &test_admin_keypair()], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Err(_));
+
+        // A single admin imports the participant as pending...
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                set_threshold(test_admin::id(), 2),
+                import(
+                    mainnet_validator_identity.pubkey(),
+                    testnet_validator_identity.pubkey(),
+                    payer.pubkey(),
+                    test_admin::id(),
+                ),
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &test_admin_keypair()], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
+
+        // A single admin no longer approves...
+        let mut transaction = Transaction::new_with_payer(
+            &[approve(participant, test_admin::id())],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &test_admin_keypair()], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Err(_));
+
+        // ...nor lowers the threshold or replaces the admins
+        let mut transaction = Transaction::new_with_payer(
+            &[set_threshold(test_admin::id(), 1)],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &test_admin_keypair()], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Err(_));
+
+        let mut transaction = Transaction::new_with_payer(
+            &[set_admins(
+                test_admin::id(),
+                admin_set(&[test_admin::id()]).unwrap(),
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &test_admin_keypair()], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Err(_));
+
+        // Anyone may fund the decision account beforehand...
+        let mut transaction = Transaction::new_with_payer(
+            &[system_instruction::transfer(
+                &payer.pubkey(),
+                &decision_address(&participant),
+                1,
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
+
+        // ...which does not prevent the first vote
+        let mut transaction = Transaction::new_with_payer(
+            &[vote(
+                participant,
+                test_admin::id(),
+                payer.pubkey(),
+                ParticipantDecision::Approve,
+                test_admin::id(),
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &test_admin_keypair()], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
+
+        assert_eq!(
+            banks_client
+                .get_packed_account_data::<ParticipantV2>(participant)
+                .await
+                .unwrap()
+                .state,
+            ParticipantState::Pending
+        );
+        let pending_decision = banks_client
+            .get_packed_account_data::<PendingDecision>(decision_address(&participant))
+            .await
+            .unwrap();
+        assert_eq!(pending_decision.decision, ParticipantDecision::Approve);
+        assert_eq!(pending_decision.creator, test_admin::id());
+        assert_eq!(
+            pending_decision.voters().collect::<Vec<_>>(),
+            vec![&test_admin::id()]
+        );
+
+        // A vote for a different decision, failure...
+        let mut transaction = Transaction::new_with_payer(
+            &[vote(
+                participant,
+                other_admin.pubkey(),
+                payer.pubkey(),
+                ParticipantDecision::Reject,
+                test_admin::id(),
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &other_admin], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Err(_));
+
+        // Refunding the decision account to an admin that did not create it, failure...
+        let mut transaction = Transaction::new_with_payer(
+            &[vote(
+                participant,
+                other_admin.pubkey(),
+                payer.pubkey(),
+                ParticipantDecision::Approve,
+                other_admin.pubkey(),
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &other_admin], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Err(_));
+
+        // Second vote, the decision applies and its creator receives the lamports...
+        let decision_lamports = banks_client
+            .get_balance(decision_address(&participant))
+            .await
+            .unwrap();
+        let creator_lamports = banks_client.get_balance(test_admin::id()).await.unwrap();
+        let mut transaction = Transaction::new_with_payer(
+            &[vote(
+                participant,
+                other_admin.pubkey(),
+                payer.pubkey(),
+                ParticipantDecision::Approve,
+                test_admin::id(),
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &other_admin], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
+        assert_eq!(
+            banks_client.get_balance(test_admin::id()).await.unwrap(),
+            creator_lamports + decision_lamports
+        );
+
+        assert_eq!(
+            banks_client
+                .get_packed_account_data::<ParticipantV2>(participant)
+                .await
+                .unwrap()
+                .state,
+            ParticipantState::Approved
+        );
+        assert_eq!(
+            banks_client
+                .get_account(decision_address(&participant))
+                .await
+                .unwrap(),
+            None
+        );
+
+        // Withdrawing discards a pending decision...
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                vote(
+                    participant,
+                    test_admin::id(),
+                    payer.pubkey(),
+                    ParticipantDecision::Reject,
+                    test_admin::id(),
+                ),
+                withdraw(
+                    participant,
+                    mainnet_validator_identity.pubkey(),
+                    mainnet_validator_identity.pubkey(),
+                    testnet_validator_identity.pubkey(),
+                    Some(test_admin::id()),
+                ),
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(
+            &[&payer, &test_admin_keypair(), &mainnet_validator_identity],
+            recent_blockhash,
+        );
+        assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
+
+        assert_eq!(banks_client.get_account(participant).await.unwrap(), None);
+        assert_eq!(
+            banks_client
+                .get_account(decision_address(&participant))
+                .await
+                .unwrap(),
+            None
+        );
+
+        // Lowering the threshold takes a vote too...
+        let mut transaction = Transaction::new_with_payer(
+            &[vote_config(
+                test_admin::id(),
+                payer.pubkey(),
+                ConfigDecision::SetThreshold(1),
+                test_admin::id(),
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &test_admin_keypair()], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
+
+        assert_eq!(
+            banks_client
+                .get_packed_account_data::<Config>(config_address())
+                .await
+                .unwrap()
+                .threshold,
+            2
+        );
+        let pending_decision = banks_client
+            .get_packed_account_data::<PendingConfigDecision>(config_decision_address())
+            .await
+            .unwrap();
+        assert_eq!(pending_decision.decision, ConfigDecision::SetThreshold(1));
+        assert_eq!(
+            pending_decision.voters().collect::<Vec<_>>(),
+            vec![&test_admin::id()]
+        );
+
+        // A vote for a different config decision, failure...
+        let mut transaction = Transaction::new_with_payer(
+            &[vote_config(
+                other_admin.pubkey(),
+                payer.pubkey(),
+                ConfigDecision::SetAdmins(admin_set(&[other_admin.pubkey()]).unwrap()),
+                test_admin::id(),
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &other_admin], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Err(_));
+
+        // Second vote, the threshold is lowered...
+        let mut transaction = Transaction::new_with_payer(
+            &[vote_config(
+                other_admin.pubkey(),
+                payer.pubkey(),
+                ConfigDecision::SetThreshold(1),
+                test_admin::id(),
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &other_admin], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
+
+        assert_eq!(
+            banks_client
+                .get_packed_account_data::<Config>(config_address())
+                .await
+                .unwrap()
+                .threshold,
+            1
+        );
+        assert_eq!(
+            banks_client
+                .get_account(config_decision_address())
+                .await
+                .unwrap(),
+            None
+        );
+
+        // ...and a single admin changes the config again
+        let mut transaction = Transaction::new_with_payer(
+            &[set_threshold(test_admin::id(), 2)],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &test_admin_keypair()], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_migrate() {
+        let program_id = crate::id();
+
+        let participant = Pubkey::new_unique();
+        let mainnet_validator_identity = Keypair::new();
+        let testnet_validator_identity = Keypair::new();
+        let new_participant = participant_address(&mainnet_validator_identity.pubkey());
+
+        // An approved participant in the original layout, at a random address...
+        let mut data = vec![0; Participant::get_packed_len()];
+        Participant {
+            state: ParticipantState::Approved,
+            testnet_identity: testnet_validator_identity.pubkey(),
+            mainnet_identity: mainnet_validator_identity.pubkey(),
+        }
+        .pack_into_slice(&mut data);
+        let mut program_test = program_test();
+        program_test.add_account(
+            participant,
+            Account {
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                owner: program_id,
+                ..Account::default()
+            },
+        );
+
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        initialize_test_config(
+            &mut banks_client,
+            &payer,
+            recent_blockhash,
+            &[test_admin::id()],
+        )
+        .await;
+
+        // No contact without migrating, failure...
+        let mut transaction = Transaction::new_with_payer(
+            &[set_contact(
+                participant,
+                mainnet_validator_identity.pubkey(),
+                contact_field("https://example.com").unwrap(),
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &mainnet_validator_identity], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Err(_));
+
+        // Migrate, with a transfer trying to revive the original account...
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                migrate(
+                    participant,
+                    mainnet_validator_identity.pubkey(),
+                    testnet_validator_identity.pubkey(),
+                    mainnet_validator_identity.pubkey(),
+                    payer.pubkey(),
+                ),
+                set_contact(
+                    new_participant,
+                    mainnet_validator_identity.pubkey(),
+                    contact_field("https://example.com").unwrap(),
+                ),
+                system_instruction::transfer(
+                    &payer.pubkey(),
+                    &participant,
+                    Rent::default().minimum_balance(Participant::get_packed_len()),
+                ),
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &mainnet_validator_identity], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
+
+        // ...which holds no participant
+        let original = banks_client
+            .get_account(participant)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(original.data.iter().all(|byte| *byte == 0));
+        let participant_state = banks_client
+            .get_packed_account_data::<ParticipantV2>(new_participant)
+            .await
+            .unwrap();
+        assert_eq!(
+            participant_state.participant(),
+            Participant {
+                state: ParticipantState::Approved,
+                testnet_identity: testnet_validator_identity.pubkey(),
+                mainnet_identity: mainnet_validator_identity.pubkey()
+            }
+        );
+        assert_eq!(participant_state.version, PARTICIPANT_VERSION);
+        assert_eq!(participant_state.applied_at, 0);
+        assert_eq!(participant_state.contact(), Some("https://example.com"));
+
+        // A contact with control characters, failure...
+        let mut contact = contact_field("https://example.com").unwrap();
+        contact[0] = b'\n';
+        let mut transaction = Transaction::new_with_payer(
+            &[set_contact(
+                new_participant,
+                mainnet_validator_identity.pubkey(),
+                contact,
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &mainnet_validator_identity], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Err(_));
+        assert_eq!(
+            banks_client
+                .get_packed_account_data::<TestnetIndex>(testnet_index_address(
+                    &testnet_validator_identity.pubkey()
+                ))
+                .await
+                .unwrap()
+                .participant,
+            new_participant
+        );
+
+        // Reject with a reason...
+        let mut transaction = Transaction::new_with_payer(
+            &[reject_with_reason(new_participant, test_admin::id(), 7)],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &test_admin_keypair()], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
+
+        let participant_state = banks_client
+            .get_packed_account_data::<ParticipantV2>(new_participant)
+            .await
+            .unwrap();
+        assert_eq!(participant_state.state, ParticipantState::Rejected);
+        assert_eq!(participant_state.rejection_reason, 7);
+        assert_ne!(participant_state.decided_at, 0);
+
+        // Approval clears the reason...
+        let mut transaction = Transaction::new_with_payer(
+            &[approve(new_participant, test_admin::id())],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &test_admin_keypair()], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
+
+        let participant_state = banks_client
+            .get_packed_account_data::<ParticipantV2>(new_participant)
+            .await
+            .unwrap();
+        assert_eq!(participant_state.state, ParticipantState::Approved);
+        assert_eq!(participant_state.rejection_reason, 0);
+    }
+
+    #[tokio::test]
+    async fn test_update_identity() {
+        let mainnet_validator_identity = Keypair::new();
+        let testnet_validator_identity = Keypair::new();
+        let new_mainnet_validator_identity = Keypair::new();
+        let new_testnet_validator_identity = Keypair::new();
+        let participant = participant_address(&mainnet_validator_identity.pubkey());
+        let new_participant = participant_address(&new_mainnet_validator_identity.pubkey());
+
+        let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+        initialize_test_config(
+            &mut banks_client,
+            &payer,
+            recent_blockhash,
+            &[test_admin::id(), Pubkey::new_unique()],
+        )
+        .await;
+
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                apply(
+                    mainnet_validator_identity.pubkey(),
+                    testnet_validator_identity.pubkey(),
+                    payer.pubkey(),
+                ),
+                approve(participant, test_admin::id()),
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(
+            &[
+                &payer,
+                &mainnet_validator_identity,
+                &testnet_validator_identity,
+                &test_admin_keypair(),
+            ],
+            recent_blockhash,
+        );
+        assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
+
+        // New identity must sign, failure...
+        let mut instruction = update_identity(
+            mainnet_validator_identity.pubkey(),
+            testnet_validator_identity.pubkey(),
+            mainnet_validator_identity.pubkey(),
+            new_mainnet_validator_identity.pubkey(),
+            payer.pubkey(),
+        );
+        instruction.accounts[2].is_signer = false;
+        let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &mainnet_validator_identity], recent_blockhash);
+        assert_matches!(banks_client.process_transaction(transaction).await, Err(_));
+
+        // Swapping in the other identity of the participant, failure...
+        let mut transaction = Transaction::new_with_payer(
+            &[update_identity(
+                mainnet_validator_identity.pubkey(),
+                testnet_validator_identity.pubkey(),
+                mainnet_validator_identity.pubkey(),
+                testnet_validator_identity.pubkey(),
+                payer.pubkey(),
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(
+            &[
+                &payer,
+                &mainnet_validator_identity,
+                &testnet_validator_identity,
+            ],
+            recent_blockhash,
+        );
+        assert_matches!(banks_client.process_transaction(transaction).await, Err(_));
+
+        // Swapping in the testnet identity of another participant, failure...
+        let other_mainnet_validator_identity = Keypair::new();
+        let other_testnet_validator_identity = Keypair::new();
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                import(
+                    other_mainnet_validator_identity.pubkey(),
+                    other_testnet_validator_identity.pubkey(),
+                    payer.pubkey(),
+                    test_admin::id(),
+                ),
+                update_identity(
+                    mainnet_validator_identity.pubkey(),
+                    testnet_validator_identity.pubkey(),
+                    mainnet_validator_identity.pubkey(),
+                    other_testnet_validator_identity.pubkey(),
+                    payer.pubkey(),
+                ),
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(
+            &[
+                &payer,
+                &test_admin_keypair(),
+                &mainnet_validator_identity,
+                &other_testnet_validator_identity,
+            ],
+            recent_blockhash,
+        );
+        assert_matches!(banks_client.process_transaction(transaction).await, Err(_));
+
+        // Rotating with a pending decision, failure...
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                set_threshold(test_admin::id(), 2),
+                vote(
+                    participant,
+                    test_admin::id(),
+                    payer.pubkey(),
+                    ParticipantDecision::Reject,
+                    test_admin::id(),
+                ),
+                update_identity(
+                    mainnet_validator_identity.pubkey(),
+                    testnet_validator_identity.pubkey(),
+                    testnet_validator_identity.pubkey(),
+                    new_testnet_validator_identity.pubkey(),
+                    payer.pubkey(),
+                ),
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(
+            &[
+                &payer,
+                &test_admin_keypair(),
+                &testnet_validator_identity,
+                &new_testnet_validator_identity,
+            ],
+            recent_blockhash,
+        );
+        assert_matches!(banks_client.process_transaction(transaction).await, Err(_));
+
+        // Rotate the mainnet identity, moving the participant account, with a transfer trying to
+        // revive the old account...
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                update_identity(
+                    mainnet_validator_identity.pubkey(),
+                    testnet_validator_identity.pubkey(),
+                    mainnet_validator_identity.pubkey(),
+                    new_mainnet_validator_identity.pubkey(),
+                    payer.pubkey(),
+                ),
+                system_instruction::transfer(
+                    &payer.pubkey(),
+                    &participant,
+                    Rent::default().minimum_balance(ParticipantV2::get_packed_len()),
+                ),
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(
+            &[
+                &payer,
+                &mainnet_validator_identity,
+                &new_mainnet_validator_identity,
+            ],
+            recent_blockhash,
+        );
+        assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
+
+        // ...which holds no participant
+        assert_eq!(
+            banks_client
+                .get_packed_account_data::<ParticipantV2>(participant)
+                .await
+                .unwrap()
+                .state,
+            ParticipantState::Uninitialized
+        );
+        assert_eq!(
+            banks_client
+                .get_packed_account_data::<ParticipantV2>(new_participant)
+                .await
+                .unwrap()
+                .participant(),
+            Participant {
+                state: ParticipantState::Approved,
+                testnet_identity: testnet_validator_identity.pubkey(),
+                mainnet_identity: new_mainnet_validator_identity.pubkey()
+            }
+        );
+
+        // Rotate the testnet identity, moving the testnet index...
+        let mut transaction = Transaction::new_with_payer(
+            &[update_identity(
+                new_mainnet_validator_identity.pubkey(),
+                testnet_validator_identity.pubkey(),
+                testnet_validator_identity.pubkey(),
+                new_testnet_validator_identity.pubkey(),
+                payer.pubkey(),
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(
+            &[
+                &payer,
+                &testnet_validator_identity,
+                &new_testnet_validator_identity,
+            ],
+            recent_blockhash,
+        );
+        assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
+
+        assert_eq!(
+            banks_client
+                .get_account(testnet_index_address(&testnet_validator_identity.pubkey()))
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            banks_client
+                .get_packed_account_data::<TestnetIndex>(testnet_index_address(
+                    &new_testnet_validator_identity.pubkey()
+                ))
+                .await
+                .unwrap()
+                .participant,
+            new_participant
+        );
+        assert_eq!(
+            banks_client
+                .get_packed_account_data::<ParticipantV2>(new_participant)
+                .await
+                .unwrap()
+                .participant(),
+            Participant {
+                state: ParticipantState::Approved,
+                testnet_identity: new_testnet_validator_identity.pubkey(),
+                mainnet_identity: new_mainnet_validator_identity.pubkey()
+            }
+        );
+
+        // The replaced mainnet identity is free to register again, reusing its revived account
+        let later_testnet_validator_identity = Keypair::new();
+        let mut transaction = Transaction::new_with_payer(
+            &[apply(
+                mainnet_validator_identity.pubkey(),
+                later_testnet_validator_identity.pubkey(),
+                payer.pubkey(),
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(
+            &[
+                &payer,
+                &mainnet_validator_identity,
+                &later_testnet_validator_identity,
+            ],
+            recent_blockhash,
+        );
+        assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
+        assert_eq!(
+            banks_client
+                .get_packed_account_data::<ParticipantV2>(participant)
+                .await
+                .unwrap()
+                .state,
+            ParticipantState::Pending
         );
     }
 }
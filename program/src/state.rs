@@ -1,12 +1,81 @@
 //! Program state
 use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
 use solana_program::{
+    clock::UnixTimestamp,
     msg,
     program_error::ProgramError,
     program_pack::{Pack, Sealed},
     pubkey::Pubkey,
 };
 
+/// Seed of the program address of the `Config` account
+pub const CONFIG_SEED: &[u8] = b"config";
+
+/// Maximum number of admins in the `Config` account
+pub const MAX_ADMINS: usize = 5;
+
+/// Address of the `Config` account, and its bump seed
+pub fn find_config_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED], &crate::id())
+}
+
+/// Address of the `Config` account
+pub fn config_address() -> Pubkey {
+    find_config_address().0
+}
+
+/// Seed of the program address of the `PendingDecision` account of a participant
+pub const DECISION_SEED: &[u8] = b"decision";
+
+/// Address of the `PendingDecision` account of `participant`, and its bump seed
+pub fn find_decision_address(participant: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[DECISION_SEED, participant.as_ref()], &crate::id())
+}
+
+/// Address of the `PendingDecision` account of `participant`
+pub fn decision_address(participant: &Pubkey) -> Pubkey {
+    find_decision_address(participant).0
+}
+
+/// Address of the `PendingConfigDecision` account, and its bump seed
+pub fn find_config_decision_address() -> (Pubkey, u8) {
+    find_decision_address(&config_address())
+}
+
+/// Address of the `PendingConfigDecision` account
+pub fn config_decision_address() -> Pubkey {
+    find_config_decision_address().0
+}
+
+/// Seed of the program address of a participant account, derived from its mainnet identity
+pub const PARTICIPANT_SEED: &[u8] = b"participant";
+
+/// Address of the participant account registering `mainnet_identity`, and its bump seed
+pub fn find_participant_address(mainnet_identity: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PARTICIPANT_SEED, mainnet_identity.as_ref()], &crate::id())
+}
+
+/// Address of the participant account registering `mainnet_identity`
+pub fn participant_address(mainnet_identity: &Pubkey) -> Pubkey {
+    find_participant_address(mainnet_identity).0
+}
+
+/// Seed of the program address of the `TestnetIndex` account of a testnet identity
+pub const TESTNET_INDEX_SEED: &[u8] = b"testnet-index";
+
+/// Address of the `TestnetIndex` account of `testnet_identity`, and its bump seed
+pub fn find_testnet_index_address(testnet_identity: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[TESTNET_INDEX_SEED, testnet_identity.as_ref()],
+        &crate::id(),
+    )
+}
+
+/// Address of the `TestnetIndex` account of `testnet_identity`
+pub fn testnet_index_address(testnet_identity: &Pubkey) -> Pubkey {
+    find_testnet_index_address(testnet_identity).0
+}
+
 #[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
 pub enum ParticipantState {
     /// Default account state after creating it
@@ -57,6 +126,529 @@ impl Pack for Participant {
     }
 }
 
+/// The participant registering a testnet identity, held in the account at
+/// `testnet_index_address(testnet_identity)`.  Together with the participant account at
+/// `participant_address(mainnet_identity)` it reserves both identities of a participant
+#[derive(Clone, Debug, Default, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub struct TestnetIndex {
+    pub participant: Pubkey,
+}
+
+impl Sealed for TestnetIndex {}
+
+impl Pack for TestnetIndex {
+    const LEN: usize = 32; // see `test::get_packed_len()` for justification of "32"
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let data = self.try_to_vec().unwrap();
+        dst[..data.len()].copy_from_slice(&data);
+    }
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let mut mut_src: &[u8] = src;
+        Self::deserialize(&mut mut_src).map_err(|err| {
+            msg!(
+                "Error: failed to deserialize testnet index account: {}",
+                err
+            );
+            ProgramError::InvalidAccountData
+        })
+    }
+}
+
+/// Schema version of `ParticipantV2` accounts
+pub const PARTICIPANT_VERSION: u8 = 2;
+
+/// Maximum length in bytes of the contact of a `ParticipantV2`
+pub const MAX_CONTACT_LEN: usize = 64;
+
+/// Participant account with application metadata, at `participant_address(mainnet_identity)`.
+/// Accounts of `Participant::LEN` bytes hold the original layout at a random address, which
+/// `RegistryInstruction::Migrate` moves into this one
+#[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub struct ParticipantV2 {
+    /// `PARTICIPANT_VERSION`, 1 when unpacked from an account in the original layout, or 0 in
+    /// an account not yet written by the program
+    pub version: u8,
+    pub testnet_identity: Pubkey,
+    pub mainnet_identity: Pubkey,
+    pub state: ParticipantState,
+
+    /// When the participant applied, or 0 if unknown
+    pub applied_at: UnixTimestamp,
+
+    /// When the participant was last approved or rejected, or 0 if not yet decided or unknown
+    pub decided_at: UnixTimestamp,
+
+    /// Reason code of a rejection, assigned by the admins.  0 if no reason was given
+    pub rejection_reason: u8,
+
+    /// Contact or URL published by the participant, UTF-8 padded with zeros
+    pub contact: [u8; MAX_CONTACT_LEN],
+}
+
+impl Default for ParticipantV2 {
+    fn default() -> Self {
+        Self {
+            version: PARTICIPANT_VERSION,
+            testnet_identity: Pubkey::default(),
+            mainnet_identity: Pubkey::default(),
+            state: ParticipantState::default(),
+            applied_at: 0,
+            decided_at: 0,
+            rejection_reason: 0,
+            contact: [0; MAX_CONTACT_LEN],
+        }
+    }
+}
+
+impl From<Participant> for ParticipantV2 {
+    fn from(participant: Participant) -> Self {
+        let mut participant_v2 = Self {
+            version: 1,
+            ..Self::default()
+        };
+        participant_v2.rewrite(participant);
+        participant_v2
+    }
+}
+
+impl ParticipantV2 {
+    /// The identities and state of the participant, in the original layout
+    pub fn participant(&self) -> Participant {
+        Participant {
+            testnet_identity: self.testnet_identity,
+            mainnet_identity: self.mainnet_identity,
+            state: self.state.clone(),
+        }
+    }
+
+    /// Replace the identities and state of the participant, keeping its metadata
+    pub fn rewrite(&mut self, participant: Participant) {
+        self.testnet_identity = participant.testnet_identity;
+        self.mainnet_identity = participant.mainnet_identity;
+        self.state = participant.state;
+    }
+
+    /// The contact of the participant, if set and valid UTF-8
+    pub fn contact(&self) -> Option<&str> {
+        let len = self
+            .contact
+            .iter()
+            .position(|byte| *byte == 0)
+            .unwrap_or(MAX_CONTACT_LEN);
+        std::str::from_utf8(&self.contact[..len])
+            .ok()
+            .filter(|contact| !contact.is_empty())
+    }
+
+    /// Unpack a participant account of either layout.  Accounts in the original layout have no
+    /// metadata, and accounts of any other size are refused
+    pub fn unpack_any(src: &[u8]) -> Result<Self, ProgramError> {
+        if src.len() == Participant::LEN {
+            Participant::unpack_from_slice(src).map(Self::from)
+        } else if src.len() == Self::LEN {
+            Self::unpack_from_slice(src)
+        } else {
+            msg!(
+                "Error: {} bytes is not the size of a participant account",
+                src.len()
+            );
+            Err(ProgramError::InvalidAccountData)
+        }
+    }
+
+    /// Pack into a participant account of either layout, dropping the metadata from accounts in
+    /// the original layout
+    pub fn pack_any(&self, dst: &mut [u8]) {
+        if dst.len() == Participant::LEN {
+            self.participant().pack_into_slice(dst)
+        } else {
+            self.pack_into_slice(dst)
+        }
+    }
+}
+
+/// Pack `contact` into the contact field of a `ParticipantV2`
+pub fn contact_field(contact: &str) -> Result<[u8; MAX_CONTACT_LEN], ProgramError> {
+    if contact.len() > MAX_CONTACT_LEN || contact.contains('\0') {
+        msg!(
+            "Error: contact must be at most {} bytes long and not contain NUL",
+            MAX_CONTACT_LEN
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let mut contact_field = [0; MAX_CONTACT_LEN];
+    contact_field[..contact.len()].copy_from_slice(contact.as_bytes());
+    check_contact_field(&contact_field)?;
+    Ok(contact_field)
+}
+
+/// Check that `contact_field` holds UTF-8 without control characters, padded with zeros
+pub fn check_contact_field(contact_field: &[u8; MAX_CONTACT_LEN]) -> Result<(), ProgramError> {
+    let len = contact_field
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(MAX_CONTACT_LEN);
+    let is_valid = contact_field[len..].iter().all(|byte| *byte == 0)
+        && std::str::from_utf8(&contact_field[..len])
+            .map(|contact| !contact.chars().any(char::is_control))
+            .unwrap_or(false);
+    if !is_valid {
+        msg!("Error: contact must be UTF-8 without control characters, padded with zeros");
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(())
+}
+
+impl Sealed for ParticipantV2 {}
+
+impl Pack for ParticipantV2 {
+    const LEN: usize = 147; // see `test::get_packed_len()` for justification of "147"
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let data = self.try_to_vec().unwrap();
+        dst[..data.len()].copy_from_slice(&data);
+    }
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let mut mut_src: &[u8] = src;
+        let participant = Self::deserialize(&mut mut_src).map_err(|err| {
+            msg!("Error: failed to deserialize participant account: {}", err);
+            ProgramError::InvalidAccountData
+        })?;
+        if participant.version > PARTICIPANT_VERSION {
+            msg!(
+                "Error: unsupported participant account version {}",
+                participant.version
+            );
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(participant)
+    }
+}
+
+/// Schema version of the `Config` account
+pub const CONFIG_VERSION: u8 = 1;
+
+/// Program configuration, held in the account at `config_address()`
+#[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub struct Config {
+    /// `CONFIG_VERSION`
+    pub version: u8,
+
+    /// Addresses authorized to administer the registry.  Unused entries are `Pubkey::default()`
+    pub admins: [Pubkey; MAX_ADMINS],
+
+    /// Admin to be replaced by `proposed_admin` once it accepts, or `Pubkey::default()`
+    pub proposing_admin: Pubkey,
+
+    /// Admin proposed by `RegistryInstruction::ProposeAdmin`, or `Pubkey::default()`
+    pub proposed_admin: Pubkey,
+
+    /// Number of admins that must vote for a participant or config decision before it applies.
+    /// With a threshold of 0 or 1 a single admin decides by signing `Approve`, `Reject`,
+    /// `Rewrite`, `SetAdmins` or `SetThreshold`
+    pub threshold: u8,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            admins: [Pubkey::default(); MAX_ADMINS],
+            proposing_admin: Pubkey::default(),
+            proposed_admin: Pubkey::default(),
+            threshold: 0,
+        }
+    }
+}
+
+impl Config {
+    /// A configuration administered by `admins`
+    pub fn new(admins: &[Pubkey]) -> Result<Self, ProgramError> {
+        Ok(Self {
+            admins: admin_set(admins)?,
+            ..Self::default()
+        })
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.admins().next().is_some()
+    }
+
+    pub fn is_admin(&self, address: &Pubkey) -> bool {
+        *address != Pubkey::default() && self.admins.contains(address)
+    }
+
+    /// The configured admins
+    pub fn admins(&self) -> impl Iterator<Item = &Pubkey> {
+        self.admins
+            .iter()
+            .filter(|admin| **admin != Pubkey::default())
+    }
+
+    /// Number of admin votes a participant or config decision requires
+    pub fn required_votes(&self) -> usize {
+        (self.threshold as usize).max(1)
+    }
+
+    /// Check that the admins can meet the threshold
+    pub fn check_threshold(&self) -> Result<(), ProgramError> {
+        if self.required_votes() > self.admins().count() {
+            msg!(
+                "Error: threshold of {} exceeds the number of admins",
+                self.threshold
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+        Ok(())
+    }
+}
+
+/// Pack `admins` into the fixed size admin set of a `Config`.  At least one and at most
+/// `MAX_ADMINS` distinct admins are required
+pub fn admin_set(admins: &[Pubkey]) -> Result<[Pubkey; MAX_ADMINS], ProgramError> {
+    if admins.is_empty() || admins.len() > MAX_ADMINS {
+        msg!("Error: between 1 and {} admins are required", MAX_ADMINS);
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let mut admin_set = [Pubkey::default(); MAX_ADMINS];
+    for (i, admin) in admins.iter().enumerate() {
+        if *admin == Pubkey::default() || admins[..i].contains(admin) {
+            msg!("Error: invalid or duplicate admin {}", admin);
+            return Err(ProgramError::InvalidArgument);
+        }
+        admin_set[i] = *admin;
+    }
+    Ok(admin_set)
+}
+
+/// Validate the fixed size admin set of an instruction, whose unused entries are
+/// `Pubkey::default()`
+pub fn admin_set_of(admins: &[Pubkey; MAX_ADMINS]) -> Result<[Pubkey; MAX_ADMINS], ProgramError> {
+    admin_set(
+        &admins
+            .iter()
+            .filter(|admin| **admin != Pubkey::default())
+            .cloned()
+            .collect::<Vec<_>>(),
+    )
+}
+
+impl Sealed for Config {}
+
+impl Pack for Config {
+    const LEN: usize = 226; // see `test::get_packed_len()` for justification of "226"
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let data = self.try_to_vec().unwrap();
+        dst[..data.len()].copy_from_slice(&data);
+    }
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let mut mut_src: &[u8] = src;
+        let config = Self::deserialize(&mut mut_src).map_err(|err| {
+            msg!("Error: failed to deserialize config account: {}", err);
+            ProgramError::InvalidAccountData
+        })?;
+        if config.version > CONFIG_VERSION {
+            msg!(
+                "Error: unsupported config account version {}",
+                config.version
+            );
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(config)
+    }
+}
+
+/// A state transition of a participant, applied once enough admins vote for it
+#[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub enum ParticipantDecision {
+    Approve,
+    Reject,
+    Rewrite(Participant),
+    RejectWithReason(u8),
+}
+
+impl ParticipantDecision {
+    pub fn apply(&self, participant: &mut ParticipantV2) {
+        match self {
+            Self::Approve => participant.state = ParticipantState::Approved,
+            Self::Reject => participant.state = ParticipantState::Rejected,
+            Self::Rewrite(new_participant) => participant.rewrite(new_participant.clone()),
+            Self::RejectWithReason(rejection_reason) => {
+                participant.state = ParticipantState::Rejected;
+                participant.rejection_reason = *rejection_reason;
+            }
+        }
+    }
+}
+
+/// The decision admins are voting on for a participant, held in the account at
+/// `decision_address(participant)` until it applies
+#[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub struct PendingDecision {
+    pub participant: Pubkey,
+    pub decision: ParticipantDecision,
+
+    /// Admin that created the decision, who receives the lamports of its account once it closes
+    pub creator: Pubkey,
+
+    /// Admins that voted for `decision`.  Unused entries are `Pubkey::default()`
+    pub votes: [Pubkey; MAX_ADMINS],
+}
+
+impl PendingDecision {
+    pub fn new(participant: Pubkey, decision: ParticipantDecision, creator: Pubkey) -> Self {
+        Self {
+            participant,
+            decision,
+            creator,
+            votes: [Pubkey::default(); MAX_ADMINS],
+        }
+    }
+
+    /// The admins that voted
+    pub fn voters(&self) -> impl Iterator<Item = &Pubkey> {
+        voters(&self.votes)
+    }
+
+    /// Record the vote of `admin`.  Votes of addresses that are no longer admins are discarded
+    pub fn vote(&mut self, admin: Pubkey, config: &Config) {
+        vote(&mut self.votes, admin, config)
+    }
+
+    /// Number of votes of current admins
+    pub fn votes_of(&self, config: &Config) -> usize {
+        self.voters().filter(|voter| config.is_admin(voter)).count()
+    }
+}
+
+fn voters(votes: &[Pubkey; MAX_ADMINS]) -> impl Iterator<Item = &Pubkey> {
+    votes.iter().filter(|voter| **voter != Pubkey::default())
+}
+
+fn vote(votes: &mut [Pubkey; MAX_ADMINS], admin: Pubkey, config: &Config) {
+    let mut new_votes = voters(votes)
+        .filter(|voter| config.is_admin(voter) && **voter != admin)
+        .cloned()
+        .collect::<Vec<_>>();
+    new_votes.push(admin);
+
+    *votes = [Pubkey::default(); MAX_ADMINS];
+    for (i, voter) in new_votes.into_iter().take(MAX_ADMINS).enumerate() {
+        votes[i] = voter;
+    }
+}
+
+impl Sealed for PendingDecision {}
+
+impl Pack for PendingDecision {
+    const LEN: usize = 290; // see `test::get_packed_len()` for justification of "290"
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let data = self.try_to_vec().unwrap();
+        dst[..data.len()].copy_from_slice(&data);
+    }
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let mut mut_src: &[u8] = src;
+        Self::deserialize(&mut mut_src).map_err(|err| {
+            msg!(
+                "Error: failed to deserialize pending decision account: {}",
+                err
+            );
+            ProgramError::InvalidAccountData
+        })
+    }
+}
+
+/// A change to the `Config` account, applied once enough admins vote for it
+#[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub enum ConfigDecision {
+    SetAdmins([Pubkey; MAX_ADMINS]),
+    SetThreshold(u8),
+}
+
+impl ConfigDecision {
+    /// Apply the change to `config`, which fails if the admins could no longer meet the threshold
+    pub fn apply(&self, config: &mut Config) -> Result<(), ProgramError> {
+        match self {
+            Self::SetAdmins(admins) => {
+                config.admins = admin_set_of(admins)?;
+                config.proposing_admin = Pubkey::default();
+                config.proposed_admin = Pubkey::default();
+            }
+            Self::SetThreshold(threshold) => config.threshold = *threshold,
+        }
+        config.check_threshold()
+    }
+}
+
+/// The config change admins are voting on, held in the account at `config_decision_address()`
+/// until it applies
+#[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub struct PendingConfigDecision {
+    pub decision: ConfigDecision,
+
+    /// Admin that created the decision, who receives the lamports of its account once it closes
+    pub creator: Pubkey,
+
+    /// Admins that voted for `decision`.  Unused entries are `Pubkey::default()`
+    pub votes: [Pubkey; MAX_ADMINS],
+}
+
+impl PendingConfigDecision {
+    pub fn new(decision: ConfigDecision, creator: Pubkey) -> Self {
+        Self {
+            decision,
+            creator,
+            votes: [Pubkey::default(); MAX_ADMINS],
+        }
+    }
+
+    /// The admins that voted
+    pub fn voters(&self) -> impl Iterator<Item = &Pubkey> {
+        voters(&self.votes)
+    }
+
+    /// Record the vote of `admin`.  Votes of addresses that are no longer admins are discarded
+    pub fn vote(&mut self, admin: Pubkey, config: &Config) {
+        vote(&mut self.votes, admin, config)
+    }
+
+    /// Number of votes of current admins
+    pub fn votes_of(&self, config: &Config) -> usize {
+        self.voters().filter(|voter| config.is_admin(voter)).count()
+    }
+}
+
+impl Sealed for PendingConfigDecision {}
+
+impl Pack for PendingConfigDecision {
+    const LEN: usize = 353; // see `test::get_packed_len()` for justification of "353"
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let data = self.try_to_vec().unwrap();
+        dst[..data.len()].copy_from_slice(&data);
+    }
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let mut mut_src: &[u8] = src;
+        Self::deserialize(&mut mut_src).map_err(|err| {
+            msg!(
+                "Error: failed to deserialize pending config decision account: {}",
+                err
+            );
+            ProgramError::InvalidAccountData
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -67,5 +659,160 @@ mod tests {
             Participant::get_packed_len(),
             solana_program::borsh::get_packed_len::<Participant>()
         );
+        assert_eq!(
+            ParticipantV2::get_packed_len(),
+            solana_program::borsh::get_packed_len::<ParticipantV2>()
+        );
+        assert_eq!(
+            TestnetIndex::get_packed_len(),
+            solana_program::borsh::get_packed_len::<TestnetIndex>()
+        );
+        assert_eq!(
+            Config::get_packed_len(),
+            solana_program::borsh::get_packed_len::<Config>()
+        );
+        assert_eq!(
+            PendingDecision::get_packed_len(),
+            solana_program::borsh::get_packed_len::<PendingDecision>()
+        );
+        assert_eq!(
+            PendingConfigDecision::get_packed_len(),
+            solana_program::borsh::get_packed_len::<PendingConfigDecision>()
+        );
+    }
+
+    #[test]
+    fn pending_decision_votes() {
+        let admin = Pubkey::new_unique();
+        let other_admin = Pubkey::new_unique();
+        let former_admin = Pubkey::new_unique();
+        let mut config = Config::new(&[admin, other_admin, former_admin]).unwrap();
+        config.threshold = 2;
+        assert!(config.check_threshold().is_ok());
+
+        let mut pending_decision =
+            PendingDecision::new(Pubkey::new_unique(), ParticipantDecision::Approve, admin);
+        pending_decision.vote(former_admin, &config);
+        pending_decision.vote(admin, &config);
+        pending_decision.vote(admin, &config);
+        assert_eq!(pending_decision.votes_of(&config), 2);
+
+        config.admins = admin_set(&[admin, other_admin]).unwrap();
+        assert_eq!(pending_decision.votes_of(&config), 1);
+        pending_decision.vote(other_admin, &config);
+        assert_eq!(
+            pending_decision.voters().collect::<Vec<_>>(),
+            vec![&admin, &other_admin]
+        );
+
+        config.threshold = 3;
+        assert!(config.check_threshold().is_err());
+
+        let mut participant = ParticipantV2::default();
+        ParticipantDecision::Approve.apply(&mut participant);
+        assert_eq!(participant.state, ParticipantState::Approved);
+        ParticipantDecision::RejectWithReason(3).apply(&mut participant);
+        assert_eq!(participant.state, ParticipantState::Rejected);
+        assert_eq!(participant.rejection_reason, 3);
+    }
+
+    #[test]
+    fn participant_layouts() {
+        let participant = Participant {
+            testnet_identity: Pubkey::new_unique(),
+            mainnet_identity: Pubkey::new_unique(),
+            state: ParticipantState::Approved,
+        };
+
+        let mut original = vec![0; Participant::get_packed_len()];
+        participant.pack_into_slice(&mut original);
+        let mut participant_v2 = ParticipantV2::unpack_any(&original).unwrap();
+        assert_eq!(participant_v2.version, 1);
+        assert_eq!(participant_v2.participant(), participant);
+        assert_eq!(participant_v2.contact(), None);
+
+        participant_v2.version = PARTICIPANT_VERSION;
+        participant_v2.applied_at = 1_600_000_000;
+        participant_v2.contact = contact_field("https://example.com").unwrap();
+        let mut extended = vec![0; ParticipantV2::get_packed_len()];
+        participant_v2.pack_any(&mut extended);
+        assert_eq!(
+            ParticipantV2::unpack_any(&extended).unwrap(),
+            participant_v2
+        );
+        assert_eq!(participant_v2.contact(), Some("https://example.com"));
+
+        participant_v2.pack_any(&mut original);
+        assert_eq!(
+            Participant::unpack_from_slice(&original).unwrap(),
+            participant
+        );
+
+        let uninitialized = ParticipantV2::unpack_any(&[0; ParticipantV2::LEN]).unwrap();
+        assert_eq!(uninitialized.version, 0);
+        assert_eq!(uninitialized.state, ParticipantState::Uninitialized);
+
+        extended[0] = PARTICIPANT_VERSION + 1;
+        assert!(ParticipantV2::unpack_any(&extended).is_err());
+        assert!(ParticipantV2::unpack_any(&[0; Config::LEN]).is_err());
+
+        assert!(contact_field(&"x".repeat(MAX_CONTACT_LEN + 1)).is_err());
+        assert!(contact_field("line\nbreak").is_err());
+
+        let mut field = contact_field("https://example.com").unwrap();
+        assert!(check_contact_field(&field).is_ok());
+        field[MAX_CONTACT_LEN - 1] = b'x';
+        assert!(check_contact_field(&field).is_err());
+        field[MAX_CONTACT_LEN - 1] = 0;
+        field[0] = 0xff;
+        assert!(check_contact_field(&field).is_err());
+        assert!(check_contact_field(&[0; MAX_CONTACT_LEN]).is_ok());
+    }
+
+    #[test]
+    fn config_admins() {
+        let admin = Pubkey::new_unique();
+        let other_admin = Pubkey::new_unique();
+
+        assert!(!Config::default().is_initialized());
+        assert!(!Config::default().is_admin(&Pubkey::default()));
+
+        let config = Config::new(&[admin, other_admin]).unwrap();
+        assert!(config.is_initialized());
+        assert!(config.is_admin(&admin));
+        assert!(config.is_admin(&other_admin));
+        assert!(!config.is_admin(&Pubkey::default()));
+        assert_eq!(
+            config.admins().collect::<Vec<_>>(),
+            vec![&admin, &other_admin]
+        );
+
+        assert!(Config::new(&[]).is_err());
+        assert!(Config::new(&[admin, admin]).is_err());
+        assert!(Config::new(&[Pubkey::default()]).is_err());
+        assert!(Config::new(&[admin; MAX_ADMINS + 1]).is_err());
+
+        let mut config = Config::new(&[admin, other_admin]).unwrap();
+        assert!(ConfigDecision::SetThreshold(2).apply(&mut config).is_ok());
+        assert_eq!(config.required_votes(), 2);
+        assert!(ConfigDecision::SetThreshold(3)
+            .apply(&mut config.clone())
+            .is_err());
+        assert!(ConfigDecision::SetAdmins(admin_set(&[admin]).unwrap())
+            .apply(&mut config.clone())
+            .is_err());
+        config.proposing_admin = admin;
+        config.proposed_admin = Pubkey::new_unique();
+        let new_admin = Pubkey::new_unique();
+        assert!(
+            ConfigDecision::SetAdmins(admin_set(&[admin, new_admin]).unwrap())
+                .apply(&mut config)
+                .is_ok()
+        );
+        assert_eq!(
+            config.admins().collect::<Vec<_>>(),
+            vec![&admin, &new_admin]
+        );
+        assert_eq!(config.proposed_admin, Pubkey::default());
     }
 }